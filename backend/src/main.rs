@@ -18,6 +18,7 @@ mod validators;
 
 use std::sync::Arc;
 
+use axum::extract::DefaultBodyLimit;
 use axum::Router;
 use bson::doc;
 use hickory_resolver::config::*;
@@ -32,27 +33,46 @@ use s3::Region;
 use tokio::net::TcpListener;
 use tower_cookies::CookieManagerLayer;
 use tower_http::trace::TraceLayer;
-use tracing::{debug, info};
+use tracing::{debug, error, info};
 
 use crate::platform::business::routes::BusinessRoutes;
 use crate::platform::business::service::BusinessService;
 // use crate::platform::dns::repo::MongoDomainRepo;
 // use crate::platform::dns::routes::DnsRoutes;
 // use crate::platform::dns::service::DnsService;
-use crate::platform::user::repo::MongoUserRepo;
+use crate::platform::user::repo::{MongoSessionRepo, MongoUserRepo};
 use crate::platform::user::routes::UserRoutes;
 use crate::platform::user::service::UserService;
+use crate::tenant::cart::repo::MongoCartRepo;
+use crate::tenant::cart::routes::PubCartRoutes;
+use crate::tenant::cart::service::CartService;
+use crate::tenant::category::repo::MongoCategoryRepo;
+use crate::tenant::category::routes::CategoryRoutes;
+use crate::tenant::category::service::CategoryService;
+use crate::tenant::discount::repo::MongoDiscountRepo;
+use crate::tenant::discount::routes::DiscountRoutes;
+use crate::tenant::discount::service::DiscountService;
 use crate::tenant::file::repo::MongoFileRepo;
-use crate::tenant::file::routes::FileRoutes;
+use crate::tenant::file::routes::{FileRoutes, LocalStorageRoutes, PubFileRoutes};
 use crate::tenant::file::service::FileService;
+use crate::tenant::file::storage::local::LocalStorage;
+use crate::tenant::file::storage::s3::S3Storage;
+use crate::tenant::file::storage::FileStorage;
 use crate::tenant::order::repo::MongoOrderRepo;
-use crate::tenant::order::routes::{PubOrderRoutes, OrderRoutes};
+use crate::tenant::order::routes::{OrderRoutes, PubOrderRoutes};
 use crate::tenant::product::repo::MongoProductRepo;
-use crate::tenant::product::routes::{PubProductRoutes, ProductRoutes};
+use crate::tenant::product::routes::{ProductRoutes, PubProductRoutes};
 use crate::tenant::product::service::ProductService;
+use crate::tenant::shipping::repo::MongoShippingRepo;
+use crate::tenant::shipping::routes::{PubShippingRoutes, ShippingRoutes};
+use crate::tenant::shipping::service::ShippingService;
 use crate::tenant::store::repo::{MongoStoreRegRepo, MongoStoreRepo};
 use crate::tenant::store::routes::{PubStoreRoutes, StoreRoutes};
 use crate::tenant::store::service::StoreService;
+use crate::tenant::tax::repo::MongoTaxRepo;
+use crate::tenant::tax::routes::TaxRoutes;
+use crate::tenant::tax::service::TaxService;
+use crate::utils::jwt::{self, SigningKeys};
 use crate::utils::log::init_tracing;
 use crate::utils::router::RoutePacked;
 use crate::{platform::business::repo::MongoBusinessRepo, tenant::order::service::OrderService};
@@ -60,13 +80,19 @@ use crate::{platform::business::repo::MongoBusinessRepo, tenant::order::service:
 type AppState = Arc<State>;
 
 struct State {
-    pub user_service: UserService<MongoUserRepo>,
+    pub user_service: UserService<MongoUserRepo, MongoSessionRepo>,
     pub business_service: BusinessService<MongoBusinessRepo>,
     // pub dns_service: DnsService<MongoDomainRepo>,
     pub product_service: ProductService<MongoProductRepo>,
     pub order_service: OrderService<MongoOrderRepo>,
     pub store_service: StoreService<MongoStoreRepo, MongoStoreRegRepo>,
     pub file_service: FileService<MongoFileRepo>,
+    pub shipping_service: ShippingService<MongoShippingRepo>,
+    pub category_service: CategoryService<MongoCategoryRepo>,
+    pub cart_service: CartService<MongoCartRepo>,
+    pub discount_service: DiscountService<MongoDiscountRepo>,
+    pub tax_service: TaxService<MongoTaxRepo>,
+    pub local_storage: Option<Arc<LocalStorage>>,
     pub store_suffix: String,
 }
 
@@ -80,16 +106,14 @@ async fn main() {
     info!("ROOT_DB_NAME = {}", root_db);
     let store_suffix = format!(".{}", std::env::var("STORE_SUFFIX").unwrap());
     info!("STORE_SUFFIX = {}", store_suffix);
-    let s3_uri = std::env::var("STORAGE_URI").unwrap();
-    info!("STORAGE_URI = {}", s3_uri);
-    let s3_region = std::env::var("STORAGE_REGION").unwrap();
-    info!("STORAGE_REGION = {}", s3_region);
-    let s3_user = std::env::var("STORAGE_USER").unwrap();
-    info!("STORAGE_USER = {}", s3_user);
-    let s3_bucket = std::env::var("STORAGE_BUCKET_NAME").unwrap();
-    info!("STORAGE_BUCKET_NAME = {}", s3_bucket);
-    let s3_pass = std::env::var("STORAGE_PASSWORD").unwrap();
-    info!("STORAGE_PASSWORD = {}", s3_pass);
+    let storage_backend = std::env::var("STORAGE_BACKEND").unwrap_or("s3".to_string());
+    info!("STORAGE_BACKEND = {}", storage_backend);
+    let jwt_keys = std::env::var("JWT_KEYS")
+        .map_err(|_| "JWT_KEYS is not set, expected comma separated kid:secret pairs".to_string())
+        .and_then(|keys| SigningKeys::parse(&keys))
+        .unwrap_or_else(|e| panic!("Invalid JWT_KEYS: {}", e));
+    info!("JWT_KEYS current kid = {}", jwt_keys.current());
+    jwt::configure(jwt_keys);
 
     let api_listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
     info!("api listening on {}", api_listener.local_addr().unwrap());
@@ -105,12 +129,6 @@ async fn main() {
         MongoClient::with_options(MongoClientOptions::parse(db_uri).await.unwrap()).unwrap();
     let db = mongo_client.database(&root_db);
 
-    let region = Region::Custom {
-        endpoint: s3_uri,
-        region: s3_region,
-    };
-    let credentials = Credentials::new(Some(&s3_user), Some(&s3_pass), None, None, None).unwrap();
-
     // TODO: dev only
     {
         let admin_db = mongo_client.database("admin");
@@ -120,25 +138,15 @@ async fn main() {
             })
             .await;
         debug!("replset initialization result: {:#?}", res);
-
-        let res = Bucket::create_with_path_style(
-            &s3_bucket,
-            region.clone(),
-            credentials.clone(),
-            Default::default(),
-        )
-        .await;
-        res.map(|v| {
-            debug!("bucket creation result: {:#?}", v.bucket);
-        })
-        .map_err(|e| {
-            debug!("bucket creation error: {:#?}", e);
-        });
     }
 
-    let bucket = *Bucket::new(&s3_bucket, region, credentials)
-        .unwrap()
-        .with_path_style();
+    let (storage, local_storage): (Arc<dyn FileStorage>, _) = match storage_backend.as_str() {
+        "local" => {
+            let storage = Arc::new(local_storage());
+            (storage.clone(), Some(storage))
+        }
+        _ => (Arc::new(S3Storage::new(s3_bucket().await)), None),
+    };
 
     let resolver = Resolver::builder_with_config(
         hickory_resolver::system_conf::read_system_conf()
@@ -149,21 +157,32 @@ async fn main() {
     .build();
 
     let user_repo = MongoUserRepo::new(&db);
+    let session_repo = MongoSessionRepo::new(&db);
     let business_repo = MongoBusinessRepo::new(&db);
     // let domain_repo = MongoDomainRepo::new(&db);
     let store_reg_repo = MongoStoreRegRepo::new(&db);
     let product_repo = MongoProductRepo::new(mongo_client.clone());
     let order_repo = MongoOrderRepo::new(mongo_client.clone());
     let store_repo = MongoStoreRepo::new(mongo_client.clone());
+    let shipping_repo = MongoShippingRepo::new(mongo_client.clone());
+    let category_repo = MongoCategoryRepo::new(mongo_client.clone());
+    let cart_repo = MongoCartRepo::new(mongo_client.clone());
+    let discount_repo = MongoDiscountRepo::new(mongo_client.clone());
+    let tax_repo = MongoTaxRepo::new(mongo_client.clone());
     let file_repo = MongoFileRepo::new(mongo_client);
 
-    let user_service = UserService::new(user_repo);
+    let user_service = UserService::new(user_repo, session_repo);
     let business_service = BusinessService::new(business_repo);
     // let dns_service = DnsService::new(domain_repo, resolver);
     let product_service = ProductService::new(product_repo);
     let order_service = OrderService::new(order_repo);
     let store_service = StoreService::new(store_repo, store_reg_repo, resolver);
-    let file_service = FileService::new(file_repo, bucket);
+    let file_service = FileService::new(file_repo, storage);
+    let shipping_service = ShippingService::new(shipping_repo);
+    let category_service = CategoryService::new(category_repo);
+    let cart_service = CartService::new(cart_repo);
+    let discount_service = DiscountService::new(discount_repo);
+    let tax_service = TaxService::new(tax_repo);
 
     let state = Arc::new(State {
         user_service,
@@ -173,9 +192,17 @@ async fn main() {
         order_service,
         store_service,
         file_service,
+        shipping_service,
+        category_service,
+        cart_service,
+        discount_service,
+        tax_service,
+        local_storage,
         store_suffix,
     });
 
+    tokio::spawn(sweep_files_periodically(state.clone()));
+
     let api = Router::new()
         .route("/api/v1/health", axum::routing::get(|| async { "OK" }))
         .nest_packed(UserRoutes::make_router())
@@ -185,6 +212,15 @@ async fn main() {
         .nest_packed(OrderRoutes::make_router())
         .nest_packed(StoreRoutes::make_router())
         .nest_packed(FileRoutes::make_router())
+        .nest_packed(ShippingRoutes::make_router())
+        .nest_packed(CategoryRoutes::make_router())
+        .nest_packed(DiscountRoutes::make_router())
+        .nest_packed(TaxRoutes::make_router())
+        .nest_packed(local_storage_router())
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            middlewares::session::sessions,
+        ))
         .layer(CookieManagerLayer::new())
         .layer(TraceLayer::new_for_http())
         .with_state(state.clone());
@@ -198,7 +234,10 @@ async fn main() {
         .merge(PubStoreRoutes::make_router().1)
         .nest_packed(PubProductRoutes::make_router())
         .nest_packed(PubOrderRoutes::make_router())
-        // .layer(CookieManagerLayer::new())
+        .nest_packed(PubShippingRoutes::make_router())
+        .nest_packed(PubCartRoutes::make_router())
+        .nest_packed(PubFileRoutes::make_router())
+        .layer(CookieManagerLayer::new())
         .layer(TraceLayer::new_for_http())
         .with_state(state);
 
@@ -209,3 +248,91 @@ async fn main() {
     )
     .unwrap();
 }
+
+async fn s3_bucket() -> Bucket {
+    let s3_uri = std::env::var("STORAGE_URI").unwrap();
+    info!("STORAGE_URI = {}", s3_uri);
+    let s3_region = std::env::var("STORAGE_REGION").unwrap();
+    info!("STORAGE_REGION = {}", s3_region);
+    let s3_user = std::env::var("STORAGE_USER").unwrap();
+    info!("STORAGE_USER = {}", s3_user);
+    let s3_bucket = std::env::var("STORAGE_BUCKET_NAME").unwrap();
+    info!("STORAGE_BUCKET_NAME = {}", s3_bucket);
+    let s3_pass = std::env::var("STORAGE_PASSWORD").unwrap();
+    info!("STORAGE_PASSWORD = {}", s3_pass);
+
+    let region = Region::Custom {
+        endpoint: s3_uri,
+        region: s3_region,
+    };
+    let credentials = Credentials::new(Some(&s3_user), Some(&s3_pass), None, None, None).unwrap();
+
+    // TODO: dev only
+    {
+        let res = Bucket::create_with_path_style(
+            &s3_bucket,
+            region.clone(),
+            credentials.clone(),
+            Default::default(),
+        )
+        .await;
+        res.map(|v| {
+            debug!("bucket creation result: {:#?}", v.bucket);
+        })
+        .map_err(|e| {
+            debug!("bucket creation error: {:#?}", e);
+        });
+    }
+
+    *Bucket::new(&s3_bucket, region, credentials)
+        .unwrap()
+        .with_path_style()
+}
+
+fn local_storage() -> LocalStorage {
+    let root = std::env::var("STORAGE_LOCAL_ROOT").unwrap();
+    info!("STORAGE_LOCAL_ROOT = {}", root);
+    let public_url = std::env::var("STORAGE_PUBLIC_URL").unwrap();
+    info!("STORAGE_PUBLIC_URL = {}", public_url);
+    let secret = std::env::var("STORAGE_SIGNING_KEY").unwrap();
+
+    LocalStorage::new(root, &public_url, &secret)
+}
+
+/// The local storage routes take whole files, past the default body limit.
+fn local_storage_router() -> (&'static str, Router<AppState>) {
+    let (prefix, router) = LocalStorageRoutes::make_router();
+    (
+        prefix,
+        router.layer(DefaultBodyLimit::max(16 * 1024 * 1024)),
+    )
+}
+
+/// Leaves a dry-run file sweep report for every business once a day. The
+/// reports only lead to deletions once confirmed through the API.
+async fn sweep_files_periodically(state: AppState) {
+    let period = std::time::Duration::from_secs(24 * 60 * 60);
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+
+    loop {
+        interval.tick().await;
+
+        let business_ids = match state.business_service.list_active_ids().await {
+            Ok(ids) => ids,
+            Err(e) => {
+                error!("file sweep error: {:?}", e);
+                continue;
+            }
+        };
+
+        for business_id in business_ids {
+            if let Err(e) = state
+                .file_service
+                .background_sweep(&state.product_service, &state.store_service, business_id)
+                .await
+            {
+                error!("file sweep error for {}: {:?}", business_id, e);
+            }
+        }
+    }
+}
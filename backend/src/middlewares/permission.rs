@@ -0,0 +1,63 @@
+use axum::{
+    extract::{FromRequestParts, Request},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::extractors::cookies::FromCookies;
+use crate::platform::business::api::BusinessSession;
+use crate::utils::error::{ApiError, ApiResult};
+
+/// Runs ahead of routes declared with `#[route(..., perm = "resource:action")]`
+/// and rejects members of the current business lacking that permission.
+pub async fn require(
+    resource: &'static str,
+    action: &'static str,
+    request: Request,
+    next: Next,
+) -> ApiResult<Response> {
+    let (mut parts, body) = request.into_parts();
+    let FromCookies(session) =
+        FromCookies::<BusinessSession>::from_request_parts(&mut parts, &()).await?;
+
+    if !session.has_permission(resource, action, None) {
+        return Err(ApiError::permission_denied(resource, action));
+    }
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::platform::business::domain::PERMISSION_REGISTRY;
+    use crate::tenant::{
+        category::routes::CategoryRoutes, discount::routes::DiscountRoutes,
+        file::routes::FileRoutes, order::routes::OrderRoutes, product::routes::ProductRoutes,
+        shipping::routes::ShippingRoutes, store::routes::StoreRoutes, tax::routes::TaxRoutes,
+    };
+
+    #[test]
+    fn test_route_permissions_are_registered() {
+        let declared = [
+            ProductRoutes::PERMISSIONS,
+            OrderRoutes::PERMISSIONS,
+            StoreRoutes::PERMISSIONS,
+            FileRoutes::PERMISSIONS,
+            ShippingRoutes::PERMISSIONS,
+            CategoryRoutes::PERMISSIONS,
+            DiscountRoutes::PERMISSIONS,
+            TaxRoutes::PERMISSIONS,
+        ];
+
+        for (resource, action) in declared.concat() {
+            assert!(
+                PERMISSION_REGISTRY
+                    .iter()
+                    .any(|(r, actions)| *r == resource && actions.contains(&action)),
+                "{}:{} is missing from the registry",
+                resource,
+                action
+            );
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use tower_cookies::Cookies;
+use tracing::debug;
+
+use crate::platform::business::api::{BusinessSession, BusinessToken};
+use crate::platform::user::api::{RefreshToken, UserSession, UserToken};
+use crate::platform::user::routes::sign_out;
+use crate::utils::error::ApiResult;
+use crate::AppState;
+
+/// Keeps the session cookies in line with the session store before handlers
+/// read them: revoked sessions are signed out and expired access tokens are
+/// renewed from the refresh token.
+pub async fn sessions(
+    State(state): State<AppState>,
+    cookies: Cookies,
+    request: Request,
+    next: Next,
+) -> ApiResult<Response> {
+    let user = match UserToken::try_from(&cookies)? {
+        UserToken::UserSession(session) => {
+            match state.user_service.current_session(&session).await? {
+                Some(current) => {
+                    if current.two_factor != session.two_factor {
+                        cookies.add(UserToken::UserSession(current.clone()).try_into()?);
+                    }
+                    Some(current)
+                }
+                None => {
+                    debug!(session_id = %session.session_id.to_hex(), "Session revoked");
+                    sign_out(&cookies)?;
+                    None
+                }
+            }
+        }
+        UserToken::None => refresh(&state, &cookies).await?,
+        // A sign in or sign up in progress is left to finish
+        _ => None,
+    };
+
+    // Business tokens end with the session they were issued to, and follow
+    // the membership they were issued for
+    if let Ok(business) = BusinessSession::try_from(&cookies) {
+        let current = match user {
+            Some(ref user) if user.session_id == business.session_id => {
+                state
+                    .business_service
+                    .current_session(&business, user)
+                    .await?
+            }
+            _ => None,
+        };
+        match current {
+            Some(current) if current.membership_version == business.membership_version => {}
+            Some(current) => cookies.add(BusinessToken::BusinessSession(current).try_into()?),
+            None => cookies.add(BusinessToken::None.try_into()?),
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+async fn refresh(state: &AppState, cookies: &Cookies) -> ApiResult<Option<UserSession>> {
+    let Some(token) = RefreshToken::from_cookies(cookies) else {
+        return Ok(None);
+    };
+
+    match state.user_service.refresh(token).await {
+        Ok((session, refresh)) => {
+            cookies.add(UserToken::UserSession(session.clone()).try_into()?);
+            if let Some(refresh) = refresh {
+                cookies.add(refresh.into());
+            }
+            Ok(Some(session))
+        }
+        Err(e) => {
+            debug!(error = ?e, "Session not renewed");
+            sign_out(cookies)?;
+            Ok(None)
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use bson::oid::ObjectId;
 use chrono::{DateTime, Duration, Utc};
 use o2o::o2o;
 use serde::{Deserialize, Serialize};
@@ -6,6 +7,7 @@ use ts_rs::TS;
 
 use super::domain::*;
 use crate::{
+    platform::user::domain::SESSION_DAYS,
     types::{email::Email, id::Id, name::Name},
     utils::{
         error::ApiError,
@@ -53,6 +55,7 @@ pub struct BusinessMemberDto {
     pub role: MemberRole,
     pub status: MembershipStatus,
     pub permissions: Vec<Permission>,
+    pub role_template: Option<String>,
     #[from(~.into())]
     pub invited_by: Id,
     #[from(~.map(|dt| dt.to_chrono()))]
@@ -76,11 +79,31 @@ pub struct BusinessListQuery {
 pub struct BusinessSession {
     pub business_id: Id,
     pub user_id: Id,
+    // The user session it was issued to, it ends with it
+    pub session_id: ObjectId,
+    // Of the member when issued, a stale session is issued again
+    pub membership_version: u32,
     pub role: MemberRole,
     pub permissions: Vec<Permission>,
 }
 
 impl BusinessSession {
+    pub fn new(
+        business_id: ObjectId,
+        user_id: ObjectId,
+        session_id: ObjectId,
+        member: BusinessMember,
+    ) -> Self {
+        Self {
+            business_id: business_id.into(),
+            user_id: user_id.into(),
+            session_id,
+            membership_version: member.version,
+            role: member.role,
+            permissions: member.permissions,
+        }
+    }
+
     pub fn has_permission(&self, resource: &str, action: &str, target: Option<&str>) -> bool {
         if matches!(self.role, MemberRole::Owner) {
             return true;
@@ -124,7 +147,7 @@ impl TryFrom<&Cookies> for BusinessToken {
 impl<'c> TryInto<Cookie<'c>> for BusinessToken {
     type Error = ApiError;
     fn try_into(self) -> Result<Cookie<'c>, ApiError> {
-        let token = encode_jwt(self, Duration::days(7))?;
+        let token = encode_jwt(self, Duration::days(SESSION_DAYS))?;
         Ok(Cookie::build(("business_token", token)).path("/").build())
     }
 }
@@ -140,6 +163,8 @@ pub struct BusinessSwitch {
 pub struct InvitationCreate {
     pub email: Email,
     pub role: MemberRole,
+    // Defaults to the template keyed like the role
+    pub role_template: Option<String>,
     pub permissions: Option<Vec<Permission>>,
 }
 
@@ -154,7 +179,11 @@ pub struct InvitationAccept {
 pub struct MemberUpdate {
     pub email: Email,
     pub role: Option<MemberRole>,
+    pub role_template: Option<String>,
+    // Detaches the member from its role template
     pub permissions: Option<Vec<Permission>>,
+    // Active or suspended
+    pub status: Option<MembershipStatus>,
 }
 
 #[derive(Debug, Deserialize, TS)]
@@ -171,6 +200,39 @@ pub struct PermissionCheck {
     pub target: Option<String>,
 }
 
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct RoleTemplateUpdate {
+    pub key: String,
+    pub name: Option<String>,
+    pub permissions: Option<Vec<Permission>>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct RoleTemplateRemove {
+    pub key: String,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct RoleTemplateListResponse {
+    pub templates: Vec<RoleTemplate>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct PermissionResource {
+    pub resource: String,
+    pub actions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct PermissionRegistryResponse {
+    pub resources: Vec<PermissionResource>,
+}
+
 #[derive(Debug, Deserialize, TS)]
 #[ts(export, bound = "")]
 pub struct InvitationResend {
@@ -2,7 +2,8 @@ use bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-use crate::types::{email::Email, name::Name};
+use crate::tenant::order::domain::CustomOrderState;
+use crate::types::{currency::Currency, email::Email, name::Name};
 
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(rename_all = "snake_case")]
@@ -20,6 +21,18 @@ pub enum PlanType {
     Enterprise,
 }
 
+impl PlanType {
+    /// Total bytes a business on this plan may keep in file storage.
+    pub fn storage_quota(&self) -> u64 {
+        const GIB: u64 = 1024 * 1024 * 1024;
+        match self {
+            PlanType::Free => GIB,
+            PlanType::Premium => 20 * GIB,
+            PlanType::Enterprise => 200 * GIB,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(rename_all = "snake_case")]
 pub enum MemberRole {
@@ -30,6 +43,19 @@ pub enum MemberRole {
     ReadOnly,
 }
 
+impl MemberRole {
+    /// The role template members invited with this role get by default.
+    pub fn template_key(&self) -> &'static str {
+        match self {
+            MemberRole::Owner => "owner",
+            MemberRole::Admin => "admin",
+            MemberRole::Manager => "manager",
+            MemberRole::Member => "member",
+            MemberRole::ReadOnly => "read_only",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, TS)]
 #[serde(rename_all = "snake_case")]
 pub enum MembershipStatus {
@@ -56,7 +82,8 @@ impl Permission {
     }
 
     pub fn matches(&self, resource: &str, action: &str, target: Option<&str>) -> bool {
-        if self.resource != resource || self.action != action {
+        let wildcard = |granted: &str, required: &str| granted == "*" || granted == required;
+        if !wildcard(&self.resource, resource) || !wildcard(&self.action, action) {
             return false;
         }
 
@@ -79,6 +106,59 @@ impl Permission {
     }
 }
 
+/// Resources members can be granted access to, with their actions. Routes
+/// declare what they need with `#[route(..., perm = "resource:action")]`.
+pub const PERMISSION_REGISTRY: &[(&str, &[&str])] = &[
+    ("products", &["read", "write", "delete"]),
+    ("orders", &["read", "write"]),
+    ("stores", &["read", "write", "delete"]),
+    ("files", &["read", "write", "delete"]),
+    ("shipping", &["read", "write", "delete"]),
+    ("categories", &["read", "write", "delete"]),
+    ("discounts", &["read", "write", "delete"]),
+    ("taxes", &["read", "write", "delete"]),
+];
+
+/// A named permission set, e.g. "Order confirmer". Members assigned to it
+/// follow the edits made to it.
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+pub struct RoleTemplate {
+    pub key: String,
+    pub name: String,
+    pub permissions: Vec<Permission>,
+}
+
+impl RoleTemplate {
+    fn new(key: &str, name: &str, permissions: Vec<Permission>) -> Self {
+        Self {
+            key: key.to_string(),
+            name: name.to_string(),
+            permissions,
+        }
+    }
+
+    /// Built-in templates every business starts with, keyed like the roles.
+    pub fn presets() -> Vec<Self> {
+        let all = |action| Permission::new("*", action, Some("*"));
+        vec![
+            Self::new("admin", "Admin", vec![all("*")]),
+            Self::new("manager", "Manager", vec![all("read"), all("write")]),
+            Self::new("read_only", "Read only", vec![all("read")]),
+        ]
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let valid_key = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_';
+        if self.key.is_empty() || !self.key.chars().all(valid_key) {
+            return Err("Keys are made of lowercase letters, digits and underscores".to_string());
+        }
+        if self.name.trim().is_empty() {
+            return Err("Role templates need a name".to_string());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BusinessMember {
     pub email: Email,
@@ -86,14 +166,27 @@ pub struct BusinessMember {
     pub role: MemberRole,
     pub status: MembershipStatus,
     pub permissions: Vec<Permission>,
+    // Key of the role template the permissions come from. Members saved
+    // before templates existed load with an empty key, see
+    // `BusinessRecord::assign_legacy_templates`
+    #[serde(default = "legacy_role_template")]
+    pub role_template: Option<String>,
     pub invited_by: ObjectId,
     pub invitation_token: Option<String>, // Present when status is Pending
     pub invitation_expires_at: Option<DateTime>, // Present when status is Pending
     pub joined_at: Option<DateTime>,
+    // Bumped whenever the role, permissions or status change, so business
+    // sessions issued before are brought up to date
+    #[serde(default)]
+    pub version: u32,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
 
+fn legacy_role_template() -> Option<String> {
+    Some(String::new())
+}
+
 impl BusinessMember {
     pub fn new_invitation(
         email: Email,
@@ -112,10 +205,12 @@ impl BusinessMember {
             role,
             status: MembershipStatus::Pending,
             permissions: Vec::new(),
+            role_template: None,
             invited_by,
             invitation_token: Some(token),
             invitation_expires_at: Some(expires_at),
             joined_at: None,
+            version: 0,
             created_at: now,
             updated_at: now,
         }
@@ -135,10 +230,12 @@ impl BusinessMember {
             role,
             status: MembershipStatus::Active,
             permissions: Vec::new(),
+            role_template: None,
             invited_by,
             invitation_token: None,
             invitation_expires_at: None,
             joined_at: Some(now),
+            version: 0,
             created_at: now,
             updated_at: now,
         }
@@ -151,6 +248,16 @@ impl BusinessMember {
         }
     }
 
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, MembershipStatus::Active)
+    }
+
+    /// Records a change to what the member may do.
+    pub fn changed(&mut self) {
+        self.version += 1;
+        self.updated_at = DateTime::now();
+    }
+
     pub fn can_accept_invitation(&self) -> bool {
         matches!(self.status, MembershipStatus::Pending) && !self.is_invitation_expired()
     }
@@ -166,6 +273,24 @@ impl BusinessMember {
         )
     }
 
+    /// Whether the member holds everything `other` is given, so members
+    /// can't hand out more access than they have.
+    pub fn can_grant(&self, other: &BusinessMember) -> bool {
+        if matches!(self.role, MemberRole::Owner) {
+            return true;
+        }
+
+        (self.can_manage_members() || !other.can_manage_members())
+            && self.can_grant_permissions(&other.permissions)
+    }
+
+    /// Whether the member holds every one of the permissions.
+    pub fn can_grant_permissions(&self, permissions: &[Permission]) -> bool {
+        permissions
+            .iter()
+            .all(|p| self.has_permission(&p.resource, &p.action, p.scope.as_deref()))
+    }
+
     pub fn has_permission(&self, resource: &str, action: &str, target: Option<&str>) -> bool {
         // Owner has all permissions
         if matches!(self.role, MemberRole::Owner) {
@@ -184,19 +309,32 @@ impl BusinessMember {
         self.invitation_token = None;
         self.invitation_expires_at = None;
         self.joined_at = Some(DateTime::now());
-        self.updated_at = DateTime::now();
+        self.changed();
+    }
+
+    pub fn assign_template(&mut self, template: &RoleTemplate) {
+        self.permissions = template.permissions.clone();
+        self.role_template = Some(template.key.clone());
+        self.changed();
+    }
+
+    /// Permissions set by hand, detached from any role template.
+    pub fn set_permissions(&mut self, permissions: Vec<Permission>) {
+        self.permissions = permissions;
+        self.role_template = None;
+        self.changed();
     }
 
     pub fn add_permission(&mut self, permission: Permission) {
         self.permissions.push(permission);
-        self.updated_at = DateTime::now();
+        self.changed();
     }
 
     pub fn remove_permission(&mut self, resource: &str, action: &str, scope: Option<&str>) {
         self.permissions.retain(|p| {
             !(p.resource == resource && p.action == action && p.scope.as_deref() == scope)
         });
-        self.updated_at = DateTime::now();
+        self.changed();
     }
 }
 
@@ -211,6 +349,8 @@ pub struct BusinessRecord {
     pub status: BusinessStatus,
     pub settings: BusinessSettings,
     pub members: Vec<BusinessMember>,
+    #[serde(default = "RoleTemplate::presets")]
+    pub role_templates: Vec<RoleTemplate>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -220,6 +360,14 @@ pub struct BusinessSettings {
     pub allow_member_invitations: bool,
     pub require_invitation_approval: bool,
     pub default_member_permissions: Vec<Permission>,
+    #[serde(default)]
+    pub custom_order_states: Vec<CustomOrderState>,
+    // Prices are entered in it and new stores sell in it
+    #[serde(default)]
+    pub currency: Currency,
+    // Members can only open the business from sessions signed in with 2FA
+    #[serde(default)]
+    pub require_two_factor: bool,
 }
 
 impl Default for BusinessSettings {
@@ -228,10 +376,19 @@ impl Default for BusinessSettings {
             allow_member_invitations: true,
             require_invitation_approval: false,
             default_member_permissions: vec![Permission::new("*", "read", Some("*"))],
+            custom_order_states: Vec::new(),
+            currency: Currency::default(),
+            require_two_factor: false,
         }
     }
 }
 
+impl BusinessSettings {
+    pub fn find_custom_order_state(&self, key: &str) -> Option<&CustomOrderState> {
+        self.custom_order_states.iter().find(|s| s.key == key)
+    }
+}
+
 impl BusinessRecord {
     pub fn new(
         name: Name,
@@ -261,6 +418,7 @@ impl BusinessRecord {
             status: BusinessStatus::Active,
             settings: BusinessSettings::default(),
             members: vec![owner_member],
+            role_templates: RoleTemplate::presets(),
             created_at: now,
             updated_at: now,
         }
@@ -323,7 +481,38 @@ impl BusinessRecord {
         }
 
         self.members[member_index].status = MembershipStatus::Removed;
-        self.members[member_index].updated_at = DateTime::now();
+        self.members[member_index].changed();
+        self.updated_at = DateTime::now();
+        Ok(())
+    }
+
+    /// Suspends or reinstates a member who joined.
+    pub fn set_member_status(
+        &mut self,
+        email: &str,
+        status: MembershipStatus,
+    ) -> Result<(), String> {
+        let member = self
+            .members
+            .iter_mut()
+            .find(|m| m.email.as_str() == email)
+            .ok_or("Member not found")?;
+
+        if matches!(member.role, MemberRole::Owner) {
+            return Err("Cannot change the owner status".to_string());
+        }
+        if !matches!(
+            status,
+            MembershipStatus::Active | MembershipStatus::Suspended
+        ) || !matches!(
+            member.status,
+            MembershipStatus::Active | MembershipStatus::Suspended
+        ) {
+            return Err("Only joined members can be suspended or reinstated".to_string());
+        }
+
+        member.status = status;
+        member.changed();
         self.updated_at = DateTime::now();
         Ok(())
     }
@@ -340,8 +529,14 @@ impl BusinessRecord {
             return Err("Cannot change owner role".to_string());
         }
 
-        self.members[member_index].role = new_role;
-        self.members[member_index].updated_at = DateTime::now();
+        // The permissions follow the new role, as they would for an invitation
+        let template = self.find_role_template(new_role.template_key()).cloned();
+        let member = &mut self.members[member_index];
+        member.role = new_role;
+        match template {
+            Some(template) => member.assign_template(&template),
+            None => member.set_permissions(self.settings.default_member_permissions.clone()),
+        }
         self.updated_at = DateTime::now();
         Ok(())
     }
@@ -362,6 +557,105 @@ impl BusinessRecord {
         Ok(())
     }
 
+    pub fn find_role_template(&self, key: &str) -> Option<&RoleTemplate> {
+        self.role_templates.iter().find(|t| t.key == key)
+    }
+
+    pub fn add_role_template(&mut self, template: RoleTemplate) -> Result<(), String> {
+        template.validate()?;
+        if self.find_role_template(&template.key).is_some() {
+            return Err(format!("Role template '{}' already exists", template.key));
+        }
+
+        self.role_templates.push(template);
+        self.updated_at = DateTime::now();
+        Ok(())
+    }
+
+    /// Edits a template, the members assigned to it get its new permissions.
+    pub fn update_role_template(
+        &mut self,
+        key: &str,
+        name: Option<String>,
+        permissions: Option<Vec<Permission>>,
+    ) -> Result<&RoleTemplate, String> {
+        let index = self
+            .role_templates
+            .iter()
+            .position(|t| t.key == key)
+            .ok_or("Role template not found")?;
+
+        let mut template = self.role_templates[index].clone();
+        if let Some(name) = name {
+            template.name = name;
+        }
+        template.validate()?;
+
+        if let Some(permissions) = permissions {
+            template.permissions = permissions;
+            for member in &mut self.members {
+                if member.role_template.as_deref() == Some(key) {
+                    member.assign_template(&template);
+                }
+            }
+        }
+
+        self.role_templates[index] = template;
+        self.updated_at = DateTime::now();
+        Ok(&self.role_templates[index])
+    }
+
+    pub fn remove_role_template(&mut self, key: &str) -> Result<(), String> {
+        let in_use = self.members.iter().any(|m| {
+            m.role_template.as_deref() == Some(key)
+                && !matches!(m.status, MembershipStatus::Removed)
+        });
+        if in_use {
+            return Err("Role template is assigned to members".to_string());
+        }
+
+        let before = self.role_templates.len();
+        self.role_templates.retain(|t| t.key != key);
+        if self.role_templates.len() == before {
+            return Err("Role template not found".to_string());
+        }
+        self.updated_at = DateTime::now();
+        Ok(())
+    }
+
+    /// Gives members saved before role templates existed the template of
+    /// their role, as they would get when invited now. Their permissions
+    /// came from the old defaults and admins only had access through their
+    /// role. Their version moves on, so their sessions are issued again.
+    pub fn assign_legacy_templates(&mut self) {
+        for member in &mut self.members {
+            if member.role_template.as_deref() != Some("") {
+                continue;
+            }
+            let key = member.role.template_key();
+            match self.role_templates.iter().find(|t| t.key == key) {
+                Some(template) => member.assign_template(template),
+                None => member.role_template = None,
+            }
+        }
+    }
+
+    pub fn assign_role_template(&mut self, email: &str, key: &str) -> Result<(), String> {
+        let template = self
+            .find_role_template(key)
+            .cloned()
+            .ok_or("Role template not found")?;
+        let member = self
+            .members
+            .iter_mut()
+            .find(|m| m.email.as_str() == email)
+            .ok_or("Member not found")?;
+
+        member.assign_template(&template);
+        self.updated_at = DateTime::now();
+        Ok(())
+    }
+
     pub fn get_active_members(&self) -> Vec<&BusinessMember> {
         self.members
             .iter()
@@ -387,3 +681,165 @@ pub struct BusinessFilter {
     pub created_after: Option<DateTime>,
     pub created_before: Option<DateTime>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn business() -> BusinessRecord {
+        let owner = ObjectId::new();
+        let mut business = BusinessRecord::new(
+            Name::new("Shop").unwrap(),
+            owner,
+            Email::new("owner@example.com").unwrap(),
+            None,
+        );
+        business.members.push(BusinessMember::new_active_member(
+            Email::new("clerk@example.com").unwrap(),
+            ObjectId::new(),
+            MemberRole::Manager,
+            owner,
+        ));
+        business
+    }
+
+    #[test]
+    fn test_membership_changes_bump_version() {
+        let mut business = business();
+        let clerk = "clerk@example.com";
+
+        business
+            .update_member_role(clerk, MemberRole::ReadOnly)
+            .unwrap();
+        business
+            .set_member_status(clerk, MembershipStatus::Suspended)
+            .unwrap();
+        let member = business.find_member_by_email(clerk).unwrap();
+        assert_eq!(member.version, 2);
+        assert!(!member.is_active());
+
+        business.remove_member(clerk).unwrap();
+        let member = business.find_member_by_email(clerk).unwrap();
+        assert_eq!(member.version, 3);
+
+        // Removed members can't be brought back, nor owners suspended
+        assert!(business
+            .set_member_status(clerk, MembershipStatus::Active)
+            .is_err());
+        assert!(business
+            .set_member_status("owner@example.com", MembershipStatus::Suspended)
+            .is_err());
+    }
+
+    #[test]
+    fn test_role_template_changes_reach_members() {
+        let mut business = business();
+        let clerk = "clerk@example.com";
+        let read = Permission {
+            resource: "orders".to_string(),
+            action: "read".to_string(),
+            scope: Some("*".to_string()),
+        };
+
+        business.assign_role_template(clerk, "read_only").unwrap();
+        business
+            .update_role_template("read_only", None, Some(vec![read]))
+            .unwrap();
+        let member = business.find_member_by_email(clerk).unwrap();
+        assert_eq!(member.permissions.len(), 1);
+        assert!(member.permissions[0].matches("orders", "read", None));
+        assert_eq!(member.version, 2);
+
+        // Templates in use stay, custom permissions detach the member
+        assert!(business.remove_role_template("read_only").is_err());
+        business.members[1].set_permissions(Vec::new());
+        assert!(business.remove_role_template("read_only").is_ok());
+        assert!(business.find_role_template("read_only").is_none());
+    }
+
+    #[test]
+    fn test_legacy_members_get_their_role_template() {
+        let mut business = business();
+        let clerk = "clerk@example.com";
+        let narrowed = "narrowed@example.com";
+        let read = |resource| Permission::new(resource, "read", Some("*"));
+        business
+            .update_member_role(clerk, MemberRole::Admin)
+            .unwrap();
+        business.members.push(BusinessMember::new_active_member(
+            Email::new(narrowed).unwrap(),
+            ObjectId::new(),
+            MemberRole::Admin,
+            business.owner_id,
+        ));
+        business.members[2].set_permissions(vec![read("orders")]);
+
+        // Saved before templates: no key and the old default permissions
+        let mut saved = bson::to_document(&business).unwrap();
+        let members = saved.get_array_mut("members").unwrap();
+        let legacy = members[1].as_document_mut().unwrap();
+        legacy.remove("role_template");
+        legacy.insert("permissions", bson::to_bson(&[read("*")]).unwrap());
+
+        let mut business: BusinessRecord = bson::from_document(saved).unwrap();
+        business.assign_legacy_templates();
+        let member = business.find_member_by_email(clerk).unwrap();
+        assert_eq!(member.role_template.as_deref(), Some("admin"));
+        assert!(member.has_permission("orders", "write", None));
+
+        // Permissions set by hand are kept
+        let member = business.find_member_by_email(narrowed).unwrap();
+        assert_eq!(member.role_template, None);
+        assert!(!member.has_permission("orders", "write", None));
+    }
+
+    #[test]
+    fn test_role_change_reassigns_template() {
+        let mut business = business();
+        let clerk = "clerk@example.com";
+
+        business
+            .update_member_role(clerk, MemberRole::Admin)
+            .unwrap();
+        let member = business.find_member_by_email(clerk).unwrap();
+        assert_eq!(member.role_template.as_deref(), Some("admin"));
+        assert!(member.has_permission("settings", "delete", None));
+
+        // Demoted admins lose *:*
+        business
+            .update_member_role(clerk, MemberRole::ReadOnly)
+            .unwrap();
+        let member = business.find_member_by_email(clerk).unwrap();
+        assert_eq!(member.role_template.as_deref(), Some("read_only"));
+        assert!(member.has_permission("orders", "read", None));
+        assert!(!member.has_permission("orders", "write", None));
+        assert!(!member.has_permission("settings", "delete", None));
+        assert_eq!(member.version, 2);
+    }
+
+    #[test]
+    fn test_members_grant_only_what_they_hold() {
+        let mut business = business();
+        let clerk = "clerk@example.com";
+        business.assign_role_template(clerk, "manager").unwrap();
+        let manager = business.find_member_by_email(clerk).unwrap().clone();
+
+        let invite = |role, template: &str| {
+            let mut member = BusinessMember::new_invitation(
+                Email::new("new@example.com").unwrap(),
+                role,
+                ObjectId::new(),
+                "token".to_string(),
+                72,
+            );
+            member.assign_template(business.find_role_template(template).unwrap());
+            member
+        };
+
+        assert!(manager.can_grant(&invite(MemberRole::Member, "read_only")));
+        assert!(manager.can_grant(&invite(MemberRole::Manager, "manager")));
+        assert!(!manager.can_grant(&invite(MemberRole::Member, "admin")));
+        assert!(!manager.can_grant(&invite(MemberRole::Admin, "read_only")));
+        assert!(business.members[0].can_grant(&invite(MemberRole::Admin, "admin")));
+    }
+}
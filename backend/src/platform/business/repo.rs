@@ -6,6 +6,7 @@ use mongodb::{options::FindOptions, Collection, Database};
 use super::domain::*;
 use crate::utils::error::{ApiError, ApiResult};
 
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait BusinessRepo: Send + Sync {
     async fn create(&self, business: BusinessRecord) -> ApiResult<BusinessRecord>;
@@ -82,6 +83,12 @@ impl MongoBusinessRepo {
     }
 }
 
+/// Brings businesses saved by earlier versions up to date.
+fn loaded(mut business: BusinessRecord) -> BusinessRecord {
+    business.assign_legacy_templates();
+    business
+}
+
 #[async_trait]
 impl BusinessRepo for MongoBusinessRepo {
     async fn create(&self, business: BusinessRecord) -> ApiResult<BusinessRecord> {
@@ -103,7 +110,7 @@ impl BusinessRepo for MongoBusinessRepo {
             .await
             .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
 
-        Ok(business)
+        Ok(business.map(loaded))
     }
 
     async fn find_by_name(&self, name: &str) -> ApiResult<Option<BusinessRecord>> {
@@ -113,7 +120,7 @@ impl BusinessRepo for MongoBusinessRepo {
             .await
             .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
 
-        Ok(business)
+        Ok(business.map(loaded))
     }
 
     async fn update(
@@ -197,7 +204,7 @@ impl BusinessRepo for MongoBusinessRepo {
             .await
             .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
         {
-            businesses.push(business);
+            businesses.push(loaded(business));
         }
 
         Ok((businesses, total))
@@ -224,7 +231,7 @@ impl BusinessRepo for MongoBusinessRepo {
             .await
             .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
         {
-            businesses.push(business);
+            businesses.push(loaded(business));
         }
 
         Ok(businesses)
@@ -251,7 +258,7 @@ impl BusinessRepo for MongoBusinessRepo {
             .await
             .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
         {
-            businesses.push(business);
+            businesses.push(loaded(business));
         }
 
         Ok(businesses)
@@ -267,6 +274,6 @@ impl BusinessRepo for MongoBusinessRepo {
             .await
             .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
 
-        Ok(business)
+        Ok(business.map(loaded))
     }
 }
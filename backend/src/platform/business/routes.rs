@@ -6,7 +6,7 @@ use tower_cookies::Cookies;
 use super::api::*;
 use crate::extractors::cookies::FromCookies;
 use crate::extractors::json::Json;
-use crate::platform::business::domain::BusinessSettings;
+use crate::platform::business::domain::{BusinessSettings, RoleTemplate, PERMISSION_REGISTRY};
 use crate::platform::user::api::{MessageResponse, UserSession};
 use crate::utils::error::ApiResult;
 use crate::AppState;
@@ -105,11 +105,19 @@ impl BusinessRoutes {
         FromCookies(user_token): FromCookies<UserSession>,
         #[json] settings: BusinessSettings,
     ) -> ApiResult<Json<BusinessDto>> {
-        state
+        let business_id = business_token.business_id;
+        let currency = settings.currency.clone();
+
+        let business = state
             .business_service
             .update_business_settings(business_token, user_token, settings)
-            .await
-            .map(Json)
+            .await?;
+        state
+            .store_service
+            .set_base_currency(business_id, &currency)
+            .await?;
+
+        Ok(Json(business))
     }
 
     /// Create an invitation to join the business
@@ -203,6 +211,82 @@ impl BusinessRoutes {
         }))
     }
 
+    /// List the role templates of the business
+    #[route(method = post, path = "/roles/list", res = RoleTemplateListResponse)]
+    async fn list_role_templates(
+        State(state): State<AppState>,
+        FromCookies(business_token): FromCookies<BusinessSession>,
+    ) -> ApiResult<Json<RoleTemplateListResponse>> {
+        let templates = state
+            .business_service
+            .list_role_templates(business_token)
+            .await?;
+
+        Ok(Json(RoleTemplateListResponse { templates }))
+    }
+
+    /// Create a role template
+    #[route(method = post, path = "/roles", res = RoleTemplate)]
+    async fn create_role_template(
+        State(state): State<AppState>,
+        FromCookies(business_token): FromCookies<BusinessSession>,
+        FromCookies(user_token): FromCookies<UserSession>,
+        #[json] template: RoleTemplate,
+    ) -> ApiResult<Json<RoleTemplate>> {
+        state
+            .business_service
+            .create_role_template(business_token, user_token, template)
+            .await
+            .map(Json)
+    }
+
+    /// Edit a role template and the permissions of the members assigned to it
+    #[route(method = put, path = "/roles/edit", res = RoleTemplate)]
+    async fn update_role_template(
+        State(state): State<AppState>,
+        FromCookies(business_token): FromCookies<BusinessSession>,
+        FromCookies(user_token): FromCookies<UserSession>,
+        #[json] update_req: RoleTemplateUpdate,
+    ) -> ApiResult<Json<RoleTemplate>> {
+        state
+            .business_service
+            .update_role_template(business_token, user_token, update_req)
+            .await
+            .map(Json)
+    }
+
+    /// Remove a role template no member is assigned to
+    #[route(method = delete, path = "/roles", res = MessageResponse)]
+    async fn remove_role_template(
+        State(state): State<AppState>,
+        FromCookies(business_token): FromCookies<BusinessSession>,
+        FromCookies(user_token): FromCookies<UserSession>,
+        #[json] remove_req: RoleTemplateRemove,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .business_service
+            .remove_role_template(business_token, user_token, remove_req.key)
+            .await?;
+
+        Ok(Json(MessageResponse {
+            message: "Role template removed successfully".to_string(),
+        }))
+    }
+
+    /// List the resources and actions permissions can be granted for
+    #[route(method = get, path = "/permissions/registry", res = PermissionRegistryResponse)]
+    async fn permission_registry() -> ApiResult<Json<PermissionRegistryResponse>> {
+        let resources = PERMISSION_REGISTRY
+            .iter()
+            .map(|(resource, actions)| PermissionResource {
+                resource: resource.to_string(),
+                actions: actions.iter().map(|a| a.to_string()).collect(),
+            })
+            .collect();
+
+        Ok(Json(PermissionRegistryResponse { resources }))
+    }
+
     /// Check if current user has specific permission
     #[route(method = post, path = "/permissions/check", res = PermissionCheckResponse)]
     async fn check_permission(
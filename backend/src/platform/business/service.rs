@@ -55,6 +55,34 @@ impl<B: BusinessRepo> BusinessService<B> {
         Ok(BusinessDto::from(business))
     }
 
+    /// Ids of all active businesses, for background jobs.
+    pub async fn list_active_ids(&self) -> ApiResult<Vec<Id>> {
+        let filter = BusinessFilter {
+            status: Some(BusinessStatus::Active),
+            ..Default::default()
+        };
+
+        let mut ids = Vec::new();
+        let mut page = 1;
+        loop {
+            let (businesses, total) = self.business_repo.list(filter.clone(), page, 100).await?;
+
+            ids.extend(businesses.iter().map(|b| Id::from(b._id)));
+            if businesses.is_empty() || (page * 100) as u64 >= total {
+                return Ok(ids);
+            }
+            page += 1;
+        }
+    }
+
+    pub async fn get_settings(&self, business_id: Id) -> ApiResult<BusinessSettings> {
+        self.business_repo
+            .find_by_id(business_id.into_inner())
+            .await?
+            .map(|business| business.settings)
+            .ok_or_else(|| ApiError::not_found("business", "Business not found"))
+    }
+
     pub async fn list_user_businesses(
         &self,
         user: UserSession,
@@ -108,12 +136,26 @@ impl<B: BusinessRepo> BusinessService<B> {
             72,
         );
 
-        if let Some(permissions) = invitation.permissions {
+        if let Some(ref key) = invitation.role_template {
+            let template = business
+                .find_role_template(key)
+                .ok_or_else(|| ApiError::not_found("role template", key.clone()))?;
+            new_member.assign_template(template);
+        } else if let Some(permissions) = invitation.permissions {
             new_member.permissions = permissions;
+        } else if let Some(template) = business.find_role_template(new_member.role.template_key()) {
+            new_member.assign_template(template);
         } else {
             new_member.permissions = business.settings.default_member_permissions.clone();
         }
 
+        if !inviter_member.can_grant(&new_member) {
+            return Err(ApiError::forbidden(
+                "invitation",
+                "Members can only be given permissions the inviter has",
+            ));
+        }
+
         business
             .add_invitation(new_member)
             .map_err(|e| ApiError::conflict("invitation", e.to_string()))?;
@@ -175,23 +217,10 @@ impl<B: BusinessRepo> BusinessService<B> {
         user: UserSession,
         update_req: MemberUpdate,
     ) -> ApiResult<BusinessMemberDto> {
-        let mut business = self
-            .business_repo
-            .find_by_id(business_session.business_id.into_inner())
-            .await?
-            .ok_or_else(|| ApiError::not_found("business", "Business not found"))?;
-
-        // Check if user can manage members
-        let user_member = business
-            .find_member_by_user_id(&user.user_id)
-            .ok_or_else(|| ApiError::forbidden("business", "Not a member of this business"))?;
-
-        if !user_member.can_manage_members() {
-            return Err(ApiError::forbidden(
-                "business",
-                "No permission to manage members",
-            ));
-        }
+        let (mut business, actor) = self.find_managed(&business_session, &user).await?;
+        let granted = update_req.role.is_some()
+            || update_req.role_template.is_some()
+            || update_req.permissions.is_some();
 
         // Update role if provided
         if let Some(new_role) = update_req.role {
@@ -200,6 +229,12 @@ impl<B: BusinessRepo> BusinessService<B> {
                 .map_err(|e| ApiError::unauthorized(e.to_string()))?;
         }
 
+        if let Some(ref key) = update_req.role_template {
+            business
+                .assign_role_template(update_req.email.as_str(), key)
+                .map_err(|e| ApiError::validation("role_template", e))?;
+        }
+
         // Update permissions if provided
         if let Some(new_permissions) = update_req.permissions {
             let member_index = business
@@ -208,8 +243,26 @@ impl<B: BusinessRepo> BusinessService<B> {
                 .position(|m| m.email == update_req.email)
                 .ok_or_else(|| ApiError::not_found("business", "Member not found"))?;
 
-            business.members[member_index].permissions = new_permissions;
-            business.members[member_index].updated_at = DateTime::now();
+            business.members[member_index].set_permissions(new_permissions);
+        }
+
+        // Admins whose permissions were narrowed can't win them back
+        if granted {
+            let member = business
+                .find_member_by_email(update_req.email.as_str())
+                .ok_or_else(|| ApiError::not_found("business", "Member not found"))?;
+            if !actor.can_grant(member) {
+                return Err(ApiError::forbidden(
+                    "business",
+                    "Members can only be given permissions you have",
+                ));
+            }
+        }
+
+        if let Some(status) = update_req.status {
+            business
+                .set_member_status(update_req.email.as_str(), status)
+                .map_err(|e| ApiError::validation("status", e))?;
         }
 
         let updated_business = self
@@ -230,13 +283,31 @@ impl<B: BusinessRepo> BusinessService<B> {
         user: UserSession,
         member_email: String,
     ) -> ApiResult<()> {
-        let mut business = self
+        let (mut business, _) = self.find_managed(&business_session, &user).await?;
+
+        business
+            .remove_member(&member_email)
+            .map_err(|e| ApiError::unauthorized(e))?;
+
+        self.business_repo
+            .update(business_session.business_id.into_inner(), business)
+            .await?;
+        Ok(())
+    }
+
+    /// The business and the member acting on it, once checked they may
+    /// manage its members.
+    async fn find_managed(
+        &self,
+        business_session: &BusinessSession,
+        user: &UserSession,
+    ) -> ApiResult<(BusinessRecord, BusinessMember)> {
+        let business = self
             .business_repo
             .find_by_id(business_session.business_id.into_inner())
             .await?
             .ok_or_else(|| ApiError::not_found("business", "Business not found"))?;
 
-        // Check if user can manage members
         let user_member = business
             .find_member_by_user_id(&user.user_id)
             .ok_or_else(|| ApiError::forbidden("business", "Not a member of this business"))?;
@@ -248,13 +319,90 @@ impl<B: BusinessRepo> BusinessService<B> {
             ));
         }
 
+        let actor = user_member.clone();
+        Ok((business, actor))
+    }
+
+    pub async fn list_role_templates(
+        &self,
+        business_session: BusinessSession,
+    ) -> ApiResult<Vec<RoleTemplate>> {
+        let business = self
+            .business_repo
+            .find_by_id(business_session.business_id.into_inner())
+            .await?
+            .ok_or_else(|| ApiError::not_found("business", "Business not found"))?;
+
+        Ok(business.role_templates)
+    }
+
+    pub async fn create_role_template(
+        &self,
+        business_session: BusinessSession,
+        user: UserSession,
+        template: RoleTemplate,
+    ) -> ApiResult<RoleTemplate> {
+        let (mut business, actor) = self.find_managed(&business_session, &user).await?;
+        if !actor.can_grant_permissions(&template.permissions) {
+            return Err(ApiError::forbidden(
+                "role template",
+                "Templates can only hold permissions you have",
+            ));
+        }
+
         business
-            .remove_member(&member_email)
-            .map_err(|e| ApiError::unauthorized(e))?;
+            .add_role_template(template.clone())
+            .map_err(|e| ApiError::validation("key", e))?;
 
-        self.business_repo
-            .update(business_session.business_id.into_inner(), business)
-            .await?;
+        self.business_repo.update(business._id, business).await?;
+        Ok(template)
+    }
+
+    /// Members assigned to the template get its new permissions right away.
+    pub async fn update_role_template(
+        &self,
+        business_session: BusinessSession,
+        user: UserSession,
+        update_req: RoleTemplateUpdate,
+    ) -> ApiResult<RoleTemplate> {
+        let (mut business, actor) = self.find_managed(&business_session, &user).await?;
+        if business.find_role_template(&update_req.key).is_none() {
+            return Err(ApiError::not_found("role template", update_req.key));
+        }
+        if let Some(ref permissions) = update_req.permissions {
+            if !actor.can_grant_permissions(permissions) {
+                return Err(ApiError::forbidden(
+                    "role template",
+                    "Templates can only hold permissions you have",
+                ));
+            }
+        }
+
+        let template = business
+            .update_role_template(&update_req.key, update_req.name, update_req.permissions)
+            .map_err(|e| ApiError::validation("name", e))?
+            .clone();
+
+        self.business_repo.update(business._id, business).await?;
+        Ok(template)
+    }
+
+    pub async fn remove_role_template(
+        &self,
+        business_session: BusinessSession,
+        user: UserSession,
+        key: String,
+    ) -> ApiResult<()> {
+        let (mut business, _) = self.find_managed(&business_session, &user).await?;
+        if business.find_role_template(&key).is_none() {
+            return Err(ApiError::not_found("role template", key));
+        }
+
+        business
+            .remove_role_template(&key)
+            .map_err(|e| ApiError::conflict("role template", e))?;
+
+        self.business_repo.update(business._id, business).await?;
         Ok(())
     }
 
@@ -331,31 +479,57 @@ impl<B: BusinessRepo> BusinessService<B> {
             .ok_or_else(|| ApiError::not_found("business", "Business not found"))?;
 
         // Verify user is a member of this business
-        business
+        let member = business
             .find_member_by_user_id(&user.user_id)
+            .filter(|m| m.is_active())
+            .cloned()
             .ok_or_else(|| ApiError::forbidden("business", "Not a member of this business"))?;
 
-        let member = business
-            .members
-            .into_iter()
-            .find(|m| {
-                if let Some(id) = m.user_id {
-                    id == user.user_id
+        if business.settings.require_two_factor && !user.two_factor {
+            return Err(ApiError::forbidden(
+                "business",
+                "This business requires signing in with two-factor authentication",
+            ));
+        }
+
+        Ok(BusinessToken::BusinessSession(BusinessSession::new(
+            business._id,
+            user.user_id,
+            user.session_id,
+            member,
+        )))
+    }
+
+    /// The session as it should be now: none once the user is no longer an
+    /// active member or lacks the second factor the business requires,
+    /// issued again when the membership changed since.
+    pub async fn current_session(
+        &self,
+        session: &BusinessSession,
+        user: &UserSession,
+    ) -> ApiResult<Option<BusinessSession>> {
+        let Some(business) = self
+            .business_repo
+            .find_by_id(session.business_id.into_inner())
+            .await?
+        else {
+            return Ok(None);
+        };
+        if business.settings.require_two_factor && !user.two_factor {
+            return Ok(None);
+        }
+
+        let user_id = session.user_id.into_inner();
+        Ok(business
+            .find_member_by_user_id(&user_id)
+            .filter(|m| m.is_active())
+            .map(|member| {
+                if member.version == session.membership_version {
+                    session.clone()
                 } else {
-                    false
+                    BusinessSession::new(business._id, user_id, session.session_id, member.clone())
                 }
-            })
-            .ok_or(ApiError::forbidden(
-                "business",
-                "Not a member of this business",
-            ))?;
-
-        Ok(BusinessToken::BusinessSession(BusinessSession {
-            user_id: user.user_id.into(),
-            business_id: business._id.into(),
-            role: member.role,
-            permissions: member.permissions,
-        }))
+            }))
     }
 
     pub async fn get_business_statistics(
@@ -408,6 +582,32 @@ impl<B: BusinessRepo> BusinessService<B> {
             ));
         }
 
+        let mut keys = std::collections::HashSet::new();
+        for state in &settings.custom_order_states {
+            let key = state.key.trim();
+            if key.is_empty() || state.label.trim().is_empty() {
+                return Err(ApiError::validation(
+                    "custom_order_states",
+                    "Custom order states need a key and a label",
+                ));
+            }
+            if !keys.insert(key) {
+                return Err(ApiError::validation(
+                    "custom_order_states",
+                    format!("Duplicate custom order state '{}'", key),
+                ));
+            }
+        }
+
+        // Whoever turns it on would be locked out without it
+        if settings.require_two_factor && !business.settings.require_two_factor && !user.two_factor
+        {
+            return Err(ApiError::forbidden(
+                "business",
+                "Sign in with two-factor authentication before requiring it",
+            ));
+        }
+
         business.settings = settings;
         business.updated_at = DateTime::now();
 
@@ -481,3 +681,136 @@ impl<B: BusinessRepo> BusinessService<B> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+
+    use super::*;
+    use crate::platform::business::repo::MockBusinessRepo;
+    use crate::types::email::Email;
+    use crate::types::name::Name;
+
+    const ADMIN: &str = "admin@example.com";
+
+    /// A business whose admin was narrowed down to reading orders, with
+    /// the sessions of that admin.
+    fn narrowed_admin(
+        template: Option<&str>,
+        updates: usize,
+    ) -> (
+        BusinessService<MockBusinessRepo>,
+        BusinessSession,
+        UserSession,
+    ) {
+        let owner = ObjectId::new();
+        let admin = ObjectId::new();
+        let mut business = BusinessRecord::new(
+            Name::new("Shop").unwrap(),
+            owner,
+            Email::new("owner@example.com").unwrap(),
+            None,
+        );
+        business.members.push(BusinessMember::new_active_member(
+            Email::new(ADMIN).unwrap(),
+            admin,
+            MemberRole::Admin,
+            owner,
+        ));
+        match template {
+            Some(key) => business.assign_role_template(ADMIN, key).unwrap(),
+            None => business.members[1].set_permissions(vec![Permission::new(
+                "orders",
+                "read",
+                Some("*"),
+            )]),
+        }
+
+        let member = business.members[1].clone();
+        let business_session =
+            BusinessSession::new(business._id, admin, ObjectId::new(), member.clone());
+        let user = UserSession {
+            user_id: admin,
+            email: member.email,
+            session_id: business_session.session_id,
+            two_factor: false,
+        };
+
+        let mut repo = MockBusinessRepo::new();
+        repo.expect_find_by_id()
+            .returning(move |_| Ok(Some(business.clone())));
+        repo.expect_update()
+            .times(updates)
+            .returning(|_, business| Ok(business));
+        (BusinessService::new(repo), business_session, user)
+    }
+
+    fn everything() -> Vec<Permission> {
+        vec![Permission::new("*", "*", Some("*"))]
+    }
+
+    fn is_forbidden<T>(result: ApiResult<T>) -> bool {
+        matches!(result, Err(ApiError::Forbidden { .. }))
+    }
+
+    #[tokio::test]
+    async fn test_narrowed_admin_cant_grant_themselves_more() {
+        let (service, business, user) = narrowed_admin(None, 0);
+        let update = |role_template: Option<&str>, permissions| MemberUpdate {
+            email: Email::new(ADMIN).unwrap(),
+            role: None,
+            role_template: role_template.map(str::to_string),
+            permissions,
+            status: None,
+        };
+
+        let result = service
+            .update_member(
+                business.clone(),
+                user.clone(),
+                update(None, Some(everything())),
+            )
+            .await;
+        assert!(is_forbidden(result));
+
+        let result = service
+            .update_member(business, user, update(Some("admin"), None))
+            .await;
+        assert!(is_forbidden(result));
+    }
+
+    #[tokio::test]
+    async fn test_narrowed_admin_cant_create_wider_templates() {
+        let (service, business, user) = narrowed_admin(None, 0);
+        let template = RoleTemplate {
+            key: "everything".to_string(),
+            name: "Everything".to_string(),
+            permissions: everything(),
+        };
+
+        let result = service.create_role_template(business, user, template).await;
+        assert!(is_forbidden(result));
+    }
+
+    #[tokio::test]
+    async fn test_narrowed_admin_cant_widen_their_template() {
+        let (service, business, user) = narrowed_admin(Some("read_only"), 1);
+        let update = |permissions| RoleTemplateUpdate {
+            key: "read_only".to_string(),
+            name: None,
+            permissions: Some(permissions),
+        };
+
+        let result = service
+            .update_role_template(business.clone(), user.clone(), update(everything()))
+            .await;
+        assert!(is_forbidden(result));
+
+        // Narrowing it further is fine
+        let orders = vec![Permission::new("orders", "read", Some("*"))];
+        let result = service
+            .update_role_template(business, user, update(orders))
+            .await;
+        assert!(result.is_ok());
+    }
+}
@@ -44,6 +44,10 @@ pub enum AuthStep {
         otp: String,
         password: Password,
     },
+    // An authenticator or recovery code, after a login
+    TwoFactor {
+        code: String,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,13 +67,32 @@ pub enum UserToken {
         email: Email,
         otp_hash: Hash,
     },
+    // The password was right, the second factor is still to come
+    TwoFactorPending {
+        user_id: ObjectId,
+    },
     UserSession(UserSession),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserSession {
     pub user_id: ObjectId,
     pub email: Email,
+    pub session_id: ObjectId,
+    #[serde(default)]
+    pub two_factor: bool,
+}
+
+impl UserSession {
+    /// A session signing in, stored along its first refresh token.
+    pub fn new(user_id: ObjectId, email: Email, two_factor: bool) -> Self {
+        Self {
+            user_id,
+            email,
+            session_id: ObjectId::new(),
+            two_factor,
+        }
+    }
 }
 
 impl UserToken {
@@ -79,6 +102,7 @@ impl UserToken {
             UserToken::SignupEmail { .. } => "Signup Email",
             UserToken::SignupPhone { .. } => "Signup Phone",
             UserToken::ResetPassword { .. } => "Reset Password",
+            UserToken::TwoFactorPending { .. } => "Two Factor Pending",
             UserToken::UserSession { .. } => "User Session",
         }
     }
@@ -98,11 +122,55 @@ impl TryFrom<&Cookies> for UserToken {
 impl<'c> TryInto<Cookie<'c>> for UserToken {
     type Error = ApiError;
     fn try_into(self) -> Result<Cookie<'c>, ApiError> {
-        let token = encode_jwt(self, Duration::days(7))?;
+        let ttl = match self {
+            UserToken::UserSession(_) => Duration::minutes(ACCESS_TOKEN_MINUTES),
+            UserToken::TwoFactorPending { .. } => Duration::minutes(TWO_FACTOR_PENDING_MINUTES),
+            _ => Duration::days(1),
+        };
+        let token = encode_jwt(self, ttl)?;
         Ok(Cookie::build(("user_token", token)).path("/").build())
     }
 }
 
+/// Renews the access token of a session, a new one is handed out on each use.
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    pub session_id: ObjectId,
+    pub secret: String,
+}
+
+impl RefreshToken {
+    pub const COOKIE: &'static str = "refresh_token";
+
+    pub fn hash(&self) -> String {
+        blake3::hash(self.secret.as_bytes()).to_hex().to_string()
+    }
+
+    pub fn from_cookies(cookies: &Cookies) -> Option<Self> {
+        let cookie = cookies.get(Self::COOKIE)?;
+        let (session_id, secret) = cookie.value().split_once('.')?;
+        Some(Self {
+            session_id: ObjectId::parse_str(session_id).ok()?,
+            secret: secret.to_string(),
+        })
+    }
+
+    pub fn removal<'c>() -> Cookie<'c> {
+        Cookie::build((Self::COOKIE, "")).path("/").build()
+    }
+}
+
+impl<'c> From<RefreshToken> for Cookie<'c> {
+    fn from(token: RefreshToken) -> Self {
+        let value = format!("{}.{}", token.session_id.to_hex(), token.secret);
+        Cookie::build((RefreshToken::COOKIE, value))
+            .path("/")
+            .http_only(true)
+            .max_age(tower_cookies::cookie::time::Duration::days(SESSION_DAYS))
+            .build()
+    }
+}
+
 impl TryFrom<&Cookies> for UserSession {
     type Error = ApiError;
 
@@ -169,6 +237,8 @@ pub struct UserDto {
     pub last_name: Name,
     pub phone: PhoneNumber,
     pub status: UserStatus,
+    #[from(@.two_factor.as_ref().is_some_and(TwoFactor::is_enabled))]
+    pub two_factor_enabled: bool,
     #[from(~.to_chrono())]
     pub last_login: DateTime<Utc>,
     #[from(~.to_chrono())]
@@ -184,6 +254,57 @@ pub struct LoginResponse {
     pub expires_at: DateTime<Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct SessionDto {
+    pub id: String,
+    pub user_agent: Option<String>,
+    // The session these cookies belong to
+    pub current: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl SessionDto {
+    pub fn new(session: SessionRecord, current: &UserSession) -> Self {
+        Self {
+            id: session._id.to_hex(),
+            user_agent: session.user_agent,
+            current: session._id == current.session_id,
+            created_at: session.created_at.to_chrono(),
+            last_used_at: session.last_used_at.to_chrono(),
+            expires_at: session.expires_at.to_chrono(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct SessionListResponse {
+    pub sessions: Vec<SessionDto>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+/// What an authenticator app needs, `otpauth_url` being meant for a QR code.
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct TwoFactorSetup {
+    pub secret: String,
+    pub otpauth_url: String,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct RecoveryCodesResponse {
+    pub codes: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, TS)]
 #[ts(export, bound = "")]
 pub struct MessageResponse {
@@ -1,5 +1,7 @@
 use bson::{oid::ObjectId, DateTime};
+use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
 use ts_rs::TS;
 
 use crate::types::{email::Email, name::Name, phone::PhoneNumber, username::Username};
@@ -27,6 +29,8 @@ pub struct UserRecord {
     pub last_login: DateTime,
     pub login_attempts: u32,
     pub locked_until: Option<DateTime>,
+    #[serde(default)]
+    pub two_factor: Option<TwoFactor>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -54,6 +58,7 @@ impl UserRecord {
             last_login: now,
             login_attempts: 0,
             locked_until: None,
+            two_factor: None,
             created_at: now,
             updated_at: now,
         }
@@ -67,8 +72,176 @@ impl UserRecord {
         }
     }
 
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, UserStatus::Active)
+    }
+
     pub fn can_login(&self) -> bool {
-        matches!(self.status, UserStatus::Active) && !self.is_locked()
+        self.is_active() && !self.is_locked()
+    }
+
+    pub fn has_two_factor(&self) -> bool {
+        self.two_factor.as_ref().is_some_and(TwoFactor::is_enabled)
+    }
+}
+
+pub const TWO_FACTOR_ISSUER: &str = "Benxo";
+pub const RECOVERY_CODES: usize = 10;
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+// Codes of the steps right before and after are accepted, for clock drift
+const TOTP_SKEW: u64 = 1;
+
+/// TOTP second factor of an account. Recovery codes are only kept hashed and
+/// each one works once.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactor {
+    // Base32, as authenticator apps take it
+    pub secret: String,
+    pub recovery_hashes: Vec<String>,
+    // Unset until a first code proves the authenticator was set up
+    pub enabled_at: Option<DateTime>,
+    // Time step of the last code accepted, codes can't be replayed
+    pub last_step: Option<i64>,
+}
+
+impl TwoFactor {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret: Secret::Raw(secret).to_encoded().to_string(),
+            recovery_hashes: Vec::new(),
+            enabled_at: None,
+            last_step: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled_at.is_some()
+    }
+
+    pub fn totp(&self, account: &Email) -> Result<TOTP, String> {
+        let secret = Secret::Encoded(self.secret.clone())
+            .to_bytes()
+            .map_err(|e| e.to_string())?;
+        TOTP::new(
+            Algorithm::SHA1,
+            TOTP_DIGITS,
+            0,
+            TOTP_STEP,
+            secret,
+            Some(TWO_FACTOR_ISSUER.to_string()),
+            account.to_string(),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// Checks an authenticator code at `now`, in seconds since the epoch.
+    pub fn verify_code(&mut self, account: &Email, code: &str, now: u64) -> bool {
+        let Ok(totp) = self.totp(account) else {
+            return false;
+        };
+        let current = now / TOTP_STEP;
+        let steps = current.saturating_sub(TOTP_SKEW)..=current + TOTP_SKEW;
+        for step in steps {
+            let step_id = step as i64;
+            if self.last_step.is_some_and(|last| step_id <= last) {
+                continue;
+            }
+            if totp.check(code.trim(), step * TOTP_STEP) {
+                self.last_step = Some(step_id);
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn set_recovery_codes(&mut self, codes: &[String]) {
+        self.recovery_hashes = codes.iter().map(|c| Self::hash_recovery_code(c)).collect();
+    }
+
+    /// Spends a recovery code, whichever way it was typed.
+    pub fn use_recovery_code(&mut self, code: &str) -> bool {
+        let hash = Self::hash_recovery_code(code);
+        let Some(index) = self.recovery_hashes.iter().position(|h| *h == hash) else {
+            return false;
+        };
+        self.recovery_hashes.remove(index);
+        true
+    }
+
+    pub fn hash_recovery_code(code: &str) -> String {
+        let normalized: String = code
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        blake3::hash(normalized.as_bytes()).to_hex().to_string()
+    }
+}
+
+// Access tokens are short-lived, the session store is checked on every request
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+// Sessions idle for longer have to sign in again
+pub const SESSION_DAYS: i64 = 30;
+// Time left to enter the second factor once the password was checked
+pub const TWO_FACTOR_PENDING_MINUTES: i64 = 5;
+// A refresh token just rotated is still accepted from requests sent in parallel
+pub const REFRESH_GRACE_SECONDS: i64 = 30;
+
+/// A signed in device, backing the access tokens issued to it. Only hashes of
+/// refresh tokens are kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionRecord {
+    pub _id: ObjectId,
+    pub user_id: ObjectId,
+    pub refresh_hash: String,
+    pub previous_hash: Option<String>,
+    pub user_agent: Option<String>,
+    // Signed in with a second factor
+    #[serde(default)]
+    pub two_factor: bool,
+    pub created_at: DateTime,
+    pub last_used_at: DateTime,
+    pub expires_at: DateTime,
+    pub revoked_at: Option<DateTime>,
+}
+
+impl SessionRecord {
+    pub fn new(
+        id: ObjectId,
+        user_id: ObjectId,
+        refresh_hash: String,
+        user_agent: Option<String>,
+        two_factor: bool,
+    ) -> Self {
+        let now = DateTime::now();
+        Self {
+            _id: id,
+            user_id,
+            refresh_hash,
+            previous_hash: None,
+            user_agent,
+            two_factor,
+            created_at: now,
+            last_used_at: now,
+            expires_at: Self::expiry(),
+            revoked_at: None,
+        }
+    }
+
+    pub fn expiry() -> DateTime {
+        DateTime::from_chrono(Utc::now() + Duration::days(SESSION_DAYS))
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && DateTime::now() < self.expires_at
+    }
+
+    /// Whether `hash` is the refresh token replaced moments ago.
+    pub fn is_previous(&self, hash: &str) -> bool {
+        let grace = Duration::seconds(REFRESH_GRACE_SECONDS);
+        self.previous_hash.as_deref() == Some(hash)
+            && Utc::now() < self.last_used_at.to_chrono() + grace
     }
 }
 
@@ -81,3 +254,25 @@ pub struct UserFilter {
     pub created_after: Option<DateTime>,
     pub created_before: Option<DateTime>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_factor_codes() {
+        let email = Email::new("owner@example.com").unwrap();
+        let mut two_factor = TwoFactor::new(vec![7; 20]);
+        let now = 1_700_000_000;
+        let code = two_factor.totp(&email).unwrap().generate(now);
+
+        // A code is accepted a step late, but only once
+        assert!(two_factor.verify_code(&email, &code, now + TOTP_STEP));
+        assert!(!two_factor.verify_code(&email, &code, now + TOTP_STEP));
+        assert!(!two_factor.verify_code(&email, "000000", now + 10 * TOTP_STEP));
+
+        two_factor.set_recovery_codes(&["abcde-12345".to_string()]);
+        assert!(two_factor.use_recovery_code(" ABCDE 12345"));
+        assert!(!two_factor.use_recovery_code("abcde-12345"));
+    }
+}
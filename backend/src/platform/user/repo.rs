@@ -1,10 +1,14 @@
 use async_trait::async_trait;
 use bson::{doc, oid::ObjectId, to_bson, DateTime};
-use mongodb::{options::FindOptions, Collection, Database};
+use mongodb::{
+    options::{FindOptions, ReturnDocument},
+    Collection, Database,
+};
 
 use super::domain::*;
 use crate::utils::error::{ApiError, ApiResult};
 
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait UserRepo: Send + Sync {
     async fn create(&self, user: UserRecord) -> ApiResult<UserRecord>;
@@ -25,6 +29,16 @@ pub trait UserRepo: Send + Sync {
     async fn increment_login_attempts(&self, id: ObjectId) -> ApiResult<()>;
     async fn reset_login_attempts(&self, id: ObjectId) -> ApiResult<()>;
     async fn lock_user(&self, id: ObjectId, until: DateTime) -> ApiResult<()>;
+    /// Records the authenticator code of `step` as used, unless a code of
+    /// this step or a later one was. The user is returned when it wasn't.
+    async fn consume_totp_step(&self, id: ObjectId, step: i64) -> ApiResult<Option<UserRecord>>;
+    /// Spends the recovery code, unless it was already. The user is
+    /// returned when it wasn't.
+    async fn consume_recovery_code(
+        &self,
+        id: ObjectId,
+        hash: &str,
+    ) -> ApiResult<Option<UserRecord>>;
 }
 
 pub struct MongoUserRepo {
@@ -244,4 +258,179 @@ impl UserRepo for MongoUserRepo {
 
         Ok(())
     }
+
+    async fn consume_totp_step(&self, id: ObjectId, step: i64) -> ApiResult<Option<UserRecord>> {
+        let filter = doc! {
+            "_id": id,
+            "$or": [
+                { "two_factor.last_step": null },
+                { "two_factor.last_step": { "$lt": step } },
+            ],
+        };
+        let update = doc! { "$set": { "two_factor.last_step": step } };
+
+        self.collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
+
+    async fn consume_recovery_code(
+        &self,
+        id: ObjectId,
+        hash: &str,
+    ) -> ApiResult<Option<UserRecord>> {
+        let filter = doc! { "_id": id, "two_factor.recovery_hashes": hash };
+        let update = doc! { "$pull": { "two_factor.recovery_hashes": hash } };
+
+        self.collection
+            .find_one_and_update(filter, update)
+            .return_document(ReturnDocument::After)
+            .await
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait SessionRepo: Send + Sync {
+    async fn create(&self, session: SessionRecord) -> ApiResult<SessionRecord>;
+    async fn find_by_id(&self, id: ObjectId) -> ApiResult<Option<SessionRecord>>;
+    async fn list_active(&self, user_id: ObjectId) -> ApiResult<Vec<SessionRecord>>;
+    /// Replaces the refresh token hash, unless it changed since it was read.
+    async fn rotate(&self, id: ObjectId, from: &str, to: &str) -> ApiResult<bool>;
+    async fn revoke(&self, user_id: ObjectId, id: ObjectId) -> ApiResult<bool>;
+    async fn revoke_all(&self, user_id: ObjectId) -> ApiResult<u64>;
+    /// Records whether sessions passed a second factor, every session of the
+    /// user when no id is given.
+    async fn set_two_factor(
+        &self,
+        user_id: ObjectId,
+        id: Option<ObjectId>,
+        two_factor: bool,
+    ) -> ApiResult<u64>;
+}
+
+pub struct MongoSessionRepo {
+    collection: Collection<SessionRecord>,
+}
+
+impl MongoSessionRepo {
+    pub fn new(db: &Database) -> Self {
+        Self {
+            collection: db.collection("sessions"),
+        }
+    }
+}
+
+#[async_trait]
+impl SessionRepo for MongoSessionRepo {
+    async fn create(&self, session: SessionRecord) -> ApiResult<SessionRecord> {
+        self.collection
+            .insert_one(&session)
+            .await
+            .map_err(|e| ApiError::database(e.to_string()))?;
+
+        Ok(session)
+    }
+
+    async fn find_by_id(&self, id: ObjectId) -> ApiResult<Option<SessionRecord>> {
+        self.collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
+
+    async fn list_active(&self, user_id: ObjectId) -> ApiResult<Vec<SessionRecord>> {
+        let filter = doc! {
+            "user_id": user_id,
+            "revoked_at": null,
+            "expires_at": { "$gt": DateTime::now() },
+        };
+        let options = FindOptions::builder()
+            .sort(doc! { "last_used_at": -1 })
+            .build();
+
+        let mut cursor = self
+            .collection
+            .find(filter)
+            .with_options(options)
+            .await
+            .map_err(|e| ApiError::database(e.to_string()))?;
+
+        let mut sessions = Vec::new();
+        while cursor
+            .advance()
+            .await
+            .map_err(|e| ApiError::database(e.to_string()))?
+        {
+            sessions.push(
+                cursor
+                    .deserialize_current()
+                    .map_err(|e| ApiError::database(e.to_string()))?,
+            );
+        }
+
+        Ok(sessions)
+    }
+
+    async fn rotate(&self, id: ObjectId, from: &str, to: &str) -> ApiResult<bool> {
+        let filter = doc! { "_id": id, "refresh_hash": from, "revoked_at": null };
+        let update = doc! {
+            "$set": {
+                "refresh_hash": to,
+                "previous_hash": from,
+                "last_used_at": DateTime::now(),
+                "expires_at": SessionRecord::expiry(),
+            }
+        };
+
+        self.collection
+            .update_one(filter, update)
+            .await
+            .map(|result| result.modified_count == 1)
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
+
+    async fn revoke(&self, user_id: ObjectId, id: ObjectId) -> ApiResult<bool> {
+        let filter = doc! { "_id": id, "user_id": user_id, "revoked_at": null };
+        let update = doc! { "$set": { "revoked_at": DateTime::now() } };
+
+        self.collection
+            .update_one(filter, update)
+            .await
+            .map(|result| result.modified_count == 1)
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
+
+    async fn revoke_all(&self, user_id: ObjectId) -> ApiResult<u64> {
+        let filter = doc! { "user_id": user_id, "revoked_at": null };
+        let update = doc! { "$set": { "revoked_at": DateTime::now() } };
+
+        self.collection
+            .update_many(filter, update)
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
+
+    async fn set_two_factor(
+        &self,
+        user_id: ObjectId,
+        id: Option<ObjectId>,
+        two_factor: bool,
+    ) -> ApiResult<u64> {
+        let mut filter = doc! { "user_id": user_id, "revoked_at": null };
+        if let Some(id) = id {
+            filter.insert("_id", id);
+        }
+        let update = doc! { "$set": { "two_factor": two_factor } };
+
+        self.collection
+            .update_many(filter, update)
+            .await
+            .map(|result| result.modified_count)
+            .map_err(|e| ApiError::database(e.to_string()))
+    }
 }
@@ -1,12 +1,14 @@
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use macros::routes;
 use tower_cookies::Cookies;
 
 use crate::extractors::cookies::FromCookies;
 use crate::extractors::json::Json;
+use crate::platform::business::api::BusinessToken;
 use crate::platform::user::api::*;
-use crate::utils::error::ApiResult;
+use crate::types::id::Id;
+use crate::utils::error::{ApiError, ApiResult};
 use crate::AppState;
 
 pub struct UserRoutes;
@@ -17,17 +19,36 @@ impl UserRoutes {
     async fn auth(
         State(state): State<AppState>,
         cookies: Cookies,
+        headers: HeaderMap,
         FromCookies(token): FromCookies<UserToken>,
         #[json] auth_req: AuthStep,
     ) -> ApiResult<Json<MessageResponse>> {
-        let (token, msg) = state.user_service.auth(auth_req, token).await?;
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let (token, refresh, msg) = state.user_service.auth(auth_req, token, user_agent).await?;
         cookies.add(token.try_into()?);
+        if let Some(refresh) = refresh {
+            cookies.add(refresh.into());
+        }
         Ok(Json(msg))
     }
 
     #[route(method = delete, path = "/logout")]
     async fn logout(State(state): State<AppState>, cookies: Cookies) -> ApiResult<StatusCode> {
-        cookies.add(UserToken::None.try_into()?);
+        if let Ok(session) = UserSession::try_from(&cookies) {
+            let revoked = state
+                .user_service
+                .revoke_session(&session, session.session_id)
+                .await;
+            // Sessions revoked elsewhere are signed out all the same
+            if !matches!(revoked, Ok(()) | Err(ApiError::NotFound { .. })) {
+                return revoked.map(|_| StatusCode::OK);
+            }
+        }
+        sign_out(&cookies)?;
         Ok(StatusCode::OK)
     }
 
@@ -38,4 +59,130 @@ impl UserRoutes {
     ) -> ApiResult<Json<UserDto>> {
         state.user_service.me(token.user_id).await.map(Json)
     }
+
+    /// List the devices signed in to the account
+    #[route(method = post, path = "/sessions/list", res = SessionListResponse)]
+    async fn list_sessions(
+        State(state): State<AppState>,
+        FromCookies(token): FromCookies<UserSession>,
+    ) -> ApiResult<Json<SessionListResponse>> {
+        let sessions = state.user_service.list_sessions(&token).await?;
+        Ok(Json(SessionListResponse { sessions }))
+    }
+
+    /// Sign a device out
+    #[route(method = delete, path = "/sessions/{session_id}", res = MessageResponse)]
+    async fn revoke_session(
+        State(state): State<AppState>,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<UserSession>,
+        #[path] session_id: Id,
+    ) -> ApiResult<Json<MessageResponse>> {
+        let id = session_id.into_inner();
+        state.user_service.revoke_session(&token, id).await?;
+        if id == token.session_id {
+            sign_out(&cookies)?;
+        }
+
+        Ok(Json(MessageResponse {
+            message: "The session was signed out".to_string(),
+        }))
+    }
+
+    /// Sign every device out, this one included
+    #[route(method = delete, path = "/sessions", res = MessageResponse)]
+    async fn revoke_all_sessions(
+        State(state): State<AppState>,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<UserSession>,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .user_service
+            .revoke_all_sessions(token.user_id)
+            .await?;
+        sign_out(&cookies)?;
+
+        Ok(Json(MessageResponse {
+            message: "All sessions were signed out".to_string(),
+        }))
+    }
+
+    /// Start setting up an authenticator app
+    #[route(method = post, path = "/two-factor/setup", res = TwoFactorSetup)]
+    async fn setup_two_factor(
+        State(state): State<AppState>,
+        FromCookies(token): FromCookies<UserSession>,
+    ) -> ApiResult<Json<TwoFactorSetup>> {
+        state.user_service.setup_two_factor(&token).await.map(Json)
+    }
+
+    /// Confirm the authenticator app with a code, recovery codes are returned once
+    #[route(method = post, path = "/two-factor/enable", res = RecoveryCodesResponse)]
+    async fn enable_two_factor(
+        State(state): State<AppState>,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<UserSession>,
+        #[json] req: TwoFactorCode,
+    ) -> ApiResult<Json<RecoveryCodesResponse>> {
+        let codes = state
+            .user_service
+            .enable_two_factor(&token, &req.code)
+            .await?;
+        cookies.add(
+            UserToken::UserSession(UserSession {
+                two_factor: true,
+                ..token
+            })
+            .try_into()?,
+        );
+
+        Ok(Json(RecoveryCodesResponse { codes }))
+    }
+
+    /// Replace the recovery codes
+    #[route(method = post, path = "/two-factor/recovery-codes", res = RecoveryCodesResponse)]
+    async fn regenerate_recovery_codes(
+        State(state): State<AppState>,
+        FromCookies(token): FromCookies<UserSession>,
+        #[json] req: TwoFactorCode,
+    ) -> ApiResult<Json<RecoveryCodesResponse>> {
+        let codes = state
+            .user_service
+            .regenerate_recovery_codes(&token, &req.code)
+            .await?;
+        Ok(Json(RecoveryCodesResponse { codes }))
+    }
+
+    /// Turn two-factor authentication off
+    #[route(method = delete, path = "/two-factor", res = MessageResponse)]
+    async fn disable_two_factor(
+        State(state): State<AppState>,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<UserSession>,
+        #[json] req: TwoFactorCode,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .user_service
+            .disable_two_factor(&token, &req.code)
+            .await?;
+        cookies.add(
+            UserToken::UserSession(UserSession {
+                two_factor: false,
+                ..token
+            })
+            .try_into()?,
+        );
+
+        Ok(Json(MessageResponse {
+            message: "Two-factor authentication is off".to_string(),
+        }))
+    }
+}
+
+/// Drops the session cookies, the business one included.
+pub fn sign_out(cookies: &Cookies) -> ApiResult<()> {
+    cookies.add(UserToken::None.try_into()?);
+    cookies.add(BusinessToken::None.try_into()?);
+    cookies.remove(RefreshToken::removal());
+    Ok(())
 }
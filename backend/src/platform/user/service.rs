@@ -4,7 +4,7 @@ use chrono::{Duration, Utc};
 
 use super::api::*;
 use super::domain::*;
-use super::repo::UserRepo;
+use super::repo::{SessionRepo, UserRepo};
 use crate::platform::user::mail::send_verification_email;
 use crate::platform::user::mail::send_verification_otp;
 use crate::types::email::Email;
@@ -14,14 +14,17 @@ use crate::utils::rand::generate_otp;
 use crate::utils::serde_helpers::JsonOption;
 
 mod auth;
+mod session;
+mod two_factor;
 
-pub struct UserService<R: UserRepo> {
+pub struct UserService<R: UserRepo, S: SessionRepo> {
     repo: R,
+    sessions: S,
 }
 
-impl<R: UserRepo> UserService<R> {
-    pub fn new(repo: R) -> Self {
-        Self { repo }
+impl<R: UserRepo, S: SessionRepo> UserService<R, S> {
+    pub fn new(repo: R, sessions: S) -> Self {
+        Self { repo, sessions }
     }
 
     pub async fn me(&self, id: ObjectId) -> ApiResult<UserDto> {
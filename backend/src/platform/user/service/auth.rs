@@ -8,12 +8,31 @@ use crate::{
     utils::jwt::decode_jwt,
 };
 
-impl<R: UserRepo> UserService<R> {
+impl<R: UserRepo, S: SessionRepo> UserService<R, S> {
+    /// Runs a sign up, sign in or password reset step. Signing in starts a
+    /// session, returned with its refresh token.
     #[instrument(skip(self), fields(step = ?step, token_type = %token.type_name()))]
     pub async fn auth(
         &self,
         step: AuthStep,
         token: UserToken,
+        user_agent: Option<String>,
+    ) -> ApiResult<(UserToken, Option<RefreshToken>, MessageResponse)> {
+        let (token, message) = self.auth_step(step, token).await?;
+        let refresh = match token {
+            UserToken::UserSession(ref session) => {
+                Some(self.start_session(session, user_agent).await?)
+            }
+            _ => None,
+        };
+
+        Ok((token, refresh, message))
+    }
+
+    async fn auth_step(
+        &self,
+        step: AuthStep,
+        token: UserToken,
     ) -> ApiResult<(UserToken, MessageResponse)> {
         match (step, token) {
             (AuthStep::SignupEmail { email }, _) => self.handle_email_step(email).await,
@@ -52,6 +71,9 @@ impl<R: UserRepo> UserService<R> {
             (AuthStep::Login { email, password }, _) => {
                 self.handle_login_step(email, password).await
             }
+            (AuthStep::TwoFactor { code }, UserToken::TwoFactorPending { user_id }) => {
+                self.handle_two_factor_step(user_id, code).await
+            }
             (step, token) => {
                 warn!(
                     step = ?step,
@@ -193,10 +215,7 @@ impl<R: UserRepo> UserService<R> {
         info!(user_id = %user._id.to_hex(), "User created successfully");
 
         Ok((
-            UserToken::UserSession(UserSession {
-                user_id: user._id,
-                email: user.email,
-            }),
+            UserToken::UserSession(UserSession::new(user._id, user.email, false)),
             MessageResponse {
                 message: "You have successfully signed up".to_string(),
             },
@@ -247,6 +266,12 @@ impl<R: UserRepo> UserService<R> {
         otp_hash: blake3::Hash,
         password: Password,
     ) -> ApiResult<(UserToken, MessageResponse)> {
+        let provided_otp_hash = blake3::hash(otp.as_bytes());
+        if provided_otp_hash != otp_hash {
+            warn!("Invalid OTP provided");
+            return Err(ApiError::unauthorized("Invalid OTP"));
+        }
+
         let mut user = self
             .repo
             .find_by_email(email.as_str())
@@ -271,7 +296,10 @@ impl<R: UserRepo> UserService<R> {
 
         let _user = self.repo.update(id, user).await?;
 
-        info!(user_id = %id.to_hex(), "Password updated successfully");
+        // Whoever holds a session from before must sign in with the new one
+        let revoked = self.revoke_all_sessions(id).await?;
+
+        info!(user_id = %id.to_hex(), revoked, "Password updated successfully");
 
         Ok((
             UserToken::None,
@@ -317,21 +345,89 @@ impl<R: UserRepo> UserService<R> {
                 login_attempts = user.login_attempts,
                 "Invalid password provided"
             );
+            self.record_failed_login(&user).await?;
+            return Err(ApiError::unauthorized("Invalid credentials"));
+        }
 
-            self.repo.increment_login_attempts(user._id).await?;
+        self.check_can_login(&user)?;
 
-            if user.login_attempts >= 4 {
-                let lock_until = bson::DateTime::from_chrono(Utc::now() + Duration::hours(1));
-                self.repo.lock_user(user._id, lock_until).await?;
-                warn!(
-                    user_id = %user._id.to_hex(),
-                    "Account locked due to too many failed attempts"
-                );
-            }
+        // Failed attempts are only reset once the second factor is in too
+        if user.has_two_factor() {
+            info!(user_id = %user._id.to_hex(), "Second factor required");
+            return Ok((
+                UserToken::TwoFactorPending { user_id: user._id },
+                MessageResponse {
+                    message: "Enter the code from your authenticator app".to_string(),
+                },
+            ));
+        }
 
-            return Err(ApiError::unauthorized("Invalid credentials"));
+        self.complete_login(user, false).await
+    }
+
+    #[instrument(skip(self, code), fields(user_id = %user_id.to_hex()))]
+    async fn handle_two_factor_step(
+        &self,
+        user_id: ObjectId,
+        code: String,
+    ) -> ApiResult<(UserToken, MessageResponse)> {
+        let mut user = self
+            .repo
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ApiError::unauthorized("Invalid credentials"))?;
+        self.check_can_login(&user)?;
+
+        let email = user.email.clone();
+        let Some(two_factor) = user.two_factor.as_mut().filter(|t| t.is_enabled()) else {
+            warn!("Second factor no longer set up");
+            return Err(ApiError::invalid_token());
+        };
+
+        // The code is only spent if no request sent in parallel spent it first
+        let now = Utc::now().timestamp() as u64;
+        let consumed = if two_factor.verify_code(&email, &code, now) {
+            let step = two_factor.last_step.unwrap_or_default();
+            self.repo.consume_totp_step(user_id, step).await?
+        } else if two_factor.use_recovery_code(&code) {
+            let hash = TwoFactor::hash_recovery_code(&code);
+            let consumed = self.repo.consume_recovery_code(user_id, &hash).await?;
+            info!(
+                remaining = two_factor.recovery_hashes.len(),
+                "Recovery code used"
+            );
+            consumed
+        } else {
+            warn!(
+                login_attempts = user.login_attempts,
+                "Invalid second factor provided"
+            );
+            self.record_failed_login(&user).await?;
+            return Err(ApiError::unauthorized("Invalid code"));
+        };
+        let Some(user) = consumed else {
+            warn!("Second factor already used");
+            return Err(ApiError::unauthorized("Invalid code"));
+        };
+
+        self.complete_login(user, true).await
+    }
+
+    pub(super) async fn record_failed_login(&self, user: &UserRecord) -> ApiResult<()> {
+        self.repo.increment_login_attempts(user._id).await?;
+
+        if user.login_attempts >= 4 {
+            let lock_until = bson::DateTime::from_chrono(Utc::now() + Duration::hours(1));
+            self.repo.lock_user(user._id, lock_until).await?;
+            warn!(
+                user_id = %user._id.to_hex(),
+                "Account locked due to too many failed attempts"
+            );
         }
+        Ok(())
+    }
 
+    pub(super) fn check_can_login(&self, user: &UserRecord) -> ApiResult<()> {
         if user.is_locked() {
             warn!(user_id = %user._id.to_hex(), "Attempt to login to locked account");
             return Err(ApiError::unauthorized("Account is temporarily locked"));
@@ -341,12 +437,22 @@ impl<R: UserRepo> UserService<R> {
             warn!(user_id = %user._id.to_hex(), "Attempt to login to inactive account");
             return Err(ApiError::unauthorized("Account is not active"));
         }
+        Ok(())
+    }
 
+    async fn complete_login(
+        &self,
+        user: UserRecord,
+        two_factor: bool,
+    ) -> ApiResult<(UserToken, MessageResponse)> {
         self.repo.reset_login_attempts(user._id).await?;
         let id = user._id;
         let email = user.email.clone();
         let mut updated_user = user;
         updated_user.last_login = bson::DateTime::now();
+        // The whole record is written back, it must not restore the attempts
+        updated_user.login_attempts = 0;
+        updated_user.locked_until = None;
         let _ = self.repo.update(id, updated_user).await.map_err(|e| {
             error!(error = ?e, user_id = %id.to_hex(), "Failed to update user login timestamp");
             e
@@ -355,10 +461,57 @@ impl<R: UserRepo> UserService<R> {
         info!(user_id = %id.to_hex(), "Login successful");
 
         Ok((
-            UserToken::UserSession(UserSession { user_id: id, email }),
+            UserToken::UserSession(UserSession::new(id, email, two_factor)),
             MessageResponse {
                 message: "Login successful".to_string(),
             },
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::user::repo::{MockSessionRepo, MockUserRepo};
+    use crate::types::phone::PhoneNumber;
+
+    #[tokio::test]
+    async fn test_password_reset_ends_every_session() {
+        let email = Email::new("user@example.com").unwrap();
+        let user = UserRecord::new(
+            email.clone(),
+            Username::new("user").unwrap(),
+            Name::new("First").unwrap(),
+            Name::new("Last").unwrap(),
+            PhoneNumber::new("0662666666").unwrap(),
+            "hash".to_string(),
+        );
+        let user_id = user._id;
+
+        let mut repo = MockUserRepo::new();
+        repo.expect_find_by_email()
+            .returning(move |_| Ok(Some(user.clone())));
+        repo.expect_update().times(1).returning(|_, user| Ok(user));
+        let mut sessions = MockSessionRepo::new();
+        sessions
+            .expect_revoke_all()
+            .withf(move |id| *id == user_id)
+            .times(1)
+            .returning(|_| Ok(2));
+
+        let service = UserService::new(repo, sessions);
+        let otp = "123456".to_string();
+        let token = UserToken::ResetPassword {
+            email,
+            otp_hash: blake3::hash(otp.as_bytes()),
+        };
+        let step = AuthStep::ResetPasswordFinalize {
+            otp,
+            password: Password::new("correct-horse-battery").unwrap(),
+        };
+
+        let (token, refresh, _) = service.auth(step, token, None).await.unwrap();
+        assert!(matches!(token, UserToken::None));
+        assert!(refresh.is_none());
+    }
+}
@@ -0,0 +1,121 @@
+use tracing::{info, instrument, warn};
+
+use super::*;
+use crate::utils::rand::generate_token;
+
+impl<R: UserRepo, S: SessionRepo> UserService<R, S> {
+    pub(super) async fn start_session(
+        &self,
+        session: &UserSession,
+        user_agent: Option<String>,
+    ) -> ApiResult<RefreshToken> {
+        let refresh = RefreshToken {
+            session_id: session.session_id,
+            secret: generate_token()?,
+        };
+        let record = SessionRecord::new(
+            session.session_id,
+            session.user_id,
+            refresh.hash(),
+            user_agent,
+            session.two_factor,
+        );
+        self.sessions.create(record).await?;
+
+        info!(session_id = %session.session_id.to_hex(), "Session started");
+        Ok(refresh)
+    }
+
+    /// Issues a new access token. The refresh token is rotated, none is
+    /// returned when it was already rotated by a request sent in parallel.
+    #[instrument(skip_all, fields(session_id = %token.session_id.to_hex()))]
+    pub async fn refresh(
+        &self,
+        token: RefreshToken,
+    ) -> ApiResult<(UserSession, Option<RefreshToken>)> {
+        let ended = || ApiError::unauthorized("The session has ended");
+        let session = self
+            .sessions
+            .find_by_id(token.session_id)
+            .await?
+            .filter(SessionRecord::is_active)
+            .ok_or_else(ended)?;
+
+        let user = self
+            .repo
+            .find_by_id(session.user_id)
+            .await?
+            .ok_or_else(ended)?;
+        // A banned or disabled account is signed out of every device, a
+        // locked one only can't sign in again until the lock expires
+        if !user.is_active() {
+            warn!(user_id = %user._id.to_hex(), "Session of an inactive account");
+            self.revoke_all_sessions(user._id).await?;
+            return Err(ended());
+        }
+
+        let access = UserSession {
+            user_id: user._id,
+            email: user.email,
+            session_id: session._id,
+            two_factor: session.two_factor,
+        };
+
+        let hash = token.hash();
+        if hash == session.refresh_hash {
+            let next = RefreshToken {
+                session_id: session._id,
+                secret: generate_token()?,
+            };
+            let rotated = self
+                .sessions
+                .rotate(session._id, &hash, &next.hash())
+                .await?;
+            return Ok((access, rotated.then_some(next)));
+        }
+
+        if session.is_previous(&hash) {
+            return Ok((access, None));
+        }
+
+        // A refresh token is only used once, a replayed one was likely stolen
+        warn!("Refresh token reused, revoking the session");
+        self.sessions.revoke(session.user_id, session._id).await?;
+        Err(ended())
+    }
+
+    /// The session as it should be now: none once revoked, issued again when
+    /// its second factor changed since.
+    pub async fn current_session(&self, session: &UserSession) -> ApiResult<Option<UserSession>> {
+        Ok(self
+            .sessions
+            .find_by_id(session.session_id)
+            .await?
+            .filter(|s| s.user_id == session.user_id && s.is_active())
+            .map(|s| UserSession {
+                two_factor: s.two_factor,
+                ..session.clone()
+            }))
+    }
+
+    pub async fn list_sessions(&self, current: &UserSession) -> ApiResult<Vec<SessionDto>> {
+        let sessions = self.sessions.list_active(current.user_id).await?;
+        Ok(sessions
+            .into_iter()
+            .map(|session| SessionDto::new(session, current))
+            .collect())
+    }
+
+    pub async fn revoke_session(&self, current: &UserSession, id: ObjectId) -> ApiResult<()> {
+        if !self.sessions.revoke(current.user_id, id).await? {
+            return Err(ApiError::not_found("session", id.to_hex()));
+        }
+        Ok(())
+    }
+
+    /// Signs the user out everywhere, also done on the next refresh of any
+    /// session once the account is no longer active.
+    pub async fn revoke_all_sessions(&self, user_id: ObjectId) -> ApiResult<u64> {
+        self.sessions.revoke_all(user_id).await
+    }
+}
@@ -0,0 +1,220 @@
+use tracing::{info, instrument, warn};
+
+use super::*;
+use crate::utils::rand::{generate_recovery_code, generate_totp_secret};
+
+impl<R: UserRepo, S: SessionRepo> UserService<R, S> {
+    /// Starts setting up an authenticator, 2FA is on once a code confirms it.
+    #[instrument(skip_all, fields(user_id = %session.user_id.to_hex()))]
+    pub async fn setup_two_factor(&self, session: &UserSession) -> ApiResult<TwoFactorSetup> {
+        let mut user = self.find_user(session.user_id).await?;
+        if user.has_two_factor() {
+            return Err(ApiError::conflict(
+                "two_factor",
+                "Two-factor authentication is already enabled",
+            ));
+        }
+
+        let two_factor = TwoFactor::new(generate_totp_secret()?);
+        let otpauth_url = two_factor
+            .totp(&user.email)
+            .map_err(ApiError::internal)?
+            .get_url();
+        let setup = TwoFactorSetup {
+            secret: two_factor.secret.clone(),
+            otpauth_url,
+        };
+
+        user.two_factor = Some(two_factor);
+        self.repo.update(user._id, user).await?;
+        Ok(setup)
+    }
+
+    /// Turns 2FA on, the current session counting as signed in with it.
+    #[instrument(skip_all, fields(user_id = %session.user_id.to_hex()))]
+    pub async fn enable_two_factor(
+        &self,
+        session: &UserSession,
+        code: &str,
+    ) -> ApiResult<Vec<String>> {
+        let mut user = self.find_user(session.user_id).await?;
+        self.check_can_login(&user)?;
+        let email = user.email.clone();
+
+        let valid = pending(&mut user)?.verify_code(&email, code, now());
+        self.check_code(&user, valid).await?;
+
+        let two_factor = pending(&mut user)?;
+        let codes = recovery_codes()?;
+        two_factor.set_recovery_codes(&codes);
+        two_factor.enabled_at = Some(bson::DateTime::now());
+
+        self.repo.update(user._id, user).await?;
+        self.sessions
+            .set_two_factor(session.user_id, Some(session.session_id), true)
+            .await?;
+
+        info!("Two-factor authentication enabled");
+        Ok(codes)
+    }
+
+    /// Replaces the recovery codes, the ones handed out before stop working.
+    #[instrument(skip_all, fields(user_id = %session.user_id.to_hex()))]
+    pub async fn regenerate_recovery_codes(
+        &self,
+        session: &UserSession,
+        code: &str,
+    ) -> ApiResult<Vec<String>> {
+        let mut user = self.find_user(session.user_id).await?;
+        self.check_can_login(&user)?;
+        let email = user.email.clone();
+
+        let valid = enabled(&mut user)?.verify_code(&email, code, now());
+        self.check_code(&user, valid).await?;
+
+        let codes = recovery_codes()?;
+        enabled(&mut user)?.set_recovery_codes(&codes);
+
+        self.repo.update(user._id, user).await?;
+        info!("Recovery codes regenerated");
+        Ok(codes)
+    }
+
+    /// Turns 2FA off given a current or recovery code. No session counts as
+    /// signed in with it anymore.
+    #[instrument(skip_all, fields(user_id = %session.user_id.to_hex()))]
+    pub async fn disable_two_factor(&self, session: &UserSession, code: &str) -> ApiResult<()> {
+        let mut user = self.find_user(session.user_id).await?;
+        self.check_can_login(&user)?;
+        let email = user.email.clone();
+        let two_factor = enabled(&mut user)?;
+
+        let valid =
+            two_factor.verify_code(&email, code, now()) || two_factor.use_recovery_code(code);
+        self.check_code(&user, valid).await?;
+
+        user.two_factor = None;
+        self.repo.update(user._id, user).await?;
+        self.sessions
+            .set_two_factor(session.user_id, None, false)
+            .await?;
+
+        info!("Two-factor authentication disabled");
+        Ok(())
+    }
+
+    /// Refuses a wrong code, counted like a failed sign in so the code
+    /// can't be guessed from a stolen session.
+    async fn check_code(&self, user: &UserRecord, valid: bool) -> ApiResult<()> {
+        if valid {
+            return Ok(());
+        }
+
+        warn!(
+            login_attempts = user.login_attempts,
+            "Invalid second factor provided"
+        );
+        self.record_failed_login(user).await?;
+        Err(ApiError::unauthorized("Invalid code"))
+    }
+
+    async fn find_user(&self, id: ObjectId) -> ApiResult<UserRecord> {
+        self.repo
+            .find_by_id(id)
+            .await?
+            .ok_or_else(|| ApiError::not_found("user", id.to_hex()))
+    }
+}
+
+fn pending(user: &mut UserRecord) -> ApiResult<&mut TwoFactor> {
+    user.two_factor
+        .as_mut()
+        .filter(|t| !t.is_enabled())
+        .ok_or_else(|| ApiError::validation("two_factor", "Set up an authenticator app first"))
+}
+
+fn enabled(user: &mut UserRecord) -> ApiResult<&mut TwoFactor> {
+    user.two_factor
+        .as_mut()
+        .filter(|t| t.is_enabled())
+        .ok_or_else(|| ApiError::validation("two_factor", "Two-factor authentication is off"))
+}
+
+fn recovery_codes() -> ApiResult<Vec<String>> {
+    (0..RECOVERY_CODES)
+        .map(|_| generate_recovery_code())
+        .collect()
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::user::repo::{MockSessionRepo, MockUserRepo};
+    use crate::types::name::Name;
+    use crate::types::username::Username;
+
+    fn user(login_attempts: u32) -> UserRecord {
+        let mut user = UserRecord::new(
+            Email::new("user@example.com").unwrap(),
+            Username::new("user").unwrap(),
+            Name::new("First").unwrap(),
+            Name::new("Last").unwrap(),
+            PhoneNumber::new("0662666666").unwrap(),
+            "hash".to_string(),
+        );
+        let mut two_factor = TwoFactor::new(vec![7; 20]);
+        two_factor.enabled_at = Some(bson::DateTime::now());
+        user.two_factor = Some(two_factor);
+        user.login_attempts = login_attempts;
+        user
+    }
+
+    fn session(user: &UserRecord) -> UserSession {
+        UserSession {
+            user_id: user._id,
+            email: user.email.clone(),
+            session_id: ObjectId::new(),
+            two_factor: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wrong_codes_count_towards_the_lock() {
+        let user = user(4);
+        let session = session(&user);
+
+        let mut repo = MockUserRepo::new();
+        repo.expect_find_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        repo.expect_increment_login_attempts()
+            .times(2)
+            .returning(|_| Ok(()));
+        repo.expect_lock_user().times(2).returning(|_, _| Ok(()));
+        repo.expect_update().never();
+        let service = UserService::new(repo, MockSessionRepo::new());
+
+        let result = service.disable_two_factor(&session, "wrong").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+        let result = service.regenerate_recovery_codes(&session, "wrong").await;
+        assert!(matches!(result, Err(ApiError::Unauthorized { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_locked_accounts_cant_change_two_factor() {
+        let mut user = user(5);
+        user.locked_until = Some(bson::DateTime::from_chrono(Utc::now() + Duration::hours(1)));
+        let session = session(&user);
+
+        let mut repo = MockUserRepo::new();
+        repo.expect_find_by_id()
+            .returning(move |_| Ok(Some(user.clone())));
+        repo.expect_increment_login_attempts().never();
+        let service = UserService::new(repo, MockSessionRepo::new());
+
+        assert!(service.disable_two_factor(&session, "wrong").await.is_err());
+    }
+}
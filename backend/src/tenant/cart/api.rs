@@ -0,0 +1,134 @@
+use bigdecimal::BigDecimal;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use tower_cookies::cookie::time::Duration;
+use tower_cookies::{Cookie, Cookies};
+use ts_rs::TS;
+
+use crate::tenant::order::domain::ShippingAddress;
+use crate::tenant::shipping::domain::DeliveryMethod;
+use crate::tenant::store::api::StoreRegDto;
+use crate::types::{currency::Currency, id::Id, phone::PhoneNumber};
+
+const CART_COOKIE: &str = "cart_token";
+
+/// Opaque cart token carried by the storefront cookie, if the visitor has one.
+#[derive(Debug, Clone, Default)]
+pub struct CartToken(pub Option<String>);
+
+impl TryFrom<&Cookies> for CartToken {
+    type Error = crate::utils::error::ApiError;
+
+    fn try_from(cookies: &Cookies) -> Result<Self, Self::Error> {
+        Ok(CartToken(
+            cookies
+                .get(CART_COOKIE)
+                .map(|c| c.value().to_string())
+                .filter(|v| !v.is_empty()),
+        ))
+    }
+}
+
+impl<'c> From<CartToken> for Cookie<'c> {
+    fn from(token: CartToken) -> Self {
+        // An empty token expires the cookie right away
+        let max_age = match token.0 {
+            Some(_) => Duration::days(30),
+            None => Duration::ZERO,
+        };
+        Cookie::build((CART_COOKIE, token.0.unwrap_or_default()))
+            .path("/")
+            .http_only(true)
+            .max_age(max_age)
+            .build()
+    }
+}
+
+/// Identifies a visitor's cart on a given store.
+#[derive(Debug, Clone)]
+pub struct CartSession {
+    pub store: StoreRegDto,
+    pub token: CartToken,
+}
+
+impl CartSession {
+    pub fn new(store: &StoreRegDto, token: CartToken) -> Self {
+        Self {
+            store: store.clone(),
+            token,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct CartItemAdd {
+    pub product_id: Id,
+    pub variant_sku: String,
+    pub quantity: u32,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct CartItemUpdate {
+    pub product_id: Id,
+    pub variant_sku: String,
+    /// Zero removes the line
+    pub quantity: u32,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct CartItemRemove {
+    pub product_id: Id,
+    pub variant_sku: String,
+}
+
+#[derive(Debug, Clone, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct CartLineDto {
+    pub product_id: Id,
+    pub product_slug: String,
+    pub product_title: String,
+    pub variant_sku: String,
+    pub options: IndexMap<String, String>,
+    pub image: Option<String>,
+    pub quantity: u32,
+    #[ts(as = "String")]
+    pub unit_price: BigDecimal,
+    #[ts(as = "Option<String>")]
+    pub compare_at: Option<BigDecimal>,
+    #[ts(as = "String")]
+    pub total_price: BigDecimal,
+    pub stocks: usize,
+    /// Why the line cannot be checked out as is, e.g. missing or out of stock
+    pub issue: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct CartDto {
+    pub items: Vec<CartLineDto>,
+    pub item_count: u32,
+    #[ts(as = "String")]
+    pub subtotal: BigDecimal,
+    pub currency: Currency,
+    /// False when any line has an issue
+    pub valid: bool,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct CartCheckout {
+    pub customer_email: Option<String>,
+    pub customer_name: String,
+    pub customer_phone: PhoneNumber,
+    pub shipping_address: ShippingAddress,
+    pub billing_address: Option<ShippingAddress>,
+    pub delivery_method: Option<DeliveryMethod>,
+    pub discount_code: Option<String>,
+    pub notes: Option<String>,
+    /// Subtotal the customer was shown, checkout is refused if prices moved
+    #[ts(as = "String")]
+    pub expected_subtotal: BigDecimal,
+}
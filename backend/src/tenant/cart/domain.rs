@@ -0,0 +1,144 @@
+use bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+pub const MAX_CART_LINES: usize = 50;
+pub const MAX_LINE_QUANTITY: u32 = 999;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CartItem {
+    pub product_id: ObjectId,
+    pub variant_sku: String,
+    pub quantity: u32,
+}
+
+/// A storefront cart, identified by the opaque token kept in the visitor's
+/// cookie. Only references are stored; prices are always read live.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CartRecord {
+    pub _id: ObjectId,
+    pub token: String,
+    pub store_id: ObjectId,
+    pub items: Vec<CartItem>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl CartRecord {
+    pub fn new(token: String, store_id: ObjectId) -> Self {
+        let now = DateTime::now();
+
+        Self {
+            _id: ObjectId::new(),
+            token,
+            store_id,
+            items: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn position(&self, product_id: ObjectId, variant_sku: &str) -> Option<usize> {
+        self.items
+            .iter()
+            .position(|i| i.product_id == product_id && i.variant_sku == variant_sku)
+    }
+
+    /// Adds `quantity` to the line for this variant, creating it if needed.
+    pub fn add_item(
+        &mut self,
+        product_id: ObjectId,
+        variant_sku: String,
+        quantity: u32,
+    ) -> Result<(), &'static str> {
+        if quantity == 0 {
+            return Err("Quantity must be at least 1");
+        }
+
+        match self.position(product_id, &variant_sku) {
+            Some(i) => {
+                let line = &mut self.items[i];
+                line.quantity = line
+                    .quantity
+                    .saturating_add(quantity)
+                    .min(MAX_LINE_QUANTITY);
+            }
+            None => {
+                if self.items.len() >= MAX_CART_LINES {
+                    return Err("Cart cannot hold more items");
+                }
+                self.items.push(CartItem {
+                    product_id,
+                    variant_sku,
+                    quantity: quantity.min(MAX_LINE_QUANTITY),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the quantity of an existing line, a quantity of zero removes it.
+    pub fn set_quantity(
+        &mut self,
+        product_id: ObjectId,
+        variant_sku: &str,
+        quantity: u32,
+    ) -> Result<(), &'static str> {
+        let i = self
+            .position(product_id, variant_sku)
+            .ok_or("Item is not in the cart")?;
+
+        if quantity == 0 {
+            self.items.remove(i);
+        } else {
+            self.items[i].quantity = quantity.min(MAX_LINE_QUANTITY);
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_item(&mut self, product_id: ObjectId, variant_sku: &str) -> bool {
+        match self.position(product_id, variant_sku) {
+            Some(i) => {
+                self.items.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_item_merges_lines() {
+        let mut cart = CartRecord::new("t".to_string(), ObjectId::new());
+        let product = ObjectId::new();
+
+        cart.add_item(product, "A".to_string(), 2).unwrap();
+        cart.add_item(product, "A".to_string(), 3).unwrap();
+        cart.add_item(product, "B".to_string(), 1).unwrap();
+
+        assert_eq!(cart.items.len(), 2);
+        assert_eq!(cart.items[0].quantity, 5);
+        assert!(cart.add_item(product, "C".to_string(), 0).is_err());
+    }
+
+    #[test]
+    fn test_set_quantity_and_remove() {
+        let mut cart = CartRecord::new("t".to_string(), ObjectId::new());
+        let product = ObjectId::new();
+        cart.add_item(product, "A".to_string(), 1).unwrap();
+
+        cart.set_quantity(product, "A", 4).unwrap();
+        assert_eq!(cart.items[0].quantity, 4);
+
+        cart.set_quantity(product, "A", 0).unwrap();
+        assert!(cart.items.is_empty());
+
+        assert!(cart.set_quantity(product, "A", 1).is_err());
+        assert!(!cart.remove_item(product, "A"));
+    }
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId, DateTime};
+use mongodb::{Client, Collection};
+
+use super::domain::*;
+use crate::utils::error::{ApiError, ApiResult};
+
+#[async_trait]
+pub trait CartRepo: Send + Sync {
+    async fn find_by_token(
+        &self,
+        business_id: ObjectId,
+        store_id: ObjectId,
+        token: &str,
+    ) -> ApiResult<Option<CartRecord>>;
+    async fn save(&self, business_id: ObjectId, cart: CartRecord) -> ApiResult<CartRecord>;
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+}
+
+pub struct MongoCartRepo {
+    client: Client,
+}
+
+impl MongoCartRepo {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn get_collection(&self, business_id: ObjectId) -> Collection<CartRecord> {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+            .collection("carts")
+    }
+}
+
+#[async_trait]
+impl CartRepo for MongoCartRepo {
+    async fn find_by_token(
+        &self,
+        business_id: ObjectId,
+        store_id: ObjectId,
+        token: &str,
+    ) -> ApiResult<Option<CartRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find_one(doc! { "token": token, "store_id": store_id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn save(&self, business_id: ObjectId, mut cart: CartRecord) -> ApiResult<CartRecord> {
+        let collection = self.get_collection(business_id);
+
+        cart.updated_at = DateTime::now();
+
+        collection
+            .replace_one(doc! { "_id": cart._id }, &cart)
+            .upsert(true)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to save cart: {}", e)))?;
+
+        Ok(cart)
+    }
+
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .delete_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to delete cart: {}", e)))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use axum::extract::State;
+use macros::routes;
+use tower_cookies::Cookies;
+
+use super::super::store::extractors::Store;
+use super::api::*;
+use crate::extractors::cookies::FromCookies;
+use crate::extractors::json::Json;
+use crate::platform::user::api::MessageResponse;
+use crate::utils::error::ApiResult;
+use crate::AppState;
+
+pub struct PubCartRoutes;
+
+#[routes(prefix = "/api/v1/cart", state = AppState)]
+impl PubCartRoutes {
+    #[route(method=get, path="/items", res=CartDto)]
+    async fn get_cart(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        FromCookies(token): FromCookies<CartToken>,
+    ) -> ApiResult<Json<CartDto>> {
+        let session = CartSession::new(&store_key, token);
+        state
+            .cart_service
+            .get_cart(&state.product_service, &session)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/items", res=CartDto)]
+    async fn add_item(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<CartToken>,
+        #[json] req: CartItemAdd,
+    ) -> ApiResult<Json<CartDto>> {
+        let session = CartSession::new(&store_key, token);
+        let (token, cart) = state
+            .cart_service
+            .add_item(&state.product_service, &session, req)
+            .await?;
+        cookies.add(token.into());
+        Ok(Json(cart))
+    }
+
+    #[route(method=patch, path="/items", res=CartDto)]
+    async fn update_item(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        FromCookies(token): FromCookies<CartToken>,
+        #[json] req: CartItemUpdate,
+    ) -> ApiResult<Json<CartDto>> {
+        let session = CartSession::new(&store_key, token);
+        state
+            .cart_service
+            .update_item(&state.product_service, &session, req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/items", res=CartDto)]
+    async fn remove_item(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        FromCookies(token): FromCookies<CartToken>,
+        #[json] req: CartItemRemove,
+    ) -> ApiResult<Json<CartDto>> {
+        let session = CartSession::new(&store_key, token);
+        state
+            .cart_service
+            .remove_item(&state.product_service, &session, req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/clear", res=MessageResponse)]
+    async fn clear_cart(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<CartToken>,
+    ) -> ApiResult<Json<MessageResponse>> {
+        let session = CartSession::new(&store_key, token);
+        state.cart_service.clear(&session).await?;
+        cookies.add(CartToken(None).into());
+        Ok(Json(MessageResponse {
+            message: "Cart cleared successfully".to_string(),
+        }))
+    }
+
+    #[route(method=post, path="/checkout", res=MessageResponse)]
+    async fn checkout(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        cookies: Cookies,
+        FromCookies(token): FromCookies<CartToken>,
+        #[json] req: CartCheckout,
+    ) -> ApiResult<Json<MessageResponse>> {
+        let session = CartSession::new(&store_key, token);
+        let order_req = state
+            .cart_service
+            .checkout(&state.product_service, &session, req)
+            .await?;
+        state
+            .order_service
+            .pub_create_order(
+                &state.product_service,
+                &state.shipping_service,
+                &state.discount_service,
+                &state.tax_service,
+                &store_key,
+                order_req,
+            )
+            .await?;
+        state.cart_service.clear(&session).await?;
+        cookies.add(CartToken(None).into());
+        Ok(Json(MessageResponse {
+            message: "Order placed successfully".to_string(),
+        }))
+    }
+}
@@ -0,0 +1,326 @@
+use bigdecimal::BigDecimal;
+
+use super::api::*;
+use super::domain::*;
+use super::repo::CartRepo;
+use crate::tenant::order::api::{OrderItemCreate, PubOrderCreate};
+use crate::tenant::product::repo::ProductRepo;
+use crate::tenant::product::service::ProductService;
+use crate::tenant::store::api::StoreRegDto;
+use crate::utils::error::{ApiError, ApiResult};
+
+pub struct CartService<R: CartRepo> {
+    repo: R,
+}
+
+impl<R: CartRepo> CartService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    async fn find_cart(&self, session: &CartSession) -> ApiResult<Option<CartRecord>> {
+        match session.token.0 {
+            Some(ref token) => {
+                self.repo
+                    .find_by_token(
+                        session.store.business_id.into_inner(),
+                        session.store.store_id.into_inner(),
+                        token,
+                    )
+                    .await
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Prices every line from the live product in the store's currency,
+    /// flagging lines that can no longer be bought as is.
+    async fn price_cart<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        store: &StoreRegDto,
+        cart: &CartRecord,
+    ) -> ApiResult<CartDto> {
+        let mut items = Vec::with_capacity(cart.items.len());
+        let mut subtotal = BigDecimal::from(0);
+        let mut item_count = 0;
+
+        for item in &cart.items {
+            let product = match product_service
+                .pub_get_product(store.business_id, item.product_id.into())
+                .await
+            {
+                Ok(product) => Some(product),
+                Err(ApiError::NotFound { .. }) => None,
+                Err(e) => return Err(e),
+            };
+
+            let variant = product
+                .as_ref()
+                .and_then(|p| p.variants.iter().find(|v| v.sku == item.variant_sku));
+
+            let line = match (product.as_ref(), variant) {
+                (Some(product), Some(variant)) => {
+                    let mut variant = variant.clone();
+                    let priced = variant.localize(&store.currency, &store.base_currency);
+                    let unit_price = match priced {
+                        true => variant.price.clone(),
+                        false => BigDecimal::from(0),
+                    };
+                    let total_price = &unit_price * BigDecimal::from(item.quantity);
+                    let issue = if !priced {
+                        Some(format!("Not sold in {}", store.currency))
+                    } else if variant.stocks == 0 {
+                        Some("Out of stock".to_string())
+                    } else if variant.stocks < item.quantity as usize {
+                        Some(format!("Only {} left in stock", variant.stocks))
+                    } else {
+                        None
+                    };
+
+                    CartLineDto {
+                        product_id: product.id,
+                        product_slug: product.slug.clone(),
+                        product_title: product.title.to_string(),
+                        variant_sku: variant.sku.clone(),
+                        options: variant.options.clone(),
+                        image: variant.images.first().or(product.images.first()).cloned(),
+                        quantity: item.quantity,
+                        unit_price,
+                        compare_at: variant.compare_at.clone(),
+                        total_price,
+                        stocks: variant.stocks,
+                        issue,
+                    }
+                }
+                _ => CartLineDto {
+                    product_id: item.product_id.into(),
+                    product_slug: product.as_ref().map(|p| p.slug.clone()).unwrap_or_default(),
+                    product_title: product
+                        .as_ref()
+                        .map(|p| p.title.to_string())
+                        .unwrap_or_default(),
+                    variant_sku: item.variant_sku.clone(),
+                    options: Default::default(),
+                    image: None,
+                    quantity: item.quantity,
+                    unit_price: BigDecimal::from(0),
+                    compare_at: None,
+                    total_price: BigDecimal::from(0),
+                    stocks: 0,
+                    issue: Some("No longer available".to_string()),
+                },
+            };
+
+            if line.issue.is_none() {
+                subtotal += &line.total_price;
+                item_count += line.quantity;
+            }
+            items.push(line);
+        }
+
+        Ok(CartDto {
+            valid: items.iter().all(|l| l.issue.is_none()),
+            items,
+            item_count,
+            subtotal,
+            currency: store.currency.clone(),
+        })
+    }
+
+    pub async fn get_cart<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        session: &CartSession,
+    ) -> ApiResult<CartDto> {
+        match self.find_cart(session).await? {
+            Some(cart) => {
+                self.price_cart(product_service, &session.store, &cart)
+                    .await
+            }
+            None => Ok(CartDto {
+                valid: true,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// Adds a variant to the visitor's cart, creating the cart when needed.
+    /// Returns the token to store in the cookie alongside the priced cart.
+    pub async fn add_item<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        session: &CartSession,
+        req: CartItemAdd,
+    ) -> ApiResult<(CartToken, CartDto)> {
+        let product = product_service
+            .pub_get_product(session.store.business_id, req.product_id)
+            .await?;
+        let variant = product
+            .variants
+            .iter()
+            .find(|v| v.sku == req.variant_sku)
+            .ok_or_else(|| {
+                ApiError::validation(
+                    "variant_sku",
+                    format!("Variant with SKU '{}' not found", req.variant_sku),
+                )
+            })?;
+        if variant
+            .price_in(&session.store.currency, &session.store.base_currency)
+            .is_none()
+        {
+            return Err(ApiError::validation(
+                "variant_sku",
+                format!(
+                    "Variant '{}' is not sold in {}",
+                    req.variant_sku, session.store.currency
+                ),
+            ));
+        }
+
+        let mut cart = match self.find_cart(session).await? {
+            Some(cart) => cart,
+            None => CartRecord::new(
+                uuid::Uuid::new_v4().simple().to_string(),
+                session.store.store_id.into_inner(),
+            ),
+        };
+
+        let product_id = product.id.into_inner();
+        let in_cart = cart
+            .items
+            .iter()
+            .find(|i| i.product_id == product_id && i.variant_sku == req.variant_sku)
+            .map_or(0, |i| i.quantity);
+
+        if variant.stocks < in_cart.saturating_add(req.quantity) as usize {
+            return Err(ApiError::validation(
+                "quantity",
+                format!("Only {} left in stock", variant.stocks),
+            ));
+        }
+
+        cart.add_item(product_id, req.variant_sku, req.quantity)
+            .map_err(|e| ApiError::validation("quantity", e))?;
+
+        let cart = self
+            .repo
+            .save(session.store.business_id.into_inner(), cart)
+            .await?;
+        let priced = self
+            .price_cart(product_service, &session.store, &cart)
+            .await?;
+
+        Ok((CartToken(Some(cart.token)), priced))
+    }
+
+    pub async fn update_item<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        session: &CartSession,
+        req: CartItemUpdate,
+    ) -> ApiResult<CartDto> {
+        let mut cart = self
+            .find_cart(session)
+            .await?
+            .ok_or(ApiError::not_found("cart", "Cart not found"))?;
+
+        cart.set_quantity(req.product_id.into_inner(), &req.variant_sku, req.quantity)
+            .map_err(|e| ApiError::validation("variant_sku", e))?;
+
+        let cart = self
+            .repo
+            .save(session.store.business_id.into_inner(), cart)
+            .await?;
+        self.price_cart(product_service, &session.store, &cart)
+            .await
+    }
+
+    pub async fn remove_item<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        session: &CartSession,
+        req: CartItemRemove,
+    ) -> ApiResult<CartDto> {
+        let mut cart = self
+            .find_cart(session)
+            .await?
+            .ok_or(ApiError::not_found("cart", "Cart not found"))?;
+
+        if !cart.remove_item(req.product_id.into_inner(), &req.variant_sku) {
+            return Err(ApiError::validation(
+                "variant_sku",
+                "Item is not in the cart",
+            ));
+        }
+
+        let cart = self
+            .repo
+            .save(session.store.business_id.into_inner(), cart)
+            .await?;
+        self.price_cart(product_service, &session.store, &cart)
+            .await
+    }
+
+    pub async fn clear(&self, session: &CartSession) -> ApiResult<()> {
+        if let Some(cart) = self.find_cart(session).await? {
+            self.repo
+                .delete(session.store.business_id.into_inner(), cart._id)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Turns the cart into an order request. The cart is repriced first and
+    /// checkout is refused when any line changed from what the customer was
+    /// shown. The cart is left in place until the order goes through.
+    pub async fn checkout<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        session: &CartSession,
+        req: CartCheckout,
+    ) -> ApiResult<PubOrderCreate> {
+        let cart = self
+            .find_cart(session)
+            .await?
+            .filter(|c| !c.items.is_empty())
+            .ok_or(ApiError::validation("cart", "Cart is empty"))?;
+
+        let priced = self
+            .price_cart(product_service, &session.store, &cart)
+            .await?;
+        if !priced.valid {
+            return Err(ApiError::conflict(
+                "cart",
+                "Some items in the cart are no longer available",
+            ));
+        }
+        if priced.subtotal != req.expected_subtotal {
+            return Err(ApiError::conflict(
+                "cart",
+                "Cart prices have changed, please review the cart",
+            ));
+        }
+
+        Ok(PubOrderCreate {
+            customer_email: req.customer_email,
+            customer_name: req.customer_name,
+            customer_phone: req.customer_phone,
+            items: cart
+                .items
+                .iter()
+                .map(|i| OrderItemCreate {
+                    product_id: i.product_id.into(),
+                    variant_sku: i.variant_sku.clone(),
+                    quantity: i.quantity,
+                })
+                .collect(),
+            shipping_address: req.shipping_address,
+            billing_address: req.billing_address,
+            delivery_method: req.delivery_method,
+            discount_code: req.discount_code,
+            notes: req.notes,
+        })
+    }
+}
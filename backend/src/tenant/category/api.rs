@@ -0,0 +1,94 @@
+use chrono::{DateTime, Utc};
+use o2o::o2o;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::domain::*;
+use crate::types::{id::Id, name::Name, slug::Slug};
+use crate::utils::serde_helpers::JsonOption;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct CategoryCreate {
+    pub name: Name,
+    pub slug: Slug,
+    pub parent_id: Option<Id>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(CategoryRecord)]
+pub struct CategoryDto {
+    #[from(@._id.into())]
+    pub id: Id,
+    pub name: Name,
+    pub slug: Slug,
+    #[from(~.map(Into::into))]
+    pub parent_id: Option<Id>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub position: i32,
+    #[from(~.to_chrono())]
+    pub created_at: DateTime<Utc>,
+    #[from(~.to_chrono())]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct CategoryTreeNode {
+    #[serde(flatten)]
+    pub category: CategoryDto,
+    pub children: Vec<CategoryTreeNode>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct CategoryTreeResponse {
+    pub categories: Vec<CategoryTreeNode>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct CategoryListQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub parent_id: Option<Id>,
+    pub search: Option<String>,
+}
+
+#[macros::json_option_serde]
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct CategoryUpdate {
+    pub name: JsonOption<Name>,
+    pub slug: JsonOption<Slug>,
+    pub parent_id: JsonOption<Option<Id>>,
+    pub description: JsonOption<Option<String>>,
+    pub image: JsonOption<Option<String>>,
+    pub position: JsonOption<i32>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct CategoryListResponse {
+    pub categories: Vec<CategoryDto>,
+    pub total: u64,
+    pub page: u32,
+    pub limit: u32,
+}
+
+/// A category as rendered on the storefront collection page.
+#[derive(Debug, Serialize)]
+pub struct CollectionView {
+    #[serde(flatten)]
+    pub category: CategoryDto,
+    pub parent: Option<CategoryDto>,
+    pub children: Vec<CategoryDto>,
+    /// Slugs of the category and all of its descendants, used to list products.
+    #[serde(skip)]
+    pub subtree_slugs: Vec<String>,
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{name::Name, slug::Slug};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CategoryRecord {
+    pub _id: ObjectId,
+    pub name: Name,
+    pub slug: Slug,
+    pub parent_id: Option<ObjectId>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub position: i32,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl CategoryRecord {
+    pub fn new(
+        name: Name,
+        slug: Slug,
+        parent_id: Option<ObjectId>,
+        description: Option<String>,
+        image: Option<String>,
+        position: i32,
+    ) -> Self {
+        let now = DateTime::now();
+
+        Self {
+            _id: Default::default(),
+            name,
+            slug,
+            parent_id,
+            description,
+            image,
+            position,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+/// Returns true when attaching `id` under `parent_id` would make `id` its own
+/// ancestor. `parents` maps every category of the business to its parent.
+pub fn creates_cycle(
+    id: ObjectId,
+    parent_id: ObjectId,
+    parents: &HashMap<ObjectId, Option<ObjectId>>,
+) -> bool {
+    let mut current = Some(parent_id);
+    let mut steps = 0;
+
+    while let Some(node) = current {
+        if node == id || steps > parents.len() {
+            return true;
+        }
+        current = parents.get(&node).copied().flatten();
+        steps += 1;
+    }
+
+    false
+}
+
+/// Ids of `root` and every category nested below it.
+pub fn subtree_ids(root: ObjectId, categories: &[CategoryRecord]) -> Vec<ObjectId> {
+    let mut ids = vec![root];
+    let mut i = 0;
+
+    while i < ids.len() {
+        let current = ids[i];
+        ids.extend(
+            categories
+                .iter()
+                .filter(|c| c.parent_id == Some(current) && !ids.contains(&c._id))
+                .map(|c| c._id)
+                .collect::<Vec<_>>(),
+        );
+        i += 1;
+    }
+
+    ids
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CategoryFilter {
+    pub parent_id: Option<ObjectId>,
+    pub search: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(id: ObjectId, parent_id: Option<ObjectId>) -> CategoryRecord {
+        CategoryRecord {
+            _id: id,
+            parent_id,
+            ..CategoryRecord::new(
+                Name::new("Shoes").unwrap(),
+                Slug::new("shoes").unwrap(),
+                None,
+                None,
+                None,
+                0,
+            )
+        }
+    }
+
+    #[test]
+    fn test_creates_cycle() {
+        let (a, b, c) = (ObjectId::new(), ObjectId::new(), ObjectId::new());
+        let parents = HashMap::from([(a, None), (b, Some(a)), (c, Some(b))]);
+
+        assert!(creates_cycle(a, c, &parents));
+        assert!(creates_cycle(a, a, &parents));
+        assert!(!creates_cycle(c, a, &parents));
+    }
+
+    #[test]
+    fn test_subtree_ids() {
+        let (a, b, c, d) = (
+            ObjectId::new(),
+            ObjectId::new(),
+            ObjectId::new(),
+            ObjectId::new(),
+        );
+        let categories = vec![
+            category(a, None),
+            category(b, Some(a)),
+            category(c, Some(b)),
+            category(d, None),
+        ];
+
+        assert_eq!(subtree_ids(a, &categories), vec![a, b, c]);
+        assert_eq!(subtree_ids(d, &categories), vec![d]);
+    }
+}
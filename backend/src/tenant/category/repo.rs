@@ -0,0 +1,229 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId, DateTime};
+use futures::stream::TryStreamExt;
+use mongodb::{options::FindOptions, Client, Collection};
+
+use super::domain::*;
+use crate::utils::error::{ApiError, ApiResult};
+
+#[async_trait]
+pub trait CategoryRepo: Send + Sync {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        category: CategoryRecord,
+    ) -> ApiResult<CategoryRecord>;
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<CategoryRecord>>;
+    async fn find_by_slug(
+        &self,
+        business_id: ObjectId,
+        slug: &str,
+    ) -> ApiResult<Option<CategoryRecord>>;
+    async fn find_all(&self, business_id: ObjectId) -> ApiResult<Vec<CategoryRecord>>;
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        category: CategoryRecord,
+    ) -> ApiResult<CategoryRecord>;
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: CategoryFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<CategoryRecord>, u64)>;
+}
+
+pub struct MongoCategoryRepo {
+    client: Client,
+}
+
+impl MongoCategoryRepo {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn get_collection(&self, business_id: ObjectId) -> Collection<CategoryRecord> {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+            .collection("categories")
+    }
+
+    fn build_filter_query(&self, filter: &CategoryFilter) -> bson::Document {
+        let mut query = doc! {};
+
+        if let Some(parent_id) = filter.parent_id {
+            query.insert("parent_id", parent_id);
+        }
+
+        if let Some(ref search) = filter.search {
+            query.insert(
+                "$or",
+                vec![
+                    doc! {
+                        "name": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                    doc! {
+                        "slug": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                ],
+            );
+        }
+
+        query
+    }
+}
+
+#[async_trait]
+impl CategoryRepo for MongoCategoryRepo {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        category: CategoryRecord,
+    ) -> ApiResult<CategoryRecord> {
+        let collection = self.get_collection(business_id);
+
+        collection.insert_one(&category).await.map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                ApiError::conflict("category", "Category with this slug already exists")
+            } else {
+                ApiError::internal(format!("Failed to create category: {}", e))
+            }
+        })?;
+
+        Ok(category)
+    }
+
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<CategoryRecord>> {
+        let collection = self.get_collection(business_id);
+
+        let category = collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        Ok(category)
+    }
+
+    async fn find_by_slug(
+        &self,
+        business_id: ObjectId,
+        slug: &str,
+    ) -> ApiResult<Option<CategoryRecord>> {
+        let collection = self.get_collection(business_id);
+
+        let category = collection
+            .find_one(doc! { "slug": slug })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        Ok(category)
+    }
+
+    async fn find_all(&self, business_id: ObjectId) -> ApiResult<Vec<CategoryRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! {})
+            .sort(doc! { "position": 1, "name": 1 })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        mut category: CategoryRecord,
+    ) -> ApiResult<CategoryRecord> {
+        let collection = self.get_collection(business_id);
+
+        category.updated_at = DateTime::now();
+
+        let result = collection
+            .replace_one(doc! { "_id": id }, &category)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update category: {}", e)))?;
+
+        if result.matched_count == 0 {
+            return Err(ApiError::not_found("category", "Category not found"));
+        }
+
+        Ok(category)
+    }
+
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+
+        let result = collection
+            .delete_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to delete category: {}", e)))?;
+
+        if result.deleted_count == 0 {
+            return Err(ApiError::not_found("category", "Category not found"));
+        }
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: CategoryFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<CategoryRecord>, u64)> {
+        let collection = self.get_collection(business_id);
+        let query = self.build_filter_query(&filter);
+
+        let total = collection
+            .count_documents(query.clone())
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to count categories: {}", e)))?;
+
+        let skip = ((page.max(1) - 1) * limit) as u64;
+
+        let find_options = FindOptions::builder()
+            .skip(skip)
+            .limit(limit as i64)
+            .sort(doc! { "position": 1, "name": 1 })
+            .build();
+
+        let mut cursor = collection
+            .find(query)
+            .with_options(find_options)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        let mut categories = Vec::new();
+        while let Some(category) = cursor
+            .try_next()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
+        {
+            categories.push(category);
+        }
+
+        Ok((categories, total))
+    }
+}
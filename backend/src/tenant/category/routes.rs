@@ -0,0 +1,104 @@
+use axum::extract::{Path, Query, State};
+use macros::routes;
+
+use super::api::*;
+use crate::extractors::cookies::FromCookies;
+use crate::extractors::json::Json;
+use crate::platform::business::api::BusinessSession;
+use crate::platform::user::api::MessageResponse;
+use crate::types::id::Id;
+use crate::utils::error::ApiResult;
+use crate::AppState;
+
+pub struct CategoryRoutes;
+
+#[routes(prefix = "/api/v1/categories", state = AppState)]
+impl CategoryRoutes {
+    #[route(method=post, path="/create", res=CategoryDto, perm="categories:write")]
+    async fn create_category(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[json] category: CategoryCreate,
+    ) -> ApiResult<Json<CategoryDto>> {
+        state
+            .category_service
+            .create_category(business, category)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/tree", res=CategoryTreeResponse, perm="categories:read")]
+    async fn category_tree(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+    ) -> ApiResult<Json<CategoryTreeResponse>> {
+        state
+            .category_service
+            .tree(business)
+            .await
+            .map(|categories| CategoryTreeResponse { categories })
+            .map(Json)
+    }
+
+    #[route(method=post, path="/list", res=CategoryListResponse, perm="categories:read")]
+    async fn list_categories(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: CategoryListQuery,
+    ) -> ApiResult<Json<CategoryListResponse>> {
+        state
+            .category_service
+            .list_categories(business, query)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/{category_id}", res=CategoryDto, perm="categories:read")]
+    async fn get_category(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] category_id: Id,
+    ) -> ApiResult<Json<CategoryDto>> {
+        state
+            .category_service
+            .get_category(business, category_id)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=patch, path="/{category_id}", res=CategoryDto, perm="categories:write")]
+    async fn edit_category(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] category_id: Id,
+        #[json] update_req: CategoryUpdate,
+    ) -> ApiResult<Json<CategoryDto>> {
+        state
+            .category_service
+            .update_category(
+                &state.product_service,
+                &state.discount_service,
+                business,
+                category_id,
+                update_req,
+            )
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/{category_id}", res=MessageResponse, perm="categories:delete")]
+    async fn delete_category(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] category_id: Id,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .category_service
+            .delete_category(&state.product_service, business, category_id)
+            .await
+            .map(|_| MessageResponse {
+                message: "Category deleted successfully".to_string(),
+            })
+            .map(Json)
+    }
+}
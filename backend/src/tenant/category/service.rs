@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+
+use bson::oid::ObjectId;
+
+use super::api::*;
+use super::domain::*;
+use super::repo::CategoryRepo;
+use crate::platform::business::api::BusinessSession;
+use crate::tenant::discount::repo::DiscountRepo;
+use crate::tenant::discount::service::DiscountService;
+use crate::tenant::product::repo::ProductRepo;
+use crate::tenant::product::service::ProductService;
+use crate::types::id::Id;
+use crate::utils::error::{ApiError, ApiResult};
+
+pub struct CategoryService<R: CategoryRepo> {
+    repo: R,
+}
+
+impl<R: CategoryRepo> CategoryService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_category(
+        &self,
+        business: BusinessSession,
+        create_req: CategoryCreate,
+    ) -> ApiResult<CategoryDto> {
+        let business_id = business.business_id.into_inner();
+
+        if self
+            .repo
+            .find_by_slug(business_id, create_req.slug.as_str())
+            .await?
+            .is_some()
+        {
+            return Err(ApiError::conflict(
+                "category",
+                "Category with this slug already exists",
+            ));
+        }
+
+        let parent_id = create_req.parent_id.map(Id::into_inner);
+        if let Some(parent_id) = parent_id {
+            self.repo
+                .find_by_id(business_id, parent_id)
+                .await?
+                .ok_or(ApiError::validation(
+                    "parent_id",
+                    "Parent category not found",
+                ))?;
+        }
+
+        self.repo
+            .create(
+                business_id,
+                CategoryRecord::new(
+                    create_req.name,
+                    create_req.slug,
+                    parent_id,
+                    create_req.description,
+                    create_req.image,
+                    create_req.position.unwrap_or_default(),
+                ),
+            )
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn get_category(
+        &self,
+        business: BusinessSession,
+        category_id: Id,
+    ) -> ApiResult<CategoryDto> {
+        let id = category_id.into_inner();
+        self.repo
+            .find_by_id(business.business_id.into_inner(), id)
+            .await?
+            .ok_or(ApiError::not_found("category", id.to_hex()))
+            .map(Into::into)
+    }
+
+    pub async fn list_categories(
+        &self,
+        business: BusinessSession,
+        query: CategoryListQuery,
+    ) -> ApiResult<CategoryListResponse> {
+        let CategoryListQuery {
+            page,
+            limit,
+            parent_id,
+            search,
+        } = query;
+
+        let filter = CategoryFilter {
+            parent_id: parent_id.map(Id::into_inner),
+            search,
+        };
+        let page = page.unwrap_or(1);
+        let limit = limit.unwrap_or(10);
+
+        let (categories, total) = self
+            .repo
+            .list(business.business_id.into_inner(), filter, page, limit)
+            .await?;
+
+        let views: Vec<_> = categories.into_iter().map(Into::into).collect();
+        Ok(CategoryListResponse {
+            categories: views,
+            total,
+            page,
+            limit,
+        })
+    }
+
+    pub async fn tree(&self, business: BusinessSession) -> ApiResult<Vec<CategoryTreeNode>> {
+        let categories = self
+            .repo
+            .find_all(business.business_id.into_inner())
+            .await?;
+
+        let mut by_parent: HashMap<Option<ObjectId>, Vec<CategoryRecord>> = HashMap::new();
+        for category in categories {
+            by_parent
+                .entry(category.parent_id)
+                .or_default()
+                .push(category);
+        }
+
+        fn build(
+            parent_id: Option<ObjectId>,
+            by_parent: &mut HashMap<Option<ObjectId>, Vec<CategoryRecord>>,
+        ) -> Vec<CategoryTreeNode> {
+            by_parent
+                .remove(&parent_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|category| {
+                    let children = build(Some(category._id), by_parent);
+                    CategoryTreeNode {
+                        category: category.into(),
+                        children,
+                    }
+                })
+                .collect()
+        }
+
+        Ok(build(None, &mut by_parent))
+    }
+
+    pub async fn update_category<P: ProductRepo, D: DiscountRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        discount_service: &DiscountService<D>,
+        business: BusinessSession,
+        category_id: Id,
+        update_req: CategoryUpdate,
+    ) -> ApiResult<CategoryDto> {
+        let id = category_id.into_inner();
+        let business_id = business.business_id.into_inner();
+        let mut record = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or(ApiError::not_found("category", id.to_hex()))?;
+
+        let old_slug = record.slug.clone();
+
+        if let Some(Some(parent_id)) = update_req.parent_id.clone().to_option() {
+            let parent_id = parent_id.into_inner();
+            let parents: HashMap<_, _> = self
+                .repo
+                .find_all(business_id)
+                .await?
+                .into_iter()
+                .map(|c| (c._id, c.parent_id))
+                .collect();
+
+            if !parents.contains_key(&parent_id) {
+                return Err(ApiError::validation(
+                    "parent_id",
+                    "Parent category not found",
+                ));
+            }
+
+            if creates_cycle(id, parent_id, &parents) {
+                return Err(ApiError::validation(
+                    "parent_id",
+                    "A category cannot be nested under itself or its descendants",
+                ));
+            }
+        }
+
+        update_req.name.map(|v| record.name = v);
+        update_req.slug.map(|v| record.slug = v);
+        update_req
+            .parent_id
+            .ok_then(|v| record.parent_id = v.flatten().map(Id::into_inner));
+        update_req.description.map(|v| record.description = v);
+        update_req.image.map(|v| record.image = v);
+        update_req.position.map(|v| record.position = v);
+
+        if record.slug != old_slug {
+            if self
+                .repo
+                .find_by_slug(business_id, record.slug.as_str())
+                .await?
+                .is_some()
+            {
+                return Err(ApiError::conflict(
+                    "category",
+                    "Category with this slug already exists",
+                ));
+            }
+
+            product_service
+                .rename_category(
+                    business.business_id,
+                    old_slug.as_str(),
+                    record.slug.as_str(),
+                )
+                .await?;
+            discount_service
+                .rename_category(
+                    business.business_id,
+                    old_slug.as_str(),
+                    record.slug.as_str(),
+                )
+                .await?;
+        }
+
+        self.repo
+            .update(business_id, id, record)
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn delete_category<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        business: BusinessSession,
+        category_id: Id,
+    ) -> ApiResult<()> {
+        let id = category_id.into_inner();
+        let business_id = business.business_id.into_inner();
+        let record = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or(ApiError::not_found("category", id.to_hex()))?;
+
+        let filter = CategoryFilter {
+            parent_id: Some(id),
+            search: None,
+        };
+        let (_, children) = self.repo.list(business_id, filter, 1, 1).await?;
+        if children > 0 {
+            return Err(ApiError::conflict(
+                "category",
+                "Category still has subcategories",
+            ));
+        }
+
+        let products = product_service
+            .count_in_category(business.business_id, record.slug.as_str())
+            .await?;
+        if products > 0 {
+            return Err(ApiError::conflict(
+                "category",
+                format!("Category is still used by {} products", products),
+            ));
+        }
+
+        self.repo.delete(business_id, id).await
+    }
+
+    /// Validates that a product category refers to an existing category.
+    /// An empty category means the product is uncategorized.
+    pub async fn ensure_exists(&self, business_id: Id, slug: &str) -> ApiResult<()> {
+        if slug.is_empty() {
+            return Ok(());
+        }
+
+        self.repo
+            .find_by_slug(business_id.into_inner(), slug)
+            .await?
+            .map(|_| ())
+            .ok_or(ApiError::validation(
+                "category",
+                format!("Category '{}' does not exist", slug),
+            ))
+    }
+
+    /// Maps every category slug to its display name.
+    pub async fn pub_category_names(&self, business_id: Id) -> ApiResult<HashMap<String, String>> {
+        Ok(self
+            .repo
+            .find_all(business_id.into_inner())
+            .await?
+            .into_iter()
+            .map(|c| (c.slug.to_string(), c.name.to_string()))
+            .collect())
+    }
+
+    pub async fn pub_get_collection(
+        &self,
+        business_id: Id,
+        slug: &str,
+    ) -> ApiResult<CollectionView> {
+        let categories = self.repo.find_all(business_id.into_inner()).await?;
+
+        let category = categories
+            .iter()
+            .find(|c| c.slug.as_str() == slug)
+            .cloned()
+            .ok_or(ApiError::not_found("category", slug.to_string()))?;
+
+        let subtree = subtree_ids(category._id, &categories);
+        let subtree_slugs = categories
+            .iter()
+            .filter(|c| subtree.contains(&c._id))
+            .map(|c| c.slug.to_string())
+            .collect();
+
+        let parent = category
+            .parent_id
+            .and_then(|parent_id| categories.iter().find(|c| c._id == parent_id))
+            .cloned()
+            .map(Into::into);
+
+        let children = categories
+            .iter()
+            .filter(|c| c.parent_id == Some(category._id))
+            .cloned()
+            .map(Into::into)
+            .collect();
+
+        Ok(CollectionView {
+            category: category.into(),
+            parent,
+            children,
+            subtree_slugs,
+        })
+    }
+}
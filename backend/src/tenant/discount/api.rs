@@ -0,0 +1,96 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use o2o::o2o;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::domain::*;
+use crate::types::{id::Id, name::Name};
+use crate::utils::serde_helpers::JsonOption;
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, o2o, TS)]
+#[map_owned(DiscountScope)]
+#[ts(export, bound = "")]
+pub struct DiscountScopeDto {
+    #[map(~.into_iter().map(Into::into).collect())]
+    pub product_ids: Vec<Id>,
+    pub categories: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct DiscountCreate {
+    pub code: Option<String>,
+    pub title: Name,
+    pub kind: DiscountKind,
+    pub scope: Option<DiscountScopeDto>,
+    #[ts(as = "Option<String>")]
+    pub min_subtotal: Option<BigDecimal>,
+    pub usage_limit: Option<u32>,
+    pub per_customer_limit: Option<u32>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(DiscountRecord)]
+pub struct DiscountDto {
+    #[from(@._id.into())]
+    pub id: Id,
+    pub code: Option<String>,
+    pub title: Name,
+    pub kind: DiscountKind,
+    #[from(~.into())]
+    pub scope: DiscountScopeDto,
+    #[ts(as = "Option<String>")]
+    pub min_subtotal: Option<BigDecimal>,
+    pub usage_limit: Option<u32>,
+    pub usage_count: u32,
+    pub per_customer_limit: Option<u32>,
+    #[from(~.map(|d| d.to_chrono()))]
+    pub starts_at: Option<DateTime<Utc>>,
+    #[from(~.map(|d| d.to_chrono()))]
+    pub ends_at: Option<DateTime<Utc>>,
+    pub active: bool,
+    #[from(~.to_chrono())]
+    pub created_at: DateTime<Utc>,
+    #[from(~.to_chrono())]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct DiscountListQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub active: Option<bool>,
+    pub search: Option<String>,
+}
+
+#[macros::json_option_serde]
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct DiscountUpdate {
+    pub code: JsonOption<Option<String>>,
+    pub title: JsonOption<Name>,
+    pub kind: JsonOption<DiscountKind>,
+    pub scope: JsonOption<DiscountScopeDto>,
+    #[ts(as = "JsonOption<Option<String>>")]
+    pub min_subtotal: JsonOption<Option<BigDecimal>>,
+    pub usage_limit: JsonOption<Option<u32>>,
+    pub per_customer_limit: JsonOption<Option<u32>>,
+    pub starts_at: JsonOption<Option<DateTime<Utc>>>,
+    pub ends_at: JsonOption<Option<DateTime<Utc>>>,
+    pub active: JsonOption<bool>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct DiscountListResponse {
+    pub discounts: Vec<DiscountDto>,
+    pub total: u64,
+    pub page: u32,
+    pub limit: u32,
+}
@@ -0,0 +1,460 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+use bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::types::name::Name;
+
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DiscountKind {
+    Percentage {
+        #[ts(as = "String")]
+        percent: BigDecimal,
+    },
+    Fixed {
+        #[ts(as = "String")]
+        amount: BigDecimal,
+    },
+    FreeShipping,
+    /// For every `buy_quantity` eligible units, the cheapest `get_quantity`
+    /// units get `percent` off (100 makes them free).
+    BuyXGetY {
+        buy_quantity: u32,
+        get_quantity: u32,
+        #[ts(as = "String")]
+        percent: BigDecimal,
+    },
+}
+
+impl DiscountKind {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let zero = BigDecimal::from(0);
+        let hundred = BigDecimal::from(100);
+
+        match self {
+            DiscountKind::Percentage { percent } | DiscountKind::BuyXGetY { percent, .. }
+                if percent <= &zero || percent > &hundred =>
+            {
+                Err("Percent must be between 0 and 100")
+            }
+            DiscountKind::Fixed { amount } if amount <= &zero => {
+                Err("Amount must be greater than zero")
+            }
+            DiscountKind::BuyXGetY {
+                buy_quantity,
+                get_quantity,
+                ..
+            } if *buy_quantity == 0 || *get_quantity == 0 => {
+                Err("Buy and get quantities must be greater than zero")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DiscountScope {
+    pub product_ids: Vec<ObjectId>,
+    pub categories: Vec<String>,
+}
+
+impl DiscountScope {
+    /// An empty scope covers every product.
+    pub fn covers(&self, line: &DiscountLine) -> bool {
+        (self.product_ids.is_empty() && self.categories.is_empty())
+            || line
+                .product_id
+                .is_some_and(|id| self.product_ids.contains(&id))
+            || self.categories.iter().any(|c| c == &line.category)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiscountRecord {
+    pub _id: ObjectId,
+    // Automatic promotions have no code
+    pub code: Option<String>,
+    pub title: Name,
+    pub kind: DiscountKind,
+    pub scope: DiscountScope,
+    pub min_subtotal: Option<BigDecimal>,
+    pub usage_limit: Option<u32>,
+    pub usage_count: u32,
+    pub per_customer_limit: Option<u32>,
+    pub starts_at: Option<DateTime>,
+    pub ends_at: Option<DateTime>,
+    pub active: bool,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl DiscountRecord {
+    pub fn new(
+        code: Option<String>,
+        title: Name,
+        kind: DiscountKind,
+        scope: DiscountScope,
+        active: bool,
+    ) -> Self {
+        let now = DateTime::now();
+
+        Self {
+            _id: Default::default(),
+            code: code.map(|c| normalize_code(&c)),
+            title,
+            kind,
+            scope,
+            min_subtotal: None,
+            usage_limit: None,
+            usage_count: 0,
+            per_customer_limit: None,
+            starts_at: None,
+            ends_at: None,
+            active,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn is_live(&self, now: DateTime) -> bool {
+        self.active
+            && self.starts_at.is_none_or(|s| s <= now)
+            && self.ends_at.is_none_or(|e| now < e)
+            && self.usage_limit.is_none_or(|l| self.usage_count < l)
+    }
+
+    /// Computes what this discount takes off, given what is still left to
+    /// discount on every line and on shipping after earlier discounts.
+    pub fn apply(
+        &self,
+        lines: &[DiscountLine],
+        remaining: &[BigDecimal],
+        shipping_remaining: &BigDecimal,
+    ) -> DiscountApplication {
+        let zero = BigDecimal::from(0);
+        let mut application = DiscountApplication {
+            lines: vec![zero.clone(); lines.len()],
+            order: zero.clone(),
+        };
+
+        let subtotal: BigDecimal = lines.iter().map(DiscountLine::total).sum();
+        if matches!(self.min_subtotal, Some(ref min) if &subtotal < min) {
+            return application;
+        }
+
+        let eligible: Vec<usize> = (0..lines.len())
+            .filter(|&i| self.scope.covers(&lines[i]))
+            .collect();
+        if eligible.is_empty() {
+            return application;
+        }
+
+        match &self.kind {
+            DiscountKind::Percentage { percent } => {
+                for &i in &eligible {
+                    let off = percent_of(&lines[i].total(), percent);
+                    application.lines[i] = off.min(remaining[i].clone());
+                }
+            }
+            DiscountKind::BuyXGetY {
+                buy_quantity,
+                get_quantity,
+                percent,
+            } => {
+                let mut units: Vec<(usize, &BigDecimal)> = eligible
+                    .iter()
+                    .flat_map(|&i| (0..lines[i].quantity).map(move |_| (i, &lines[i].unit_price)))
+                    .collect();
+                units.sort_by(|a, b| b.1.cmp(a.1));
+
+                let group = (buy_quantity + get_quantity) as usize;
+                let discounted = units.len() / group * *get_quantity as usize;
+                for &(i, price) in units.iter().rev().take(discounted) {
+                    application.lines[i] += percent_of(price, percent);
+                }
+                for &i in &eligible {
+                    if application.lines[i] > remaining[i] {
+                        application.lines[i] = remaining[i].clone();
+                    }
+                }
+            }
+            DiscountKind::Fixed { amount } => {
+                let available: BigDecimal = eligible.iter().map(|&i| &remaining[i]).sum();
+                application.order = amount.clone().min(available);
+            }
+            DiscountKind::FreeShipping => {
+                application.order = shipping_remaining.clone();
+            }
+        }
+
+        application
+    }
+}
+
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+fn percent_of(amount: &BigDecimal, percent: &BigDecimal) -> BigDecimal {
+    (amount * percent / BigDecimal::from(100)).with_scale_round(2, RoundingMode::HalfUp)
+}
+
+/// A priced order line as seen by the discount engine.
+#[derive(Debug, Clone)]
+pub struct DiscountLine {
+    pub product_id: Option<ObjectId>,
+    pub category: String,
+    pub unit_price: BigDecimal,
+    pub quantity: u32,
+}
+
+impl DiscountLine {
+    pub fn total(&self) -> BigDecimal {
+        &self.unit_price * BigDecimal::from(self.quantity)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscountApplication {
+    // Amount taken off each line, in line order
+    pub lines: Vec<BigDecimal>,
+    // Amount taken off the order as a whole
+    pub order: BigDecimal,
+}
+
+impl DiscountApplication {
+    pub fn total(&self) -> BigDecimal {
+        self.lines.iter().sum::<BigDecimal>() + &self.order
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AppliedDiscount {
+    pub discount: DiscountRecord,
+    pub application: DiscountApplication,
+}
+
+/// Applies the discounts in order, each one only seeing what the previous
+/// ones left, so lines and shipping never go below zero. Discounts that end
+/// up taking nothing off are left out.
+pub fn apply_discounts<'a>(
+    discounts: &[&'a DiscountRecord],
+    lines: &[DiscountLine],
+    shipping_cost: &BigDecimal,
+) -> Vec<(&'a DiscountRecord, DiscountApplication)> {
+    let zero = BigDecimal::from(0);
+    let mut remaining: Vec<BigDecimal> = lines.iter().map(DiscountLine::total).collect();
+    let mut shipping_remaining = shipping_cost.clone();
+    let mut applied = Vec::new();
+
+    for &discount in discounts {
+        let application = discount.apply(lines, &remaining, &shipping_remaining);
+        if application.total() <= zero {
+            continue;
+        }
+
+        for (left, off) in remaining.iter_mut().zip(&application.lines) {
+            *left -= off;
+        }
+
+        match discount.kind {
+            DiscountKind::FreeShipping => shipping_remaining -= &application.order,
+            _ => {
+                // Order-level amounts come off the lines they were capped by
+                let mut left = application.order.clone();
+                for (i, line_left) in remaining.iter_mut().enumerate() {
+                    if left <= zero {
+                        break;
+                    }
+                    if discount.scope.covers(&lines[i]) {
+                        let off = left.clone().min(line_left.clone());
+                        *line_left -= &off;
+                        left -= off;
+                    }
+                }
+            }
+        }
+
+        applied.push((discount, application));
+    }
+
+    applied
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiscountRedemption {
+    pub _id: ObjectId,
+    pub discount_id: ObjectId,
+    pub order_id: ObjectId,
+    pub customer: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DiscountFilter {
+    pub active: Option<bool>,
+    pub search: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn dec(v: &str) -> BigDecimal {
+        BigDecimal::from_str(v).unwrap()
+    }
+
+    fn line(product_id: ObjectId, category: &str, price: &str, quantity: u32) -> DiscountLine {
+        DiscountLine {
+            product_id: Some(product_id),
+            category: category.into(),
+            unit_price: dec(price),
+            quantity,
+        }
+    }
+
+    fn discount(kind: DiscountKind, scope: DiscountScope) -> DiscountRecord {
+        DiscountRecord::new(
+            Some("promo".into()),
+            Name::new("Promo").unwrap(),
+            kind,
+            scope,
+            true,
+        )
+    }
+
+    #[test]
+    fn test_kind_validation() {
+        assert!(DiscountKind::Percentage { percent: dec("0") }
+            .validate()
+            .is_err());
+        assert!(DiscountKind::Percentage {
+            percent: dec("101")
+        }
+        .validate()
+        .is_err());
+        assert!(DiscountKind::Percentage { percent: dec("15") }
+            .validate()
+            .is_ok());
+        assert!(DiscountKind::Fixed { amount: dec("-1") }
+            .validate()
+            .is_err());
+        assert!(DiscountKind::BuyXGetY {
+            buy_quantity: 2,
+            get_quantity: 0,
+            percent: dec("100"),
+        }
+        .validate()
+        .is_err());
+    }
+
+    #[test]
+    fn test_is_live() {
+        let now = DateTime::now();
+        let mut d = discount(DiscountKind::FreeShipping, DiscountScope::default());
+        assert_eq!(d.code.as_deref(), Some("PROMO"));
+        assert!(d.is_live(now));
+
+        d.starts_at = Some(DateTime::from_millis(now.timestamp_millis() + 60_000));
+        assert!(!d.is_live(now));
+
+        d.starts_at = None;
+        d.ends_at = Some(now);
+        assert!(!d.is_live(now));
+
+        d.ends_at = None;
+        d.usage_limit = Some(3);
+        d.usage_count = 3;
+        assert!(!d.is_live(now));
+    }
+
+    #[test]
+    fn test_percentage_scoped_to_category() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let lines = vec![line(a, "shoes", "1000", 2), line(b, "hats", "500", 1)];
+        let d = discount(
+            DiscountKind::Percentage { percent: dec("10") },
+            DiscountScope {
+                product_ids: vec![],
+                categories: vec!["shoes".into()],
+            },
+        );
+
+        let applied = apply_discounts(&[&d], &lines, &dec("400"));
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].1.lines, vec![dec("200"), dec("0")]);
+        assert_eq!(applied[0].1.order, dec("0"));
+    }
+
+    #[test]
+    fn test_buy_x_get_y_discounts_cheapest_units() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let lines = vec![line(a, "", "1000", 3), line(b, "", "300", 2)];
+        let d = discount(
+            DiscountKind::BuyXGetY {
+                buy_quantity: 2,
+                get_quantity: 1,
+                percent: dec("100"),
+            },
+            DiscountScope::default(),
+        );
+
+        // Five units make one full group, so the single cheapest unit is free
+        let applied = apply_discounts(&[&d], &lines, &dec("0"));
+        assert_eq!(applied[0].1.lines, vec![dec("0"), dec("300")]);
+
+        let lines = vec![line(a, "", "1000", 6)];
+        let applied = apply_discounts(&[&d], &lines, &dec("0"));
+        assert_eq!(applied[0].1.lines, vec![dec("2000")]);
+    }
+
+    #[test]
+    fn test_stacking_never_goes_below_zero() {
+        let a = ObjectId::new();
+        let lines = vec![line(a, "", "1000", 1)];
+        let half = discount(
+            DiscountKind::Percentage { percent: dec("50") },
+            DiscountScope::default(),
+        );
+        let fixed = discount(
+            DiscountKind::Fixed { amount: dec("800") },
+            DiscountScope::default(),
+        );
+        let again = discount(
+            DiscountKind::Fixed { amount: dec("100") },
+            DiscountScope::default(),
+        );
+        let shipping = discount(DiscountKind::FreeShipping, DiscountScope::default());
+
+        let applied = apply_discounts(&[&half, &fixed, &again, &shipping], &lines, &dec("600"));
+        assert_eq!(applied.len(), 3);
+        assert_eq!(applied[0].1.total(), dec("500"));
+        assert_eq!(applied[1].1.order, dec("500"));
+        assert_eq!(applied[2].1.order, dec("600"));
+    }
+
+    #[test]
+    fn test_min_subtotal_and_product_scope() {
+        let (a, b) = (ObjectId::new(), ObjectId::new());
+        let lines = vec![line(a, "", "1000", 1)];
+
+        let mut d = discount(
+            DiscountKind::Fixed { amount: dec("100") },
+            DiscountScope::default(),
+        );
+        d.min_subtotal = Some(dec("2000"));
+        assert!(apply_discounts(&[&d], &lines, &dec("0")).is_empty());
+
+        let d = discount(
+            DiscountKind::Fixed { amount: dec("100") },
+            DiscountScope {
+                product_ids: vec![b],
+                categories: vec![],
+            },
+        );
+        assert!(apply_discounts(&[&d], &lines, &dec("0")).is_empty());
+    }
+}
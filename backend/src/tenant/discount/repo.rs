@@ -0,0 +1,366 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId, DateTime};
+use futures::stream::TryStreamExt;
+use mongodb::{options::FindOptions, Client, Collection, Database};
+
+use super::domain::*;
+use crate::utils::error::{ApiError, ApiResult};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait]
+pub trait DiscountRepo: Send + Sync {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        discount: DiscountRecord,
+    ) -> ApiResult<DiscountRecord>;
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<DiscountRecord>>;
+    async fn find_by_code(
+        &self,
+        business_id: ObjectId,
+        code: &str,
+    ) -> ApiResult<Option<DiscountRecord>>;
+    async fn find_automatic(&self, business_id: ObjectId) -> ApiResult<Vec<DiscountRecord>>;
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        discount: DiscountRecord,
+    ) -> ApiResult<DiscountRecord>;
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+    /// Moves every discount scoped to the `from` category over to `to`.
+    /// Returns how many were changed.
+    async fn rename_category(&self, business_id: ObjectId, from: &str, to: &str) -> ApiResult<u64>;
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: DiscountFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<DiscountRecord>, u64)>;
+    /// Counts one use of the discount unless its usage limit is already
+    /// reached. Returns whether the use was counted.
+    async fn increment_usage(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        usage_limit: Option<u32>,
+    ) -> ApiResult<bool>;
+    async fn decrement_usage(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+    async fn record_redemption(
+        &self,
+        business_id: ObjectId,
+        redemption: DiscountRedemption,
+    ) -> ApiResult<()>;
+    async fn count_redemptions(
+        &self,
+        business_id: ObjectId,
+        discount_id: ObjectId,
+        customer: &str,
+    ) -> ApiResult<u64>;
+    /// Removes what the order redeemed of the discount.
+    async fn delete_redemptions(
+        &self,
+        business_id: ObjectId,
+        discount_id: ObjectId,
+        order_id: ObjectId,
+    ) -> ApiResult<()>;
+}
+
+pub struct MongoDiscountRepo {
+    client: Client,
+}
+
+impl MongoDiscountRepo {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn get_database(&self, business_id: ObjectId) -> Database {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+    }
+
+    fn get_collection(&self, business_id: ObjectId) -> Collection<DiscountRecord> {
+        self.get_database(business_id).collection("discounts")
+    }
+
+    fn get_redemptions(&self, business_id: ObjectId) -> Collection<DiscountRedemption> {
+        self.get_database(business_id)
+            .collection("discount_redemptions")
+    }
+
+    fn build_filter_query(&self, filter: &DiscountFilter) -> bson::Document {
+        let mut query = doc! {};
+
+        if let Some(active) = filter.active {
+            query.insert("active", active);
+        }
+
+        if let Some(ref search) = filter.search {
+            query.insert(
+                "$or",
+                vec![
+                    doc! {
+                        "code": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                    doc! {
+                        "title": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                ],
+            );
+        }
+
+        query
+    }
+}
+
+#[async_trait]
+impl DiscountRepo for MongoDiscountRepo {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        discount: DiscountRecord,
+    ) -> ApiResult<DiscountRecord> {
+        let collection = self.get_collection(business_id);
+
+        collection.insert_one(&discount).await.map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                ApiError::conflict("discount", "Discount already exists")
+            } else {
+                ApiError::internal(format!("Failed to create discount: {}", e))
+            }
+        })?;
+
+        Ok(discount)
+    }
+
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<DiscountRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn find_by_code(
+        &self,
+        business_id: ObjectId,
+        code: &str,
+    ) -> ApiResult<Option<DiscountRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find_one(doc! { "code": code })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn find_automatic(&self, business_id: ObjectId) -> ApiResult<Vec<DiscountRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! { "active": true, "code": null })
+            .sort(doc! { "created_at": 1 })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        mut discount: DiscountRecord,
+    ) -> ApiResult<DiscountRecord> {
+        let collection = self.get_collection(business_id);
+
+        discount.updated_at = DateTime::now();
+
+        // Usage is only ever changed through increment/decrement
+        let result = collection
+            .replace_one(
+                doc! { "_id": id, "usage_count": discount.usage_count },
+                &discount,
+            )
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update discount: {}", e)))?;
+
+        if result.matched_count == 0 {
+            return Err(ApiError::conflict(
+                "discount",
+                "Discount was used or removed while editing, please retry",
+            ));
+        }
+
+        Ok(discount)
+    }
+
+    async fn rename_category(&self, business_id: ObjectId, from: &str, to: &str) -> ApiResult<u64> {
+        let collection = self.get_collection(business_id);
+
+        let result = collection
+            .update_many(
+                doc! { "scope.categories": from },
+                doc! { "$set": { "scope.categories.$[c]": to, "updated_at": DateTime::now() } },
+            )
+            .array_filters(vec![doc! { "c": from }])
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to rename category: {}", e)))?;
+
+        Ok(result.modified_count)
+    }
+
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+
+        let result = collection
+            .delete_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to delete discount: {}", e)))?;
+
+        if result.deleted_count == 0 {
+            return Err(ApiError::not_found("discount", "Discount not found"));
+        }
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: DiscountFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<DiscountRecord>, u64)> {
+        let collection = self.get_collection(business_id);
+        let query = self.build_filter_query(&filter);
+
+        let total = collection
+            .count_documents(query.clone())
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to count discounts: {}", e)))?;
+
+        let skip = ((page.max(1) - 1) * limit) as u64;
+
+        let find_options = FindOptions::builder()
+            .skip(skip)
+            .limit(limit as i64)
+            .sort(doc! { "created_at": -1 })
+            .build();
+
+        let mut cursor = collection
+            .find(query)
+            .with_options(find_options)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        let mut discounts = Vec::new();
+        while let Some(discount) = cursor
+            .try_next()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
+        {
+            discounts.push(discount);
+        }
+
+        Ok((discounts, total))
+    }
+
+    async fn increment_usage(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        usage_limit: Option<u32>,
+    ) -> ApiResult<bool> {
+        let collection = self.get_collection(business_id);
+
+        let mut filter = doc! { "_id": id };
+        if let Some(limit) = usage_limit {
+            filter.insert("usage_count", doc! { "$lt": limit });
+        }
+
+        let result = collection
+            .update_one(filter, doc! { "$inc": { "usage_count": 1 } })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update discount: {}", e)))?;
+
+        Ok(result.modified_count == 1)
+    }
+
+    async fn decrement_usage(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .update_one(
+                doc! { "_id": id, "usage_count": { "$gt": 0 } },
+                doc! { "$inc": { "usage_count": -1 } },
+            )
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update discount: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn record_redemption(
+        &self,
+        business_id: ObjectId,
+        redemption: DiscountRedemption,
+    ) -> ApiResult<()> {
+        let collection = self.get_redemptions(business_id);
+
+        collection
+            .insert_one(&redemption)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to record redemption: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn count_redemptions(
+        &self,
+        business_id: ObjectId,
+        discount_id: ObjectId,
+        customer: &str,
+    ) -> ApiResult<u64> {
+        let collection = self.get_redemptions(business_id);
+
+        collection
+            .count_documents(doc! { "discount_id": discount_id, "customer": customer })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to count redemptions: {}", e)))
+    }
+
+    async fn delete_redemptions(
+        &self,
+        business_id: ObjectId,
+        discount_id: ObjectId,
+        order_id: ObjectId,
+    ) -> ApiResult<()> {
+        let collection = self.get_redemptions(business_id);
+
+        collection
+            .delete_many(doc! { "discount_id": discount_id, "order_id": order_id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to delete redemptions: {}", e)))?;
+
+        Ok(())
+    }
+}
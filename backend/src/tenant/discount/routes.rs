@@ -0,0 +1,85 @@
+use axum::extract::{Path, Query, State};
+use macros::routes;
+
+use super::api::*;
+use crate::extractors::cookies::FromCookies;
+use crate::extractors::json::Json;
+use crate::platform::business::api::BusinessSession;
+use crate::platform::user::api::MessageResponse;
+use crate::types::id::Id;
+use crate::utils::error::ApiResult;
+use crate::AppState;
+
+pub struct DiscountRoutes;
+
+#[routes(prefix = "/api/v1/discounts", state = AppState)]
+impl DiscountRoutes {
+    #[route(method=post, path="/create", res=DiscountDto, perm="discounts:write")]
+    async fn create_discount(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[json] create_req: DiscountCreate,
+    ) -> ApiResult<Json<DiscountDto>> {
+        state
+            .discount_service
+            .create_discount(&state.category_service, business, create_req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/list", res=DiscountListResponse, perm="discounts:read")]
+    async fn list_discounts(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: DiscountListQuery,
+    ) -> ApiResult<Json<DiscountListResponse>> {
+        state
+            .discount_service
+            .list_discounts(business, query)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/{discount_id}", res=DiscountDto, perm="discounts:read")]
+    async fn get_discount(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] discount_id: Id,
+    ) -> ApiResult<Json<DiscountDto>> {
+        state
+            .discount_service
+            .get_discount(business, discount_id)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=patch, path="/{discount_id}", res=DiscountDto, perm="discounts:write")]
+    async fn update_discount(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] discount_id: Id,
+        #[json] update_req: DiscountUpdate,
+    ) -> ApiResult<Json<DiscountDto>> {
+        state
+            .discount_service
+            .update_discount(&state.category_service, business, discount_id, update_req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/{discount_id}", res=MessageResponse, perm="discounts:delete")]
+    async fn delete_discount(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] discount_id: Id,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .discount_service
+            .delete_discount(business, discount_id)
+            .await
+            .map(|_| MessageResponse {
+                message: "Discount deleted successfully".to_string(),
+            })
+            .map(Json)
+    }
+}
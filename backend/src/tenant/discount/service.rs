@@ -0,0 +1,522 @@
+use bigdecimal::BigDecimal;
+use bson::{oid::ObjectId, DateTime};
+
+use super::api::*;
+use super::domain::*;
+use super::repo::DiscountRepo;
+use crate::platform::business::api::BusinessSession;
+use crate::tenant::category::repo::CategoryRepo;
+use crate::tenant::category::service::CategoryService;
+use crate::types::id::Id;
+use crate::utils::error::{ApiError, ApiResult};
+
+pub struct DiscountService<R: DiscountRepo> {
+    repo: R,
+}
+
+impl<R: DiscountRepo> DiscountService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_discount<C: CategoryRepo>(
+        &self,
+        category_service: &CategoryService<C>,
+        business: BusinessSession,
+        create_req: DiscountCreate,
+    ) -> ApiResult<DiscountDto> {
+        let business_id = business.business_id.into_inner();
+
+        let mut record = DiscountRecord::new(
+            create_req.code,
+            create_req.title,
+            create_req.kind,
+            create_req.scope.map(Into::into).unwrap_or_default(),
+            create_req.active,
+        );
+        record.min_subtotal = create_req.min_subtotal;
+        record.usage_limit = create_req.usage_limit;
+        record.per_customer_limit = create_req.per_customer_limit;
+        record.starts_at = create_req.starts_at.map(DateTime::from_chrono);
+        record.ends_at = create_req.ends_at.map(DateTime::from_chrono);
+
+        self.validate(business_id, &record).await?;
+        Self::validate_scope(category_service, business.business_id, &record.scope).await?;
+
+        self.repo.create(business_id, record).await.map(Into::into)
+    }
+
+    pub async fn get_discount(
+        &self,
+        business: BusinessSession,
+        discount_id: Id,
+    ) -> ApiResult<DiscountDto> {
+        let id = discount_id.into_inner();
+        self.repo
+            .find_by_id(business.business_id.into_inner(), id)
+            .await?
+            .ok_or(ApiError::not_found("discount", id.to_hex()))
+            .map(Into::into)
+    }
+
+    pub async fn update_discount<C: CategoryRepo>(
+        &self,
+        category_service: &CategoryService<C>,
+        business: BusinessSession,
+        discount_id: Id,
+        update_req: DiscountUpdate,
+    ) -> ApiResult<DiscountDto> {
+        let id = discount_id.into_inner();
+        let business_id = business.business_id.into_inner();
+        let mut record = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or(ApiError::not_found("discount", id.to_hex()))?;
+
+        update_req
+            .code
+            .map(|v| record.code = v.map(|c| normalize_code(&c)));
+        update_req.title.map(|v| record.title = v);
+        update_req.kind.map(|v| record.kind = v);
+        let scope_changed = update_req
+            .scope
+            .map(|v| record.scope = v.into())
+            .to_option()
+            .is_some();
+        update_req.min_subtotal.map(|v| record.min_subtotal = v);
+        update_req.usage_limit.map(|v| record.usage_limit = v);
+        update_req
+            .per_customer_limit
+            .map(|v| record.per_customer_limit = v);
+        update_req
+            .starts_at
+            .map(|v| record.starts_at = v.map(DateTime::from_chrono));
+        update_req
+            .ends_at
+            .map(|v| record.ends_at = v.map(DateTime::from_chrono));
+        update_req.active.map(|v| record.active = v);
+
+        self.validate(business_id, &record).await?;
+        if scope_changed {
+            Self::validate_scope(category_service, business.business_id, &record.scope).await?;
+        }
+
+        self.repo
+            .update(business_id, id, record)
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn delete_discount(
+        &self,
+        business: BusinessSession,
+        discount_id: Id,
+    ) -> ApiResult<()> {
+        self.repo
+            .delete(business.business_id.into_inner(), discount_id.into_inner())
+            .await
+    }
+
+    pub async fn list_discounts(
+        &self,
+        business: BusinessSession,
+        query: DiscountListQuery,
+    ) -> ApiResult<DiscountListResponse> {
+        let DiscountListQuery {
+            page,
+            limit,
+            active,
+            search,
+        } = query;
+
+        let filter = DiscountFilter { active, search };
+        let page = page.unwrap_or(1);
+        let limit = limit.unwrap_or(10);
+
+        let (discounts, total) = self
+            .repo
+            .list(business.business_id.into_inner(), filter, page, limit)
+            .await?;
+
+        let views: Vec<_> = discounts.into_iter().map(Into::into).collect();
+        Ok(DiscountListResponse {
+            discounts: views,
+            total,
+            page,
+            limit,
+        })
+    }
+
+    async fn validate(&self, business_id: ObjectId, record: &DiscountRecord) -> ApiResult<()> {
+        record
+            .kind
+            .validate()
+            .map_err(|e| ApiError::validation("kind", e))?;
+
+        if let (Some(starts_at), Some(ends_at)) = (record.starts_at, record.ends_at) {
+            if ends_at <= starts_at {
+                return Err(ApiError::validation(
+                    "ends_at",
+                    "End date must be after the start date",
+                ));
+            }
+        }
+
+        if let Some(ref code) = record.code {
+            if code.is_empty() {
+                return Err(ApiError::validation("code", "Code cannot be empty"));
+            }
+
+            let taken = self
+                .repo
+                .find_by_code(business_id, code)
+                .await?
+                .is_some_and(|d| d._id != record._id);
+            if taken {
+                return Err(ApiError::conflict(
+                    "discount",
+                    "Discount with this code already exists",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Scopes name categories by slug, which are kept in step by
+    // `rename_category`, so they have to exist in the first place
+    async fn validate_scope<C: CategoryRepo>(
+        category_service: &CategoryService<C>,
+        business_id: Id,
+        scope: &DiscountScope,
+    ) -> ApiResult<()> {
+        for category in &scope.categories {
+            if category.is_empty() {
+                return Err(ApiError::validation("scope", "Category cannot be empty"));
+            }
+            category_service
+                .ensure_exists(business_id, category)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn rename_category(&self, business_id: Id, from: &str, to: &str) -> ApiResult<u64> {
+        self.repo
+            .rename_category(business_id.into_inner(), from, to)
+            .await
+    }
+
+    /// Works out which live automatic promotions and which code apply to the
+    /// given lines. A code that is unknown, expired, used up or that takes
+    /// nothing off is rejected rather than silently ignored.
+    pub async fn resolve(
+        &self,
+        business_id: Id,
+        code: Option<&str>,
+        customer: &str,
+        lines: &[DiscountLine],
+        shipping_cost: &BigDecimal,
+    ) -> ApiResult<Vec<AppliedDiscount>> {
+        let business_id = business_id.into_inner();
+        let now = DateTime::now();
+
+        let mut candidates = Vec::new();
+        for discount in self.repo.find_automatic(business_id).await? {
+            if discount.is_live(now)
+                && self
+                    .within_customer_limit(business_id, &discount, customer)
+                    .await?
+            {
+                candidates.push(discount);
+            }
+        }
+
+        let code = code.map(normalize_code).filter(|c| !c.is_empty());
+        if let Some(ref code) = code {
+            let discount = self
+                .repo
+                .find_by_code(business_id, code)
+                .await?
+                .filter(|d| d.is_live(now))
+                .ok_or(ApiError::validation(
+                    "discount_code",
+                    "Discount code is invalid or expired",
+                ))?;
+
+            if !self
+                .within_customer_limit(business_id, &discount, customer)
+                .await?
+            {
+                return Err(ApiError::validation(
+                    "discount_code",
+                    "Discount code was already used",
+                ));
+            }
+            candidates.push(discount);
+        }
+
+        let refs: Vec<_> = candidates.iter().collect();
+        let applied: Vec<_> = apply_discounts(&refs, lines, shipping_cost)
+            .into_iter()
+            .map(|(discount, application)| AppliedDiscount {
+                discount: discount.clone(),
+                application,
+            })
+            .collect();
+
+        if code.is_some() && !applied.iter().any(|a| a.discount.code == code) {
+            return Err(ApiError::validation(
+                "discount_code",
+                "Discount code does not apply to this order",
+            ));
+        }
+
+        Ok(applied)
+    }
+
+    /// Turns customers away early, `reserve` is what holds the limit.
+    async fn within_customer_limit(
+        &self,
+        business_id: ObjectId,
+        discount: &DiscountRecord,
+        customer: &str,
+    ) -> ApiResult<bool> {
+        match discount.per_customer_limit {
+            Some(limit) => Ok(self
+                .repo
+                .count_redemptions(business_id, discount._id, customer)
+                .await?
+                < limit as u64),
+            None => Ok(true),
+        }
+    }
+
+    /// Counts a use of every applied discount and records its redemption by
+    /// the customer on the order, giving them all back if any of them ran
+    /// out in the meantime.
+    pub async fn reserve(
+        &self,
+        business_id: Id,
+        applied: &[AppliedDiscount],
+        order_id: ObjectId,
+        customer: &str,
+    ) -> ApiResult<()> {
+        for (i, a) in applied.iter().enumerate() {
+            if let Err(e) = self
+                .reserve_one(business_id.into_inner(), &a.discount, order_id, customer)
+                .await
+            {
+                self.release(business_id, &applied[..i], order_id).await?;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn reserve_one(
+        &self,
+        business_id: ObjectId,
+        discount: &DiscountRecord,
+        order_id: ObjectId,
+        customer: &str,
+    ) -> ApiResult<()> {
+        if !self
+            .repo
+            .increment_usage(business_id, discount._id, discount.usage_limit)
+            .await?
+        {
+            return Err(ApiError::conflict(
+                "discount",
+                format!("Discount '{}' is no longer available", discount.title),
+            ));
+        }
+
+        // The redemption is recorded before the customer's are counted, so of
+        // checkouts racing for their last use the one counting last sees them
+        // all and the limit is never gone over
+        self.repo
+            .record_redemption(
+                business_id,
+                DiscountRedemption {
+                    _id: ObjectId::new(),
+                    discount_id: discount._id,
+                    order_id,
+                    customer: customer.to_string(),
+                    created_at: DateTime::now(),
+                },
+            )
+            .await?;
+
+        let within_limit = match discount.per_customer_limit {
+            Some(limit) => {
+                self.repo
+                    .count_redemptions(business_id, discount._id, customer)
+                    .await?
+                    <= limit as u64
+            }
+            None => true,
+        };
+        if !within_limit {
+            self.repo
+                .delete_redemptions(business_id, discount._id, order_id)
+                .await?;
+            self.repo.decrement_usage(business_id, discount._id).await?;
+            return Err(ApiError::conflict(
+                "discount",
+                format!("Discount '{}' was already used", discount.title),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Gives back what `reserve` took for the order.
+    pub async fn release(
+        &self,
+        business_id: Id,
+        applied: &[AppliedDiscount],
+        order_id: ObjectId,
+    ) -> ApiResult<()> {
+        let business_id = business_id.into_inner();
+
+        for a in applied {
+            self.repo
+                .delete_redemptions(business_id, a.discount._id, order_id)
+                .await?;
+            self.repo
+                .decrement_usage(business_id, a.discount._id)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::tenant::discount::repo::MockDiscountRepo;
+    use crate::types::name::Name;
+
+    #[derive(Default)]
+    struct Stored {
+        redemptions: Vec<DiscountRedemption>,
+        usage_count: u32,
+    }
+
+    /// A repo keeping the redemptions and usage count of one discount. The
+    /// first count only happens once another redemption was recorded, as if
+    /// a second checkout raced the first.
+    fn racing_repo() -> (MockDiscountRepo, Arc<Mutex<Stored>>) {
+        let stored = Arc::new(Mutex::new(Stored::default()));
+        let counts = AtomicUsize::new(0);
+        let mut repo = MockDiscountRepo::new();
+
+        let s = stored.clone();
+        repo.expect_increment_usage().returning(move |_, _, limit| {
+            let mut s = s.lock().unwrap();
+            if limit.is_some_and(|l| s.usage_count >= l) {
+                return Ok(false);
+            }
+            s.usage_count += 1;
+            Ok(true)
+        });
+        let s = stored.clone();
+        repo.expect_decrement_usage().returning(move |_, _| {
+            s.lock().unwrap().usage_count -= 1;
+            Ok(())
+        });
+        let s = stored.clone();
+        repo.expect_record_redemption()
+            .returning(move |_, redemption| {
+                s.lock().unwrap().redemptions.push(redemption);
+                Ok(())
+            });
+        let s = stored.clone();
+        repo.expect_count_redemptions()
+            .returning(move |_, discount_id, customer| {
+                if counts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    while s.lock().unwrap().redemptions.len() < 2 {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+                }
+                Ok(s.lock()
+                    .unwrap()
+                    .redemptions
+                    .iter()
+                    .filter(|r| r.discount_id == discount_id && r.customer == customer)
+                    .count() as u64)
+            });
+        let s = stored.clone();
+        repo.expect_delete_redemptions()
+            .returning(move |_, discount_id, order_id| {
+                s.lock()
+                    .unwrap()
+                    .redemptions
+                    .retain(|r| r.discount_id != discount_id || r.order_id != order_id);
+                Ok(())
+            });
+
+        (repo, stored)
+    }
+
+    #[test]
+    fn test_racing_checkouts_stay_within_customer_limit() {
+        let (repo, stored) = racing_repo();
+        let service = Arc::new(DiscountService::new(repo));
+        let business_id = Id::new();
+
+        let mut discount = DiscountRecord::new(
+            Some("promo".into()),
+            Name::new("Promo").unwrap(),
+            DiscountKind::FreeShipping,
+            DiscountScope::default(),
+            true,
+        );
+        discount.per_customer_limit = Some(1);
+        let applied = vec![AppliedDiscount {
+            discount,
+            application: DiscountApplication {
+                lines: Vec::new(),
+                order: BigDecimal::from(0),
+            },
+        }];
+
+        // Checkouts run on threads of their own, the repo blocks one of them
+        let checkout = |customer: &'static str| {
+            let service = service.clone();
+            let applied = applied.clone();
+            std::thread::spawn(move || {
+                tokio::runtime::Builder::new_current_thread()
+                    .build()
+                    .unwrap()
+                    .block_on(service.reserve(business_id, &applied, ObjectId::new(), customer))
+            })
+        };
+
+        // The first to count sees both redemptions and backs off, at most
+        // one of them goes through
+        let (a, b) = (checkout("0555000000"), checkout("0555000000"));
+        let passed = [a.join().unwrap(), b.join().unwrap()]
+            .iter()
+            .filter(|r| r.is_ok())
+            .count();
+        assert!(passed <= 1);
+        assert_eq!(stored.lock().unwrap().redemptions.len(), passed);
+        assert_eq!(stored.lock().unwrap().usage_count, passed as u32);
+
+        let passed = passed + checkout("0555000000").join().unwrap().is_ok() as usize;
+        assert_eq!(passed, 1);
+        assert!(checkout("0555000000").join().unwrap().is_err());
+        assert!(checkout("0666000000").join().unwrap().is_ok());
+        assert_eq!(stored.lock().unwrap().redemptions.len(), 2);
+        assert_eq!(stored.lock().unwrap().usage_count, 2);
+    }
+}
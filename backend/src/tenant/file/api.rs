@@ -7,6 +7,7 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use super::domain::*;
+use super::processing::OutputFormat;
 use crate::{
     types::{id::Id, name::Name},
     utils::serde_helpers::JsonOption,
@@ -19,6 +20,9 @@ pub struct FileCreate {
     pub name: Name,
     pub mime_type: Option<String>,
     pub size: Option<u64>,
+    // Images used by nothing are only served publicly when asked for
+    #[serde(default)]
+    pub public: bool,
 }
 
 #[derive(Debug, Serialize, o2o, TS)]
@@ -31,6 +35,10 @@ pub struct FileDto {
     pub name: Name,
     pub mime_type: Cow<'static, str>,
     pub size: Option<u64>,
+    pub metadata: HashMap<String, String>,
+    pub public: bool,
+    #[from(~.map(|d| d.to_chrono()))]
+    pub finalized_at: Option<DateTime<Utc>>,
     #[from(~.to_chrono())]
     pub created_at: DateTime<Utc>,
     #[from(~.to_chrono())]
@@ -63,3 +71,77 @@ pub struct PresignedUrlResponse {
     pub dynamic_fields: HashMap<String, String>,
     pub expiration: DateTime<Utc>,
 }
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(MimeTypeUsage)]
+pub struct MimeTypeUsageDto {
+    pub mime_type: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct StorageUsageResponse {
+    pub used: u64,
+    pub quota: u64,
+    pub by_mime_type: Vec<MimeTypeUsageDto>,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(SweptFile)]
+pub struct SweptFileDto {
+    #[from(~.into())]
+    pub file_id: Id,
+    pub key: String,
+    pub stored_size: u64,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(FileSweepRecord)]
+pub struct FileSweepDto {
+    #[from(@._id.into())]
+    pub id: Id,
+    pub status: FileSweepStatus,
+    #[from(~.into_iter().map(Into::into).collect())]
+    pub missing_uploads: Vec<SweptFileDto>,
+    pub stray_objects: Vec<StrayObject>,
+    #[from(~.into_iter().map(Into::into).collect())]
+    pub unreferenced: Vec<SweptFileDto>,
+    pub deleted_files: u64,
+    pub deleted_objects: u64,
+    #[from(~.to_chrono())]
+    pub created_at: DateTime<Utc>,
+    #[from(~.map(|d| d.to_chrono()))]
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct FileSweepListResponse {
+    pub sweeps: Vec<FileSweepDto>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct FileSweepConfirm {
+    // Unreferenced files are only reported unless asked for explicitly
+    #[serde(default)]
+    pub include_unreferenced: bool,
+}
+
+// ------ Public Api models ------
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImageQuery {
+    pub w: Option<u32>,
+    pub fmt: Option<OutputFormat>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignedDownloadQuery {
+    pub expires: Option<i64>,
+    pub signature: Option<String>,
+}
@@ -1,4 +1,5 @@
 use std::borrow::Cow;
+use std::collections::HashSet;
 
 use bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
@@ -13,7 +14,17 @@ pub struct FileRecord {
     pub name: Name,
     pub mime_type: Cow<'static, str>,
     pub size: Option<u64>,
+    // Bytes counted against the storage quota: the original and its
+    // renditions, or the most a pending upload may take until it is finalized
+    #[serde(default)]
+    pub stored_size: u64,
     pub metadata: std::collections::HashMap<String, String>,
+    // Served to anyone from the store domain, even when nothing uses it
+    #[serde(default)]
+    pub public: bool,
+    // Set once the upload has been checked by `finalize_file`
+    #[serde(default)]
+    pub finalized_at: Option<DateTime>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -28,15 +39,130 @@ impl Default for FileRecord {
             name: Default::default(),
             mime_type: Cow::from("application/octet-stream"),
             size: None,
+            stored_size: 0,
             metadata: Default::default(),
+            public: false,
+            finalized_at: None,
             created_at: now,
             updated_at: now,
         }
     }
 }
 
+impl FileRecord {
+    pub fn is_finalized(&self) -> bool {
+        // Images finalized before `finalized_at` was kept are known by the
+        // dimensions that were recorded for them
+        self.finalized_at.is_some() || self.metadata.contains_key("width")
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct FileFilter {
     pub mime_type: Option<String>,
     pub search: Option<String>,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MimeTypeUsage {
+    pub mime_type: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSweepStatus {
+    // Dry run, nothing deleted yet
+    Pending,
+    Confirmed,
+    // A newer report replaced this one before it was confirmed
+    Superseded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweptFile {
+    pub file_id: ObjectId,
+    pub key: String,
+    pub stored_size: u64,
+}
+
+impl From<&FileRecord> for SweptFile {
+    fn from(record: &FileRecord) -> Self {
+        Self {
+            file_id: record._id,
+            key: record.key.clone(),
+            stored_size: record.stored_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+pub struct StrayObject {
+    pub key: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSweepRecord {
+    pub _id: ObjectId,
+    pub status: FileSweepStatus,
+    // Records whose upload never arrived
+    pub missing_uploads: Vec<SweptFile>,
+    // Objects under the files prefix that no record owns
+    pub stray_objects: Vec<StrayObject>,
+    // Uploaded files that no product, variant or store points at
+    pub unreferenced: Vec<SweptFile>,
+    pub deleted_files: u64,
+    pub deleted_objects: u64,
+    pub created_at: DateTime,
+    pub confirmed_at: Option<DateTime>,
+}
+
+impl FileSweepRecord {
+    pub fn is_empty(&self) -> bool {
+        self.missing_uploads.is_empty()
+            && self.stray_objects.is_empty()
+            && self.unreferenced.is_empty()
+    }
+}
+
+/// Ids of the uploaded files a string points at, either through their
+/// storage URL (`.../files/{id}/...`) or the resize endpoint (`/img/{id}`).
+/// Templates can hold any number of them.
+pub fn file_ids_in(text: &str, ids: &mut HashSet<ObjectId>) {
+    for marker in ["/files/", "/img/"] {
+        let mut rest = text;
+        while let Some(at) = rest.find(marker) {
+            rest = &rest[at + marker.len()..];
+            if let Some(id) = rest.get(..24).and_then(|s| ObjectId::parse_str(s).ok()) {
+                ids.insert(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_ids_in() {
+        let mut ids = HashSet::new();
+        file_ids_in(
+            "https://cdn.example.com/bucket/biz/64b7f0c2a1e4d3f2b1c0a9e8/files/64b7f0c2a1e4d3f2b1c0a9e9/shoe.jpg",
+            &mut ids,
+        );
+        file_ids_in(
+            r#"<img src="/img/64b7f0c2a1e4d3f2b1c0a9ea?w=480"><a href="/files/nope">x</a>"#,
+            &mut ids,
+        );
+
+        let mut found: Vec<_> = ids.into_iter().map(|id| id.to_hex()).collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec!["64b7f0c2a1e4d3f2b1c0a9e9", "64b7f0c2a1e4d3f2b1c0a9ea"]
+        );
+    }
+}
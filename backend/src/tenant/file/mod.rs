@@ -1,5 +1,7 @@
 pub mod api;
 pub mod domain;
+pub mod processing;
 pub mod repo;
 pub mod routes;
 pub mod service;
+pub mod storage;
@@ -0,0 +1,331 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::Deserialize;
+
+/// WebP renditions generated for every uploaded image, by name and maximum
+/// width. Images narrower than a rendition are never upscaled.
+pub const RENDITIONS: &[(&str, u32)] = &[("thumbnail", 160), ("card", 480), ("full", 1600)];
+
+const RENDITION_QUALITY: f32 = 80.0;
+const RESIZE_QUALITY: u8 = 80;
+const ORIGINAL_QUALITY: u8 = 90;
+
+pub struct Rendition {
+    pub name: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub bytes: Vec<u8>,
+}
+
+pub struct ProcessedImage {
+    pub width: u32,
+    pub height: u32,
+    // The original re-encoded in its own format, without metadata
+    pub original: Vec<u8>,
+    pub renditions: Vec<Rendition>,
+}
+
+/// Detects the MIME type from the file content itself.
+pub fn sniff_mime(bytes: &[u8]) -> Option<&'static str> {
+    infer::get(bytes).map(|t| t.mime_type())
+}
+
+pub fn mime_matches(declared: &str, sniffed: &str) -> bool {
+    fn normalize(mime: &str) -> String {
+        let mime = mime
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        match mime.as_str() {
+            "image/jpg" | "image/pjpeg" => "image/jpeg".to_string(),
+            _ => mime,
+        }
+    }
+
+    normalize(declared) == normalize(sniffed)
+}
+
+fn processable_format(mime: &str) -> Option<ImageFormat> {
+    match mime {
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/png" => Some(ImageFormat::Png),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+pub fn is_processable(mime: &str) -> bool {
+    processable_format(mime).is_some()
+}
+
+/// Size of an image scaled down to `max_width`, keeping its aspect ratio.
+pub fn fit_width(width: u32, height: u32, max_width: u32) -> (u32, u32) {
+    if width <= max_width {
+        return (width, height);
+    }
+
+    let scaled = (height as u64 * max_width as u64).div_ceil(width as u64);
+    (max_width, (scaled as u32).max(1))
+}
+
+/// Widths the on-the-fly resize endpoint accepts.
+pub const RESIZE_WIDTHS: &[u32] = &[160, 320, 480, 640, 800, 1200, 1600, 2048];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Webp,
+    Jpeg,
+    Png,
+}
+
+impl OutputFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "webp",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::Png => "png",
+        }
+    }
+}
+
+/// Decodes the image with its EXIF orientation applied. Everything else in
+/// the metadata is dropped.
+fn decode(bytes: &[u8], mime: &str) -> Result<DynamicImage, String> {
+    let format =
+        processable_format(mime).ok_or_else(|| format!("Unsupported image type {}", mime))?;
+
+    let mut decoder = ImageReader::with_format(Cursor::new(bytes), format)
+        .into_decoder()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let orientation = decoder
+        .orientation()
+        .map_err(|e| format!("Failed to read image orientation: {}", e))?;
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    image.apply_orientation(orientation);
+
+    Ok(image)
+}
+
+fn encode(image: &DynamicImage, format: OutputFormat, quality: u8) -> Result<Vec<u8>, String> {
+    match format {
+        OutputFormat::Webp => encode_webp(image, quality as f32),
+        OutputFormat::Jpeg => {
+            let mut buf = Vec::new();
+            image
+                .to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(&mut buf, quality))
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+            Ok(buf)
+        }
+        OutputFormat::Png => {
+            let mut buf = Cursor::new(Vec::new());
+            image
+                .write_to(&mut buf, ImageFormat::Png)
+                .map_err(|e| format!("Failed to encode image: {}", e))?;
+            Ok(buf.into_inner())
+        }
+    }
+}
+
+fn scale_down(image: &DynamicImage, max_width: u32) -> DynamicImage {
+    let (width, height) = fit_width(image.width(), image.height(), max_width);
+    if width == image.width() {
+        image.clone()
+    } else {
+        image.resize_exact(width, height, FilterType::Lanczos3)
+    }
+}
+
+/// Decodes the image, applies its EXIF orientation and re-encodes it without
+/// any metadata, along with one WebP per rendition.
+pub fn process_image(bytes: &[u8], mime: &str) -> Result<ProcessedImage, String> {
+    let image = decode(bytes, mime)?;
+
+    let original_format = match mime {
+        "image/jpeg" => OutputFormat::Jpeg,
+        "image/png" => OutputFormat::Png,
+        _ => OutputFormat::Webp,
+    };
+    let original = encode(&image, original_format, ORIGINAL_QUALITY)?;
+
+    let mut renditions = Vec::with_capacity(RENDITIONS.len());
+    for &(name, max_width) in RENDITIONS {
+        let resized = scale_down(&image, max_width);
+
+        renditions.push(Rendition {
+            name,
+            width: resized.width(),
+            height: resized.height(),
+            bytes: encode_webp(&resized, RENDITION_QUALITY)?,
+        });
+    }
+
+    Ok(ProcessedImage {
+        width: image.width(),
+        height: image.height(),
+        original,
+        renditions,
+    })
+}
+
+/// Scales the image down to `max_width`, if given, and re-encodes it.
+pub fn resize_image(
+    bytes: &[u8],
+    mime: &str,
+    max_width: Option<u32>,
+    format: OutputFormat,
+) -> Result<Vec<u8>, String> {
+    let image = decode(bytes, mime)?;
+    let image = match max_width {
+        Some(max_width) => scale_down(&image, max_width),
+        None => image,
+    };
+
+    encode(&image, format, RESIZE_QUALITY)
+}
+
+fn encode_webp(image: &DynamicImage, quality: f32) -> Result<Vec<u8>, String> {
+    let image = if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    };
+
+    webp::Encoder::from_image(&image)
+        .map(|e| e.encode(quality).to_vec())
+        .map_err(|e| format!("Failed to encode WebP: {}", e))
+}
+
+/// Name of a rendition inside the file's storage folder.
+pub fn rendition_file_name(name: &str) -> String {
+    format!("renditions/{}.webp", name)
+}
+
+/// Name of a cached resize inside the file's storage folder.
+pub fn resized_file_name(max_width: Option<u32>, format: OutputFormat) -> String {
+    match max_width {
+        Some(width) => format!("cache/{}.{}", width, format.extension()),
+        None => format!("cache/original.{}", format.extension()),
+    }
+}
+
+/// Rewrites the URL of an uploaded file to one of its renditions. URLs that
+/// do not point at an uploaded file, and unknown sizes, give `None`.
+pub fn rendition_url(url: &str, size: &str) -> Option<String> {
+    if !RENDITIONS.iter().any(|(name, _)| *name == size) {
+        return None;
+    }
+
+    let (path, _) = url.split_once(['?', '#']).unwrap_or((url, ""));
+    let (folder, file_name) = path.rsplit_once('/')?;
+    let (files, file_id) = folder.rsplit_once('/')?;
+
+    let is_object_id = file_id.len() == 24 && file_id.bytes().all(|b| b.is_ascii_hexdigit());
+    if !files.ends_with("/files") || !is_object_id || file_name.is_empty() {
+        return None;
+    }
+
+    Some(format!("{}/{}", folder, rendition_file_name(size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn test_mime_matches() {
+        assert!(mime_matches("image/jpg", "image/jpeg"));
+        assert!(mime_matches("Image/PNG; charset=binary", "image/png"));
+        assert!(!mime_matches("image/png", "application/pdf"));
+    }
+
+    #[test]
+    fn test_fit_width() {
+        assert_eq!(fit_width(100, 50, 160), (100, 50));
+        assert_eq!(fit_width(3200, 2400, 1600), (1600, 1200));
+        assert_eq!(fit_width(1000, 333, 480), (480, 160));
+        assert_eq!(fit_width(5000, 1, 160), (160, 1));
+    }
+
+    #[test]
+    fn test_process_image() {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(800, 400, Rgb([200, 10, 10])))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        assert_eq!(sniff_mime(&png), Some("image/png"));
+        let processed = process_image(&png, "image/png").unwrap();
+        assert_eq!((processed.width, processed.height), (800, 400));
+        assert_eq!(sniff_mime(&processed.original), Some("image/png"));
+
+        let sizes: Vec<_> = processed
+            .renditions
+            .iter()
+            .map(|r| (r.name, r.width, r.height))
+            .collect();
+        assert_eq!(
+            sizes,
+            vec![
+                ("thumbnail", 160, 80),
+                ("card", 480, 240),
+                ("full", 800, 400)
+            ]
+        );
+        assert!(processed
+            .renditions
+            .iter()
+            .all(|r| sniff_mime(&r.bytes) == Some("image/webp")));
+    }
+
+    #[test]
+    fn test_resize_image() {
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(1000, 500, Rgb([10, 200, 10])))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        let jpeg = resize_image(&png, "image/png", Some(400), OutputFormat::Jpeg).unwrap();
+        assert_eq!(sniff_mime(&jpeg), Some("image/jpeg"));
+        let resized = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((resized.width(), resized.height()), (400, 200));
+
+        let webp = resize_image(&png, "image/png", None, OutputFormat::Webp).unwrap();
+        assert_eq!(sniff_mime(&webp), Some("image/webp"));
+        assert_eq!(
+            resized_file_name(None, OutputFormat::Webp),
+            "cache/original.webp"
+        );
+    }
+
+    #[test]
+    fn test_rendition_url() {
+        let url = "https://cdn.example.com/bucket/biz/64b7f0c2a1e4d3f2b1c0a9e8/files/64b7f0c2a1e4d3f2b1c0a9e9/shoe.jpg";
+        assert_eq!(
+            rendition_url(url, "card").as_deref(),
+            Some("https://cdn.example.com/bucket/biz/64b7f0c2a1e4d3f2b1c0a9e8/files/64b7f0c2a1e4d3f2b1c0a9e9/renditions/card.webp")
+        );
+        assert_eq!(rendition_url(url, "huge"), None);
+        assert_eq!(rendition_url("https://example.com/shoe.jpg", "card"), None);
+    }
+}
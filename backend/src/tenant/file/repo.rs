@@ -1,11 +1,12 @@
 use async_trait::async_trait;
 use bson::{doc, oid::ObjectId, to_bson, DateTime};
 use futures::stream::TryStreamExt;
-use mongodb::{options::FindOptions, Client, Collection};
+use mongodb::{options::FindOptions, Client, Collection, Database};
 
 use super::domain::*;
 use crate::utils::error::{ApiError, ApiResult};
 
+#[cfg_attr(test, mockall::automock)]
 #[async_trait]
 pub trait FileRepo: Send + Sync {
     async fn create(&self, business_id: ObjectId, file: FileRecord) -> ApiResult<FileRecord>;
@@ -29,6 +30,33 @@ pub trait FileRepo: Send + Sync {
         page: u32,
         limit: u32,
     ) -> ApiResult<(Vec<FileRecord>, u64)>;
+    /// Moves the business' storage counter by `delta` bytes.
+    async fn add_storage_usage(&self, business_id: ObjectId, delta: i64) -> ApiResult<()>;
+    async fn get_storage_usage(&self, business_id: ObjectId) -> ApiResult<u64>;
+    async fn usage_by_mime_type(&self, business_id: ObjectId) -> ApiResult<Vec<MimeTypeUsage>>;
+    async fn find_all(&self, business_id: ObjectId) -> ApiResult<Vec<FileRecord>>;
+    /// Stores a new sweep report, superseding any report still pending.
+    async fn create_sweep(
+        &self,
+        business_id: ObjectId,
+        sweep: FileSweepRecord,
+    ) -> ApiResult<FileSweepRecord>;
+    async fn find_sweep(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<FileSweepRecord>>;
+    async fn update_sweep(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        sweep: FileSweepRecord,
+    ) -> ApiResult<FileSweepRecord>;
+    async fn list_sweeps(
+        &self,
+        business_id: ObjectId,
+        limit: u32,
+    ) -> ApiResult<Vec<FileSweepRecord>>;
 }
 
 pub struct MongoFileRepo {
@@ -40,10 +68,21 @@ impl MongoFileRepo {
         Self { client }
     }
 
-    fn get_collection(&self, business_id: ObjectId) -> Collection<FileRecord> {
+    fn get_database(&self, business_id: ObjectId) -> Database {
         self.client
             .database(&format!("biz-{}", business_id.to_hex()))
-            .collection("files")
+    }
+
+    fn get_collection(&self, business_id: ObjectId) -> Collection<FileRecord> {
+        self.get_database(business_id).collection("files")
+    }
+
+    fn get_usage(&self, business_id: ObjectId) -> Collection<bson::Document> {
+        self.get_database(business_id).collection("usage")
+    }
+
+    fn get_sweeps(&self, business_id: ObjectId) -> Collection<FileSweepRecord> {
+        self.get_database(business_id).collection("file_sweeps")
     }
 
     fn build_filter_query(&self, filter: &FileFilter) -> bson::Document {
@@ -196,4 +235,157 @@ impl FileRepo for MongoFileRepo {
 
         Ok((files, total))
     }
+
+    async fn add_storage_usage(&self, business_id: ObjectId, delta: i64) -> ApiResult<()> {
+        if delta == 0 {
+            return Ok(());
+        }
+
+        self.get_usage(business_id)
+            .update_one(
+                doc! { "_id": "storage" },
+                doc! { "$inc": { "bytes": delta } },
+            )
+            .upsert(true)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update storage usage: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn get_storage_usage(&self, business_id: ObjectId) -> ApiResult<u64> {
+        let usage = self
+            .get_usage(business_id)
+            .find_one(doc! { "_id": "storage" })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        Ok(usage
+            .and_then(|u| u.get_i64("bytes").ok())
+            .map_or(0, |bytes| bytes.max(0) as u64))
+    }
+
+    async fn usage_by_mime_type(&self, business_id: ObjectId) -> ApiResult<Vec<MimeTypeUsage>> {
+        let collection = self.get_collection(business_id);
+
+        let pipeline = vec![
+            doc! { "$group": {
+                "_id": "$mime_type",
+                "files": { "$sum": 1_i64 },
+                "bytes": { "$sum": { "$toLong": { "$ifNull": ["$stored_size", 0] } } },
+            }},
+            doc! { "$project": {
+                "_id": 0,
+                "mime_type": "$_id",
+                "files": 1,
+                "bytes": 1,
+            }},
+            doc! { "$sort": { "bytes": -1, "mime_type": 1 } },
+        ];
+
+        let mut cursor = collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| ApiError::internal(format!("Aggregation failed: {}", e)))?;
+
+        let mut usage = Vec::new();
+        while let Some(row) = cursor
+            .try_next()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
+        {
+            usage.push(
+                bson::from_document(row)
+                    .map_err(|e| ApiError::internal(format!("Failed to decode usage: {}", e)))?,
+            );
+        }
+
+        Ok(usage)
+    }
+
+    async fn find_all(&self, business_id: ObjectId) -> ApiResult<Vec<FileRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! {})
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn create_sweep(
+        &self,
+        business_id: ObjectId,
+        sweep: FileSweepRecord,
+    ) -> ApiResult<FileSweepRecord> {
+        let collection = self.get_sweeps(business_id);
+
+        collection
+            .update_many(
+                doc! { "status": to_bson(&FileSweepStatus::Pending).unwrap() },
+                doc! { "$set": { "status": to_bson(&FileSweepStatus::Superseded).unwrap() } },
+            )
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update file sweeps: {}", e)))?;
+
+        collection
+            .insert_one(&sweep)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to create file sweep: {}", e)))?;
+
+        Ok(sweep)
+    }
+
+    async fn find_sweep(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<FileSweepRecord>> {
+        let collection = self.get_sweeps(business_id);
+
+        collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn update_sweep(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        sweep: FileSweepRecord,
+    ) -> ApiResult<FileSweepRecord> {
+        let collection = self.get_sweeps(business_id);
+
+        let result = collection
+            .replace_one(doc! { "_id": id }, &sweep)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update file sweep: {}", e)))?;
+
+        if result.matched_count == 0 {
+            return Err(ApiError::not_found("file sweep", id.to_hex()));
+        }
+
+        Ok(sweep)
+    }
+
+    async fn list_sweeps(
+        &self,
+        business_id: ObjectId,
+        limit: u32,
+    ) -> ApiResult<Vec<FileSweepRecord>> {
+        let collection = self.get_sweeps(business_id);
+
+        collection
+            .find(doc! {})
+            .sort(doc! { "created_at": -1 })
+            .limit(limit as i64)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
 }
@@ -1,20 +1,26 @@
-use axum::extract::{Path, Query, State};
+use std::collections::HashMap;
+
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
 use macros::routes;
 
+use super::super::store::extractors::Store;
 use super::api::*;
+use super::processing;
 use crate::extractors::cookies::FromCookies;
 use crate::extractors::json::Json;
 use crate::platform::business::api::BusinessSession;
 use crate::platform::user::api::MessageResponse;
 use crate::types::id::Id;
-use crate::utils::error::ApiResult;
+use crate::utils::error::{ApiError, ApiResult};
 use crate::AppState;
 
 pub struct FileRoutes;
 
 #[routes(prefix = "/api/v1/files", state = AppState)]
 impl FileRoutes {
-    #[route(method=post, path="/create", res=FileDto)]
+    #[route(method=post, path="/create", res=FileDto, perm="files:write")]
     async fn create_file(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -27,7 +33,7 @@ impl FileRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/{file_id}", res=FileDto)]
+    #[route(method=post, path="/{file_id}", res=FileDto, perm="files:read")]
     async fn get_file(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -40,7 +46,7 @@ impl FileRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/key/{key}", res=FileDto)]
+    #[route(method=post, path="/key/{key}", res=FileDto, perm="files:read")]
     async fn get_file_by_key(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -53,7 +59,7 @@ impl FileRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/list", res=FileListResponse)]
+    #[route(method=post, path="/list", res=FileListResponse, perm="files:read")]
     async fn list_files(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -66,7 +72,59 @@ impl FileRoutes {
             .map(Json)
     }
 
-    #[route(method=delete, path="/{file_id}", res=MessageResponse)]
+    #[route(method=post, path="/usage", res=StorageUsageResponse, perm="files:read")]
+    async fn get_storage_usage(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+    ) -> ApiResult<Json<StorageUsageResponse>> {
+        state
+            .file_service
+            .get_storage_usage(&state.business_service, business)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/sweeps/run", res=FileSweepDto, perm="files:write")]
+    async fn sweep_files(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+    ) -> ApiResult<Json<FileSweepDto>> {
+        state
+            .file_service
+            .sweep_files(&state.product_service, &state.store_service, business)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/sweeps/list", res=FileSweepListResponse, perm="files:read")]
+    async fn list_sweeps(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+    ) -> ApiResult<Json<FileSweepListResponse>> {
+        state.file_service.list_sweeps(business).await.map(Json)
+    }
+
+    #[route(method=post, path="/sweeps/{sweep_id}/confirm", res=FileSweepDto, perm="files:delete")]
+    async fn confirm_sweep(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] sweep_id: Id,
+        #[json] confirm: FileSweepConfirm,
+    ) -> ApiResult<Json<FileSweepDto>> {
+        state
+            .file_service
+            .confirm_sweep(
+                &state.product_service,
+                &state.store_service,
+                business,
+                sweep_id,
+                confirm,
+            )
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/{file_id}", res=MessageResponse, perm="files:delete")]
     async fn delete_file(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -82,7 +140,7 @@ impl FileRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/upload/{file_id}", res=PresignedUrlResponse)]
+    #[route(method=post, path="/upload/{file_id}", res=PresignedUrlResponse, perm="files:write")]
     async fn generate_upload_url(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -90,8 +148,380 @@ impl FileRoutes {
     ) -> ApiResult<Json<PresignedUrlResponse>> {
         state
             .file_service
-            .generate_file_upload_url(business, file_id)
+            .generate_file_upload_url(&state.business_service, business, file_id)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/finalize/{file_id}", res=FileDto, perm="files:write")]
+    async fn finalize_file(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] file_id: Id,
+    ) -> ApiResult<Json<FileDto>> {
+        state
+            .file_service
+            .finalize_file(business, file_id)
             .await
             .map(Json)
     }
 }
+
+pub struct PubFileRoutes;
+
+#[routes(prefix = "/img", state = AppState)]
+impl PubFileRoutes {
+    #[route(method=get, path="/{file_id}")]
+    async fn get_image(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        #[path] file_id: Id,
+        Query(query): Query<ImageQuery>,
+    ) -> ApiResult<impl IntoResponse> {
+        let (bytes, mime_type) = state
+            .file_service
+            .pub_get_image(
+                &state.product_service,
+                &state.store_service,
+                store_key.business_id,
+                file_id,
+                query,
+            )
+            .await?;
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, mime_type),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            bytes,
+        ))
+    }
+}
+
+/// Upload and download endpoints of the local storage backend, mounted at
+/// `storage::local::ROUTE_PREFIX` when it is in use.
+pub struct LocalStorageRoutes;
+
+#[routes(prefix = "/api/v1/storage", state = AppState)]
+impl LocalStorageRoutes {
+    #[route(method=post, path="/upload")]
+    async fn upload(
+        State(state): State<AppState>,
+        mut multipart: Multipart,
+    ) -> ApiResult<impl IntoResponse> {
+        let storage = state
+            .local_storage
+            .as_ref()
+            .ok_or(ApiError::not_found("route", "/upload"))?;
+
+        let mut fields = HashMap::new();
+        let mut file = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?
+        {
+            let name = field.name().unwrap_or_default().to_string();
+            if name == "file" {
+                let content_type = field
+                    .content_type()
+                    .unwrap_or("application/octet-stream")
+                    .to_string();
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?;
+                file = Some((bytes, content_type));
+            } else {
+                let value = field
+                    .text()
+                    .await
+                    .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?;
+                fields.insert(name, value);
+            }
+        }
+
+        let (bytes, content_type) = file.ok_or(ApiError::missing_field("file"))?;
+        storage
+            .accept_upload(&fields, &bytes, &content_type)
+            .await?;
+
+        Ok(StatusCode::NO_CONTENT)
+    }
+
+    #[route(method=get, path="/{*key}")]
+    async fn download(
+        State(state): State<AppState>,
+        Path(key): Path<String>,
+        Query(query): Query<SignedDownloadQuery>,
+    ) -> ApiResult<impl IntoResponse> {
+        let storage = state
+            .local_storage
+            .as_ref()
+            .ok_or(ApiError::not_found("file", key.clone()))?;
+
+        let bytes = storage
+            .download(&key, query.expires, query.signature.as_deref())
+            .await?;
+        let mime_type = processing::sniff_mime(&bytes).unwrap_or("application/octet-stream");
+
+        Ok(([(header::CONTENT_TYPE, mime_type)], bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use axum::Router;
+    use bson::oid::ObjectId;
+    use hickory_resolver::config::ResolverConfig;
+    use hickory_resolver::name_server::TokioConnectionProvider;
+    use hickory_resolver::Resolver;
+    use image::{DynamicImage, ImageFormat, Rgb, RgbImage};
+    use mongodb::options::{ClientOptions, ServerAddress};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::platform::business::domain::BusinessRecord;
+    use crate::platform::business::repo::{MockBusinessRepo, MongoBusinessRepo};
+    use crate::platform::business::service::BusinessService;
+    use crate::platform::user::repo::{MongoSessionRepo, MongoUserRepo};
+    use crate::platform::user::service::UserService;
+    use crate::tenant::cart::repo::MongoCartRepo;
+    use crate::tenant::cart::service::CartService;
+    use crate::tenant::category::repo::MongoCategoryRepo;
+    use crate::tenant::category::service::CategoryService;
+    use crate::tenant::discount::repo::MongoDiscountRepo;
+    use crate::tenant::discount::service::DiscountService;
+    use crate::tenant::file::domain::FileRecord;
+    use crate::tenant::file::repo::{MockFileRepo, MongoFileRepo};
+    use crate::tenant::file::service::FileService;
+    use crate::tenant::file::storage::local::LocalStorage;
+    use crate::tenant::file::storage::FileStorage;
+    use crate::tenant::order::repo::MongoOrderRepo;
+    use crate::tenant::order::service::OrderService;
+    use crate::tenant::product::repo::MongoProductRepo;
+    use crate::tenant::product::service::ProductService;
+    use crate::tenant::shipping::repo::MongoShippingRepo;
+    use crate::tenant::shipping::service::ShippingService;
+    use crate::tenant::store::repo::{MongoStoreRegRepo, MongoStoreRepo};
+    use crate::tenant::store::service::StoreService;
+    use crate::tenant::tax::repo::MongoTaxRepo;
+    use crate::tenant::tax::service::TaxService;
+    use crate::types::email::Email;
+    use crate::types::name::Name;
+    use crate::utils::router::RoutePacked;
+    use crate::State as AppStateInner;
+
+    const PUBLIC_URL: &str = "http://localhost:3000";
+
+    /// App state serving `storage`. Nothing else is reached by the storage
+    /// routes, so the other services point at a database never connected to.
+    fn app_state(storage: Arc<LocalStorage>) -> AppState {
+        let client = mongodb::Client::with_options(
+            ClientOptions::builder()
+                .hosts(vec![ServerAddress::default()])
+                .build(),
+        )
+        .unwrap();
+        let db = client.database("test");
+        let resolver = Resolver::builder_with_config(
+            ResolverConfig::default(),
+            TokioConnectionProvider::default(),
+        )
+        .build();
+
+        Arc::new(AppStateInner {
+            user_service: UserService::new(MongoUserRepo::new(&db), MongoSessionRepo::new(&db)),
+            business_service: BusinessService::new(MongoBusinessRepo::new(&db)),
+            product_service: ProductService::new(MongoProductRepo::new(client.clone())),
+            order_service: OrderService::new(MongoOrderRepo::new(client.clone())),
+            store_service: StoreService::new(
+                MongoStoreRepo::new(client.clone()),
+                MongoStoreRegRepo::new(&db),
+                resolver,
+            ),
+            file_service: FileService::new(MongoFileRepo::new(client.clone()), storage.clone()),
+            shipping_service: ShippingService::new(MongoShippingRepo::new(client.clone())),
+            category_service: CategoryService::new(MongoCategoryRepo::new(client.clone())),
+            cart_service: CartService::new(MongoCartRepo::new(client.clone())),
+            discount_service: DiscountService::new(MongoDiscountRepo::new(client.clone())),
+            tax_service: TaxService::new(MongoTaxRepo::new(client)),
+            local_storage: Some(storage),
+            store_suffix: ".localhost".to_string(),
+        })
+    }
+
+    /// A file repo keeping a single record and the storage counter.
+    fn file_repo() -> MockFileRepo {
+        let record = Arc::new(Mutex::new(None::<FileRecord>));
+        let usage = Arc::new(Mutex::new(0i64));
+        let mut repo = MockFileRepo::new();
+
+        let created = record.clone();
+        repo.expect_create().returning(move |_, file| {
+            *created.lock().unwrap() = Some(file.clone());
+            Ok(file)
+        });
+        let found = record.clone();
+        repo.expect_find_by_id()
+            .returning(move |_, _| Ok(found.lock().unwrap().clone()));
+        repo.expect_update().returning(move |_, _, file| {
+            *record.lock().unwrap() = Some(file.clone());
+            Ok(file)
+        });
+        let added = usage.clone();
+        repo.expect_add_storage_usage().returning(move |_, delta| {
+            *added.lock().unwrap() += delta;
+            Ok(())
+        });
+        repo.expect_get_storage_usage()
+            .returning(move |_| Ok(*usage.lock().unwrap() as u64));
+
+        repo
+    }
+
+    fn multipart(fields: &HashMap<String, String>, file: &[u8]) -> Request<Body> {
+        const BOUNDARY: &str = "benxo-test-boundary";
+
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"shoe.png\"\r\nContent-Type: image/png\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(file);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+
+        Request::post("/api/v1/storage/upload")
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_file_flow_on_local_storage() {
+        let root = std::env::temp_dir().join(format!("benxo-storage-{}", ObjectId::new()));
+        let storage = Arc::new(LocalStorage::new(&root, PUBLIC_URL, "secret"));
+        let app = Router::new()
+            .nest_packed(LocalStorageRoutes::make_router())
+            .with_state(app_state(storage.clone()));
+
+        let owner = ObjectId::new();
+        let business = BusinessRecord::new(
+            Name::new("Shop").unwrap(),
+            owner,
+            Email::new("owner@example.com").unwrap(),
+            None,
+        );
+        let session = BusinessSession::new(
+            business._id,
+            owner,
+            ObjectId::new(),
+            business.members[0].clone(),
+        );
+        let mut business_repo = MockBusinessRepo::new();
+        business_repo
+            .expect_find_by_id()
+            .returning(move |_| Ok(Some(business.clone())));
+        let business_service = BusinessService::new(business_repo);
+        let files = FileService::new(file_repo(), storage.clone());
+
+        let mut png = Cursor::new(Vec::new());
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(400, 200, Rgb([200, 10, 10])))
+            .write_to(&mut png, ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        let file = files
+            .create_file(
+                session.clone(),
+                FileCreate {
+                    key: "shoe.png".to_string(),
+                    name: Name::new("Shoe").unwrap(),
+                    mime_type: Some("image/png".to_string()),
+                    size: Some(png.len() as u64),
+                    public: false,
+                },
+            )
+            .await
+            .unwrap();
+
+        let upload = files
+            .generate_file_upload_url(&business_service, session.clone(), file.id)
+            .await
+            .unwrap();
+        assert_eq!(upload.url, format!("{PUBLIC_URL}/api/v1/storage/upload"));
+
+        // Nothing to finalize before the upload
+        assert!(files.finalize_file(session.clone(), file.id).await.is_err());
+
+        let res = app
+            .clone()
+            .oneshot(multipart(&upload.fields, &png))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        // A form signed for another file is refused
+        let mut forged = upload.fields.clone();
+        forged.insert(
+            "key".to_string(),
+            format!("biz/{}/files/x/shoe.png", session.business_id),
+        );
+        let res = app.clone().oneshot(multipart(&forged, &png)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let file = files.finalize_file(session.clone(), file.id).await.unwrap();
+        assert!(file.finalized_at.is_some());
+        assert_eq!(file.metadata["width"], "400");
+
+        let url = files
+            .generate_file_download_url(session.clone(), file.id)
+            .await
+            .unwrap()
+            .url;
+        let path = url.strip_prefix(PUBLIC_URL).unwrap();
+        let res = app
+            .clone()
+            .oneshot(Request::get(path).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "image/png");
+        let key =
+            FileService::<MockFileRepo>::get_full_key(session.business_id, file.id, "shoe.png");
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(Some(body.to_vec()), storage.get(&key).await.unwrap());
+
+        // A tampered signature is refused
+        let res = app
+            .oneshot(
+                Request::get(path.replace("signature=", "signature=0"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        tokio::fs::remove_dir_all(&root).await.unwrap();
+    }
+}
@@ -1,24 +1,41 @@
-use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
-use chrono::{DateTime, Duration, Utc};
-use s3::bucket::Bucket;
-use s3::post_policy::*;
+use bson::oid::ObjectId;
+use chrono::{Duration, Utc};
+use tracing::error;
 
 use super::api::*;
 use super::domain::*;
+use super::processing::{self, RESIZE_WIDTHS};
 use super::repo::FileRepo;
+use super::storage::FileStorage;
 use crate::platform::business::api::BusinessSession;
+use crate::platform::business::domain::PlanType;
+use crate::platform::business::repo::BusinessRepo;
+use crate::platform::business::service::BusinessService;
+use crate::tenant::product::repo::ProductRepo;
+use crate::tenant::product::service::ProductService;
+use crate::tenant::store::repo::{StoreRegRepo, StoreRepo};
+use crate::tenant::store::service::StoreService;
 use crate::types::id::Id;
 use crate::utils::error::{ApiError, ApiResult};
 
 pub struct FileService<R: FileRepo> {
     repo: R,
-    bucket: Bucket,
+    storage: Arc<dyn FileStorage>,
 }
 
+const MAX_UPLOAD_SIZE: u64 = 10 * 1024 * 1024;
+// Fewer bytes than this are not enough to tell the type of a file
+const MIN_UPLOAD_SIZE: u64 = 4;
+// Files younger than this are left alone by the sweeper, their upload or
+// the product using them may still be on its way
+const SWEEP_GRACE_HOURS: i64 = 24;
+
 impl<R: FileRepo> FileService<R> {
-    pub fn new(repo: R, bucket: Bucket) -> Self {
-        Self { repo, bucket }
+    pub fn new(repo: R, storage: Arc<dyn FileStorage>) -> Self {
+        Self { repo, storage }
     }
 
     pub fn get_prefix(business_id: Id) -> String {
@@ -43,6 +60,7 @@ impl<R: FileRepo> FileService<R> {
             key: sanitize_filename::sanitize(create_req.key),
             name: create_req.name,
             size: create_req.size,
+            public: create_req.public,
             ..Default::default()
         };
 
@@ -81,22 +99,132 @@ impl<R: FileRepo> FileService<R> {
         let id = file_id.into_inner();
         let business_id = business.business_id.into_inner();
 
-        let file_record = self
+        let record = self
             .repo
             .find_by_id(business_id, id)
             .await?
             .ok_or(ApiError::not_found("file", id.to_hex()))?;
 
-        self.bucket
-            .delete_object(Self::get_full_key(
-                business.business_id,
-                file_id,
-                &file_record.key,
-            ))
+        self.remove_file(business.business_id, &record).await
+    }
+
+    /// Removes the record along with everything stored in its folder: the
+    /// original, its renditions and cached resizes.
+    async fn remove_file(&self, business_id: Id, record: &FileRecord) -> ApiResult<()> {
+        let folder = Self::get_full_key(business_id, record._id.into(), "");
+        for object in self.storage.list(&folder).await? {
+            self.storage.delete(&object.key).await?;
+        }
+
+        self.repo
+            .delete(business_id.into_inner(), record._id)
+            .await?;
+        self.repo
+            .add_storage_usage(business_id.into_inner(), -(record.stored_size as i64))
             .await
-            .map_err(|_| ApiError::internal("Failed to delete file from storage"))?;
+    }
 
-        self.repo.delete(business_id, id).await
+    /// Checks an uploaded file against what was declared for it. Images are
+    /// re-encoded without their metadata and get their WebP renditions.
+    pub async fn finalize_file(
+        &self,
+        business: BusinessSession,
+        file_id: Id,
+    ) -> ApiResult<FileDto> {
+        let id = file_id.into_inner();
+        let business_id = business.business_id.into_inner();
+
+        let mut record = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or(ApiError::not_found("file", id.to_hex()))?;
+
+        let key = Self::get_full_key(business.business_id, file_id, &record.key);
+        let bytes = self.storage.get(&key).await?.ok_or(ApiError::validation(
+            "file",
+            "File has not been uploaded yet",
+        ))?;
+
+        let sniffed = processing::sniff_mime(&bytes);
+        let mime_type = match sniffed {
+            Some(sniffed) if processing::mime_matches(&record.mime_type, sniffed) => sniffed,
+            Some(sniffed) if record.mime_type == FileRecord::default().mime_type => sniffed,
+            _ => {
+                return Err(ApiError::validation(
+                    "mime_type",
+                    format!(
+                        "File content is {} but was declared as {}",
+                        sniffed.unwrap_or("of an unknown type"),
+                        record.mime_type
+                    ),
+                ))
+            }
+        };
+
+        let previous_size = record.stored_size;
+        record.mime_type = mime_type.into();
+        record.size = Some(bytes.len() as u64);
+        record.stored_size = bytes.len() as u64;
+
+        if processing::is_processable(mime_type) {
+            let processed =
+                tokio::task::spawn_blocking(move || processing::process_image(&bytes, mime_type))
+                    .await
+                    .map_err(|_| ApiError::internal("Image processing task failed"))?
+                    .map_err(|e| ApiError::validation("file", e))?;
+
+            record.size = Some(processed.original.len() as u64);
+            record.stored_size = processed.original.len() as u64
+                + processed
+                    .renditions
+                    .iter()
+                    .map(|r| r.bytes.len() as u64)
+                    .sum::<u64>();
+            self.storage
+                .put(&key, &processed.original, mime_type)
+                .await?;
+
+            record
+                .metadata
+                .insert("width".to_string(), processed.width.to_string());
+            record
+                .metadata
+                .insert("height".to_string(), processed.height.to_string());
+
+            for rendition in processed.renditions {
+                let file_name = processing::rendition_file_name(rendition.name);
+                self.storage
+                    .put(
+                        &Self::get_full_key(business.business_id, file_id, &file_name),
+                        &rendition.bytes,
+                        "image/webp",
+                    )
+                    .await?;
+
+                record
+                    .metadata
+                    .insert(rendition_meta(rendition.name, "key"), file_name);
+                record.metadata.insert(
+                    rendition_meta(rendition.name, "width"),
+                    rendition.width.to_string(),
+                );
+                record.metadata.insert(
+                    rendition_meta(rendition.name, "height"),
+                    rendition.height.to_string(),
+                );
+            }
+        }
+
+        record.finalized_at = Some(bson::DateTime::now());
+
+        let stored_size = record.stored_size;
+        let record = self.repo.update(business_id, id, record).await?;
+        self.repo
+            .add_storage_usage(business_id, stored_size as i64 - previous_size as i64)
+            .await?;
+
+        Ok(record.into())
     }
 
     pub async fn list_files(
@@ -129,44 +257,85 @@ impl<R: FileRepo> FileService<R> {
         })
     }
 
-    pub async fn generate_file_upload_url(
+    /// Bytes the business may store, from its plan. Expired plans fall back
+    /// to the free quota.
+    async fn storage_quota<B: BusinessRepo>(
+        business_service: &BusinessService<B>,
+        business_id: Id,
+    ) -> ApiResult<u64> {
+        let business = business_service.get(business_id).await?;
+        let plan = if business.plan_expires_at < Utc::now() {
+            PlanType::Free
+        } else {
+            business.plan_type
+        };
+
+        Ok(plan.storage_quota())
+    }
+
+    pub async fn get_storage_usage<B: BusinessRepo>(
+        &self,
+        business_service: &BusinessService<B>,
+        business: BusinessSession,
+    ) -> ApiResult<StorageUsageResponse> {
+        let business_id = business.business_id.into_inner();
+
+        let quota = Self::storage_quota(business_service, business.business_id).await?;
+        let used = self.repo.get_storage_usage(business_id).await?;
+        let by_mime_type = self.repo.usage_by_mime_type(business_id).await?;
+
+        Ok(StorageUsageResponse {
+            used,
+            quota,
+            by_mime_type: by_mime_type.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    pub async fn generate_file_upload_url<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
         file_id: Id,
     ) -> ApiResult<PresignedUrlResponse> {
         let id = file_id.into_inner();
-        let file_record = self
+        let business_id = business.business_id.into_inner();
+        let mut file_record = self
             .repo
-            .find_by_id(business.business_id.into_inner(), id)
+            .find_by_id(business_id, id)
             .await?
             .ok_or(ApiError::not_found("file", id.to_hex()))?;
 
-        let key = Self::get_full_key(business.business_id, file_id, &file_record.key);
+        // Uploads are charged the most they may take as soon as they are
+        // allowed, `finalize_file` brings it down to what was stored. Uploads
+        // never finalized or made side by side thus stay within the quota.
+        let quota = Self::storage_quota(business_service, business.business_id).await?;
+        let used = self.repo.get_storage_usage(business_id).await?;
+        let previous_size = file_record.stored_size;
+        // What this file held so far is replaced by the new upload
+        let available = quota.saturating_sub(used.saturating_sub(previous_size));
+        let max_size = match file_record.size {
+            Some(size) => size.clamp(MIN_UPLOAD_SIZE, MAX_UPLOAD_SIZE),
+            None => MAX_UPLOAD_SIZE.min(available),
+        };
+        if max_size < MIN_UPLOAD_SIZE || max_size > available {
+            return Err(ApiError::quota_exceeded("storage", used, quota));
+        }
 
-        let policy = PostPolicy::new(600)
-            .condition(PostPolicyField::Key, PostPolicyValue::Exact(Cow::from(key)))
-            .map_err(|_| ApiError::internal("Failed to apply key condition to policy"))?
-            .condition(
-                PostPolicyField::ContentLengthRange,
-                PostPolicyValue::Range(4, 10 * 1024 * 1024),
-            )
-            .map_err(|_| {
-                ApiError::internal("Failed to apply content length condition to policy")
-            })?;
+        file_record.stored_size = max_size;
+        let file_record = self.repo.update(business_id, id, file_record).await?;
+        self.repo
+            .add_storage_usage(business_id, max_size as i64 - previous_size as i64)
+            .await?;
 
-        let res = self
-            .bucket
-            .presign_post(policy)
-            .await
-            .map_err(|_| ApiError::internal("Failed to generate presigned URL"))?;
+        let key = Self::get_full_key(business.business_id, file_id, &file_record.key);
+
+        let res = self.storage.presign_upload(&key, max_size, 600).await?;
 
         Ok(PresignedUrlResponse {
             url: res.url,
             fields: res.fields,
             dynamic_fields: res.dynamic_fields,
-            expiration: DateTime::from_timestamp(res.expiration.unix_timestamp(), 0).ok_or(
-                ApiError::internal("Failed to convert expiration to DateTime"),
-            )?,
+            expiration: res.expiration,
         })
     }
 
@@ -185,14 +354,12 @@ impl<R: FileRepo> FileService<R> {
         let ttl = 600; // TODO: use env variable
 
         let res = self
-            .bucket
-            .presign_get(
-                Self::get_full_key(business.business_id, file_id, &file_record.key),
+            .storage
+            .presign_download(
+                &Self::get_full_key(business.business_id, file_id, &file_record.key),
                 ttl,
-                None,
             )
-            .await
-            .map_err(|_| ApiError::internal("Failed to generate presigned URL"))?;
+            .await?;
 
         Ok(PresignedUrlResponse {
             url: res,
@@ -201,4 +368,341 @@ impl<R: FileRepo> FileService<R> {
             expiration: Utc::now() + Duration::seconds(ttl.into()),
         })
     }
+
+    async fn referenced_file_ids<P: ProductRepo, S: StoreRepo, Reg: StoreRegRepo>(
+        product_service: &ProductService<P>,
+        store_service: &StoreService<S, Reg>,
+        business_id: Id,
+    ) -> ApiResult<HashSet<ObjectId>> {
+        let mut ids = HashSet::new();
+        for text in product_service.image_urls(business_id).await? {
+            file_ids_in(&text, &mut ids);
+        }
+        for text in store_service.file_references(business_id).await? {
+            file_ids_in(&text, &mut ids);
+        }
+
+        Ok(ids)
+    }
+
+    /// Compares storage, file records and what products and stores point at.
+    /// Nothing is deleted here.
+    async fn scan_files<P: ProductRepo, S: StoreRepo, Reg: StoreRegRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        store_service: &StoreService<S, Reg>,
+        business_id: Id,
+    ) -> ApiResult<FileSweepRecord> {
+        let prefix = Self::get_prefix(business_id);
+
+        // Storage is listed before the records so that an upload racing the
+        // scan is never taken for a stray object
+        let objects = self.storage.list(&prefix).await?;
+        let records = self.repo.find_all(business_id.into_inner()).await?;
+        let referenced =
+            Self::referenced_file_ids(product_service, store_service, business_id).await?;
+
+        let by_id: HashMap<_, _> = records.iter().map(|r| (r._id, r)).collect();
+        let keys: HashSet<_> = objects.iter().map(|o| o.key.as_str()).collect();
+        let cutoff = bson::DateTime::from_chrono(Utc::now() - Duration::hours(SWEEP_GRACE_HOURS));
+
+        let stray_objects = objects
+            .iter()
+            .filter(|o| folder_file_id(&prefix, &o.key).is_none_or(|id| !by_id.contains_key(&id)))
+            .map(|o| StrayObject {
+                key: o.key.clone(),
+                size: o.size,
+            })
+            .collect();
+
+        let mut missing_uploads = Vec::new();
+        let mut unreferenced = Vec::new();
+        for record in records.iter().filter(|r| r.created_at < cutoff) {
+            let key = Self::get_full_key(business_id, record._id.into(), &record.key);
+            if !keys.contains(key.as_str()) {
+                missing_uploads.push(record.into());
+            } else if !referenced.contains(&record._id) {
+                unreferenced.push(record.into());
+            }
+        }
+
+        Ok(FileSweepRecord {
+            _id: ObjectId::new(),
+            status: FileSweepStatus::Pending,
+            missing_uploads,
+            stray_objects,
+            unreferenced,
+            deleted_files: 0,
+            deleted_objects: 0,
+            created_at: bson::DateTime::now(),
+            confirmed_at: None,
+        })
+    }
+
+    /// Runs a dry-run sweep and keeps its report until it is confirmed.
+    pub async fn sweep_files<P: ProductRepo, S: StoreRepo, Reg: StoreRegRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        store_service: &StoreService<S, Reg>,
+        business: BusinessSession,
+    ) -> ApiResult<FileSweepDto> {
+        let sweep = self
+            .scan_files(product_service, store_service, business.business_id)
+            .await?;
+
+        self.repo
+            .create_sweep(business.business_id.into_inner(), sweep)
+            .await
+            .map(Into::into)
+    }
+
+    /// Same as `sweep_files`, for the periodic job. Clean reports are not
+    /// kept.
+    pub async fn background_sweep<P: ProductRepo, S: StoreRepo, Reg: StoreRegRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        store_service: &StoreService<S, Reg>,
+        business_id: Id,
+    ) -> ApiResult<()> {
+        let sweep = self
+            .scan_files(product_service, store_service, business_id)
+            .await?;
+
+        if !sweep.is_empty() {
+            self.repo
+                .create_sweep(business_id.into_inner(), sweep)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_sweeps(&self, business: BusinessSession) -> ApiResult<FileSweepListResponse> {
+        let sweeps = self
+            .repo
+            .list_sweeps(business.business_id.into_inner(), 20)
+            .await?;
+
+        Ok(FileSweepListResponse {
+            sweeps: sweeps.into_iter().map(Into::into).collect(),
+        })
+    }
+
+    /// Deletes what a pending report found. Every entry is checked again
+    /// first, so files that were uploaded or put to use since the dry run
+    /// are kept.
+    pub async fn confirm_sweep<P: ProductRepo, S: StoreRepo, Reg: StoreRegRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        store_service: &StoreService<S, Reg>,
+        business: BusinessSession,
+        sweep_id: Id,
+        confirm: FileSweepConfirm,
+    ) -> ApiResult<FileSweepDto> {
+        let business_id = business.business_id;
+        let id = sweep_id.into_inner();
+
+        let mut sweep = self
+            .repo
+            .find_sweep(business_id.into_inner(), id)
+            .await?
+            .ok_or(ApiError::not_found("file sweep", id.to_hex()))?;
+
+        if sweep.status != FileSweepStatus::Pending {
+            return Err(ApiError::conflict(
+                "file sweep",
+                "Only the latest pending report can be confirmed",
+            ));
+        }
+
+        let prefix = Self::get_prefix(business_id);
+        let keys: HashSet<_> = self
+            .storage
+            .list(&prefix)
+            .await?
+            .into_iter()
+            .map(|o| o.key)
+            .collect();
+
+        for missing in &sweep.missing_uploads {
+            let Some(record) = self
+                .repo
+                .find_by_id(business_id.into_inner(), missing.file_id)
+                .await?
+            else {
+                continue;
+            };
+
+            if !keys.contains(&Self::get_full_key(
+                business_id,
+                record._id.into(),
+                &record.key,
+            )) {
+                self.remove_file(business_id, &record).await?;
+                sweep.deleted_files += 1;
+            }
+        }
+
+        for stray in &sweep.stray_objects {
+            let owned = match folder_file_id(&prefix, &stray.key) {
+                Some(file_id) => self
+                    .repo
+                    .find_by_id(business_id.into_inner(), file_id)
+                    .await?
+                    .is_some(),
+                None => false,
+            };
+
+            if !owned && keys.contains(&stray.key) {
+                self.storage.delete(&stray.key).await?;
+                sweep.deleted_objects += 1;
+            }
+        }
+
+        if confirm.include_unreferenced {
+            let referenced =
+                Self::referenced_file_ids(product_service, store_service, business_id).await?;
+
+            for unused in &sweep.unreferenced {
+                if referenced.contains(&unused.file_id) {
+                    continue;
+                }
+
+                if let Some(record) = self
+                    .repo
+                    .find_by_id(business_id.into_inner(), unused.file_id)
+                    .await?
+                {
+                    self.remove_file(business_id, &record).await?;
+                    sweep.deleted_files += 1;
+                }
+            }
+        }
+
+        sweep.status = FileSweepStatus::Confirmed;
+        sweep.confirmed_at = Some(bson::DateTime::now());
+
+        self.repo
+            .update_sweep(business_id.into_inner(), id, sweep)
+            .await
+            .map(Into::into)
+    }
+
+    /// Serves a finalized image of the business to the store domain, if it
+    /// is public or used by a product or the store.
+    pub async fn pub_get_image<P: ProductRepo, S: StoreRepo, Reg: StoreRegRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        store_service: &StoreService<S, Reg>,
+        business_id: Id,
+        file_id: Id,
+        query: ImageQuery,
+    ) -> ApiResult<(Vec<u8>, &'static str)> {
+        let id = file_id.into_inner();
+        let record = self.find_image(business_id, id).await?;
+
+        if !record.public
+            && !product_service.references_file(business_id, id).await?
+            && !store_service.references_file(business_id, id).await?
+        {
+            return Err(ApiError::not_found("image", id.to_hex()));
+        }
+
+        self.scaled_image(business_id, record, query).await
+    }
+
+    /// Same as `pub_get_image`, for images the business itself prints.
+    pub async fn get_image(
+        &self,
+        business_id: Id,
+        file_id: Id,
+        query: ImageQuery,
+    ) -> ApiResult<(Vec<u8>, &'static str)> {
+        let record = self.find_image(business_id, file_id.into_inner()).await?;
+
+        self.scaled_image(business_id, record, query).await
+    }
+
+    async fn find_image(&self, business_id: Id, id: ObjectId) -> ApiResult<FileRecord> {
+        self.repo
+            .find_by_id(business_id.into_inner(), id)
+            .await?
+            .filter(|r| r.is_finalized() && processing::is_processable(&r.mime_type))
+            .ok_or(ApiError::not_found("image", id.to_hex()))
+    }
+
+    /// Scales the image to one of the allowed widths. Results are kept next
+    /// to the original so each size is only computed once.
+    async fn scaled_image(
+        &self,
+        business_id: Id,
+        record: FileRecord,
+        query: ImageQuery,
+    ) -> ApiResult<(Vec<u8>, &'static str)> {
+        let id = record._id;
+        let file_id = Id::from(id);
+        let format = query.fmt.unwrap_or_default();
+
+        if let Some(width) = query.w {
+            if !RESIZE_WIDTHS.contains(&width) {
+                return Err(ApiError::invalid_query(
+                    "w",
+                    format!(
+                        "Width must be one of {}",
+                        RESIZE_WIDTHS
+                            .iter()
+                            .map(u32::to_string)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ));
+            }
+        }
+
+        let cache_key = Self::get_full_key(
+            business_id,
+            file_id,
+            &processing::resized_file_name(query.w, format),
+        );
+        if let Ok(Some(bytes)) = self.storage.get(&cache_key).await {
+            return Ok((bytes, format.mime_type()));
+        }
+
+        let original = self
+            .storage
+            .get(&Self::get_full_key(business_id, file_id, &record.key))
+            .await?
+            .ok_or(ApiError::not_found("image", id.to_hex()))?;
+
+        let mime_type = record.mime_type.clone();
+        let bytes = tokio::task::spawn_blocking(move || {
+            processing::resize_image(&original, &mime_type, query.w, format)
+        })
+        .await
+        .map_err(|_| ApiError::internal("Image processing task failed"))?
+        .map_err(|e| {
+            error!("image decode error: {:?}", e);
+            ApiError::not_found("image", id.to_hex())
+        })?;
+
+        if let Err(e) = self
+            .storage
+            .put(&cache_key, &bytes, format.mime_type())
+            .await
+        {
+            error!("image cache write error: {:?}", e);
+        }
+
+        Ok((bytes, format.mime_type()))
+    }
+}
+
+fn rendition_meta(name: &str, field: &str) -> String {
+    format!("{}_{}", name, field)
+}
+
+/// Id of the file whose folder holds the object, if any.
+fn folder_file_id(prefix: &str, key: &str) -> Option<ObjectId> {
+    let (folder, _) = key.strip_prefix(prefix)?.split_once('/')?;
+    ObjectId::parse_str(folder).ok()
 }
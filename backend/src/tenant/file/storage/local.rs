@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+
+use super::{FileStorage, PresignedUpload, StoredObject};
+use crate::utils::error::{ApiError, ApiResult};
+
+/// Where `LocalStorageRoutes` is mounted.
+pub const ROUTE_PREFIX: &str = "/api/v1/storage";
+
+/// Keeps objects on the local disk and has the backend itself accept
+/// uploads and serve downloads, with URLs signed the same way for both.
+/// Meant for development and tests.
+pub struct LocalStorage {
+    root: PathBuf,
+    public_url: String,
+    key: [u8; 32],
+}
+
+impl LocalStorage {
+    pub fn new(root: impl Into<PathBuf>, public_url: &str, secret: &str) -> Self {
+        Self {
+            root: root.into(),
+            public_url: public_url.trim_end_matches('/').to_string(),
+            key: blake3::derive_key("benxo local storage signing key", secret.as_bytes()),
+        }
+    }
+
+    fn path(&self, key: &str) -> ApiResult<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in key.split('/') {
+            if segment.is_empty() || segment == "." || segment == ".." || segment.contains('\\') {
+                return Err(ApiError::invalid_path("key", "Invalid storage key"));
+            }
+            path.push(segment);
+        }
+
+        Ok(path)
+    }
+
+    fn sign(&self, parts: &[&str]) -> String {
+        blake3::keyed_hash(&self.key, parts.join("\n").as_bytes())
+            .to_hex()
+            .to_string()
+    }
+
+    fn verify(&self, parts: &[&str], expires: i64, signature: &str) -> bool {
+        // Hash equality is constant time
+        expires >= Utc::now().timestamp()
+            && blake3::Hash::from_hex(signature)
+                .is_ok_and(|s| s == blake3::keyed_hash(&self.key, parts.join("\n").as_bytes()))
+    }
+
+    /// Public URL of an object.
+    pub fn url(&self, key: &str) -> String {
+        format!("{}{}/{}", self.public_url, ROUTE_PREFIX, key)
+    }
+
+    pub fn verify_upload(
+        &self,
+        key: &str,
+        max_size: u64,
+        expires: i64,
+        signature: &str,
+    ) -> ApiResult<()> {
+        let max_size = max_size.to_string();
+        let expires_str = expires.to_string();
+        if self.verify(
+            &["upload", key, &max_size, &expires_str],
+            expires,
+            signature,
+        ) {
+            Ok(())
+        } else {
+            Err(ApiError::forbidden("storage", "upload"))
+        }
+    }
+
+    pub fn verify_download(&self, key: &str, expires: i64, signature: &str) -> ApiResult<()> {
+        let expires_str = expires.to_string();
+        if self.verify(&["download", key, &expires_str], expires, signature) {
+            Ok(())
+        } else {
+            Err(ApiError::forbidden("storage", "download"))
+        }
+    }
+
+    /// Stores an object posted through a form from `presign_upload`.
+    pub async fn accept_upload(
+        &self,
+        fields: &HashMap<String, String>,
+        bytes: &[u8],
+        content_type: &str,
+    ) -> ApiResult<()> {
+        let field = |name: &'static str| fields.get(name).ok_or(ApiError::missing_field(name));
+        let key = field("key")?;
+        let max_size: u64 = field("max_size")?
+            .parse()
+            .map_err(|_| ApiError::invalid_format("max_size", "integer"))?;
+        let expires: i64 = field("expires")?
+            .parse()
+            .map_err(|_| ApiError::invalid_format("expires", "integer"))?;
+
+        self.verify_upload(key, max_size, expires, field("signature")?)?;
+
+        if bytes.len() as u64 > max_size {
+            return Err(ApiError::request_too_large(max_size as usize, bytes.len()));
+        }
+        if bytes.len() < 4 {
+            return Err(ApiError::validation("file", "File is too small"));
+        }
+
+        self.put(key, bytes, content_type).await
+    }
+
+    /// Reads an object for download. Objects are public, like in the
+    /// development bucket, but a signed link is still checked.
+    pub async fn download(
+        &self,
+        key: &str,
+        expires: Option<i64>,
+        signature: Option<&str>,
+    ) -> ApiResult<Vec<u8>> {
+        match (expires, signature) {
+            (None, None) => {}
+            (Some(expires), Some(signature)) => self.verify_download(key, expires, signature)?,
+            _ => return Err(ApiError::forbidden("storage", "download")),
+        }
+
+        self.get(key)
+            .await?
+            .ok_or(ApiError::not_found("file", key.to_string()))
+    }
+
+    async fn walk(&self, dir: PathBuf, objects: &mut Vec<StoredObject>) -> ApiResult<()> {
+        let mut pending = vec![dir];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(_) => return Err(ApiError::internal("Failed to list files in storage")),
+            };
+
+            while let Some(entry) = entries
+                .next_entry()
+                .await
+                .map_err(|_| ApiError::internal("Failed to list files in storage"))?
+            {
+                let metadata = entry
+                    .metadata()
+                    .await
+                    .map_err(|_| ApiError::internal("Failed to list files in storage"))?;
+
+                if metadata.is_dir() {
+                    pending.push(entry.path());
+                } else if let Some(key) = self.key_of(&entry.path()) {
+                    objects.push(StoredObject {
+                        key,
+                        size: metadata.len(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn key_of(&self, path: &Path) -> Option<String> {
+        let segments = path
+            .strip_prefix(&self.root)
+            .ok()?
+            .iter()
+            .map(|s| s.to_str())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(segments.join("/"))
+    }
+}
+
+#[async_trait]
+impl FileStorage for LocalStorage {
+    async fn put(&self, key: &str, bytes: &[u8], _content_type: &str) -> ApiResult<()> {
+        let path = self.path(key)?;
+
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .map_err(|_| ApiError::internal("Failed to upload file to storage"))?;
+        }
+
+        tokio::fs::write(&path, bytes)
+            .await
+            .map_err(|_| ApiError::internal("Failed to upload file to storage"))
+    }
+
+    async fn get(&self, key: &str) -> ApiResult<Option<Vec<u8>>> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(_) => Err(ApiError::internal("Failed to read file from storage")),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> ApiResult<()> {
+        let path = self.path(key)?;
+
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(_) => return Err(ApiError::internal("Failed to delete file from storage")),
+        }
+
+        // Drop the folders left empty, like an S3 prefix would disappear
+        let mut dir = path.parent();
+        while let Some(d) = dir.filter(|d| *d != self.root) {
+            if tokio::fs::remove_dir(d).await.is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> ApiResult<Vec<StoredObject>> {
+        // Start from the deepest folder the prefix names
+        let dir = match prefix.rsplit_once('/') {
+            Some((folder, _)) if !folder.is_empty() => self.path(folder)?,
+            _ => self.root.clone(),
+        };
+
+        let mut objects = Vec::new();
+        self.walk(dir, &mut objects).await?;
+        objects.retain(|o| o.key.starts_with(prefix));
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+
+        Ok(objects)
+    }
+
+    async fn presign_upload(
+        &self,
+        key: &str,
+        max_size: u64,
+        expires_in: u32,
+    ) -> ApiResult<PresignedUpload> {
+        self.path(key)?;
+
+        let expiration = Utc::now() + Duration::seconds(expires_in.into());
+        let expires = expiration.timestamp().to_string();
+        let max_size = max_size.to_string();
+        let signature = self.sign(&["upload", key, &max_size, &expires]);
+
+        Ok(PresignedUpload {
+            url: format!("{}{}/upload", self.public_url, ROUTE_PREFIX),
+            fields: HashMap::from([
+                ("key".to_string(), key.to_string()),
+                ("max_size".to_string(), max_size),
+                ("expires".to_string(), expires),
+                ("signature".to_string(), signature),
+            ]),
+            dynamic_fields: HashMap::new(),
+            expiration,
+        })
+    }
+
+    async fn presign_download(&self, key: &str, expires_in: u32) -> ApiResult<String> {
+        self.path(key)?;
+
+        let expires = (Utc::now() + Duration::seconds(expires_in.into()))
+            .timestamp()
+            .to_string();
+        let signature = self.sign(&["download", key, &expires]);
+
+        Ok(format!(
+            "{}?expires={}&signature={}",
+            self.url(key),
+            expires,
+            signature
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::oid::ObjectId;
+
+    use super::*;
+
+    fn storage() -> LocalStorage {
+        let root = std::env::temp_dir().join(format!("benxo-storage-{}", ObjectId::new()));
+        LocalStorage::new(root, "http://localhost:3000/", "secret")
+    }
+
+    #[tokio::test]
+    async fn test_put_get_list_delete() {
+        let storage = storage();
+
+        storage
+            .put("biz/1/files/a/shoe.png", b"shoe", "image/png")
+            .await
+            .unwrap();
+        storage
+            .put("biz/1/files/a/renditions/card.webp", b"card", "image/webp")
+            .await
+            .unwrap();
+        storage
+            .put("biz/1/files/b/hat.png", b"hat", "image/png")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage.get("biz/1/files/a/shoe.png").await.unwrap(),
+            Some(b"shoe".to_vec())
+        );
+        assert_eq!(storage.get("biz/1/files/c/none.png").await.unwrap(), None);
+
+        let listed: Vec<_> = storage
+            .list("biz/1/files/a/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|o| (o.key, o.size))
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("biz/1/files/a/renditions/card.webp".to_string(), 4),
+                ("biz/1/files/a/shoe.png".to_string(), 4)
+            ]
+        );
+
+        storage.delete("biz/1/files/a/shoe.png").await.unwrap();
+        storage
+            .delete("biz/1/files/a/renditions/card.webp")
+            .await
+            .unwrap();
+        storage.delete("biz/1/files/a/shoe.png").await.unwrap();
+        assert!(storage.list("biz/1/files/a/").await.unwrap().is_empty());
+        assert_eq!(storage.list("biz/").await.unwrap().len(), 1);
+
+        tokio::fs::remove_dir_all(&storage.root).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_rejects_traversal() {
+        let storage = storage();
+        assert!(storage.put("biz/../../etc/passwd", b"x", "").await.is_err());
+        assert!(storage.get("/etc/passwd").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signed_urls() {
+        let storage = storage();
+
+        let upload = storage
+            .presign_upload("biz/1/files/a/shoe.png", 1024, 600)
+            .await
+            .unwrap();
+        assert_eq!(upload.url, "http://localhost:3000/api/v1/storage/upload");
+        let expires: i64 = upload.fields["expires"].parse().unwrap();
+        let signature = &upload.fields["signature"];
+        assert!(storage
+            .verify_upload("biz/1/files/a/shoe.png", 1024, expires, signature)
+            .is_ok());
+        assert!(storage
+            .verify_upload("biz/1/files/a/shoe.png", 4096, expires, signature)
+            .is_err());
+        assert!(storage
+            .verify_upload("biz/1/files/b/shoe.png", 1024, expires, signature)
+            .is_err());
+
+        let signature = storage.sign(&["download", "biz/1/files/a/shoe.png", "100"]);
+        assert!(storage
+            .verify_download("biz/1/files/a/shoe.png", 100, &signature)
+            .is_err());
+
+        let url = storage
+            .presign_download("biz/1/files/a/shoe.png", 600)
+            .await
+            .unwrap();
+        assert!(url.starts_with("http://localhost:3000/api/v1/storage/biz/1/files/a/shoe.png?"));
+    }
+}
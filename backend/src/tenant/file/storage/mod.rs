@@ -0,0 +1,40 @@
+pub mod local;
+pub mod s3;
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use crate::utils::error::ApiResult;
+
+pub struct StoredObject {
+    pub key: String,
+    pub size: u64,
+}
+
+/// A form the client posts the file to, along with the given fields.
+pub struct PresignedUpload {
+    pub url: String,
+    pub fields: HashMap<String, String>,
+    pub dynamic_fields: HashMap<String, String>,
+    pub expiration: DateTime<Utc>,
+}
+
+/// Object storage behind `FileService`. Keys are `/` separated paths.
+#[async_trait]
+pub trait FileStorage: Send + Sync {
+    async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> ApiResult<()>;
+    /// Gives `None` when there is no object under the key.
+    async fn get(&self, key: &str) -> ApiResult<Option<Vec<u8>>>;
+    async fn delete(&self, key: &str) -> ApiResult<()>;
+    async fn list(&self, prefix: &str) -> ApiResult<Vec<StoredObject>>;
+    /// Lets the client upload a single object of at most `max_size` bytes.
+    async fn presign_upload(
+        &self,
+        key: &str,
+        max_size: u64,
+        expires_in: u32,
+    ) -> ApiResult<PresignedUpload>;
+    async fn presign_download(&self, key: &str, expires_in: u32) -> ApiResult<String>;
+}
@@ -0,0 +1,110 @@
+use std::borrow::Cow;
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use s3::bucket::Bucket;
+use s3::error::S3Error;
+use s3::post_policy::*;
+
+use super::{FileStorage, PresignedUpload, StoredObject};
+use crate::utils::error::{ApiError, ApiResult};
+
+pub struct S3Storage {
+    bucket: Bucket,
+}
+
+impl S3Storage {
+    pub fn new(bucket: Bucket) -> Self {
+        Self { bucket }
+    }
+}
+
+#[async_trait]
+impl FileStorage for S3Storage {
+    async fn put(&self, key: &str, bytes: &[u8], content_type: &str) -> ApiResult<()> {
+        self.bucket
+            .put_object_with_content_type(key, bytes, content_type)
+            .await
+            .map_err(|_| ApiError::internal("Failed to upload file to storage"))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> ApiResult<Option<Vec<u8>>> {
+        match self.bucket.get_object(key).await {
+            Ok(res) if res.status_code() == 200 => Ok(Some(res.to_vec())),
+            Ok(res) if res.status_code() == 404 => Ok(None),
+            Err(S3Error::HttpFailWithBody(404, _)) => Ok(None),
+            _ => Err(ApiError::internal("Failed to read file from storage")),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> ApiResult<()> {
+        self.bucket
+            .delete_object(key)
+            .await
+            .map_err(|_| ApiError::internal("Failed to delete file from storage"))?;
+
+        Ok(())
+    }
+
+    async fn list(&self, prefix: &str) -> ApiResult<Vec<StoredObject>> {
+        let listing = self
+            .bucket
+            .list(prefix.to_string(), None)
+            .await
+            .map_err(|_| ApiError::internal("Failed to list files in storage"))?;
+
+        Ok(listing
+            .into_iter()
+            .flat_map(|page| page.contents)
+            .map(|object| StoredObject {
+                key: object.key,
+                size: object.size,
+            })
+            .collect())
+    }
+
+    async fn presign_upload(
+        &self,
+        key: &str,
+        max_size: u64,
+        expires_in: u32,
+    ) -> ApiResult<PresignedUpload> {
+        let policy = PostPolicy::new(expires_in)
+            .condition(
+                PostPolicyField::Key,
+                PostPolicyValue::Exact(Cow::from(key.to_string())),
+            )
+            .map_err(|_| ApiError::internal("Failed to apply key condition to policy"))?
+            .condition(
+                PostPolicyField::ContentLengthRange,
+                PostPolicyValue::Range(4, max_size.min(u32::MAX as u64) as u32),
+            )
+            .map_err(|_| {
+                ApiError::internal("Failed to apply content length condition to policy")
+            })?;
+
+        let res = self
+            .bucket
+            .presign_post(policy)
+            .await
+            .map_err(|_| ApiError::internal("Failed to generate presigned URL"))?;
+
+        Ok(PresignedUpload {
+            url: res.url,
+            fields: res.fields,
+            dynamic_fields: res.dynamic_fields,
+            expiration: DateTime::from_timestamp(res.expiration.unix_timestamp(), 0).ok_or(
+                ApiError::internal("Failed to convert expiration to DateTime"),
+            )?,
+        })
+    }
+
+    async fn presign_download(&self, key: &str, expires_in: u32) -> ApiResult<String> {
+        self.bucket
+            .presign_get(key, expires_in, None)
+            .await
+            .map_err(|_| ApiError::internal("Failed to generate presigned URL"))
+    }
+}
@@ -1,5 +1,9 @@
+pub mod cart;
+pub mod category;
+pub mod discount;
 pub mod file;
 pub mod order;
 pub mod product;
 pub mod shipping;
 pub mod store;
+pub mod tax;
@@ -1,11 +1,15 @@
 use bigdecimal::BigDecimal;
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{DateTime, Utc};
 use o2o::o2o;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use super::domain::*;
+use crate::tenant::shipping::domain::DeliveryMethod;
+use crate::tenant::tax::domain::{AppliedTax, TaxBreakdown, TaxMode};
+use crate::types::currency::Currency;
 use crate::types::phone::PhoneNumber;
+use crate::utils::spreadsheet::{ImportRowError, SpreadsheetFormat};
 use crate::{types::id::Id, utils::serde_helpers::JsonOption};
 
 #[derive(Debug, Clone, Deserialize, Serialize, o2o, TS)]
@@ -35,6 +39,26 @@ pub enum PaymentStatusDto {
     PartiallyRefunded,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, o2o, TS)]
+#[serde(rename_all = "snake_case")]
+#[map_owned(PaymentKind)]
+#[ts(export)]
+pub enum PaymentKindDto {
+    Payment,
+    Refund,
+    Failure,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, o2o, TS)]
+#[serde(rename_all = "snake_case")]
+#[map_owned(PaymentMethod)]
+#[ts(export)]
+pub enum PaymentMethodDto {
+    CashOnDelivery,
+    BankTransfer,
+    Other,
+}
+
 #[derive(Debug, Deserialize, TS)]
 #[ts(export, bound = "")]
 pub struct OrderItemCreate {
@@ -52,12 +76,45 @@ pub struct OrderCreate {
     pub items: Vec<OrderItemCreate>,
     pub shipping_address: ShippingAddress,
     pub billing_address: Option<ShippingAddress>,
+    pub delivery_method: Option<DeliveryMethod>,
     #[ts(as = "String")]
     pub shipping_cost: BigDecimal,
+    // The business currency when left out
+    pub currency: Option<Currency>,
+    pub discount_code: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(Adjustment)]
+pub struct AdjustmentDto {
+    #[from(~.into())]
+    pub discount_id: Id,
+    pub code: Option<String>,
+    pub title: String,
+    #[ts(as = "String")]
+    pub amount: BigDecimal,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(OrderItem)]
+pub struct OrderItemDto {
+    #[from(~.map(Into::into))]
+    pub product_id: Option<Id>,
+    pub variant_sku: String,
+    pub product_title: String,
+    pub quantity: u32,
+    #[ts(as = "String")]
+    pub unit_price: BigDecimal,
+    #[ts(as = "String")]
+    pub total_price: BigDecimal,
+    #[from(~.into_iter().map(Into::into).collect())]
+    pub adjustments: Vec<AdjustmentDto>,
+    pub tax: Option<AppliedTax>,
     #[ts(as = "String")]
     pub tax_amount: BigDecimal,
-    pub currency: Option<String>,
-    pub notes: Option<String>,
 }
 
 #[derive(Debug, Serialize, o2o, TS)]
@@ -74,6 +131,25 @@ pub enum SourceDto {
 pub struct OrderHistoryDto {
     #[from(~.into())]
     pub status: OrderStatusDto,
+    pub custom_state: Option<String>,
+    pub note: Option<String>,
+    #[from(~.map(From::from))]
+    pub created_by: Option<SourceDto>,
+    #[from(~.to_chrono())]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(PaymentEntry)]
+pub struct PaymentEntryDto {
+    #[from(~.into())]
+    pub kind: PaymentKindDto,
+    #[from(~.into())]
+    pub method: PaymentMethodDto,
+    #[ts(as = "String")]
+    pub amount: BigDecimal,
+    pub reference: Option<String>,
     pub note: Option<String>,
     #[from(~.map(From::from))]
     pub created_by: Option<SourceDto>,
@@ -90,17 +166,30 @@ pub struct OrderDto {
     pub customer_email: Option<String>,
     pub customer_name: String,
     pub customer_phone: String,
-    pub items: Vec<OrderItem>,
+    #[from(~.into_iter().map(Into::into).collect())]
+    pub items: Vec<OrderItemDto>,
     pub shipping_address: ShippingAddress,
     pub billing_address: Option<ShippingAddress>,
+    pub delivery_method: DeliveryMethod,
     #[from(~.into())]
     pub status: OrderStatusDto,
+    pub custom_state: Option<String>,
     #[from(~.into())]
-    // pub payment_status: PaymentStatusDto,
+    pub payment_status: PaymentStatusDto,
+    #[from(~.into_iter().map(Into::into).collect())]
+    pub payments: Vec<PaymentEntryDto>,
+    #[ts(as = "String")]
+    pub amount_paid: BigDecimal,
     #[ts(as = "String")]
     pub subtotal: BigDecimal,
+    #[from(~.into_iter().map(Into::into).collect())]
+    pub adjustments: Vec<AdjustmentDto>,
+    #[ts(as = "String")]
+    pub discount_amount: BigDecimal,
     #[ts(as = "String")]
     pub shipping_cost: BigDecimal,
+    pub tax_mode: TaxMode,
+    pub taxes: Vec<TaxBreakdown>,
     #[ts(as = "String")]
     pub tax_amount: BigDecimal,
     #[ts(as = "String")]
@@ -108,6 +197,9 @@ pub struct OrderDto {
     pub currency: String,
     pub notes: Option<String>,
     pub tracking_number: Option<String>,
+    pub invoice_number: Option<u32>,
+    #[from(~.map(|d| d.to_chrono()))]
+    pub invoiced_at: Option<DateTime<Utc>>,
     #[from(~.into_iter().map(Into::into).collect())]
     pub history: Vec<OrderHistoryDto>,
     #[from(~.to_chrono())]
@@ -122,7 +214,7 @@ pub struct OrderListQuery {
     pub page: Option<u32>,
     pub limit: Option<u32>,
     pub status: Option<OrderStatusDto>,
-    // pub payment_status: Option<PaymentStatusDto>,
+    pub payment_status: Option<PaymentStatusDto>,
     pub customer_email: Option<String>,
     pub search: Option<String>,
     pub date_from: Option<DateTime<Utc>>,
@@ -134,7 +226,6 @@ pub struct OrderListQuery {
 #[ts(export, bound = "")]
 pub struct OrderUpdate {
     pub status: JsonOption<OrderStatusDto>,
-    // pub payment_status: JsonOption<PaymentStatusDto>,
     pub tracking_number: JsonOption<String>,
     pub notes: JsonOption<String>,
     pub shipping_address: JsonOption<ShippingAddress>,
@@ -145,6 +236,18 @@ pub struct OrderUpdate {
 #[ts(export, bound = "")]
 pub struct OrderStatusUpdate {
     pub status: OrderStatusDto,
+    pub custom_state: Option<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct PaymentEntryCreate {
+    pub kind: PaymentKindDto,
+    pub method: PaymentMethodDto,
+    #[ts(as = "String")]
+    pub amount: BigDecimal,
+    pub reference: Option<String>,
     pub note: Option<String>,
 }
 
@@ -157,26 +260,62 @@ pub struct OrderListResponse {
     pub limit: u32,
 }
 
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct StatusCount {
+    pub status: OrderStatusDto,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct AnalyticsPoint {
+    pub period_start: DateTime<Utc>,
+    pub orders: u64,
+    #[ts(as = "String")]
+    pub revenue: BigDecimal,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct TopSku {
+    pub product_id: Option<Id>,
+    pub variant_sku: String,
+    pub product_title: String,
+    pub quantity: u64,
+    #[ts(as = "String")]
+    pub revenue: BigDecimal,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct StoreRevenue {
+    pub store_id: Id,
+    pub orders: u64,
+    #[ts(as = "String")]
+    pub revenue: BigDecimal,
+}
+
 #[derive(Debug, Serialize, TS)]
 #[ts(export, bound = "")]
 pub struct OrderAnalytics {
+    // Of the orders counted and of every amount
+    pub currency: Currency,
     pub total_orders: u64,
     #[ts(as = "String")]
     pub total_revenue: BigDecimal,
+    #[ts(as = "String")]
+    pub collected_revenue: BigDecimal,
     pub pending_orders: u64,
     pub completed_orders: u64,
     pub cancelled_orders: u64,
     #[ts(as = "String")]
     pub average_order_value: BigDecimal,
-    // #[ts(as = "Vec<(NaiveDate, u64)>")]
-    // pub orders_per_day: Vec<(NaiveDate, u64)>,         // time series: activity trend
-    // #[ts(as = "Vec<(NaiveDate, String)>")]
-    // pub revenue_per_day: Vec<(NaiveDate, BigDecimal)>, // time series: financial trend
-    // #[ts(as = "Vec<(String, String)>")]
-    // pub top_customers: Vec<(String, BigDecimal)>,      // top customers by spend
-    // #[ts(as = "Vec<(String, u64)>")]
-    // pub top_products: Vec<(String, u64)>,              // bestsellers
-    // pub date_range: (Option<DateTime<Utc>>, Option<DateTime<Utc>>), // for context
+    pub status_counts: Vec<StatusCount>,
+    pub bucket: AnalyticsBucket,
+    pub series: Vec<AnalyticsPoint>,
+    pub top_skus: Vec<TopSku>,
+    pub revenue_per_store: Vec<StoreRevenue>,
 }
 
 #[derive(Debug, Deserialize, TS)]
@@ -184,22 +323,92 @@ pub struct OrderAnalytics {
 pub struct BulkOrderStatusUpdate {
     pub order_ids: Vec<Id>,
     pub status: OrderStatusDto,
+    pub custom_state: Option<String>,
     pub note: Option<String>,
-    pub created_by: Option<String>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct BulkUpdateFailure {
+    pub id: Id,
+    pub reason: String,
 }
 
 #[derive(Debug, Serialize, TS)]
 #[ts(export, bound = "")]
 pub struct BulkUpdateResponse {
     pub updated_count: u64,
-    pub failed_ids: Vec<Id>,
+    pub failures: Vec<BulkUpdateFailure>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum OrderDocumentKind {
+    Invoice,
+    PackingSlip,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct OrderDocumentQuery {
+    // Store whose branding is printed, defaults to the one the order came from
+    pub store_id: Option<Id>,
+}
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct BulkOrderDocuments {
+    pub order_ids: Vec<Id>,
+    pub kind: OrderDocumentKind,
+    #[ts(optional)]
+    pub store_id: Option<Id>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct OrderExportQuery {
+    pub format: Option<SpreadsheetFormat>,
+    // Comma separated `OrderColumn` names
+    pub columns: Option<String>,
+    pub status: Option<OrderStatusDto>,
+    pub payment_status: Option<PaymentStatusDto>,
+    pub customer_email: Option<String>,
+    pub search: Option<String>,
+    pub date_from: Option<DateTime<Utc>>,
+    pub date_to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct OrderImportQuery {
+    // Only validates the file unless set to false
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct OrderImportReport {
+    pub dry_run: bool,
+    pub rows: u32,
+    // Orders found in the file, all of them are created when it has no errors
+    pub orders: u32,
+    pub errors: Vec<ImportRowError>,
+    pub imported: u32,
 }
 
 #[derive(Debug, Deserialize, TS)]
 #[ts(export, bound = "")]
 pub struct AnalyticsQuery {
+    // The business currency when left out
+    #[ts(optional)]
+    pub currency: Option<Currency>,
     pub date_from: Option<DateTime<Utc>>,
     pub date_to: Option<DateTime<Utc>>,
+    #[ts(optional)]
+    pub bucket: Option<AnalyticsBucket>,
+    #[ts(optional)]
+    pub top_limit: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -218,5 +427,7 @@ pub struct PubOrderCreate {
     pub items: Vec<OrderItemCreate>,
     pub shipping_address: ShippingAddress,
     pub billing_address: Option<ShippingAddress>,
+    pub delivery_method: Option<DeliveryMethod>,
+    pub discount_code: Option<String>,
     pub notes: Option<String>,
 }
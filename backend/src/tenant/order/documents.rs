@@ -0,0 +1,668 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+
+use super::api::OrderDocumentKind;
+use super::domain::{OrderItem, OrderRecord, ShippingAddress};
+use crate::tenant::shipping::domain::DeliveryMethod;
+use crate::tenant::tax::domain::TaxMode;
+use crate::types::currency::Currency;
+use crate::utils::pdf::{Align, Color, Image, ImageId, PdfDocument, TextStyle, Weight, A4};
+
+const MARGIN: f32 = 40.0;
+const RIGHT: f32 = A4.0 - MARGIN;
+// Lowest baseline for the body, the footer sits below it
+const BOTTOM: f32 = A4.1 - 64.0;
+const FOOTER: f32 = A4.1 - 32.0;
+
+const MUTED: Color = Color::gray(0.45);
+const RULE: Color = Color::gray(0.82);
+const SHADE: Color = Color::gray(0.94);
+
+const TITLE: TextStyle = TextStyle::new(20.0, Weight::Bold);
+const BRAND: TextStyle = TextStyle::new(14.0, Weight::Bold);
+const LABEL: TextStyle = TextStyle::new(7.5, Weight::Bold).color(MUTED);
+const BODY: TextStyle = TextStyle::new(9.0, Weight::Regular);
+const STRONG: TextStyle = TextStyle::new(9.0, Weight::Bold);
+const TOTAL: TextStyle = TextStyle::new(11.0, Weight::Bold);
+const SMALL: TextStyle = TextStyle::new(7.5, Weight::Regular).color(MUTED);
+
+const LINE: f32 = 12.0;
+const SMALL_LINE: f32 = 10.0;
+
+// Forces left to right layout on amounts, which would otherwise take the
+// direction of an Arabic currency symbol
+const LRM: char = '\u{200E}';
+
+/// Who the documents are issued by, taken from a store.
+#[derive(Debug, Clone, Default)]
+pub struct Branding {
+    pub name: String,
+    // Contact and address lines printed under the name
+    pub details: Vec<String>,
+    // Encoded image, printed when it can be decoded
+    pub logo: Option<Vec<u8>>,
+}
+
+struct Column {
+    title: &'static str,
+    // Left edge, text is aligned to the right edge for numbers
+    x: f32,
+    width: f32,
+    align: Align,
+}
+
+impl Column {
+    const fn new(title: &'static str, x: f32, width: f32, align: Align) -> Self {
+        Self {
+            title,
+            x,
+            width,
+            align,
+        }
+    }
+
+    fn anchor(&self) -> f32 {
+        match self.align {
+            Align::Left => self.x,
+            Align::Center => self.x + self.width / 2.0,
+            Align::Right => self.x + self.width,
+        }
+    }
+}
+
+const INVOICE_COLUMNS: [Column; 5] = [
+    Column::new("Item", MARGIN, 235.0, Align::Left),
+    Column::new("Qty", MARGIN + 235.0, 40.0, Align::Right),
+    Column::new("Unit price", MARGIN + 275.0, 85.0, Align::Right),
+    Column::new("Tax", MARGIN + 360.0, 55.0, Align::Right),
+    Column::new("Amount", MARGIN + 415.0, 100.0, Align::Right),
+];
+
+const PACKING_COLUMNS: [Column; 3] = [
+    Column::new("Item", MARGIN, 300.0, Align::Left),
+    Column::new("SKU", MARGIN + 310.0, 145.0, Align::Left),
+    Column::new("Qty", MARGIN + 455.0, 60.0, Align::Right),
+];
+
+/// Renders one document per order into a single PDF, each starting on a new
+/// page. Every order comes with the index of its branding.
+pub fn render(
+    kind: OrderDocumentKind,
+    brandings: &[Branding],
+    orders: &[(usize, OrderRecord)],
+) -> Result<Vec<u8>, String> {
+    let title = match (kind, orders) {
+        (OrderDocumentKind::Invoice, [(_, order)]) => order
+            .invoice_reference()
+            .map(|r| format!("Invoice {}", r))
+            .unwrap_or_else(|| "Invoice".to_string()),
+        (OrderDocumentKind::Invoice, _) => "Invoices".to_string(),
+        (OrderDocumentKind::PackingSlip, [_]) => "Packing slip".to_string(),
+        (OrderDocumentKind::PackingSlip, _) => "Packing slips".to_string(),
+    };
+
+    let mut doc = PdfDocument::new(title);
+    let logos: Vec<Option<ImageId>> = brandings
+        .iter()
+        .map(|b| {
+            let image = Image::decode(b.logo.as_deref()?).ok()?;
+            Some(doc.add_image(image))
+        })
+        .collect();
+
+    for (index, order) in orders {
+        let branding = brandings
+            .get(*index)
+            .ok_or_else(|| format!("Missing branding {}", index))?;
+
+        Sheet {
+            doc: &mut doc,
+            kind,
+            branding,
+            logo: logos[*index],
+            order,
+            currency: order.currency.parse().unwrap_or_default(),
+            y: 0.0,
+        }
+        .render();
+    }
+
+    doc.finish()
+}
+
+struct Sheet<'a> {
+    doc: &'a mut PdfDocument,
+    kind: OrderDocumentKind,
+    branding: &'a Branding,
+    logo: Option<ImageId>,
+    order: &'a OrderRecord,
+    currency: Currency,
+    // Baseline of the next line
+    y: f32,
+}
+
+impl Sheet<'_> {
+    fn render(mut self) {
+        let first_page = self.doc.new_page();
+
+        self.header();
+        self.addresses();
+        self.table_header();
+        let order = self.order;
+        for item in &order.items {
+            match self.kind {
+                OrderDocumentKind::Invoice => self.invoice_row(item),
+                OrderDocumentKind::PackingSlip => self.packing_row(item),
+            }
+        }
+        match self.kind {
+            OrderDocumentKind::Invoice => self.totals(),
+            OrderDocumentKind::PackingSlip => self.packing_summary(),
+        }
+        self.notes();
+
+        self.footers(first_page);
+    }
+
+    fn columns(&self) -> &'static [Column] {
+        match self.kind {
+            OrderDocumentKind::Invoice => &INVOICE_COLUMNS,
+            OrderDocumentKind::PackingSlip => &PACKING_COLUMNS,
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self.kind {
+            OrderDocumentKind::Invoice => "INVOICE",
+            OrderDocumentKind::PackingSlip => "PACKING SLIP",
+        }
+    }
+
+    fn reference(&self) -> String {
+        match self.kind {
+            OrderDocumentKind::Invoice => self
+                .order
+                .invoice_reference()
+                .unwrap_or_else(|| self.order_reference()),
+            OrderDocumentKind::PackingSlip => self.order_reference(),
+        }
+    }
+
+    fn order_reference(&self) -> String {
+        format!("#{}", self.order._id.to_hex())
+    }
+
+    fn money(&self, amount: &BigDecimal) -> String {
+        format!("{}{}", LRM, self.currency.format(amount))
+    }
+
+    /// Starts a new page when `height` more points do not fit on this one.
+    /// Tables get their header row again.
+    fn ensure(&mut self, height: f32, in_table: bool) {
+        if self.y + height <= BOTTOM {
+            return;
+        }
+
+        self.doc.new_page();
+        self.y = MARGIN + 14.0;
+        self.doc
+            .text(MARGIN, self.y, self.title(), &STRONG, Align::Left);
+        self.doc
+            .text(RIGHT, self.y, &self.reference(), &STRONG, Align::Right);
+        self.y += 22.0;
+
+        if in_table {
+            self.table_header();
+        }
+    }
+
+    fn header(&mut self) {
+        let top = MARGIN;
+
+        if let Some(logo) = self.logo {
+            // Fits the logo in a 140 by 56 box, keeping its proportions
+            let (width, height) = self.logo_size();
+            self.doc.image(logo, MARGIN, top, width, height);
+        }
+
+        let mut y = top + 14.0;
+        self.doc
+            .text(RIGHT, y, &self.branding.name, &BRAND, Align::Right);
+        y += 4.0;
+        for line in &self.branding.details {
+            y += SMALL_LINE;
+            self.doc.text(RIGHT, y, line, &SMALL, Align::Right);
+        }
+
+        self.y = y.max(top + 56.0) + 40.0;
+        self.doc
+            .text(MARGIN, self.y, self.title(), &TITLE, Align::Left);
+
+        let date = |d: &bson::DateTime| d.to_chrono().format("%Y-%m-%d").to_string();
+        let mut meta = Vec::new();
+        match self.kind {
+            OrderDocumentKind::Invoice => {
+                if let Some(reference) = self.order.invoice_reference() {
+                    meta.push(("Invoice number", reference));
+                }
+                if let Some(ref invoiced_at) = self.order.invoiced_at {
+                    meta.push(("Invoice date", date(invoiced_at)));
+                }
+                meta.push(("Order", self.order_reference()));
+                meta.push(("Order date", date(&self.order.created_at)));
+            }
+            OrderDocumentKind::PackingSlip => {
+                meta.push(("Order", self.order_reference()));
+                meta.push(("Order date", date(&self.order.created_at)));
+                meta.push((
+                    "Delivery",
+                    delivery_label(self.order.delivery_method).into(),
+                ));
+                if let Some(ref tracking) = self.order.tracking_number {
+                    meta.push(("Tracking number", tracking.clone()));
+                }
+            }
+        }
+
+        let mut y = self.y - 12.0;
+        for (label, value) in meta {
+            self.doc.text(RIGHT - 230.0, y, label, &SMALL, Align::Left);
+            self.doc.text(RIGHT, y, &value, &BODY, Align::Right);
+            y += LINE;
+        }
+
+        self.y = y.max(self.y) + 24.0;
+    }
+
+    fn logo_size(&self) -> (f32, f32) {
+        let Some(logo) = self.logo else {
+            return (0.0, 0.0);
+        };
+        let (width, height) = self.doc.image_size(logo);
+        let scale = (140.0 / width).min(56.0 / height).min(1.0);
+
+        (width * scale, height * scale)
+    }
+
+    fn addresses(&mut self) {
+        let order = self.order;
+        let half = (RIGHT - MARGIN) / 2.0;
+
+        let mut blocks = Vec::new();
+        if self.kind == OrderDocumentKind::Invoice {
+            let mut lines = address_lines(
+                order
+                    .billing_address
+                    .as_ref()
+                    .unwrap_or(&order.shipping_address),
+            );
+            lines.push(order.customer_phone.clone());
+            lines.extend(order.customer_email.clone());
+            blocks.push(("BILL TO", lines));
+        }
+        let mut lines = address_lines(&order.shipping_address);
+        if self.kind == OrderDocumentKind::PackingSlip {
+            lines.push(order.customer_phone.clone());
+        }
+        blocks.push(("SHIP TO", lines));
+
+        let top = self.y;
+        let mut bottom = top;
+        for (column, (label, lines)) in blocks.into_iter().enumerate() {
+            let x = MARGIN + column as f32 * half;
+            let mut y = top;
+            self.doc.text(x, y, label, &LABEL, Align::Left);
+            for (i, line) in lines.iter().filter(|l| !l.is_empty()).enumerate() {
+                let style = if i == 0 { &STRONG } else { &BODY };
+                for part in self.doc.wrap(line, half - 16.0, style) {
+                    y += LINE;
+                    self.doc.text(x, y, &part, style, Align::Left);
+                }
+            }
+            bottom = bottom.max(y);
+        }
+
+        self.y = bottom + 28.0;
+    }
+
+    fn table_header(&mut self) {
+        self.doc.fill_rect(
+            MARGIN - 4.0,
+            self.y - 12.0,
+            RIGHT - MARGIN + 8.0,
+            18.0,
+            SHADE,
+        );
+        for column in self.columns() {
+            self.doc
+                .text(column.anchor(), self.y, column.title, &STRONG, column.align);
+        }
+        self.y += 20.0;
+    }
+
+    fn row_rule(&mut self) {
+        let y = self.y - LINE + 4.0;
+        self.doc.line((MARGIN, y), (RIGHT, y), 0.5, RULE);
+        self.y += 6.0;
+    }
+
+    fn invoice_row(&mut self, item: &OrderItem) {
+        let [title, qty, unit, tax, amount] = &INVOICE_COLUMNS;
+
+        let lines = self.doc.wrap(&item.product_title, title.width - 8.0, &BODY);
+        let height =
+            lines.len() as f32 * LINE + (1 + item.adjustments.len()) as f32 * SMALL_LINE + 6.0;
+        self.ensure(height, true);
+
+        let top = self.y;
+        self.doc.text(
+            qty.anchor(),
+            top,
+            &item.quantity.to_string(),
+            &BODY,
+            qty.align,
+        );
+        self.doc.text(
+            unit.anchor(),
+            top,
+            &self.money(&item.unit_price),
+            &BODY,
+            unit.align,
+        );
+        let rate = item
+            .tax
+            .as_ref()
+            .map(|t| format!("{}%", percent(&t.percent)))
+            .unwrap_or_else(|| "-".to_string());
+        self.doc.text(tax.anchor(), top, &rate, &BODY, tax.align);
+        self.doc.text(
+            amount.anchor(),
+            top,
+            &self.money(&item.total_price),
+            &BODY,
+            amount.align,
+        );
+
+        for line in &lines {
+            self.doc.text(title.x, self.y, line, &BODY, title.align);
+            self.y += LINE;
+        }
+        self.y -= LINE - SMALL_LINE;
+        self.doc.text(
+            title.x,
+            self.y,
+            &format!("SKU {}", item.variant_sku),
+            &SMALL,
+            title.align,
+        );
+
+        for adjustment in &item.adjustments {
+            self.y += SMALL_LINE;
+            self.doc
+                .text(title.x, self.y, &adjustment.title, &SMALL, title.align);
+            let discount = format!("-{}", self.money(&adjustment.amount));
+            self.doc
+                .text(amount.anchor(), self.y, &discount, &SMALL, amount.align);
+        }
+
+        self.y += LINE;
+        self.row_rule();
+    }
+
+    fn packing_row(&mut self, item: &OrderItem) {
+        let [title, sku, qty] = &PACKING_COLUMNS;
+
+        let lines = self.doc.wrap(&item.product_title, title.width - 8.0, &BODY);
+        let skus = self.doc.wrap(&item.variant_sku, sku.width - 8.0, &BODY);
+        let height = lines.len().max(skus.len()) as f32 * LINE + 6.0;
+        self.ensure(height, true);
+
+        let top = self.y;
+        self.doc.text(
+            qty.anchor(),
+            top,
+            &item.quantity.to_string(),
+            &STRONG,
+            qty.align,
+        );
+        for (i, line) in skus.iter().enumerate() {
+            self.doc
+                .text(sku.x, top + i as f32 * LINE, line, &BODY, sku.align);
+        }
+        for (i, line) in lines.iter().enumerate() {
+            self.doc
+                .text(title.x, top + i as f32 * LINE, line, &BODY, title.align);
+        }
+
+        self.y = top + lines.len().max(skus.len()) as f32 * LINE;
+        self.row_rule();
+    }
+
+    fn totals(&mut self) {
+        let order = self.order;
+        let zero = BigDecimal::from(0);
+        let inclusive = order.tax_mode == TaxMode::Inclusive;
+
+        let mut rows = vec![("Subtotal".to_string(), self.money(&order.subtotal))];
+        if order.discount_amount > zero {
+            let discount = format!("-{}", self.money(&order.discount_amount));
+            rows.push(("Discount".to_string(), discount));
+        }
+        rows.push(("Shipping".to_string(), self.money(&order.shipping_cost)));
+        for tax in &order.taxes {
+            let label = format!(
+                "{}{} {}%",
+                if inclusive { "Incl. " } else { "" },
+                tax.name,
+                percent(&tax.percent)
+            );
+            rows.push((label, self.money(&tax.amount)));
+        }
+
+        let mut due = Vec::new();
+        if order.amount_paid > zero {
+            due.push(("Paid".to_string(), self.money(&order.amount_paid)));
+            let balance = (&order.total_amount - &order.amount_paid).max(zero);
+            due.push(("Balance due".to_string(), self.money(&balance)));
+        }
+
+        let height = (rows.len() + due.len()) as f32 * LINE + 40.0;
+        self.ensure(height, false);
+
+        let label_x = RIGHT - 230.0;
+        self.y += 6.0;
+        for (label, value) in rows {
+            self.doc.text(label_x, self.y, &label, &BODY, Align::Left);
+            self.doc.text(RIGHT, self.y, &value, &BODY, Align::Right);
+            self.y += LINE;
+        }
+
+        self.doc.line(
+            (label_x, self.y - 6.0),
+            (RIGHT, self.y - 6.0),
+            0.8,
+            Color::BLACK,
+        );
+        self.y += 8.0;
+        self.doc.text(label_x, self.y, "Total", &TOTAL, Align::Left);
+        self.doc.text(
+            RIGHT,
+            self.y,
+            &self.money(&order.total_amount),
+            &TOTAL,
+            Align::Right,
+        );
+        self.y += LINE + 4.0;
+
+        for (label, value) in due {
+            self.doc.text(label_x, self.y, &label, &BODY, Align::Left);
+            self.doc.text(RIGHT, self.y, &value, &BODY, Align::Right);
+            self.y += LINE;
+        }
+    }
+
+    fn packing_summary(&mut self) {
+        let count: u32 = self.order.items.iter().map(|i| i.quantity).sum();
+        self.ensure(LINE + 6.0, false);
+
+        let qty = &PACKING_COLUMNS[2];
+        self.y += 6.0;
+        self.doc.text(
+            qty.anchor() - 60.0,
+            self.y,
+            "Total items",
+            &STRONG,
+            Align::Right,
+        );
+        self.doc
+            .text(qty.anchor(), self.y, &count.to_string(), &STRONG, qty.align);
+        self.y += LINE;
+    }
+
+    fn notes(&mut self) {
+        let Some(notes) = self.order.notes.as_deref().filter(|n| !n.trim().is_empty()) else {
+            return;
+        };
+
+        let lines: Vec<String> = notes
+            .lines()
+            .flat_map(|l| self.doc.wrap(l, RIGHT - MARGIN, &BODY))
+            .collect();
+
+        self.y += 16.0;
+        self.ensure(LINE * 2.0, false);
+        self.doc.text(MARGIN, self.y, "NOTES", &LABEL, Align::Left);
+        for line in lines {
+            self.y += LINE;
+            self.ensure(0.0, false);
+            self.doc.text(MARGIN, self.y, &line, &BODY, Align::Left);
+        }
+    }
+
+    /// Numbers the pages of this document, once their count is known.
+    fn footers(self, first_page: usize) {
+        let count = self.doc.page_count() - first_page;
+        let reference = self.reference();
+
+        for page in 0..count {
+            self.doc.select_page(first_page + page);
+            self.doc
+                .line((MARGIN, FOOTER - 12.0), (RIGHT, FOOTER - 12.0), 0.5, RULE);
+            self.doc
+                .text(MARGIN, FOOTER, &self.branding.name, &SMALL, Align::Left);
+            let numbering = format!("Page {} of {}", page + 1, count);
+            self.doc
+                .text(A4.0 / 2.0, FOOTER, &numbering, &SMALL, Align::Center);
+            self.doc
+                .text(RIGHT, FOOTER, &reference, &SMALL, Align::Right);
+        }
+    }
+}
+
+fn address_lines(address: &ShippingAddress) -> Vec<String> {
+    let locality = [address.postal_code.as_str(), address.city.as_str()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let region = [locality.as_str(), address.state.as_str()]
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut lines = vec![address.full_name.clone(), address.address_line_1.clone()];
+    lines.extend(address.address_line_2.clone());
+    lines.push(region);
+    lines.push(address.country.clone());
+    lines.extend(address.phone.clone());
+
+    lines
+}
+
+fn delivery_label(method: DeliveryMethod) -> &'static str {
+    match method {
+        DeliveryMethod::Home => "Home delivery",
+        DeliveryMethod::StopDesk => "Stop desk pickup",
+    }
+}
+
+/// A tax rate without trailing zeros, e.g. `19` or `9.5`.
+fn percent(value: &BigDecimal) -> String {
+    let text = value
+        .with_scale_round(2, RoundingMode::HalfUp)
+        .to_plain_string();
+    match text.split_once('.') {
+        Some(_) => text.trim_end_matches('0').trim_end_matches('.').to_string(),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn page_count(pdf: &[u8]) -> usize {
+        let text = String::from_utf8_lossy(pdf);
+        text.matches("/Type /Page").count() - text.matches("/Type /Pages").count()
+    }
+
+    fn order(items: usize) -> OrderRecord {
+        let mut order = OrderRecord {
+            customer_name: "أمينة بن علي".into(),
+            customer_phone: "0550 00 00 00".into(),
+            currency: "DZD".into(),
+            invoice_number: Some(42),
+            notes: Some("Call before delivery".into()),
+            ..Default::default()
+        };
+        order.shipping_address.full_name = "أمينة بن علي".into();
+        order.shipping_address.address_line_1 = "12 شارع الاستقلال".into();
+        order.shipping_address.city = "Alger".into();
+        order.shipping_address.country = "DZ".into();
+        order.items = (0..items)
+            .map(|i| OrderItem {
+                product_id: None,
+                variant_sku: format!("SKU-{}", i),
+                product_title: format!("Robe kabyle brodée à la main, modèle {}", i),
+                quantity: 1,
+                unit_price: BigDecimal::from(2500),
+                total_price: BigDecimal::from(2500),
+                adjustments: Vec::new(),
+                tax: None,
+                tax_amount: BigDecimal::from(0),
+            })
+            .collect();
+        order.calculate_totals();
+        order
+    }
+
+    #[test]
+    fn test_percent() {
+        assert_eq!(percent(&BigDecimal::from(19)), "19");
+        assert_eq!(percent(&BigDecimal::from_str("9.50").unwrap()), "9.5");
+        assert_eq!(percent(&BigDecimal::from_str("0.125").unwrap()), "0.13");
+    }
+
+    #[test]
+    fn test_render() {
+        let brandings = vec![Branding {
+            name: "متجر الياسمين".into(),
+            details: vec!["contact@example.com".into()],
+            logo: None,
+        }];
+
+        let invoice = render(OrderDocumentKind::Invoice, &brandings, &[(0, order(3))]).unwrap();
+        assert!(invoice.starts_with(b"%PDF-"));
+        assert_eq!(page_count(&invoice), 1);
+
+        // Long orders flow onto more pages, and every order of a bulk print
+        // starts its own
+        let orders = [(0, order(60)), (0, order(2))];
+        let slips = render(OrderDocumentKind::PackingSlip, &brandings, &orders).unwrap();
+        assert!(page_count(&slips) >= 3);
+
+        assert!(render(OrderDocumentKind::Invoice, &brandings, &[(1, order(1))]).is_err());
+    }
+}
@@ -3,9 +3,12 @@ use bson::{oid::ObjectId, DateTime};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::tenant::shipping::domain::DeliveryMethod;
+use crate::tenant::tax::domain::{tax_on, AppliedTax, TaxBreakdown, TaxMode};
+use crate::types::currency::Currency;
 use crate::types::name::Name;
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OrderStatus {
     #[default]
@@ -19,7 +22,66 @@ pub enum OrderStatus {
     Archived,
 }
 
-#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Confirmed => "confirmed",
+            OrderStatus::Processing => "processing",
+            OrderStatus::Shipped => "shipped",
+            OrderStatus::Delivered => "delivered",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Refunded => "refunded",
+            OrderStatus::Archived => "archived",
+        }
+    }
+
+    pub fn next_statuses(&self) -> &'static [OrderStatus] {
+        match self {
+            OrderStatus::Pending => &[OrderStatus::Confirmed, OrderStatus::Cancelled],
+            OrderStatus::Confirmed => &[OrderStatus::Processing, OrderStatus::Cancelled],
+            OrderStatus::Processing => &[OrderStatus::Shipped],
+            OrderStatus::Shipped => &[OrderStatus::Delivered, OrderStatus::Refunded],
+            OrderStatus::Delivered => &[OrderStatus::Refunded, OrderStatus::Archived],
+            OrderStatus::Cancelled | OrderStatus::Refunded => &[OrderStatus::Archived],
+            OrderStatus::Archived => &[],
+        }
+    }
+
+    pub fn can_transition_to(&self, to: &OrderStatus) -> bool {
+        self.next_statuses().contains(to)
+    }
+
+    /// Whether an order in this status can be given an invoice number, i.e.
+    /// it was confirmed and not cancelled before shipping.
+    pub fn is_invoiceable(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Confirmed
+                | OrderStatus::Processing
+                | OrderStatus::Shipped
+                | OrderStatus::Delivered
+        )
+    }
+}
+
+/// A business-defined step that an order can sit in while keeping its base
+/// `status`, e.g. "called customer, no answer" while still pending.
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+pub struct CustomOrderState {
+    pub key: String,
+    pub label: String,
+    #[ts(as = "super::api::OrderStatusDto")]
+    pub status: OrderStatus,
+}
+
+#[derive(Debug, Clone)]
+pub struct InvalidTransition {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum PaymentStatus {
     #[default]
@@ -30,15 +92,82 @@ pub enum PaymentStatus {
     PartiallyRefunded,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+impl PaymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PaymentStatus::Pending => "pending",
+            PaymentStatus::Paid => "paid",
+            PaymentStatus::Failed => "failed",
+            PaymentStatus::Refunded => "refunded",
+            PaymentStatus::PartiallyRefunded => "partially_refunded",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentKind {
+    Payment,
+    Refund,
+    Failure,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    CashOnDelivery,
+    BankTransfer,
+    Other,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PaymentEntry {
+    pub kind: PaymentKind,
+    pub method: PaymentMethod,
+    pub amount: BigDecimal,
+    pub reference: Option<String>,
+    pub note: Option<String>,
+    pub created_by: Option<Source>,
+    pub created_at: DateTime,
+}
+
+/// An amount taken off a line or off the whole order by a discount.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Adjustment {
+    pub discount_id: ObjectId,
+    pub code: Option<String>,
+    pub title: String,
+    pub amount: BigDecimal,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OrderItem {
+    // Missing on orders placed before items were linked to their product
+    #[serde(default)]
+    pub product_id: Option<ObjectId>,
     pub variant_sku: String,
     pub product_title: String,
     pub quantity: u32,
-    #[ts(as = "String")]
     pub unit_price: BigDecimal,
-    #[ts(as = "String")]
     pub total_price: BigDecimal,
+    #[serde(default)]
+    pub adjustments: Vec<Adjustment>,
+    // The rate in force when the order was placed, none for untaxed lines
+    #[serde(default)]
+    pub tax: Option<AppliedTax>,
+    #[serde(default)]
+    pub tax_amount: BigDecimal,
+}
+
+impl OrderItem {
+    pub fn discount_amount(&self) -> BigDecimal {
+        self.adjustments.iter().map(|a| &a.amount).sum()
+    }
+
+    /// What the line is taxed on, after its own discounts.
+    pub fn taxable_amount(&self) -> BigDecimal {
+        (&self.total_price - self.discount_amount()).max(BigDecimal::from(0))
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, TS)]
@@ -63,6 +192,8 @@ pub enum Source {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct OrderHistory {
     pub status: OrderStatus,
+    #[serde(default)]
+    pub custom_state: Option<String>,
     pub note: Option<String>,
     pub created_by: Option<Source>,
     pub created_at: DateTime,
@@ -77,20 +208,56 @@ pub struct OrderRecord {
     pub items: Vec<OrderItem>,
     pub shipping_address: ShippingAddress,
     pub billing_address: Option<ShippingAddress>,
+    #[serde(default)]
+    pub delivery_method: DeliveryMethod,
     pub status: OrderStatus,
-    // pub payment_status: PaymentStatus,
+    #[serde(default)]
+    pub custom_state: Option<String>,
+    #[serde(default)]
+    pub payment_status: PaymentStatus,
+    #[serde(default)]
+    pub payments: Vec<PaymentEntry>,
+    // Net amount collected, kept in sync with `payments`
+    #[serde(default)]
+    pub amount_paid: BigDecimal,
     pub subtotal: BigDecimal,
+    // Order-level discounts, line discounts live on the items
+    #[serde(default)]
+    pub adjustments: Vec<Adjustment>,
+    #[serde(default)]
+    pub discount_amount: BigDecimal,
     pub shipping_cost: BigDecimal,
+    #[serde(default)]
+    pub tax_mode: TaxMode,
+    // Tax per rate, summing up to `tax_amount`
+    #[serde(default)]
+    pub taxes: Vec<TaxBreakdown>,
     pub tax_amount: BigDecimal,
     pub total_amount: BigDecimal,
     pub currency: String,
     pub notes: Option<String>,
     pub tracking_number: Option<String>,
+    // Whether the items were taken out of stock and are still to be given back
+    #[serde(default = "default_stock_reserved")]
+    pub stock_reserved: bool,
+    // Given when the first invoice is printed, sequential per business
+    #[serde(default)]
+    pub invoice_number: Option<u32>,
+    #[serde(default)]
+    pub invoiced_at: Option<DateTime>,
     pub history: Vec<OrderHistory>,
+    // Bumped on every save, so a save based on an outdated read is refused
+    #[serde(default)]
+    pub version: u32,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
 
+// Orders saved before the flag existed were all placed through the app
+fn default_stock_reserved() -> bool {
+    true
+}
+
 impl Default for OrderRecord {
     fn default() -> Self {
         let now = DateTime::now();
@@ -112,16 +279,28 @@ impl Default for OrderRecord {
                 phone: Default::default(),
             },
             billing_address: Default::default(),
+            delivery_method: Default::default(),
             status: Default::default(),
-            // payment_status: Default::default(),
+            custom_state: Default::default(),
+            payment_status: Default::default(),
+            payments: Default::default(),
+            amount_paid: BigDecimal::from(0),
             subtotal: BigDecimal::from(0),
+            adjustments: Default::default(),
+            discount_amount: BigDecimal::from(0),
             shipping_cost: BigDecimal::from(0),
+            tax_mode: Default::default(),
+            taxes: Default::default(),
             tax_amount: BigDecimal::from(0),
             total_amount: BigDecimal::from(0),
-            currency: "USD".to_string(),
+            currency: Currency::default().to_string(),
             notes: Default::default(),
             tracking_number: Default::default(),
+            stock_reserved: true,
+            invoice_number: Default::default(),
+            invoiced_at: Default::default(),
             history: Default::default(),
+            version: 0,
             created_at: now,
             updated_at: now,
         }
@@ -137,6 +316,15 @@ impl OrderRecord {
         matches!(self.status, OrderStatus::Pending | OrderStatus::Confirmed)
     }
 
+    pub fn holds_stock(&self) -> bool {
+        self.stock_reserved
+            && !matches!(self.status, OrderStatus::Cancelled | OrderStatus::Refunded)
+    }
+
+    pub fn releases_stock(&self, status: &OrderStatus) -> bool {
+        self.holds_stock() && matches!(status, OrderStatus::Cancelled | OrderStatus::Refunded)
+    }
+
     pub fn add_history_entry(
         &mut self,
         status: OrderStatus,
@@ -145,6 +333,7 @@ impl OrderRecord {
     ) {
         self.history.push(OrderHistory {
             status: status.clone(),
+            custom_state: self.custom_state.clone(),
             note,
             created_by,
             created_at: DateTime::now(),
@@ -153,19 +342,398 @@ impl OrderRecord {
         self.updated_at = DateTime::now();
     }
 
+    /// Moves the order along the transition graph. `custom_state` must already
+    /// be resolved against the business settings and belong to `status`.
+    pub fn transition(
+        &mut self,
+        status: OrderStatus,
+        custom_state: Option<String>,
+        note: Option<String>,
+        created_by: Option<Source>,
+    ) -> Result<(), InvalidTransition> {
+        let allowed = if status == self.status {
+            custom_state != self.custom_state
+        } else {
+            self.status.can_transition_to(&status)
+                && (status != OrderStatus::Cancelled || self.can_be_cancelled())
+        };
+
+        if !allowed {
+            return Err(InvalidTransition {
+                from: self.state_name().to_string(),
+                to: custom_state.unwrap_or_else(|| status.as_str().to_string()),
+            });
+        }
+
+        self.custom_state = custom_state;
+        self.add_history_entry(status, note, created_by);
+
+        Ok(())
+    }
+
+    /// The store the order was placed through, none for manual orders.
+    pub fn store_id(&self) -> Option<ObjectId> {
+        match self.history.first()?.created_by {
+            Some(Source::Store(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn invoice_reference(&self) -> Option<String> {
+        self.invoice_number.map(|n| format!("INV-{:06}", n))
+    }
+
+    pub fn state_name(&self) -> &str {
+        self.custom_state
+            .as_deref()
+            .unwrap_or_else(|| self.status.as_str())
+    }
+
+    pub fn add_payment_entry(&mut self, entry: PaymentEntry) -> Result<(), &'static str> {
+        if entry.amount < BigDecimal::from(0) {
+            return Err("Amount cannot be negative");
+        }
+
+        match entry.kind {
+            PaymentKind::Payment | PaymentKind::Refund if entry.amount == BigDecimal::from(0) => {
+                return Err("Amount must be greater than zero");
+            }
+            PaymentKind::Refund if entry.amount > self.amount_paid => {
+                return Err("Refund exceeds the amount collected");
+            }
+            _ => {}
+        }
+
+        self.payments.push(entry);
+        self.sync_payment_status();
+        self.updated_at = DateTime::now();
+
+        Ok(())
+    }
+
+    pub fn sync_payment_status(&mut self) {
+        let zero = BigDecimal::from(0);
+        let mut collected = BigDecimal::from(0);
+        let mut refunded = BigDecimal::from(0);
+
+        for entry in &self.payments {
+            match entry.kind {
+                PaymentKind::Payment => collected += &entry.amount,
+                PaymentKind::Refund => refunded += &entry.amount,
+                PaymentKind::Failure => {}
+            }
+        }
+
+        self.amount_paid = &collected - &refunded;
+        self.payment_status = if refunded > zero {
+            if self.amount_paid > zero {
+                PaymentStatus::PartiallyRefunded
+            } else {
+                PaymentStatus::Refunded
+            }
+        } else if collected > zero && collected >= self.total_amount {
+            PaymentStatus::Paid
+        } else if collected == zero
+            && matches!(self.payments.last(), Some(e) if e.kind == PaymentKind::Failure)
+        {
+            PaymentStatus::Failed
+        } else {
+            PaymentStatus::Pending
+        };
+    }
+
     pub fn calculate_totals(&mut self) {
         self.subtotal = self.items.iter().map(|item| &item.total_price).sum();
 
-        self.total_amount = &self.subtotal + &self.shipping_cost + &self.tax_amount;
+        let discount: BigDecimal = self
+            .items
+            .iter()
+            .map(OrderItem::discount_amount)
+            .chain(self.adjustments.iter().map(|a| a.amount.clone()))
+            .sum();
+        self.discount_amount = discount.min(&self.subtotal + &self.shipping_cost);
+
+        // Lines are taxed after their own discounts. Order wide discounts
+        // (fixed amounts, free shipping) only lower what is due, and shipping
+        // is not taxed.
+        self.taxes.clear();
+        for item in &mut self.items {
+            let Some(ref tax) = item.tax else {
+                item.tax_amount = BigDecimal::from(0);
+                continue;
+            };
+
+            let taxable = item.taxable_amount();
+            item.tax_amount = tax_on(&taxable, &tax.percent, self.tax_mode);
+
+            match self
+                .taxes
+                .iter_mut()
+                .find(|t| t.name == tax.name && t.percent == tax.percent)
+            {
+                Some(breakdown) => {
+                    breakdown.taxable += taxable;
+                    breakdown.amount += &item.tax_amount;
+                }
+                None => self.taxes.push(TaxBreakdown {
+                    name: tax.name.clone(),
+                    percent: tax.percent.clone(),
+                    taxable,
+                    amount: item.tax_amount.clone(),
+                }),
+            }
+        }
+        self.tax_amount = self.items.iter().map(|i| &i.tax_amount).sum();
+
+        self.total_amount = &self.subtotal - &self.discount_amount + &self.shipping_cost;
+        if self.tax_mode == TaxMode::Exclusive {
+            self.total_amount += &self.tax_amount;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsBucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl AnalyticsBucket {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            AnalyticsBucket::Day => "day",
+            AnalyticsBucket::Week => "week",
+            AnalyticsBucket::Month => "month",
+        }
     }
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct AnalyticsFilter {
+    // Only orders in it are counted, so amounts are never mixed
+    pub currency: Currency,
+    pub date_from: Option<DateTime>,
+    pub date_to: Option<DateTime>,
+    pub bucket: AnalyticsBucket,
+    pub top_limit: u32,
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct OrderFilter {
     pub status: Option<OrderStatus>,
-    // pub payment_status: Option<PaymentStatus>,
+    pub payment_status: Option<PaymentStatus>,
     pub customer_email: Option<String>,
     pub search: Option<String>,
     pub date_from: Option<DateTime>,
     pub date_to: Option<DateTime>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: PaymentKind, amount: i64) -> PaymentEntry {
+        PaymentEntry {
+            kind,
+            method: PaymentMethod::CashOnDelivery,
+            amount: BigDecimal::from(amount),
+            reference: None,
+            note: None,
+            created_by: None,
+            created_at: DateTime::now(),
+        }
+    }
+
+    fn order(total: i64) -> OrderRecord {
+        OrderRecord {
+            total_amount: BigDecimal::from(total),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        let mut order = order(1000);
+        assert!(order
+            .transition(OrderStatus::Delivered, None, None, None)
+            .is_err());
+
+        order
+            .transition(OrderStatus::Confirmed, None, None, None)
+            .unwrap();
+        order
+            .transition(OrderStatus::Processing, None, None, None)
+            .unwrap();
+        let err = order
+            .transition(OrderStatus::Cancelled, None, None, None)
+            .unwrap_err();
+        assert_eq!(err.from, "processing");
+        assert_eq!(err.to, "cancelled");
+
+        order
+            .transition(OrderStatus::Shipped, None, None, None)
+            .unwrap();
+        order
+            .transition(OrderStatus::Delivered, None, None, None)
+            .unwrap();
+        assert!(order
+            .transition(OrderStatus::Pending, None, None, None)
+            .is_err());
+        assert_eq!(order.history.len(), 4);
+    }
+
+    #[test]
+    fn test_custom_state_transitions() {
+        let mut order = order(1000);
+        let no_answer = Some("no_answer".to_string());
+
+        order
+            .transition(OrderStatus::Pending, no_answer.clone(), None, None)
+            .unwrap();
+        assert_eq!(order.state_name(), "no_answer");
+        assert_eq!(order.history[0].custom_state, no_answer);
+
+        let err = order
+            .transition(OrderStatus::Pending, no_answer, None, None)
+            .unwrap_err();
+        assert_eq!(err.from, "no_answer");
+
+        order
+            .transition(OrderStatus::Confirmed, None, None, None)
+            .unwrap();
+        assert_eq!(order.custom_state, None);
+        assert_eq!(order.state_name(), "confirmed");
+    }
+
+    #[test]
+    fn test_payment_status_from_ledger() {
+        let mut order = order(1000);
+        assert_eq!(order.payment_status, PaymentStatus::Pending);
+
+        order
+            .add_payment_entry(entry(PaymentKind::Payment, 400))
+            .unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::Pending);
+
+        order
+            .add_payment_entry(entry(PaymentKind::Payment, 600))
+            .unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::Paid);
+        assert_eq!(order.amount_paid, BigDecimal::from(1000));
+
+        order
+            .add_payment_entry(entry(PaymentKind::Refund, 250))
+            .unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::PartiallyRefunded);
+        assert_eq!(order.amount_paid, BigDecimal::from(750));
+
+        order
+            .add_payment_entry(entry(PaymentKind::Refund, 750))
+            .unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::Refunded);
+        assert_eq!(order.amount_paid, BigDecimal::from(0));
+    }
+
+    #[test]
+    fn test_totals_with_adjustments() {
+        let adjustment = |amount: i64| Adjustment {
+            discount_id: ObjectId::new(),
+            code: None,
+            title: "Promo".into(),
+            amount: BigDecimal::from(amount),
+        };
+
+        let mut order = OrderRecord {
+            items: vec![OrderItem {
+                product_id: Some(ObjectId::new()),
+                variant_sku: "SKU".into(),
+                product_title: "Shirt".into(),
+                quantity: 2,
+                unit_price: BigDecimal::from(1000),
+                total_price: BigDecimal::from(2000),
+                adjustments: vec![adjustment(200)],
+                tax: None,
+                tax_amount: BigDecimal::from(0),
+            }],
+            adjustments: vec![adjustment(300)],
+            shipping_cost: BigDecimal::from(400),
+            ..Default::default()
+        };
+        order.calculate_totals();
+        assert_eq!(order.subtotal, BigDecimal::from(2000));
+        assert_eq!(order.discount_amount, BigDecimal::from(500));
+        assert_eq!(order.total_amount, BigDecimal::from(1900));
+
+        order.adjustments.push(adjustment(5000));
+        order.calculate_totals();
+        assert_eq!(order.total_amount, BigDecimal::from(0));
+    }
+
+    #[test]
+    fn test_totals_with_tax() {
+        let item = |sku: &str, total: i64, percent: Option<i64>| OrderItem {
+            product_id: Some(ObjectId::new()),
+            variant_sku: sku.into(),
+            product_title: "Shirt".into(),
+            quantity: 1,
+            unit_price: BigDecimal::from(total),
+            total_price: BigDecimal::from(total),
+            adjustments: Vec::new(),
+            tax: percent.map(|p| AppliedTax {
+                class: "standard".into(),
+                name: "TVA".into(),
+                percent: BigDecimal::from(p),
+            }),
+            tax_amount: BigDecimal::from(0),
+        };
+
+        let mut order = OrderRecord {
+            items: vec![
+                item("A", 1000, Some(19)),
+                item("B", 500, Some(19)),
+                item("C", 300, Some(9)),
+                item("D", 200, None),
+            ],
+            shipping_cost: BigDecimal::from(400),
+            ..Default::default()
+        };
+        order.calculate_totals();
+        assert_eq!(order.items[0].tax_amount, BigDecimal::from(190));
+        assert_eq!(order.tax_amount, BigDecimal::from(312));
+        assert_eq!(order.taxes.len(), 2);
+        assert_eq!(order.taxes[0].taxable, BigDecimal::from(1500));
+        assert_eq!(order.taxes[0].amount, BigDecimal::from(285));
+        assert_eq!(order.total_amount, BigDecimal::from(2712));
+
+        order.tax_mode = TaxMode::Inclusive;
+        order.items.truncate(1);
+        order.items[0].total_price = BigDecimal::from(1190);
+        order.calculate_totals();
+        assert_eq!(order.tax_amount, BigDecimal::from(190));
+        assert_eq!(order.total_amount, BigDecimal::from(1590));
+    }
+
+    #[test]
+    fn test_payment_failure_and_validation() {
+        let mut order = order(1000);
+
+        order
+            .add_payment_entry(entry(PaymentKind::Failure, 0))
+            .unwrap();
+        assert_eq!(order.payment_status, PaymentStatus::Failed);
+
+        assert!(order
+            .add_payment_entry(entry(PaymentKind::Payment, 0))
+            .is_err());
+        assert!(order
+            .add_payment_entry(entry(PaymentKind::Refund, 10))
+            .is_err());
+        assert!(order
+            .add_payment_entry(entry(PaymentKind::Payment, -5))
+            .is_err());
+        assert_eq!(order.payments.len(), 1);
+    }
+}
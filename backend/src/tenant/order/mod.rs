@@ -1,5 +1,7 @@
 pub mod api;
+pub mod documents;
 pub mod domain;
 pub mod repo;
 pub mod routes;
 pub mod service;
+pub mod table;
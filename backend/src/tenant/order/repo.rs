@@ -1,30 +1,60 @@
 use async_trait::async_trait;
-use bigdecimal::BigDecimal;
-use bigdecimal::FromPrimitive;
-use bson::{doc, oid::ObjectId, to_bson, DateTime};
-use chrono::Utc;
+use bigdecimal::{BigDecimal, RoundingMode};
+use bson::{doc, oid::ObjectId, to_bson, DateTime, Document};
+use futures::stream::BoxStream;
 use futures::stream::TryStreamExt;
 use futures::StreamExt;
-use mongodb::{options::FindOptions, Client, Collection};
+use mongodb::options::{FindOptions, ReturnDocument};
+use mongodb::{Client, ClientSession, Collection};
+use serde::Deserialize;
 
-use super::api::OrderAnalytics;
+use super::api::{AnalyticsPoint, OrderAnalytics, StatusCount, StoreRevenue, TopSku};
 use super::domain::*;
+use crate::tenant::product::domain::ProductRecord;
 use crate::utils::error::{ApiError, ApiResult};
 
 #[async_trait]
 pub trait OrderRepo: Send + Sync {
     async fn create(&self, business_id: ObjectId, order: OrderRecord) -> ApiResult<OrderRecord>;
+    async fn create_reserving_stock(
+        &self,
+        business_id: ObjectId,
+        order: OrderRecord,
+    ) -> ApiResult<OrderRecord>;
     async fn find_by_id(
         &self,
         business_id: ObjectId,
         id: ObjectId,
     ) -> ApiResult<Option<OrderRecord>>;
+    async fn find_by_ids(
+        &self,
+        business_id: ObjectId,
+        ids: &[ObjectId],
+    ) -> ApiResult<Vec<OrderRecord>>;
+    /// Saves the order, only if it was not saved since it was read.
     async fn update(
         &self,
         business_id: ObjectId,
         id: ObjectId,
         order: OrderRecord,
     ) -> ApiResult<OrderRecord>;
+    /// Gives the order the next invoice number of the business, unless it
+    /// already has one.
+    async fn assign_invoice_number(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<OrderRecord>;
+    /// Saves the order and gives its stock back, only if it was not saved
+    /// since it was read and is still in `previous`, so the stock can't be
+    /// released twice.
+    async fn update_releasing_stock(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        previous: &OrderStatus,
+        order: OrderRecord,
+    ) -> ApiResult<OrderRecord>;
     async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
     async fn list(
         &self,
@@ -33,14 +63,13 @@ pub trait OrderRepo: Send + Sync {
         page: u32,
         limit: u32,
     ) -> ApiResult<(Vec<OrderRecord>, u64)>;
-    async fn bulk_update_status(
+    /// All orders matching the filter, newest first, read as they are
+    /// consumed.
+    async fn stream(
         &self,
         business_id: ObjectId,
-        order_ids: Vec<ObjectId>,
-        status: OrderStatus,
-        note: Option<String>,
-        created_by: Option<String>,
-    ) -> ApiResult<u64>;
+        filter: OrderFilter,
+    ) -> ApiResult<BoxStream<'static, ApiResult<OrderRecord>>>;
     async fn get_customer_orders(
         &self,
         business_id: ObjectId,
@@ -51,8 +80,7 @@ pub trait OrderRepo: Send + Sync {
     async fn get_analytics(
         &self,
         business_id: ObjectId,
-        date_from: Option<DateTime>,
-        date_to: Option<DateTime>,
+        filter: AnalyticsFilter,
     ) -> ApiResult<OrderAnalytics>;
 }
 
@@ -71,6 +99,141 @@ impl MongoOrderRepo {
             .collection("orders")
     }
 
+    // Named sequences of the business, one document each
+    fn get_counters_collection(&self, business_id: ObjectId) -> Collection<Document> {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+            .collection("counters")
+    }
+
+    fn get_products_collection(&self, business_id: ObjectId) -> Collection<ProductRecord> {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+            .collection("products")
+    }
+
+    async fn start_transaction(&self) -> ApiResult<ClientSession> {
+        let mut session = self
+            .client
+            .start_session()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to start session: {}", e)))?;
+
+        session
+            .start_transaction()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to start transaction: {}", e)))?;
+
+        Ok(session)
+    }
+
+    async fn finish_transaction(
+        &self,
+        mut session: ClientSession,
+        result: ApiResult<()>,
+    ) -> ApiResult<()> {
+        match result {
+            Ok(()) => session
+                .commit_transaction()
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to commit transaction: {}", e))),
+            Err(e) => {
+                let _ = session.abort_transaction().await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn reserve_stocks(
+        &self,
+        session: &mut ClientSession,
+        business_id: ObjectId,
+        items: &[OrderItem],
+    ) -> ApiResult<()> {
+        let products = self.get_products_collection(business_id);
+
+        for item in items {
+            let Some(product_id) = item.product_id else {
+                continue;
+            };
+            let result = products
+                .update_one(
+                    doc! {
+                        "_id": product_id,
+                        "variants": {
+                            "$elemMatch": {
+                                "sku": &item.variant_sku,
+                                "stocks": { "$gte": item.quantity as i64 }
+                            }
+                        }
+                    },
+                    doc! {
+                        "$inc": { "variants.$.stocks": -(item.quantity as i64) },
+                        "$set": { "updated_at": DateTime::now() }
+                    },
+                )
+                .session(&mut *session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to reserve stock: {}", e)))?;
+
+            if result.matched_count == 0 {
+                return Err(ApiError::forbidden(
+                    "order",
+                    format!("Insufficient stock for variant '{}'", item.variant_sku),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn release_stocks(
+        &self,
+        session: &mut ClientSession,
+        business_id: ObjectId,
+        items: &[OrderItem],
+    ) -> ApiResult<()> {
+        let products = self.get_products_collection(business_id);
+
+        // Products or variants removed since the order was placed are skipped,
+        // as are items that were never linked to one
+        for item in items {
+            let Some(product_id) = item.product_id else {
+                continue;
+            };
+            products
+                .update_one(
+                    doc! { "_id": product_id, "variants.sku": &item.variant_sku },
+                    doc! {
+                        "$inc": { "variants.$.stocks": item.quantity as i64 },
+                        "$set": { "updated_at": DateTime::now() }
+                    },
+                )
+                .session(&mut *session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to release stock: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    // Matches the order only while it is still at `version`, orders saved
+    // before versions were kept having none
+    fn version_filter(id: ObjectId, version: u32) -> Document {
+        if version == 0 {
+            doc! { "_id": id, "version": { "$in": [0, null] } }
+        } else {
+            doc! { "_id": id, "version": version as i64 }
+        }
+    }
+
+    fn changed_meanwhile() -> ApiError {
+        ApiError::conflict(
+            "order",
+            "The order was changed meanwhile, reload it and try again",
+        )
+    }
+
     fn build_filter_query(&self, filter: &OrderFilter) -> bson::Document {
         let mut query = doc! {};
 
@@ -78,9 +241,9 @@ impl MongoOrderRepo {
             query.insert("status", to_bson(status).unwrap());
         }
 
-        // if let Some(ref payment_status) = filter.payment_status {
-        //     query.insert("payment_status", to_bson(payment_status).unwrap());
-        // }
+        if let Some(ref payment_status) = filter.payment_status {
+            query.insert("payment_status", to_bson(payment_status).unwrap());
+        }
 
         if let Some(ref customer_email) = filter.customer_email {
             query.insert("customer_email", customer_email);
@@ -106,7 +269,16 @@ impl MongoOrderRepo {
             );
         }
 
-        // TODO: add date from to
+        let mut created_at = doc! {};
+        if let Some(date_from) = filter.date_from {
+            created_at.insert("$gte", date_from);
+        }
+        if let Some(date_to) = filter.date_to {
+            created_at.insert("$lte", date_to);
+        }
+        if !created_at.is_empty() {
+            query.insert("created_at", created_at);
+        }
 
         query
     }
@@ -128,6 +300,33 @@ impl OrderRepo for MongoOrderRepo {
         Ok(order)
     }
 
+    async fn create_reserving_stock(
+        &self,
+        business_id: ObjectId,
+        order: OrderRecord,
+    ) -> ApiResult<OrderRecord> {
+        let collection = self.get_collection(business_id);
+        let mut session = self.start_transaction().await?;
+
+        let result = async {
+            self.reserve_stocks(&mut session, business_id, &order.items)
+                .await?;
+
+            collection
+                .insert_one(&order)
+                .session(&mut session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to create order: {}", e)))?;
+
+            Ok(())
+        }
+        .await;
+
+        self.finish_transaction(session, result).await?;
+
+        Ok(order)
+    }
+
     async fn find_by_id(
         &self,
         business_id: ObjectId,
@@ -143,6 +342,22 @@ impl OrderRepo for MongoOrderRepo {
         Ok(order)
     }
 
+    async fn find_by_ids(
+        &self,
+        business_id: ObjectId,
+        ids: &[ObjectId],
+    ) -> ApiResult<Vec<OrderRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! { "_id": { "$in": ids } })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
     async fn update(
         &self,
         business_id: ObjectId,
@@ -151,20 +366,128 @@ impl OrderRepo for MongoOrderRepo {
     ) -> ApiResult<OrderRecord> {
         let collection = self.get_collection(business_id);
 
+        let filter = Self::version_filter(id, order.version);
+        order.version += 1;
         order.updated_at = DateTime::now();
 
         let result = collection
-            .replace_one(doc! { "_id": id }, &order)
+            .replace_one(filter, &order)
             .await
             .map_err(|e| ApiError::internal(format!("Failed to update order: {}", e)))?;
 
+        // Gone, or saved by another request since it was read
         if result.matched_count == 0 {
-            return Err(ApiError::not_found("order", "order not found"));
+            return Err(Self::changed_meanwhile());
+        }
+
+        Ok(order)
+    }
+
+    async fn update_releasing_stock(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        previous: &OrderStatus,
+        mut order: OrderRecord,
+    ) -> ApiResult<OrderRecord> {
+        let collection = self.get_collection(business_id);
+        let mut session = self.start_transaction().await?;
+
+        let mut filter = Self::version_filter(id, order.version);
+        filter.insert("status", to_bson(previous).unwrap());
+        order.stock_reserved = false;
+        order.version += 1;
+        order.updated_at = DateTime::now();
+
+        let result = async {
+            let result = collection
+                .replace_one(filter, &order)
+                .session(&mut session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to update order: {}", e)))?;
+
+            // Gone, or moved on by another request that may have released
+            // the stock
+            if result.matched_count == 0 {
+                return Err(Self::changed_meanwhile());
+            }
+
+            self.release_stocks(&mut session, business_id, &order.items)
+                .await
         }
+        .await;
+
+        self.finish_transaction(session, result).await?;
 
         Ok(order)
     }
 
+    async fn assign_invoice_number(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<OrderRecord> {
+        let collection = self.get_collection(business_id);
+        let counters = self.get_counters_collection(business_id);
+        let mut session = self.start_transaction().await?;
+
+        // The counter only moves when the order is numbered in the same
+        // transaction, so numbers are never skipped
+        let mut numbered = None;
+        let result = async {
+            let mut order = collection
+                .find_one(doc! { "_id": id })
+                .session(&mut session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+                .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
+            if order.invoice_number.is_some() {
+                numbered = Some(order);
+                return Ok(());
+            }
+
+            let counter = counters
+                .find_one_and_update(doc! { "_id": "invoice" }, doc! { "$inc": { "seq": 1 } })
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .session(&mut session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to number invoice: {}", e)))?
+                .ok_or_else(|| ApiError::internal("Invoice counter is missing"))?;
+            let number = counter
+                .get_i32("seq")
+                .map(i64::from)
+                .or_else(|_| counter.get_i64("seq"))
+                .map_err(|e| ApiError::internal(format!("Invalid invoice counter: {}", e)))?;
+
+            order.invoice_number = Some(number as u32);
+            order.invoiced_at = Some(DateTime::now());
+            order.version += 1;
+            collection
+                .update_one(
+                    doc! { "_id": id },
+                    doc! {
+                        "$set": {
+                            "invoice_number": number,
+                            "invoiced_at": order.invoiced_at,
+                        },
+                        "$inc": { "version": 1 },
+                    },
+                )
+                .session(&mut session)
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to update order: {}", e)))?;
+
+            numbered = Some(order);
+            Ok(())
+        }
+        .await;
+
+        self.finish_transaction(session, result).await?;
+
+        numbered.ok_or_else(|| ApiError::internal("Order was not numbered"))
+    }
+
     async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
         let collection = self.get_collection(business_id);
 
@@ -221,29 +544,23 @@ impl OrderRepo for MongoOrderRepo {
         Ok((orders, total))
     }
 
-    async fn bulk_update_status(
+    async fn stream(
         &self,
         business_id: ObjectId,
-        order_ids: Vec<ObjectId>,
-        status: OrderStatus,
-        note: Option<String>,
-        created_by: Option<String>,
-    ) -> ApiResult<u64> {
-        let collection = self.get_collection(business_id);
-        let result = collection
-            .update_many(
-                doc! { "_id": order_ids },
-                doc! {
-                    "$set": {
-                        "status": to_bson(&status).unwrap(),
-                        "updated_at": DateTime::now()
-                    }
-                },
-            )
+        filter: OrderFilter,
+    ) -> ApiResult<BoxStream<'static, ApiResult<OrderRecord>>> {
+        let query = self.build_filter_query(&filter);
+
+        let cursor = self
+            .get_collection(business_id)
+            .find(query)
+            .sort(doc! { "created_at": -1 })
             .await
-            .map_err(|e| ApiError::internal(format!("Failed to update orders: {}", e)))?;
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
 
-        Ok(result.matched_count)
+        Ok(cursor
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+            .boxed())
     }
 
     async fn get_customer_orders(
@@ -259,92 +576,239 @@ impl OrderRepo for MongoOrderRepo {
     async fn get_analytics(
         &self,
         business_id: ObjectId,
-        date_from: Option<DateTime>,
-        date_to: Option<DateTime>,
+        filter: AnalyticsFilter,
     ) -> ApiResult<OrderAnalytics> {
         let collection = self.get_collection(business_id);
 
-        // ----- Build match stage -----
-        let mut match_stage = doc! {};
-        if let Some(from) = date_from {
-            match_stage.insert("created_at", doc! { "$gte": from });
+        let mut created_at = doc! {};
+        if let Some(from) = filter.date_from {
+            created_at.insert("$gte", from);
+        }
+        if let Some(to) = filter.date_to {
+            created_at.insert("$lte", to);
         }
-        if let Some(to) = date_to {
-            match_stage
-                .entry("created_at".to_string())
-                .or_insert(doc! {}.into())
-                .as_document_mut()
-                .unwrap()
-                .insert("$lte", to);
+        let mut match_stage = doc! { "currency": filter.currency.as_str() };
+        if !created_at.is_empty() {
+            match_stage.insert("created_at", created_at);
         }
 
-        // ----- Aggregation pipeline -----
+        // Amounts are stored as decimal strings, so every sum goes through
+        // `$toDecimal` and comes back out through `$toString`.
+        let earning = doc! { "$not": [{ "$in": ["$status", ["cancelled", "refunded"]] }] };
+        let earning_total = doc! {
+            "$cond": [earning.clone(), { "$toDecimal": "$total_amount" }, { "$toDecimal": "0" }]
+        };
+
         let pipeline = vec![
             doc! { "$match": match_stage },
-            doc! { "$group": {
-                "_id": null,
-                "total_orders": { "$sum": 1 },
-                "total_revenue": { "$sum": "$total_price" },
-                "pending_orders": {
-                    "$sum": {
-                        "$cond": [{ "$eq": ["$status", "pending"] }, 1, 0]
-                    }
-                },
-                "completed_orders": {
-                    "$sum": {
-                        "$cond": [{ "$eq": ["$status", "completed"] }, 1, 0]
-                    }
-                },
-                "cancelled_orders": {
-                    "$sum": {
-                        "$cond": [{ "$eq": ["$status", "cancelled"] }, 1, 0]
-                    }
-                },
-                "average_order_value": { "$avg": "$total_price" },
+            doc! { "$facet": {
+                "summary": [
+                    { "$group": {
+                        "_id": null,
+                        "total_orders": { "$sum": 1 },
+                        "earning_orders": { "$sum": { "$cond": [earning.clone(), 1, 0] } },
+                        "revenue": { "$sum": earning_total.clone() },
+                        "collected": {
+                            "$sum": { "$toDecimal": { "$ifNull": ["$amount_paid", "0"] } }
+                        },
+                    }},
+                    { "$project": {
+                        "_id": 0,
+                        "total_orders": 1,
+                        "earning_orders": 1,
+                        "revenue": { "$toString": "$revenue" },
+                        "collected": { "$toString": "$collected" },
+                    }},
+                ],
+                "statuses": [
+                    { "$group": { "_id": "$status", "count": { "$sum": 1 } } },
+                    { "$project": { "_id": 0, "status": "$_id", "count": 1 } },
+                ],
+                "series": [
+                    { "$group": {
+                        "_id": {
+                            "$dateTrunc": {
+                                "date": "$created_at",
+                                "unit": filter.bucket.unit(),
+                                "startOfWeek": "monday",
+                            }
+                        },
+                        "orders": { "$sum": 1 },
+                        "revenue": { "$sum": earning_total.clone() },
+                    }},
+                    { "$sort": { "_id": 1 } },
+                    { "$project": {
+                        "_id": 0,
+                        "period_start": "$_id",
+                        "orders": 1,
+                        "revenue": { "$toString": "$revenue" },
+                    }},
+                ],
+                "top_skus": [
+                    { "$match": { "$expr": earning.clone() } },
+                    { "$unwind": "$items" },
+                    { "$group": {
+                        "_id": { "product_id": "$items.product_id", "sku": "$items.variant_sku" },
+                        "product_title": { "$last": "$items.product_title" },
+                        "quantity": { "$sum": "$items.quantity" },
+                        "revenue": { "$sum": { "$toDecimal": "$items.total_price" } },
+                    }},
+                    { "$sort": { "quantity": -1, "revenue": -1 } },
+                    { "$limit": filter.top_limit as i64 },
+                    { "$project": {
+                        "_id": 0,
+                        "product_id": "$_id.product_id",
+                        "variant_sku": "$_id.sku",
+                        "product_title": 1,
+                        "quantity": 1,
+                        "revenue": { "$toString": "$revenue" },
+                    }},
+                ],
+                "stores": [
+                    { "$match": { "$expr": earning.clone() } },
+                    { "$set": { "store_id": { "$first": "$history.created_by.Store" } } },
+                    { "$match": { "store_id": { "$ne": null } } },
+                    { "$group": {
+                        "_id": "$store_id",
+                        "orders": { "$sum": 1 },
+                        "revenue": { "$sum": { "$toDecimal": "$total_amount" } },
+                    }},
+                    { "$sort": { "revenue": -1 } },
+                    { "$project": {
+                        "_id": 0,
+                        "store_id": "$_id",
+                        "orders": 1,
+                        "revenue": { "$toString": "$revenue" },
+                    }},
+                ],
             }},
         ];
 
-        let mut cursor = collection
+        let result = collection
             .aggregate(pipeline)
             .await
-            .map_err(|e| ApiError::internal(format!("Aggregation failed: {}", e)))?;
-
-        if let Some(result) = cursor.next().await.transpose().map_err(|e| {
-            ApiError::internal(format!("Failed to retrieve aggregation result: {}", e))
-        })? {
-            let total_orders = result.get_i32("total_orders").unwrap_or(0) as u64;
-            let total_revenue = result
-                .get_f64("total_revenue")
-                .map(BigDecimal::from_f64)
-                .map(|v| v.unwrap_or(BigDecimal::from(0)))
-                .unwrap_or(BigDecimal::from(0));
-            let pending_orders = result.get_i32("pending_orders").unwrap_or(0) as u64;
-            let completed_orders = result.get_i32("completed_orders").unwrap_or(0) as u64;
-            let cancelled_orders = result.get_i32("cancelled_orders").unwrap_or(0) as u64;
-            let average_order_value = result
-                .get_f64("average_order_value")
-                .map(BigDecimal::from_f64)
-                .map(|v| v.unwrap_or(BigDecimal::from(0)))
-                .unwrap_or(BigDecimal::from(0));
-
-            return Ok(OrderAnalytics {
-                total_orders,
-                total_revenue,
-                pending_orders,
-                completed_orders,
-                cancelled_orders,
-                average_order_value,
-            });
-        }
+            .map_err(|e| ApiError::internal(format!("Aggregation failed: {}", e)))?
+            .next()
+            .await
+            .transpose()
+            .map_err(|e| {
+                ApiError::internal(format!("Failed to retrieve aggregation result: {}", e))
+            })?
+            .ok_or_else(|| ApiError::internal("Aggregation returned no result"))?;
+
+        let facets: AnalyticsFacets = bson::from_document(result)
+            .map_err(|e| ApiError::internal(format!("Failed to decode analytics: {}", e)))?;
+
+        let summary = facets.summary.into_iter().next().unwrap_or_default();
+        let count_of = |status: OrderStatus| {
+            facets
+                .statuses
+                .iter()
+                .find(|s| s.status == status)
+                .map_or(0, |s| s.count)
+        };
+
+        let average_order_value = if summary.earning_orders > 0 {
+            (&summary.revenue / BigDecimal::from(summary.earning_orders))
+                .with_scale_round(2, RoundingMode::HalfUp)
+        } else {
+            BigDecimal::from(0)
+        };
 
-        // ----- Default (no results) -----
         Ok(OrderAnalytics {
-            total_orders: 0,
-            total_revenue: BigDecimal::from(0),
-            pending_orders: 0,
-            completed_orders: 0,
-            cancelled_orders: 0,
-            average_order_value: BigDecimal::from(0),
+            currency: filter.currency,
+            total_orders: summary.total_orders,
+            pending_orders: count_of(OrderStatus::Pending),
+            completed_orders: count_of(OrderStatus::Delivered),
+            cancelled_orders: count_of(OrderStatus::Cancelled),
+            total_revenue: summary.revenue,
+            collected_revenue: summary.collected,
+            average_order_value,
+            status_counts: facets
+                .statuses
+                .into_iter()
+                .map(|s| StatusCount {
+                    status: s.status.into(),
+                    count: s.count,
+                })
+                .collect(),
+            bucket: filter.bucket,
+            series: facets
+                .series
+                .into_iter()
+                .map(|p| AnalyticsPoint {
+                    period_start: p.period_start.to_chrono(),
+                    orders: p.orders,
+                    revenue: p.revenue,
+                })
+                .collect(),
+            top_skus: facets
+                .top_skus
+                .into_iter()
+                .map(|t| TopSku {
+                    product_id: t.product_id.map(Into::into),
+                    variant_sku: t.variant_sku,
+                    product_title: t.product_title,
+                    quantity: t.quantity,
+                    revenue: t.revenue,
+                })
+                .collect(),
+            revenue_per_store: facets
+                .stores
+                .into_iter()
+                .map(|s| StoreRevenue {
+                    store_id: s.store_id.into(),
+                    orders: s.orders,
+                    revenue: s.revenue,
+                })
+                .collect(),
         })
     }
 }
+
+#[derive(Deserialize)]
+struct AnalyticsFacets {
+    summary: Vec<SummaryRow>,
+    statuses: Vec<StatusRow>,
+    series: Vec<SeriesRow>,
+    top_skus: Vec<TopSkuRow>,
+    stores: Vec<StoreRow>,
+}
+
+#[derive(Deserialize, Default)]
+struct SummaryRow {
+    total_orders: u64,
+    earning_orders: u64,
+    revenue: BigDecimal,
+    collected: BigDecimal,
+}
+
+#[derive(Deserialize)]
+struct StatusRow {
+    status: OrderStatus,
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct SeriesRow {
+    period_start: DateTime,
+    orders: u64,
+    revenue: BigDecimal,
+}
+
+#[derive(Deserialize)]
+struct TopSkuRow {
+    #[serde(default)]
+    product_id: Option<ObjectId>,
+    variant_sku: String,
+    product_title: String,
+    quantity: u64,
+    revenue: BigDecimal,
+}
+
+#[derive(Deserialize)]
+struct StoreRow {
+    store_id: ObjectId,
+    orders: u64,
+    revenue: BigDecimal,
+}
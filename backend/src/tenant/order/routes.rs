@@ -1,20 +1,24 @@
-use axum::extract::{Path, Query, State};
+use axum::body::Body;
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use chrono::Utc;
 use macros::routes;
 
-use super::api::*;
 use super::super::store::extractors::Store;
+use super::api::*;
 use crate::extractors::cookies::FromCookies;
 use crate::extractors::json::Json;
 use crate::platform::business::api::BusinessSession;
 use crate::types::id::Id;
-use crate::utils::error::ApiResult;
+use crate::utils::error::{ApiError, ApiResult};
 use crate::AppState;
 
 pub struct OrderRoutes;
 
 #[routes(prefix = "/api/v1/orders", state = AppState)]
 impl OrderRoutes {
-    #[route(method=post, path="/create", res=OrderDto)]
+    #[route(method=post, path="/create", res=OrderDto, perm="orders:write")]
     async fn create_order(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -22,12 +26,19 @@ impl OrderRoutes {
     ) -> ApiResult<Json<OrderDto>> {
         state
             .order_service
-            .create_order(&state.product_service, business, create_req)
+            .create_order(
+                &state.business_service,
+                &state.product_service,
+                &state.discount_service,
+                &state.tax_service,
+                business,
+                create_req,
+            )
             .await
             .map(Json)
     }
 
-    #[route(method=get, path="/{order_id}", res=OrderDto)]
+    #[route(method=get, path="/{order_id}", res=OrderDto, perm="orders:read")]
     async fn get_order(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -40,7 +51,7 @@ impl OrderRoutes {
             .map(Json)
     }
 
-    #[route(method=get, path="/list", res=OrderListResponse)]
+    #[route(method=get, path="/list", res=OrderListResponse, perm="orders:read")]
     async fn list_orders(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -53,7 +64,7 @@ impl OrderRoutes {
             .map(Json)
     }
 
-    #[route(method=patch, path="/{order_id}", res=OrderDto)]
+    #[route(method=patch, path="/{order_id}", res=OrderDto, perm="orders:write")]
     async fn update_order(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -62,12 +73,12 @@ impl OrderRoutes {
     ) -> ApiResult<Json<OrderDto>> {
         state
             .order_service
-            .update_order(business, order_id, update_req)
+            .update_order(&state.business_service, business, order_id, update_req)
             .await
             .map(Json)
     }
 
-    #[route(method=patch, path="/{order_id}/status", res=OrderDto)]
+    #[route(method=patch, path="/{order_id}/status", res=OrderDto, perm="orders:write")]
     async fn update_order_status(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -76,12 +87,26 @@ impl OrderRoutes {
     ) -> ApiResult<Json<OrderDto>> {
         state
             .order_service
-            .update_order_status(business, order_id, status_update)
+            .update_order_status(&state.business_service, business, order_id, status_update)
             .await
             .map(Json)
     }
 
-    #[route(method=post, path="/bulk/status", res=BulkUpdateResponse)]
+    #[route(method=post, path="/{order_id}/payments", res=OrderDto, perm="orders:write")]
+    async fn add_payment(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] order_id: Id,
+        #[json] payment: PaymentEntryCreate,
+    ) -> ApiResult<Json<OrderDto>> {
+        state
+            .order_service
+            .add_payment(business, order_id, payment)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/bulk/status", res=BulkUpdateResponse, perm="orders:write")]
     async fn bulk_update_order_status(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -89,12 +114,126 @@ impl OrderRoutes {
     ) -> ApiResult<Json<BulkUpdateResponse>> {
         state
             .order_service
-            .bulk_update_order_status(business, bulk_update)
+            .bulk_update_order_status(&state.business_service, business, bulk_update)
             .await
             .map(Json)
     }
 
-    #[route(method=get, path="/analytics", res=OrderAnalytics)]
+    #[route(method=post, path="/{order_id}/invoice", res=OrderDto, perm="orders:write")]
+    async fn number_invoice(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] order_id: Id,
+    ) -> ApiResult<Json<OrderDto>> {
+        state
+            .order_service
+            .number_invoice(business, order_id)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=get, path="/{order_id}/invoice", perm="orders:read")]
+    async fn get_invoice(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] order_id: Id,
+        #[query] query: OrderDocumentQuery,
+    ) -> ApiResult<impl IntoResponse> {
+        let request = BulkOrderDocuments {
+            order_ids: vec![order_id],
+            kind: OrderDocumentKind::Invoice,
+            store_id: query.store_id,
+        };
+        render_documents(&state, business, request).await
+    }
+
+    #[route(method=get, path="/{order_id}/packing-slip", perm="orders:read")]
+    async fn get_packing_slip(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] order_id: Id,
+        #[query] query: OrderDocumentQuery,
+    ) -> ApiResult<impl IntoResponse> {
+        let request = BulkOrderDocuments {
+            order_ids: vec![order_id],
+            kind: OrderDocumentKind::PackingSlip,
+            store_id: query.store_id,
+        };
+        render_documents(&state, business, request).await
+    }
+
+    #[route(method=post, path="/bulk/documents", perm="orders:read")]
+    async fn bulk_documents(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[json] request: BulkOrderDocuments,
+    ) -> ApiResult<impl IntoResponse> {
+        render_documents(&state, business, request).await
+    }
+
+    #[route(method=get, path="/export", perm="orders:read")]
+    async fn export_orders(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: OrderExportQuery,
+    ) -> ApiResult<impl IntoResponse> {
+        let (format, rows) = state.order_service.export_orders(business, query).await?;
+        let file_name = format!(
+            "orders-{}.{}",
+            Utc::now().format("%Y-%m-%d"),
+            format.extension()
+        );
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, format.content_type().to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", file_name),
+                ),
+            ],
+            Body::from_stream(rows),
+        ))
+    }
+
+    #[route(method=post, path="/import", res=OrderImportReport, perm="orders:write")]
+    async fn import_orders(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: OrderImportQuery,
+        mut multipart: Multipart,
+    ) -> ApiResult<Json<OrderImportReport>> {
+        let mut file = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?
+        {
+            if field.name() == Some("file") {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?;
+                file = Some(bytes.to_vec());
+            }
+        }
+        let bytes = file.ok_or(ApiError::missing_field("file"))?;
+
+        state
+            .order_service
+            .import_orders(
+                &state.business_service,
+                &state.product_service,
+                &state.tax_service,
+                business,
+                bytes,
+                query,
+            )
+            .await
+            .map(Json)
+    }
+
+    #[route(method=get, path="/analytics", res=OrderAnalytics, perm="orders:read")]
     async fn get_analytics(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -102,12 +241,40 @@ impl OrderRoutes {
     ) -> ApiResult<Json<OrderAnalytics>> {
         state
             .order_service
-            .get_analytics(business, query.date_from, query.date_to)
+            .get_analytics(&state.business_service, business, query)
             .await
             .map(Json)
     }
 }
 
+async fn render_documents(
+    state: &AppState,
+    business: BusinessSession,
+    request: BulkOrderDocuments,
+) -> ApiResult<impl IntoResponse + use<>> {
+    let (bytes, file_name) = state
+        .order_service
+        .render_documents(
+            &state.business_service,
+            &state.store_service,
+            &state.file_service,
+            business,
+            request,
+        )
+        .await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{}\"", file_name),
+            ),
+        ],
+        bytes,
+    ))
+}
+
 pub struct PubOrderRoutes;
 
 #[routes(prefix = "/api/v1/orders", state = AppState)]
@@ -120,12 +287,15 @@ impl PubOrderRoutes {
     ) -> ApiResult<Json<()>> {
         state
             .order_service
-            .pub_create_order(&state.product_service,
-                store_key.business_id,
-                store_key.store_id,
-                create_req
+            .pub_create_order(
+                &state.product_service,
+                &state.shipping_service,
+                &state.discount_service,
+                &state.tax_service,
+                &store_key,
+                create_req,
             )
             .await
             .map(Json)
     }
-}
\ No newline at end of file
+}
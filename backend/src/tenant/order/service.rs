@@ -1,16 +1,58 @@
+use std::collections::HashSet;
+
 use bigdecimal::BigDecimal;
-use bson::DateTime;
-use chrono::Utc;
+use bson::{oid::ObjectId, DateTime};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use tracing::error;
 
 use super::api::*;
+use super::documents::{self, Branding};
 use super::domain::*;
 use super::repo::OrderRepo;
+use super::table::{self, ImportedOrder, OrderColumn};
 use crate::platform::business::api::BusinessSession;
+use crate::platform::business::repo::BusinessRepo;
+use crate::platform::business::service::BusinessService;
+use crate::tenant::discount::domain::DiscountLine;
+use crate::tenant::discount::repo::DiscountRepo;
+use crate::tenant::discount::service::DiscountService;
+use crate::tenant::file::api::ImageQuery;
+use crate::tenant::file::domain::file_ids_in;
+use crate::tenant::file::processing::OutputFormat;
+use crate::tenant::file::repo::FileRepo;
+use crate::tenant::file::service::FileService;
 use crate::tenant::product::domain::ProductVariant;
 use crate::tenant::product::repo::ProductRepo;
 use crate::tenant::product::service::ProductService;
+use crate::tenant::shipping::repo::ShippingRepo;
+use crate::tenant::shipping::service::ShippingService;
+use crate::tenant::store::api::{StoreDto, StoreRegDto};
+use crate::tenant::store::repo::{StoreRegRepo, StoreRepo};
+use crate::tenant::store::service::StoreService;
+use crate::tenant::tax::repo::TaxRepo;
+use crate::tenant::tax::service::TaxService;
+use crate::types::currency::Currency;
 use crate::types::id::Id;
 use crate::utils::error::{ApiError, ApiResult};
+use crate::utils::spreadsheet::{self, ImportRowError, SpreadsheetFormat, Table};
+
+const MAX_PRINTED_ORDERS: usize = 100;
+const MAX_IMPORTED_ROWS: usize = 5000;
+// Logos are printed at most 140pt wide, this keeps them sharp
+const LOGO_WIDTH: u32 = 320;
+
+fn not_sold_in(sku: &str, currency: &Currency) -> ApiError {
+    ApiError::forbidden(
+        "order",
+        format!("Variant '{}' has no price in {}", sku, currency),
+    )
+}
+
+impl From<InvalidTransition> for ApiError {
+    fn from(e: InvalidTransition) -> Self {
+        ApiError::invalid_transition("order", e.from, e.to)
+    }
+}
 
 pub struct OrderService<R: OrderRepo> {
     repo: R,
@@ -21,16 +63,26 @@ impl<R: OrderRepo> OrderService<R> {
         Self { repo }
     }
 
-    pub async fn create_order<P: ProductRepo>(
+    pub async fn create_order<B: BusinessRepo, P: ProductRepo, D: DiscountRepo, T: TaxRepo>(
         &self,
+        business_service: &BusinessService<B>,
         product_service: &ProductService<P>,
+        discount_service: &DiscountService<D>,
+        tax_service: &TaxService<T>,
         business: BusinessSession,
         create_req: OrderCreate,
     ) -> ApiResult<OrderDto> {
+        let base_currency = business_service
+            .get_settings(business.business_id)
+            .await?
+            .currency;
+        let currency = create_req.currency.unwrap_or_else(|| base_currency.clone());
+        let taxes = tax_service.table(business.business_id).await?;
+
         let mut order_items = Vec::new();
+        let mut categories = Vec::new();
         let mut subtotal = BigDecimal::from(0);
 
-        // TODO: wrap inside a transaction
         for item_req in create_req.items {
             let product = product_service
                 .get_product(business.clone(), item_req.product_id)
@@ -57,17 +109,29 @@ impl<R: OrderRepo> OrderService<R> {
                 ));
             }
 
-            let unit_price = variant.price.clone();
+            let unit_price = variant
+                .price_in(&currency, &base_currency)
+                .ok_or_else(|| not_sold_in(&item_req.variant_sku, &currency))?
+                .clone();
             let total_price = &unit_price * BigDecimal::from(item_req.quantity);
             subtotal += &total_price;
 
             order_items.push(OrderItem {
+                product_id: Some(product.id.into_inner()),
                 variant_sku: item_req.variant_sku,
                 product_title: product.title.to_string(),
                 quantity: item_req.quantity,
                 unit_price,
                 total_price,
+                adjustments: Vec::new(),
+                tax: taxes.applied_tax(
+                    product.tax_class.as_ref(),
+                    &create_req.shipping_address.country,
+                    &create_req.shipping_address.state,
+                ),
+                tax_amount: BigDecimal::from(0),
             });
+            categories.push(product.category);
         }
 
         // Create order record
@@ -78,10 +142,11 @@ impl<R: OrderRepo> OrderService<R> {
             items: order_items,
             shipping_address: create_req.shipping_address,
             billing_address: create_req.billing_address,
+            delivery_method: create_req.delivery_method.unwrap_or_default(),
             subtotal,
             shipping_cost: create_req.shipping_cost,
-            tax_amount: create_req.tax_amount,
-            currency: create_req.currency.unwrap_or_else(|| "USD".to_string()),
+            tax_mode: taxes.settings.mode,
+            currency: currency.to_string(),
             notes: create_req.notes,
             ..Default::default()
         };
@@ -92,12 +157,15 @@ impl<R: OrderRepo> OrderService<R> {
             Some(Source::User(business.user_id.into())),
         );
 
-        order.calculate_totals();
-
-        self.repo
-            .create(business.business_id.into(), order)
-            .await
-            .map(Into::into)
+        self.create_discounted(
+            discount_service,
+            business.business_id,
+            create_req.discount_code.as_deref(),
+            categories,
+            order,
+        )
+        .await
+        .map(Into::into)
     }
 
     pub async fn get_order(&self, business: BusinessSession, order_id: Id) -> ApiResult<OrderDto> {
@@ -109,8 +177,9 @@ impl<R: OrderRepo> OrderService<R> {
             .map(Into::into)
     }
 
-    pub async fn update_order(
+    pub async fn update_order<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
         order_id: Id,
         update_req: OrderUpdate,
@@ -124,13 +193,24 @@ impl<R: OrderRepo> OrderService<R> {
             .await?
             .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
 
+        let mut released_from = None;
+
         // Apply updates
-        update_req.status.map(|v| {
-            order.add_history_entry(v.into(), Some("Status updated".to_string()), None);
-        });
-        // update_req
-        //     .payment_status
-        //     .map(|v| order.payment_status = v.into());
+        if let Some(v) = update_req.status.to_option() {
+            let status: OrderStatus = v.into();
+            if status != order.status {
+                released_from = order.releases_stock(&status).then(|| order.status.clone());
+                self.apply_transition(
+                    business_service,
+                    &business,
+                    &mut order,
+                    status,
+                    None,
+                    Some("Status updated".to_string()),
+                )
+                .await?;
+            }
+        }
         update_req
             .tracking_number
             .map(|v| order.tracking_number = Some(v));
@@ -142,14 +222,14 @@ impl<R: OrderRepo> OrderService<R> {
             .billing_address
             .map(|v| order.billing_address = v);
 
-        self.repo
-            .update(business_id, id, order)
+        self.save_order(business_id, id, order, released_from)
             .await
             .map(Into::into)
     }
 
-    pub async fn update_order_status(
+    pub async fn update_order_status<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
         order_id: Id,
         status_update: OrderStatusUpdate,
@@ -163,11 +243,87 @@ impl<R: OrderRepo> OrderService<R> {
             .await?
             .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
 
-        order.add_history_entry(
-            status_update.status.into(),
+        let status = status_update.status.into();
+        let released_from = order.releases_stock(&status).then(|| order.status.clone());
+
+        self.apply_transition(
+            business_service,
+            &business,
+            &mut order,
+            status,
+            status_update.custom_state,
             status_update.note,
-            Some(Source::User(business.user_id.into())),
-        );
+        )
+        .await?;
+
+        self.save_order(business_id, id, order, released_from)
+            .await
+            .map(Into::into)
+    }
+
+    async fn apply_transition<B: BusinessRepo>(
+        &self,
+        business_service: &BusinessService<B>,
+        business: &BusinessSession,
+        order: &mut OrderRecord,
+        status: OrderStatus,
+        custom_state: Option<String>,
+        note: Option<String>,
+    ) -> ApiResult<()> {
+        if let Some(ref key) = custom_state {
+            let settings = business_service.get_settings(business.business_id).await?;
+            let state = settings
+                .find_custom_order_state(key)
+                .ok_or_else(|| ApiError::not_found("custom order state", key.clone()))?;
+
+            if state.status != status {
+                return Err(ApiError::validation(
+                    "custom_state",
+                    format!(
+                        "Custom state '{}' belongs to '{}' orders",
+                        key,
+                        state.status.as_str()
+                    ),
+                ));
+            }
+        }
+
+        order
+            .transition(
+                status,
+                custom_state,
+                note,
+                Some(Source::User(business.user_id.into())),
+            )
+            .map_err(Into::into)
+    }
+
+    pub async fn add_payment(
+        &self,
+        business: BusinessSession,
+        order_id: Id,
+        payment: PaymentEntryCreate,
+    ) -> ApiResult<OrderDto> {
+        let id = order_id.into_inner();
+        let business_id = business.business_id.into_inner();
+
+        let mut order = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
+
+        order
+            .add_payment_entry(PaymentEntry {
+                kind: payment.kind.into(),
+                method: payment.method.into(),
+                amount: payment.amount,
+                reference: payment.reference,
+                note: payment.note,
+                created_by: Some(Source::User(business.user_id.into())),
+                created_at: DateTime::now(),
+            })
+            .map_err(|e| ApiError::validation("amount", e))?;
 
         self.repo
             .update(business_id, id, order)
@@ -175,6 +331,24 @@ impl<R: OrderRepo> OrderService<R> {
             .map(Into::into)
     }
 
+    /// Saves the order, giving its stock back when `released_from` holds the
+    /// status it had when read.
+    async fn save_order(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        order: OrderRecord,
+        released_from: Option<OrderStatus>,
+    ) -> ApiResult<OrderRecord> {
+        if let Some(previous) = released_from {
+            self.repo
+                .update_releasing_stock(business_id, id, &previous, order)
+                .await
+        } else {
+            self.repo.update(business_id, id, order).await
+        }
+    }
+
     pub async fn list_orders(
         &self,
         business: BusinessSession,
@@ -184,7 +358,7 @@ impl<R: OrderRepo> OrderService<R> {
             page,
             limit,
             status,
-            // payment_status,
+            payment_status,
             customer_email,
             search,
             date_from,
@@ -193,7 +367,7 @@ impl<R: OrderRepo> OrderService<R> {
 
         let filter = OrderFilter {
             status: status.map(Into::into),
-            // payment_status: payment_status.map(Into::into),
+            payment_status: payment_status.map(Into::into),
             customer_email,
             search,
             date_from: date_from.map(DateTime::from_chrono),
@@ -217,46 +391,531 @@ impl<R: OrderRepo> OrderService<R> {
         })
     }
 
-    pub async fn bulk_update_order_status(
+    pub async fn bulk_update_order_status<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
         bulk_update: BulkOrderStatusUpdate,
     ) -> ApiResult<BulkUpdateResponse> {
-        let order_ids: Vec<_> = bulk_update
-            .order_ids
-            .into_iter()
-            .map(|id| id.into_inner())
-            .collect();
+        let business_id = business.business_id.into_inner();
+        let status: OrderStatus = bulk_update.status.into();
+        let mut updated_count = 0;
+        let mut failures = Vec::new();
 
-        let updated_count = self
-            .repo
-            .bulk_update_status(
-                business.business_id.into_inner(),
-                order_ids,
-                bulk_update.status.into(),
-                bulk_update.note,
-                bulk_update.created_by,
-            )
-            .await?;
+        for order_id in bulk_update.order_ids {
+            let id = order_id.into_inner();
+            let result = async {
+                let mut order = self
+                    .repo
+                    .find_by_id(business_id, id)
+                    .await?
+                    .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
+
+                let released_from = order.releases_stock(&status).then(|| order.status.clone());
+                self.apply_transition(
+                    business_service,
+                    &business,
+                    &mut order,
+                    status.clone(),
+                    bulk_update.custom_state.clone(),
+                    bulk_update.note.clone(),
+                )
+                .await?;
+
+                self.save_order(business_id, id, order, released_from).await
+            }
+            .await;
+
+            match result {
+                Ok(_) => updated_count += 1,
+                Err(e) => {
+                    let problem = e.to_problem_details();
+                    failures.push(BulkUpdateFailure {
+                        id: order_id,
+                        reason: problem.detail.unwrap_or(problem.title),
+                    });
+                }
+            }
+        }
 
         Ok(BulkUpdateResponse {
             updated_count,
-            failed_ids: vec![], // In a real implementation, you'd track failures
+            failures,
+        })
+    }
+
+    /// Streams the orders matching the query as CSV rows, or builds an XLSX
+    /// workbook of them.
+    pub async fn export_orders(
+        &self,
+        business: BusinessSession,
+        query: OrderExportQuery,
+    ) -> ApiResult<(SpreadsheetFormat, BoxStream<'static, ApiResult<Vec<u8>>>)> {
+        let columns = table::parse_columns(query.columns.as_deref())
+            .map_err(|e| ApiError::invalid_query("columns", e))?;
+        let format = query.format.unwrap_or_default();
+
+        let filter = OrderFilter {
+            status: query.status.map(Into::into),
+            payment_status: query.payment_status.map(Into::into),
+            customer_email: query.customer_email,
+            search: query.search,
+            date_from: query.date_from.map(DateTime::from_chrono),
+            date_to: query.date_to.map(DateTime::from_chrono),
+        };
+        let orders = self
+            .repo
+            .stream(business.business_id.into_inner(), filter)
+            .await?;
+
+        match format {
+            SpreadsheetFormat::Csv => {
+                let header = spreadsheet::csv_row(&table::header(&columns))
+                    .map(|row| [spreadsheet::CSV_BOM, &row].concat())
+                    .map_err(ApiError::internal);
+                let rows = orders.map(move |order| {
+                    let mut chunk = Vec::new();
+                    for row in table::rows(&order?, &columns) {
+                        chunk.extend(spreadsheet::csv_row(&row).map_err(ApiError::internal)?);
+                    }
+                    Ok(chunk)
+                });
+
+                Ok((format, stream::once(async { header }).chain(rows).boxed()))
+            }
+            SpreadsheetFormat::Xlsx => {
+                let mut rows = Vec::new();
+                let mut orders = orders;
+                while let Some(order) = orders.try_next().await? {
+                    rows.extend(table::rows(&order, &columns));
+                    if rows.len() >= spreadsheet::XLSX_MAX_ROWS {
+                        return Err(ApiError::validation(
+                            "format",
+                            "Too many rows for an XLSX file, export them as CSV",
+                        ));
+                    }
+                }
+
+                let bytes = tokio::task::spawn_blocking(move || {
+                    spreadsheet::xlsx("Orders", &table::header(&columns), &rows)
+                })
+                .await
+                .map_err(|_| ApiError::internal("Export task failed"))?
+                .map_err(ApiError::internal)?;
+
+                Ok((format, stream::once(async { Ok(bytes) }).boxed()))
+            }
+        }
+    }
+
+    /// Reads orders from a CSV or XLSX file laid out like an export. A dry run
+    /// only reports what is wrong with the file, otherwise its orders are
+    /// created once it has no errors. Imported orders keep the status they
+    /// are given and do not take stock, nor give it back when cancelled.
+    pub async fn import_orders<B: BusinessRepo, P: ProductRepo, T: TaxRepo>(
+        &self,
+        business_service: &BusinessService<B>,
+        product_service: &ProductService<P>,
+        tax_service: &TaxService<T>,
+        business: BusinessSession,
+        bytes: Vec<u8>,
+        query: OrderImportQuery,
+    ) -> ApiResult<OrderImportReport> {
+        let dry_run = query.dry_run.unwrap_or(true);
+
+        let sheet = tokio::task::spawn_blocking(move || Table::read(&bytes))
+            .await
+            .map_err(|_| ApiError::internal("Import task failed"))?
+            .map_err(|e| ApiError::validation("file", e))?;
+        if sheet.rows.len() > MAX_IMPORTED_ROWS {
+            return Err(ApiError::validation(
+                "file",
+                format!("At most {} rows can be imported at once", MAX_IMPORTED_ROWS),
+            ));
+        }
+
+        let mut errors = Vec::new();
+        let imported = table::parse(&sheet, &mut errors);
+
+        let mut skus: Vec<String> = imported
+            .iter()
+            .flat_map(|o| o.items.iter().map(|i| i.sku.clone()))
+            .collect();
+        skus.sort();
+        skus.dedup();
+        let products = match skus.is_empty() {
+            true => Vec::new(),
+            false => {
+                product_service
+                    .find_by_skus(business.business_id, &skus)
+                    .await?
+            }
+        };
+        let default_currency = business_service
+            .get_settings(business.business_id)
+            .await?
+            .currency;
+        let taxes = tax_service.table(business.business_id).await?;
+
+        let mut orders = Vec::with_capacity(imported.len());
+        for ImportedOrder {
+            row,
+            mut order,
+            currency,
+            amount_paid,
+            items,
+        } in imported
+        {
+            let currency = currency.unwrap_or_else(|| default_currency.clone());
+            let mut resolved = true;
+
+            for item in items {
+                let mut matches = products.iter().filter_map(|p| {
+                    p.variants
+                        .iter()
+                        .find(|v| v.sku == item.sku)
+                        .map(|v| (p, v))
+                });
+                let (product, variant) = match (matches.next(), matches.next()) {
+                    (Some(found), None) => found,
+                    (found, _) => {
+                        let message = match found {
+                            None => format!("Unknown SKU '{}'", item.sku),
+                            Some(_) => format!("SKU '{}' is used by several products", item.sku),
+                        };
+                        errors.push(ImportRowError {
+                            row: item.row,
+                            column: Some(OrderColumn::ItemSku.as_ref().to_string()),
+                            message,
+                        });
+                        resolved = false;
+                        continue;
+                    }
+                };
+
+                let unit_price = match item.unit_price {
+                    Some(price) => price,
+                    None => match variant.price_in(&currency, &default_currency) {
+                        Some(price) => price.clone(),
+                        None => {
+                            errors.push(ImportRowError {
+                                row: item.row,
+                                column: Some(OrderColumn::ItemUnitPrice.as_ref().to_string()),
+                                message: format!(
+                                    "SKU '{}' has no price in {}, give one",
+                                    item.sku, currency
+                                ),
+                            });
+                            resolved = false;
+                            continue;
+                        }
+                    },
+                };
+                order.items.push(OrderItem {
+                    product_id: Some(product.id.into_inner()),
+                    variant_sku: item.sku,
+                    product_title: product.title.to_string(),
+                    quantity: item.quantity,
+                    total_price: &unit_price * BigDecimal::from(item.quantity),
+                    unit_price,
+                    adjustments: Vec::new(),
+                    tax: taxes.applied_tax(
+                        product.tax_class.as_ref(),
+                        &order.shipping_address.country,
+                        &order.shipping_address.state,
+                    ),
+                    tax_amount: BigDecimal::from(0),
+                });
+            }
+            if !resolved {
+                continue;
+            }
+
+            order.currency = currency.to_string();
+            order.tax_mode = taxes.settings.mode;
+            order.calculate_totals();
+
+            let source = Source::User(business.user_id.into());
+            let status = order.status.clone();
+            order.add_history_entry(status, Some("Imported".to_string()), Some(source.clone()));
+            if let Some(amount) = amount_paid.filter(|a| *a > BigDecimal::from(0)) {
+                let paid = order.add_payment_entry(PaymentEntry {
+                    kind: PaymentKind::Payment,
+                    method: PaymentMethod::Other,
+                    amount,
+                    reference: None,
+                    note: Some("Imported".to_string()),
+                    created_by: Some(source),
+                    created_at: order.created_at,
+                });
+                if let Err(message) = paid {
+                    errors.push(ImportRowError {
+                        row,
+                        column: Some(OrderColumn::AmountPaid.as_ref().to_string()),
+                        message: message.to_string(),
+                    });
+                    continue;
+                }
+            }
+
+            orders.push(order);
+        }
+        errors.sort_by_key(|e| e.row);
+
+        let mut report = OrderImportReport {
+            dry_run,
+            rows: sheet.rows.len() as u32,
+            orders: orders.len() as u32,
+            errors,
+            imported: 0,
+        };
+        if dry_run || !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        let business_id = business.business_id.into_inner();
+        for order in orders {
+            self.repo.create(business_id, order).await?;
+            report.imported += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Gives the order the next invoice number, if it has none yet. Only
+    /// orders in an invoiceable status can be numbered.
+    pub async fn number_invoice(
+        &self,
+        business: BusinessSession,
+        order_id: Id,
+    ) -> ApiResult<OrderDto> {
+        let id = order_id.into_inner();
+        let business_id = business.business_id.into_inner();
+
+        let order = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
+        if order.invoice_number.is_some() {
+            return Ok(order.into());
+        }
+        if !order.status.is_invoiceable() {
+            return Err(ApiError::validation(
+                "status",
+                format!("A {} order can't be invoiced", order.status.as_str()),
+            ));
+        }
+
+        self.repo
+            .assign_invoice_number(business_id, id)
+            .await
+            .map(Into::into)
+    }
+
+    /// Prints invoices or packing slips of the orders into one PDF, returned
+    /// with a file name. Invoices of orders that were not numbered yet are
+    /// printed without a number.
+    pub async fn render_documents<B, S, Reg, F>(
+        &self,
+        business_service: &BusinessService<B>,
+        store_service: &StoreService<S, Reg>,
+        file_service: &FileService<F>,
+        business: BusinessSession,
+        request: BulkOrderDocuments,
+    ) -> ApiResult<(Vec<u8>, String)>
+    where
+        B: BusinessRepo,
+        S: StoreRepo,
+        Reg: StoreRegRepo,
+        F: FileRepo,
+    {
+        let business_id = business.business_id.into_inner();
+
+        let mut ids: Vec<ObjectId> = Vec::with_capacity(request.order_ids.len());
+        for id in request.order_ids.iter().map(|id| id.into_inner()) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        if ids.is_empty() || ids.len() > MAX_PRINTED_ORDERS {
+            return Err(ApiError::validation(
+                "order_ids",
+                format!(
+                    "Between 1 and {} orders can be printed at once",
+                    MAX_PRINTED_ORDERS
+                ),
+            ));
+        }
+
+        let mut found = self.repo.find_by_ids(business_id, &ids).await?;
+        let mut orders = Vec::with_capacity(ids.len());
+        for id in &ids {
+            let position = found
+                .iter()
+                .position(|o| o._id == *id)
+                .ok_or_else(|| ApiError::not_found("order", id.to_hex()))?;
+            orders.push(found.swap_remove(position));
+        }
+
+        // Orders from the same store share their branding
+        let mut stores: Vec<Option<ObjectId>> = Vec::new();
+        let mut brandings = Vec::new();
+        let mut sheets = Vec::with_capacity(orders.len());
+        for order in orders {
+            let placed_by = order.store_id();
+            let index = match stores.iter().position(|s| *s == placed_by) {
+                Some(index) => index,
+                None => {
+                    let store = Self::document_store(
+                        store_service,
+                        business.business_id,
+                        request.store_id,
+                        placed_by,
+                    )
+                    .await?;
+                    brandings.push(
+                        Self::branding(business_service, file_service, business.business_id, store)
+                            .await?,
+                    );
+                    stores.push(placed_by);
+                    brandings.len() - 1
+                }
+            };
+            sheets.push((index, order));
+        }
+
+        let file_name = match (request.kind, sheets.as_slice()) {
+            (OrderDocumentKind::Invoice, [(_, order)]) => format!(
+                "invoice-{}.pdf",
+                order
+                    .invoice_reference()
+                    .unwrap_or_else(|| order._id.to_hex())
+            ),
+            (OrderDocumentKind::PackingSlip, [(_, order)]) => {
+                format!("packing-slip-{}.pdf", order._id.to_hex())
+            }
+            (OrderDocumentKind::Invoice, _) => "invoices.pdf".to_string(),
+            (OrderDocumentKind::PackingSlip, _) => "packing-slips.pdf".to_string(),
+        };
+
+        let bytes = tokio::task::spawn_blocking(move || {
+            documents::render(request.kind, &brandings, &sheets)
+        })
+        .await
+        .map_err(|_| ApiError::internal("Document rendering task failed"))?
+        .map_err(ApiError::internal)?;
+
+        Ok((bytes, file_name))
+    }
+
+    /// The requested store, else the one the order was placed through, else
+    /// the latest store. A store that has since been deleted is skipped.
+    async fn document_store<S: StoreRepo, Reg: StoreRegRepo>(
+        store_service: &StoreService<S, Reg>,
+        business_id: Id,
+        requested: Option<Id>,
+        placed_by: Option<ObjectId>,
+    ) -> ApiResult<Option<StoreDto>> {
+        if requested.is_some() {
+            return store_service
+                .get_document_store(business_id, requested)
+                .await;
+        }
+
+        if let Some(store_id) = placed_by {
+            if let Ok(store) = store_service
+                .get_document_store(business_id, Some(store_id.into()))
+                .await
+            {
+                return Ok(store);
+            }
+        }
+
+        store_service.get_document_store(business_id, None).await
+    }
+
+    async fn branding<B: BusinessRepo, F: FileRepo>(
+        business_service: &BusinessService<B>,
+        file_service: &FileService<F>,
+        business_id: Id,
+        store: Option<StoreDto>,
+    ) -> ApiResult<Branding> {
+        let Some(store) = store else {
+            let business = business_service.get(business_id).await?;
+            return Ok(Branding {
+                name: business.name.to_string(),
+                ..Default::default()
+            });
+        };
+
+        let mut details: Vec<String> = [store.contact_email, store.contact_phone, store.address]
+            .into_iter()
+            .flatten()
+            .collect();
+        let locality = [store.zip_code, store.city]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !locality.is_empty() {
+            details.push(locality);
+        }
+
+        // A logo that cannot be loaded is left out rather than failing the
+        // whole document
+        let mut logo_ids = HashSet::new();
+        if let Some(ref logo) = store.logo {
+            file_ids_in(logo, &mut logo_ids);
+        }
+        let logo = match logo_ids.into_iter().next() {
+            Some(file_id) => file_service
+                .get_image(
+                    business_id,
+                    file_id.into(),
+                    ImageQuery {
+                        w: Some(LOGO_WIDTH),
+                        fmt: Some(OutputFormat::Png),
+                    },
+                )
+                .await
+                .map(|(bytes, _)| bytes)
+                .ok(),
+            None => None,
+        };
+
+        Ok(Branding {
+            name: store.name.to_string(),
+            details,
+            logo,
         })
     }
 
-    pub async fn get_analytics(
+    pub async fn get_analytics<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
-        date_from: Option<chrono::DateTime<Utc>>,
-        date_to: Option<chrono::DateTime<Utc>>,
+        query: AnalyticsQuery,
     ) -> ApiResult<OrderAnalytics> {
+        let currency = match query.currency {
+            Some(currency) => currency,
+            None => {
+                business_service
+                    .get_settings(business.business_id)
+                    .await?
+                    .currency
+            }
+        };
+        let filter = AnalyticsFilter {
+            currency,
+            date_from: query.date_from.map(DateTime::from_chrono),
+            date_to: query.date_to.map(DateTime::from_chrono),
+            bucket: query.bucket.unwrap_or_default(),
+            top_limit: query.top_limit.unwrap_or(10).clamp(1, 100),
+        };
+
         self.repo
-            .get_analytics(
-                business.business_id.into_inner(),
-                date_from.map(DateTime::from_chrono),
-                date_to.map(DateTime::from_chrono),
-            )
+            .get_analytics(business.business_id.into_inner(), filter)
             .await
     }
 
@@ -289,17 +948,28 @@ impl<R: OrderRepo> OrderService<R> {
         })
     }
 
-    pub async fn pub_create_order<P: ProductRepo>(
+    pub async fn pub_create_order<P, S, D, T>(
         &self,
         product_service: &ProductService<P>,
-        business_id: Id,
-        store_id: Id,
+        shipping_service: &ShippingService<S>,
+        discount_service: &DiscountService<D>,
+        tax_service: &TaxService<T>,
+        store: &StoreRegDto,
         create_req: PubOrderCreate,
-    ) -> ApiResult<()> {
+    ) -> ApiResult<()>
+    where
+        P: ProductRepo,
+        S: ShippingRepo,
+        D: DiscountRepo,
+        T: TaxRepo,
+    {
+        let business_id = store.business_id;
+        let taxes = tax_service.table(business_id).await?;
         let mut order_items = Vec::new();
+        let mut categories = Vec::new();
         let mut subtotal = BigDecimal::from(0);
+        let mut weight = BigDecimal::from(0);
 
-        // TODO: wrap inside a transaction
         for item_req in create_req.items {
             let product = product_service
                 .pub_get_product(business_id, item_req.product_id)
@@ -326,19 +996,46 @@ impl<R: OrderRepo> OrderService<R> {
                 ));
             }
 
-            let unit_price = variant.price.clone();
+            let unit_price = variant
+                .price_in(&store.currency, &store.base_currency)
+                .ok_or_else(|| not_sold_in(&item_req.variant_sku, &store.currency))?
+                .clone();
             let total_price = &unit_price * BigDecimal::from(item_req.quantity);
             subtotal += &total_price;
+            if let Some(ref w) = variant.weight {
+                weight += w * BigDecimal::from(item_req.quantity);
+            }
 
             order_items.push(OrderItem {
+                product_id: Some(product.id.into_inner()),
                 variant_sku: item_req.variant_sku,
                 product_title: product.title.to_string(),
                 quantity: item_req.quantity,
                 unit_price,
                 total_price,
+                adjustments: Vec::new(),
+                tax: taxes.applied_tax(
+                    product.tax_class.as_ref(),
+                    &create_req.shipping_address.country,
+                    &create_req.shipping_address.state,
+                ),
+                tax_amount: BigDecimal::from(0),
             });
+            categories.push(product.category);
         }
 
+        let delivery_method = create_req.delivery_method.unwrap_or_default();
+        let shipping = shipping_service
+            .quote(
+                business_id,
+                &create_req.shipping_address.country,
+                &create_req.shipping_address.state,
+                delivery_method,
+                &subtotal,
+                &weight,
+            )
+            .await?;
+
         // Create order record
         let mut order = OrderRecord {
             customer_email: create_req.customer_email,
@@ -347,10 +1044,11 @@ impl<R: OrderRepo> OrderService<R> {
             items: order_items,
             shipping_address: create_req.shipping_address,
             billing_address: create_req.billing_address,
+            delivery_method,
             subtotal,
-            shipping_cost: BigDecimal::from(0),
-            tax_amount: BigDecimal::from(0),
-            currency: "".to_string(),
+            shipping_cost: shipping.cost,
+            tax_mode: taxes.settings.mode,
+            currency: store.currency.to_string(),
             notes: create_req.notes,
             ..Default::default()
         };
@@ -358,14 +1056,95 @@ impl<R: OrderRepo> OrderService<R> {
         order.add_history_entry(
             OrderStatus::Pending,
             None,
-            Some(Source::Store(store_id.into())),
+            Some(Source::Store(store.store_id.into())),
         );
 
+        self.create_discounted(
+            discount_service,
+            business_id,
+            create_req.discount_code.as_deref(),
+            categories,
+            order,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Writes the applicable discounts onto the order as line and order
+    /// adjustments, then stores it. Discount usage and redemptions are only
+    /// kept when the order is actually created.
+    async fn create_discounted<D: DiscountRepo>(
+        &self,
+        discount_service: &DiscountService<D>,
+        business_id: Id,
+        discount_code: Option<&str>,
+        categories: Vec<String>,
+        mut order: OrderRecord,
+    ) -> ApiResult<OrderRecord> {
+        let zero = BigDecimal::from(0);
+        let lines: Vec<_> = order
+            .items
+            .iter()
+            .zip(categories)
+            .map(|(item, category)| DiscountLine {
+                product_id: item.product_id,
+                category,
+                unit_price: item.unit_price.clone(),
+                quantity: item.quantity,
+            })
+            .collect();
+
+        let customer = order.customer_phone.clone();
+        let applied = discount_service
+            .resolve(
+                business_id,
+                discount_code,
+                &customer,
+                &lines,
+                &order.shipping_cost,
+            )
+            .await?;
+
+        for a in &applied {
+            let adjustment = |amount: &BigDecimal| Adjustment {
+                discount_id: a.discount._id,
+                code: a.discount.code.clone(),
+                title: a.discount.title.to_string(),
+                amount: amount.clone(),
+            };
+
+            for (item, amount) in order.items.iter_mut().zip(&a.application.lines) {
+                if amount > &zero {
+                    item.adjustments.push(adjustment(amount));
+                }
+            }
+            if a.application.order > zero {
+                order.adjustments.push(adjustment(&a.application.order));
+            }
+        }
+
         order.calculate_totals();
 
-        self.repo
-            .create(business_id.into(), order)
+        let order_id = order._id;
+        discount_service
+            .reserve(business_id, &applied, order_id, &customer)
+            .await?;
+
+        match self
+            .repo
+            .create_reserving_stock(business_id.into(), order)
             .await
-            .map(|_| ())
+        {
+            Ok(order) => Ok(order),
+            Err(e) => {
+                if let Err(e) = discount_service
+                    .release(business_id, &applied, order_id)
+                    .await
+                {
+                    error!("discount release error: {:?}", e);
+                }
+                Err(e)
+            }
+        }
     }
 }
@@ -0,0 +1,541 @@
+//! Orders as spreadsheet rows, for exports and imports.
+//!
+//! An export has one row per order, or one row per item when an item column
+//! is picked, the order columns being repeated on every row. Imports read the
+//! same layout back: rows sharing an `id` make up one order.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use bson::DateTime;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::{AsRefStr, EnumString};
+use ts_rs::TS;
+
+use super::domain::{OrderItem, OrderRecord, OrderStatus, ShippingAddress};
+use crate::tenant::shipping::domain::DeliveryMethod;
+use crate::types::currency::Currency;
+use crate::types::phone::PhoneNumber;
+use crate::utils::spreadsheet::{Cell, ImportRowError, Row, Table};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, TS, EnumString, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+#[ts(export)]
+pub enum OrderColumn {
+    Id,
+    CreatedAt,
+    Status,
+    CustomState,
+    PaymentStatus,
+    CustomerName,
+    CustomerEmail,
+    CustomerPhone,
+    ShippingFullName,
+    #[serde(rename = "shipping_address_line_1")]
+    #[strum(serialize = "shipping_address_line_1")]
+    ShippingAddressLine1,
+    #[serde(rename = "shipping_address_line_2")]
+    #[strum(serialize = "shipping_address_line_2")]
+    ShippingAddressLine2,
+    ShippingCity,
+    ShippingState,
+    ShippingPostalCode,
+    ShippingCountry,
+    ShippingPhone,
+    DeliveryMethod,
+    Currency,
+    Subtotal,
+    DiscountAmount,
+    ShippingCost,
+    TaxAmount,
+    TotalAmount,
+    AmountPaid,
+    TrackingNumber,
+    InvoiceNumber,
+    Notes,
+    // All items in one cell, e.g. "TSHIRT-M x 2; MUG x 1"
+    Items,
+    ItemSku,
+    ItemTitle,
+    ItemQuantity,
+    ItemUnitPrice,
+    ItemTotal,
+}
+
+pub const DEFAULT_COLUMNS: &[OrderColumn] = &[
+    OrderColumn::Id,
+    OrderColumn::CreatedAt,
+    OrderColumn::Status,
+    OrderColumn::PaymentStatus,
+    OrderColumn::CustomerName,
+    OrderColumn::CustomerEmail,
+    OrderColumn::CustomerPhone,
+    OrderColumn::ShippingAddressLine1,
+    OrderColumn::ShippingCity,
+    OrderColumn::ShippingState,
+    OrderColumn::ShippingCountry,
+    OrderColumn::Items,
+    OrderColumn::ShippingCost,
+    OrderColumn::TotalAmount,
+    OrderColumn::AmountPaid,
+    OrderColumn::Currency,
+];
+
+// Needed by an import, every other column is optional
+const REQUIRED_COLUMNS: &[OrderColumn] = &[
+    OrderColumn::CustomerName,
+    OrderColumn::CustomerPhone,
+    OrderColumn::ShippingAddressLine1,
+    OrderColumn::ShippingCity,
+    OrderColumn::ShippingState,
+    OrderColumn::ShippingCountry,
+    OrderColumn::ItemSku,
+];
+
+const STATUSES: [OrderStatus; 8] = [
+    OrderStatus::Pending,
+    OrderStatus::Confirmed,
+    OrderStatus::Processing,
+    OrderStatus::Shipped,
+    OrderStatus::Delivered,
+    OrderStatus::Cancelled,
+    OrderStatus::Refunded,
+    OrderStatus::Archived,
+];
+
+impl OrderColumn {
+    pub fn is_item(&self) -> bool {
+        matches!(
+            self,
+            OrderColumn::ItemSku
+                | OrderColumn::ItemTitle
+                | OrderColumn::ItemQuantity
+                | OrderColumn::ItemUnitPrice
+                | OrderColumn::ItemTotal
+        )
+    }
+}
+
+/// Parses a comma separated list of columns, the defaults when empty.
+pub fn parse_columns(list: Option<&str>) -> Result<Vec<OrderColumn>, String> {
+    let mut columns = Vec::new();
+    for name in list.unwrap_or_default().split(',').map(str::trim) {
+        if name.is_empty() {
+            continue;
+        }
+        let column =
+            OrderColumn::from_str(name).map_err(|_| format!("Unknown column '{}'", name))?;
+        if !columns.contains(&column) {
+            columns.push(column);
+        }
+    }
+
+    if columns.is_empty() {
+        columns.extend_from_slice(DEFAULT_COLUMNS);
+    }
+    Ok(columns)
+}
+
+pub fn header(columns: &[OrderColumn]) -> Vec<Cell> {
+    columns.iter().map(|c| Cell::text(c.as_ref())).collect()
+}
+
+pub fn rows(order: &OrderRecord, columns: &[OrderColumn]) -> Vec<Vec<Cell>> {
+    if !columns.iter().any(OrderColumn::is_item) || order.items.is_empty() {
+        return vec![columns.iter().map(|c| cell(order, None, *c)).collect()];
+    }
+
+    order
+        .items
+        .iter()
+        .map(|item| {
+            columns
+                .iter()
+                .map(|c| cell(order, Some(item), *c))
+                .collect()
+        })
+        .collect()
+}
+
+fn cell(order: &OrderRecord, item: Option<&OrderItem>, column: OrderColumn) -> Cell {
+    let address = &order.shipping_address;
+    let number = |n: &BigDecimal| Cell::Number(n.clone());
+
+    match column {
+        OrderColumn::Id => Cell::text(order._id.to_hex()),
+        OrderColumn::CreatedAt => Cell::text(
+            order
+                .created_at
+                .to_chrono()
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string(),
+        ),
+        OrderColumn::Status => Cell::text(order.status.as_str()),
+        OrderColumn::CustomState => Cell::optional(order.custom_state.clone()),
+        OrderColumn::PaymentStatus => Cell::text(order.payment_status.as_str()),
+        OrderColumn::CustomerName => Cell::text(&order.customer_name),
+        OrderColumn::CustomerEmail => Cell::optional(order.customer_email.clone()),
+        OrderColumn::CustomerPhone => Cell::text(&order.customer_phone),
+        OrderColumn::ShippingFullName => Cell::text(&address.full_name),
+        OrderColumn::ShippingAddressLine1 => Cell::text(&address.address_line_1),
+        OrderColumn::ShippingAddressLine2 => Cell::optional(address.address_line_2.clone()),
+        OrderColumn::ShippingCity => Cell::text(&address.city),
+        OrderColumn::ShippingState => Cell::text(&address.state),
+        OrderColumn::ShippingPostalCode => Cell::text(&address.postal_code),
+        OrderColumn::ShippingCountry => Cell::text(&address.country),
+        OrderColumn::ShippingPhone => Cell::optional(address.phone.clone()),
+        OrderColumn::DeliveryMethod => Cell::text(match order.delivery_method {
+            DeliveryMethod::Home => "home",
+            DeliveryMethod::StopDesk => "stop_desk",
+        }),
+        OrderColumn::Currency => Cell::text(&order.currency),
+        OrderColumn::Subtotal => number(&order.subtotal),
+        OrderColumn::DiscountAmount => number(&order.discount_amount),
+        OrderColumn::ShippingCost => number(&order.shipping_cost),
+        OrderColumn::TaxAmount => number(&order.tax_amount),
+        OrderColumn::TotalAmount => number(&order.total_amount),
+        OrderColumn::AmountPaid => number(&order.amount_paid),
+        OrderColumn::TrackingNumber => Cell::optional(order.tracking_number.clone()),
+        OrderColumn::InvoiceNumber => Cell::optional(order.invoice_reference()),
+        OrderColumn::Notes => Cell::optional(order.notes.clone()),
+        OrderColumn::Items => Cell::text(
+            order
+                .items
+                .iter()
+                .map(|i| format!("{} x {}", i.variant_sku, i.quantity))
+                .collect::<Vec<_>>()
+                .join("; "),
+        ),
+        OrderColumn::ItemSku => Cell::optional(item.map(|i| i.variant_sku.clone())),
+        OrderColumn::ItemTitle => Cell::optional(item.map(|i| i.product_title.clone())),
+        OrderColumn::ItemQuantity => {
+            item.map_or(Cell::Empty, |i| Cell::Number(BigDecimal::from(i.quantity)))
+        }
+        OrderColumn::ItemUnitPrice => item.map_or(Cell::Empty, |i| number(&i.unit_price)),
+        OrderColumn::ItemTotal => item.map_or(Cell::Empty, |i| number(&i.total_price)),
+    }
+}
+
+/// An item row, its product is looked up by SKU later on.
+#[derive(Debug, Clone)]
+pub struct ImportItem {
+    pub row: u32,
+    pub sku: String,
+    pub quantity: u32,
+    // The variant price when left out
+    pub unit_price: Option<BigDecimal>,
+}
+
+/// An order read from the sheet, without items or totals yet.
+#[derive(Debug, Clone)]
+pub struct ImportedOrder {
+    // Where the order starts in the sheet
+    pub row: u32,
+    pub order: OrderRecord,
+    pub currency: Option<Currency>,
+    pub amount_paid: Option<BigDecimal>,
+    pub items: Vec<ImportItem>,
+}
+
+/// Reads the orders of the sheet, collecting what is wrong with its rows.
+/// Rows are numbered as in a spreadsheet application, the header being 1.
+pub fn parse(table: &Table, errors: &mut Vec<ImportRowError>) -> Vec<ImportedOrder> {
+    if !table.has_columns(REQUIRED_COLUMNS, errors) {
+        return Vec::new();
+    }
+
+    let mut orders: Vec<ImportedOrder> = Vec::new();
+    let mut references: HashMap<String, usize> = HashMap::new();
+
+    for index in 0..table.rows.len() {
+        let mut row = Row::new(table, index, errors);
+
+        // Further rows of an order only add items to it
+        let reference = row.text(OrderColumn::Id);
+        let existing = reference.as_ref().and_then(|r| references.get(r).copied());
+        let item = item(&mut row);
+        if let Some(position) = existing {
+            orders[position].items.extend(item);
+            continue;
+        }
+
+        let Some(mut imported) = order(&mut row) else {
+            continue;
+        };
+        imported.items.extend(item);
+        if let Some(reference) = reference {
+            references.insert(reference, orders.len());
+        }
+        orders.push(imported);
+    }
+
+    orders
+}
+
+fn item(row: &mut Row) -> Option<ImportItem> {
+    let sku = row.required(OrderColumn::ItemSku);
+    let quantity = row
+        .parsed(OrderColumn::ItemQuantity, |v| match v.parse::<u32>() {
+            Ok(quantity) if quantity > 0 => Ok(quantity),
+            _ => Err(format!("'{}' is not a positive whole number", v)),
+        })
+        .unwrap_or(1);
+    let unit_price = row.parsed(OrderColumn::ItemUnitPrice, amount);
+
+    Some(ImportItem {
+        row: row.number,
+        sku: sku?,
+        quantity,
+        unit_price,
+    })
+}
+
+fn order(row: &mut Row) -> Option<ImportedOrder> {
+    let errors = row.errors();
+
+    let customer_name = row.required(OrderColumn::CustomerName);
+    let customer_phone = row.required(OrderColumn::CustomerPhone).and_then(|v| {
+        PhoneNumber::new(&v)
+            .map_err(|e| row.error(OrderColumn::CustomerPhone, e))
+            .ok()
+    });
+    let customer_email = row.parsed(OrderColumn::CustomerEmail, |v| {
+        email_address::EmailAddress::from_str(v)
+            .map(|e| e.to_string())
+            .map_err(|_| format!("'{}' is not an email address", v))
+    });
+
+    let address_line_1 = row.required(OrderColumn::ShippingAddressLine1);
+    let city = row.required(OrderColumn::ShippingCity);
+    let state = row.required(OrderColumn::ShippingState);
+    let country = row.required(OrderColumn::ShippingCountry);
+
+    let status = row.parsed(OrderColumn::Status, |v| {
+        STATUSES
+            .into_iter()
+            .find(|s| s.as_str().eq_ignore_ascii_case(v))
+            .ok_or_else(|| format!("Unknown status '{}'", v))
+    });
+    let delivery_method = row.parsed(OrderColumn::DeliveryMethod, |v| {
+        match v.to_lowercase().as_str() {
+            "home" => Ok(DeliveryMethod::Home),
+            "stop_desk" => Ok(DeliveryMethod::StopDesk),
+            _ => Err(format!("Unknown delivery method '{}'", v)),
+        }
+    });
+    let currency = row.parsed(OrderColumn::Currency, Currency::new);
+    let created_at = row.parsed(OrderColumn::CreatedAt, date);
+    let shipping_cost = row.parsed(OrderColumn::ShippingCost, amount);
+    let amount_paid = row.parsed(OrderColumn::AmountPaid, amount);
+
+    if row.errors() > errors {
+        return None;
+    }
+    let customer_name = customer_name?;
+    let created_at = created_at.unwrap_or_else(DateTime::now);
+
+    let order = OrderRecord {
+        customer_email,
+        customer_phone: customer_phone?.as_str().to_string(),
+        shipping_address: ShippingAddress {
+            full_name: row
+                .text(OrderColumn::ShippingFullName)
+                .unwrap_or_else(|| customer_name.clone()),
+            address_line_1: address_line_1?,
+            address_line_2: row.text(OrderColumn::ShippingAddressLine2),
+            city: city?,
+            state: state?,
+            postal_code: row
+                .text(OrderColumn::ShippingPostalCode)
+                .unwrap_or_default(),
+            country: country?,
+            phone: row.text(OrderColumn::ShippingPhone),
+        },
+        customer_name,
+        delivery_method: delivery_method.unwrap_or_default(),
+        status: status.unwrap_or_default(),
+        shipping_cost: shipping_cost.unwrap_or_else(|| BigDecimal::from(0)),
+        notes: row.text(OrderColumn::Notes),
+        tracking_number: row.text(OrderColumn::TrackingNumber),
+        // The stock was handled wherever the order was taken
+        stock_reserved: false,
+        created_at,
+        updated_at: created_at,
+        ..Default::default()
+    };
+
+    Some(ImportedOrder {
+        row: row.number,
+        order,
+        currency,
+        amount_paid,
+        items: Vec::new(),
+    })
+}
+
+fn amount(value: &str) -> Result<BigDecimal, String> {
+    match BigDecimal::from_str(value) {
+        Ok(amount) if amount >= BigDecimal::from(0) => Ok(amount),
+        _ => Err(format!("'{}' is not a valid amount", value)),
+    }
+}
+
+fn date(value: &str) -> Result<DateTime, String> {
+    let parsed = chrono::DateTime::parse_from_rfc3339(value)
+        .map(|d| d.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|d| d.and_utc()))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        })
+        .map_err(|_| format!("'{}' is not a date, use YYYY-MM-DD", value))?;
+
+    Ok(DateTime::from_chrono(parsed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order() -> OrderRecord {
+        OrderRecord {
+            customer_name: "Amine".to_string(),
+            customer_phone: "0555123456".to_string(),
+            items: ["TSHIRT-M", "MUG"]
+                .iter()
+                .map(|sku| OrderItem {
+                    product_id: Default::default(),
+                    variant_sku: sku.to_string(),
+                    product_title: sku.to_string(),
+                    quantity: 2,
+                    unit_price: BigDecimal::from(500),
+                    total_price: BigDecimal::from(1000),
+                    adjustments: Vec::new(),
+                    tax: None,
+                    tax_amount: BigDecimal::from(0),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn table(rows: &[&[&str]]) -> Table {
+        Table {
+            headers: rows[0].iter().map(|h| h.to_string()).collect(),
+            rows: rows[1..]
+                .iter()
+                .map(|r| r.iter().map(|v| v.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(parse_columns(None).unwrap(), DEFAULT_COLUMNS);
+        assert_eq!(
+            parse_columns(Some("id, shipping_address_line_1,id")).unwrap(),
+            vec![OrderColumn::Id, OrderColumn::ShippingAddressLine1]
+        );
+        assert!(parse_columns(Some("id,nope")).is_err());
+    }
+
+    #[test]
+    fn test_rows_per_item() {
+        let order = order();
+        let columns = [OrderColumn::CustomerName, OrderColumn::Items];
+        let rows = rows(&order, &columns);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][1], Cell::text("TSHIRT-M x 2; MUG x 2"));
+
+        let columns = [OrderColumn::CustomerName, OrderColumn::ItemSku];
+        let rows = super::rows(&order, &columns);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1], vec![Cell::text("Amine"), Cell::text("MUG")]);
+    }
+
+    #[test]
+    fn test_parse_groups_rows() {
+        let header: &[&str] = &[
+            "id",
+            "customer_name",
+            "customer_phone",
+            "shipping_address_line_1",
+            "shipping_city",
+            "shipping_state",
+            "shipping_country",
+            "item_sku",
+            "item_quantity",
+            "created_at",
+        ];
+        let table = table(&[
+            header,
+            &[
+                "A1",
+                "Amine",
+                "0555 12 34 56",
+                "1 Rue",
+                "Oran",
+                "Oran",
+                "DZ",
+                "MUG",
+                "2",
+                "2024-05-01",
+            ],
+            &[
+                "B7", "Sara", "12345", "2 Rue", "Blida", "Blida", "DZ", "MUG", "", "",
+            ],
+            &["A1", "", "", "", "", "", "", "TSHIRT-M", "0", ""],
+            &[
+                "",
+                "Nour",
+                "0770000000",
+                "",
+                "Alger",
+                "Alger",
+                "DZ",
+                "",
+                "",
+                "",
+            ],
+        ]);
+
+        let mut errors = Vec::new();
+        let orders = parse(&table, &mut errors);
+
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].order.customer_phone, "0555123456");
+        assert_eq!(orders[0].items.len(), 2);
+        assert_eq!(orders[0].items[0].quantity, 2);
+        assert_eq!(orders[0].items[1].row, 4);
+        assert_eq!(
+            orders[0].order.created_at.to_chrono().to_rfc3339(),
+            "2024-05-01T00:00:00+00:00"
+        );
+
+        let flagged: Vec<_> = errors
+            .iter()
+            .map(|e| (e.row, e.column.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![
+                (3, "customer_phone"),
+                (4, "item_quantity"),
+                (5, "item_sku"),
+                (5, "shipping_address_line_1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_missing_columns() {
+        let mut errors = Vec::new();
+        let orders = parse(&table(&[&["customer_name"], &["Amine"]]), &mut errors);
+        assert!(orders.is_empty());
+        assert!(errors.iter().all(|e| e.row == 1));
+        assert_eq!(errors.len(), REQUIRED_COLUMNS.len() - 1);
+    }
+}
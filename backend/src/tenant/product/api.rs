@@ -7,8 +7,8 @@ use ts_rs::TS;
 
 use super::domain::*;
 use crate::{
-    types::{id::Id, name::Name},
-    utils::serde_helpers::JsonOption,
+    types::{currency::Currency, id::Id, name::Name, slug::Slug},
+    utils::{serde_helpers::JsonOption, spreadsheet::ImportRowError},
 };
 
 #[derive(Debug, Clone, Deserialize, Serialize, o2o, TS)]
@@ -33,6 +33,7 @@ pub struct ProductCreateDto {
     pub images: Vec<String>,
     pub variants: Vec<ProductVariant>,
     pub slug: String,
+    pub tax_class: Option<Slug>,
 }
 
 #[derive(Debug, Serialize, o2o, TS)]
@@ -50,12 +51,25 @@ pub struct ProductDto {
     pub images: Vec<String>,
     pub variants: Vec<ProductVariant>,
     pub slug: String,
+    pub tax_class: Option<Slug>,
     #[from(~.to_chrono())]
     pub created_at: DateTime<Utc>,
     #[from(~.to_chrono())]
     pub updated_at: DateTime<Utc>,
 }
 
+impl ProductDto {
+    /// Shows every variant at its price in `currency`, leaving out those
+    /// without one. A product left with none of its variants is not sold in
+    /// `currency` at all.
+    pub fn localize(mut self, currency: &Currency, base: &Currency) -> Option<Self> {
+        let had_variants = !self.variants.is_empty();
+        self.variants.retain_mut(|v| v.localize(currency, base));
+
+        (!had_variants || !self.variants.is_empty()).then_some(self)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, TS)]
 #[ts(export, optional_fields)]
 pub struct ProductListQuery {
@@ -79,6 +93,33 @@ pub struct ProductUpdate {
     pub status: JsonOption<ProductStatusDto>,
     pub variants: JsonOption<Vec<ProductVariant>>,
     pub slug: JsonOption<String>,
+    pub tax_class: JsonOption<Slug>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct ProductExportQuery {
+    pub status: Option<ProductStatusDto>,
+    pub category: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct ProductImportQuery {
+    // Only validates the file unless set to false
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export)]
+pub struct ProductImportReport {
+    pub dry_run: bool,
+    pub rows: u32,
+    // Products the file creates and updates, written when it has no errors
+    pub created: u32,
+    pub updated: u32,
+    pub errors: Vec<ImportRowError>,
+    pub imported: u32,
 }
 
 #[derive(Debug, Default, Serialize, TS)]
@@ -89,3 +130,48 @@ pub struct ProductListResponse {
     pub page: u32,
     pub limit: u32,
 }
+
+/// Storefront product search, as parsed from the shop page query string.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProductSearchQuery {
+    pub search: Option<String>,
+    pub category: Option<String>,
+    pub featured: Option<bool>,
+    pub min_price: Option<BigDecimal>,
+    pub max_price: Option<BigDecimal>,
+    pub options: IndexMap<String, String>,
+    pub sort: ProductSort,
+    pub page: u32,
+    pub limit: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FacetValue {
+    pub value: String,
+    pub label: String,
+    pub count: u64,
+    pub selected: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionFacet {
+    pub name: String,
+    pub values: Vec<FacetValue>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProductFacets {
+    pub categories: Vec<FacetValue>,
+    pub options: Vec<OptionFacet>,
+    pub price_min: Option<BigDecimal>,
+    pub price_max: Option<BigDecimal>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ProductSearchResponse {
+    pub products: Vec<ProductDto>,
+    pub total: u64,
+    pub page: u32,
+    pub limit: u32,
+    pub facets: ProductFacets,
+}
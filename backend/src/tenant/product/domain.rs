@@ -4,7 +4,9 @@ use indexmap::{IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::types::currency::Currency;
 use crate::types::name::Name;
+use crate::types::slug::Slug;
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -23,8 +25,39 @@ pub struct ProductVariant {
     #[ts(as = "Option<String>")]
     pub compare_at: Option<BigDecimal>,
     pub stocks: usize,
+    #[ts(as = "Option<String>")]
+    pub weight: Option<BigDecimal>,
     pub images: Vec<String>,
     pub options: IndexMap<String, String>,
+    // Prices set by hand for stores selling in another currency
+    #[serde(default)]
+    #[ts(as = "IndexMap<String, String>")]
+    pub prices: IndexMap<Currency, BigDecimal>,
+}
+
+impl ProductVariant {
+    /// The price in `currency`. The base price is in `base`, so a variant
+    /// without an override has no price in any other currency.
+    pub fn price_in(&self, currency: &Currency, base: &Currency) -> Option<&BigDecimal> {
+        match self.prices.get(currency) {
+            Some(price) => Some(price),
+            None if currency == base => Some(&self.price),
+            None => None,
+        }
+    }
+
+    /// Rewrites the variant for a store selling in `currency`, returning
+    /// whether it has a price there. A compare at price is in the base
+    /// currency, so it is dropped along an override.
+    pub fn localize(&mut self, currency: &Currency, base: &Currency) -> bool {
+        if let Some(price) = self.prices.get(currency) {
+            self.price = price.clone();
+            self.compare_at = None;
+            return true;
+        }
+
+        currency == base
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -38,6 +71,9 @@ pub struct ProductRecord {
     pub images: Vec<String>,
     pub variants: Vec<ProductVariant>,
     pub slug: String,
+    // Key of a tax class, the business default applies when empty
+    #[serde(default)]
+    pub tax_class: Option<Slug>,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -65,6 +101,7 @@ impl ProductRecord {
             images,
             variants,
             slug,
+            tax_class: None,
             created_at: now,
             updated_at: now,
         }
@@ -83,6 +120,114 @@ impl ProductRecord {
 pub struct ProductFilter {
     pub status: Option<ProductStatus>,
     pub category: Option<String>,
+    pub categories: Option<Vec<String>>,
     pub featured: Option<bool>,
     pub search: Option<String>,
+    /// Matches products with at least one variant priced within the range.
+    pub min_price: Option<BigDecimal>,
+    pub max_price: Option<BigDecimal>,
+    /// Matches products with at least one variant carrying all these options.
+    pub options: IndexMap<String, String>,
+    /// Set for stores selling in another currency than the base one. Only
+    /// variants priced in it match, and prices are read in it.
+    pub currency: Option<Currency>,
+}
+
+impl ProductFilter {
+    /// Limits the filter to what a store selling in `currency` can sell.
+    pub fn sold_in(mut self, currency: &Currency, base: &Currency) -> Self {
+        self.currency = (currency != base).then(|| currency.clone());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProductSort {
+    #[default]
+    Relevance,
+    PriceAsc,
+    PriceDesc,
+    TitleAsc,
+    TitleDesc,
+    CreatedDesc,
+    CreatedAsc,
+}
+
+impl ProductSort {
+    pub fn sort_doc(self) -> bson::Document {
+        match self {
+            ProductSort::Relevance => bson::doc! { "featured": -1, "created_at": -1, "_id": 1 },
+            ProductSort::PriceAsc => bson::doc! { "_min_price": 1, "_id": 1 },
+            ProductSort::PriceDesc => bson::doc! { "_min_price": -1, "_id": 1 },
+            ProductSort::TitleAsc => bson::doc! { "title": 1, "_id": 1 },
+            ProductSort::TitleDesc => bson::doc! { "title": -1, "_id": 1 },
+            ProductSort::CreatedDesc => bson::doc! { "created_at": -1, "_id": 1 },
+            ProductSort::CreatedAsc => bson::doc! { "created_at": 1, "_id": 1 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OptionFacetCount {
+    pub name: String,
+    pub value: String,
+    pub count: u64,
+}
+
+/// A page of products together with facet counts over the whole match.
+#[derive(Debug, Clone, Default)]
+pub struct ProductSearchResult {
+    pub products: Vec<ProductRecord>,
+    pub total: u64,
+    pub categories: Vec<FacetCount>,
+    pub options: Vec<OptionFacetCount>,
+    pub price_min: Option<BigDecimal>,
+    pub price_max: Option<BigDecimal>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_in() {
+        let dzd = Currency::new("DZD").unwrap();
+        let eur = Currency::new("EUR").unwrap();
+        let usd = Currency::new("USD").unwrap();
+        let mut variant = ProductVariant {
+            sku: "TSHIRT-M".to_string(),
+            price: BigDecimal::from(1500),
+            compare_at: Some(BigDecimal::from(2000)),
+            stocks: 3,
+            weight: None,
+            images: Vec::new(),
+            options: IndexMap::new(),
+            prices: IndexMap::from([(eur.clone(), BigDecimal::from(10))]),
+        };
+
+        assert_eq!(variant.price_in(&dzd, &dzd), Some(&BigDecimal::from(1500)));
+        assert_eq!(variant.price_in(&eur, &dzd), Some(&BigDecimal::from(10)));
+        assert_eq!(variant.price_in(&usd, &dzd), None);
+
+        // Shop queries read the override at `prices.<code>`
+        let saved = bson::to_document(&variant).unwrap();
+        assert!(saved.get_document("prices").unwrap().contains_key("EUR"));
+        assert_eq!(
+            ProductFilter::default().sold_in(&eur, &dzd).currency,
+            Some(eur.clone())
+        );
+        assert_eq!(ProductFilter::default().sold_in(&dzd, &dzd).currency, None);
+
+        assert!(!variant.clone().localize(&usd, &dzd));
+        assert!(variant.localize(&eur, &dzd));
+        assert_eq!(variant.price, BigDecimal::from(10));
+        assert_eq!(variant.compare_at, None);
+    }
 }
@@ -1,9 +1,13 @@
 use async_trait::async_trait;
-use bson::{doc, oid::ObjectId, to_bson, DateTime};
-use futures::stream::TryStreamExt;
+use bson::{doc, oid::ObjectId, to_bson, Bson, DateTime};
+use futures::stream::{StreamExt, TryStreamExt};
 use mongodb::{options::FindOptions, Client, Collection};
 
+use bigdecimal::BigDecimal;
+use serde::Deserialize;
+
 use super::domain::*;
+use super::table::ImportedProduct;
 use crate::utils::error::{ApiError, ApiResult};
 
 #[async_trait]
@@ -33,13 +37,43 @@ pub trait ProductRepo: Send + Sync {
         business_id: ObjectId,
         slug: &str,
     ) -> ApiResult<Option<ProductRecord>>;
+    /// Products having a variant with any of the SKUs.
+    async fn find_by_skus(
+        &self,
+        business_id: ObjectId,
+        skus: &[String],
+    ) -> ApiResult<Vec<ProductRecord>>;
+    async fn find_by_slugs(
+        &self,
+        business_id: ObjectId,
+        slugs: &[String],
+    ) -> ApiResult<Vec<ProductRecord>>;
+    /// Every product matching the filter, oldest first.
+    async fn find_all(
+        &self,
+        business_id: ObjectId,
+        filter: ProductFilter,
+    ) -> ApiResult<Vec<ProductRecord>>;
     async fn update(
         &self,
         business_id: ObjectId,
         id: ObjectId,
         product: ProductRecord,
     ) -> ApiResult<ProductRecord>;
+    /// Saves only the fields the imported rows set, taking their values
+    /// from `product`, so stock taken by orders meanwhile is kept.
+    async fn update_imported(
+        &self,
+        business_id: ObjectId,
+        rows: &ImportedProduct,
+        product: &ProductRecord,
+    ) -> ApiResult<()>;
     async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+    async fn rename_category(&self, business_id: ObjectId, from: &str, to: &str) -> ApiResult<u64>;
+    /// Distinct image URLs of all products and their variants.
+    async fn find_image_urls(&self, business_id: ObjectId) -> ApiResult<Vec<String>>;
+    /// Whether an image of any product or variant points at the file.
+    async fn references_file(&self, business_id: ObjectId, file_id: ObjectId) -> ApiResult<bool>;
     async fn list(
         &self,
         business_id: ObjectId,
@@ -47,6 +81,14 @@ pub trait ProductRepo: Send + Sync {
         page: u32,
         limit: u32,
     ) -> ApiResult<(Vec<ProductRecord>, u64)>;
+    async fn search(
+        &self,
+        business_id: ObjectId,
+        filter: ProductFilter,
+        sort: ProductSort,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<ProductSearchResult>;
 }
 
 pub struct MongoProductRepo {
@@ -75,10 +117,55 @@ impl MongoProductRepo {
             query.insert("category", category);
         }
 
+        if let Some(ref categories) = filter.categories {
+            query.insert("category", doc! { "$in": categories });
+        }
+
         if let Some(featured) = filter.featured {
             query.insert("featured", featured);
         }
 
+        if filter.min_price.is_some() || filter.max_price.is_some() {
+            let (variants, price) = Self::priced_variants(filter);
+            let mut bounds = Vec::new();
+            if let Some(ref min) = filter.min_price {
+                bounds.push(doc! {
+                    "$gte": [{ "$toDecimal": &price }, { "$toDecimal": min.to_string() }]
+                });
+            }
+            if let Some(ref max) = filter.max_price {
+                bounds.push(doc! {
+                    "$lte": [{ "$toDecimal": &price }, { "$toDecimal": max.to_string() }]
+                });
+            }
+            query.insert(
+                "$expr",
+                doc! {
+                    "$anyElementTrue": [{
+                        "$map": {
+                            "input": variants,
+                            "as": "v",
+                            "in": { "$and": bounds },
+                        }
+                    }]
+                },
+            );
+        }
+
+        let mut variant = doc! {};
+        if let Some(ref currency) = filter.currency {
+            variant.insert(
+                format!("prices.{}", currency.as_str()),
+                doc! { "$exists": true },
+            );
+        }
+        for (name, value) in &filter.options {
+            variant.insert(format!("options.{}", name), value);
+        }
+        if !variant.is_empty() {
+            query.insert("variants", doc! { "$elemMatch": variant });
+        }
+
         if let Some(ref search) = filter.search {
             query.insert(
                 "$or",
@@ -101,6 +188,24 @@ impl MongoProductRepo {
 
         query
     }
+
+    /// The variants a product can be sold with, and the price of the `v`
+    /// variant, both in the currency of the filter.
+    fn priced_variants(filter: &ProductFilter) -> (Bson, String) {
+        let Some(ref currency) = filter.currency else {
+            return (Bson::from("$variants"), "$$v.price".to_string());
+        };
+
+        let price = format!("$$v.prices.{}", currency.as_str());
+        let variants = doc! {
+            "$filter": {
+                "input": "$variants",
+                "as": "v",
+                "cond": { "$ne": [{ "$type": &price }, "missing"] },
+            }
+        };
+        (Bson::from(variants), price)
+    }
 }
 
 #[async_trait]
@@ -138,7 +243,6 @@ impl ProductRepo for MongoProductRepo {
         Ok(product)
     }
 
-
     async fn find_active_by_id(
         &self,
         business_id: ObjectId,
@@ -169,6 +273,56 @@ impl ProductRepo for MongoProductRepo {
         Ok(product)
     }
 
+    async fn find_by_skus(
+        &self,
+        business_id: ObjectId,
+        skus: &[String],
+    ) -> ApiResult<Vec<ProductRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! { "variants.sku": { "$in": skus } })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn find_by_slugs(
+        &self,
+        business_id: ObjectId,
+        slugs: &[String],
+    ) -> ApiResult<Vec<ProductRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! { "slug": { "$in": slugs } })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn find_all(
+        &self,
+        business_id: ObjectId,
+        filter: ProductFilter,
+    ) -> ApiResult<Vec<ProductRecord>> {
+        let collection = self.get_collection(business_id);
+        let query = self.build_filter_query(&filter);
+
+        collection
+            .find(query)
+            .sort(doc! { "created_at": 1, "_id": 1 })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
     async fn find_active_by_slug(
         &self,
         business_id: ObjectId,
@@ -206,6 +360,129 @@ impl ProductRepo for MongoProductRepo {
         Ok(product)
     }
 
+    async fn update_imported(
+        &self,
+        business_id: ObjectId,
+        rows: &ImportedProduct,
+        product: &ProductRecord,
+    ) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+        let encode =
+            |e: bson::ser::Error| ApiError::internal(format!("Failed to encode product: {}", e));
+
+        let mut set = doc! { "updated_at": product.updated_at };
+        let fields = [
+            ("title", rows.title.is_some(), to_bson(&product.title)),
+            (
+                "description",
+                rows.description.is_some(),
+                to_bson(&product.description),
+            ),
+            ("status", rows.status.is_some(), to_bson(&product.status)),
+            (
+                "featured",
+                rows.featured.is_some(),
+                to_bson(&product.featured),
+            ),
+            (
+                "category",
+                rows.category.is_some(),
+                to_bson(&product.category),
+            ),
+            (
+                "tax_class",
+                rows.tax_class.is_some(),
+                to_bson(&product.tax_class),
+            ),
+            ("images", rows.images.is_some(), to_bson(&product.images)),
+        ];
+        for (field, imported, value) in fields {
+            if imported {
+                set.insert(field, value.map_err(encode)?);
+            }
+        }
+
+        let mut array_filters = Vec::new();
+        for imported in &rows.variants {
+            let Some(variant) = product.get_variant_by_sku(&imported.sku) else {
+                continue;
+            };
+
+            // New variants are added whole, unless one with the SKU was
+            // added meanwhile
+            collection
+                .update_one(
+                    doc! { "_id": product._id, "variants.sku": { "$ne": &variant.sku } },
+                    doc! { "$push": { "variants": to_bson(variant).map_err(encode)? } },
+                )
+                .await
+                .map_err(|e| ApiError::internal(format!("Failed to update product: {}", e)))?;
+
+            let name = format!("v{}", array_filters.len());
+            let fields = [
+                ("price", imported.price.is_some(), to_bson(&variant.price)),
+                (
+                    "compare_at",
+                    imported.compare_at.is_some(),
+                    to_bson(&variant.compare_at),
+                ),
+                (
+                    "stocks",
+                    imported.stocks.is_some(),
+                    to_bson(&variant.stocks),
+                ),
+                (
+                    "weight",
+                    imported.weight.is_some(),
+                    to_bson(&variant.weight),
+                ),
+                (
+                    "images",
+                    imported.images.is_some(),
+                    to_bson(&variant.images),
+                ),
+                (
+                    "options",
+                    !imported.options.is_empty(),
+                    to_bson(&variant.options),
+                ),
+                (
+                    "prices",
+                    !imported.prices.is_empty(),
+                    to_bson(&variant.prices),
+                ),
+            ];
+            let mut used = false;
+            for (field, imported, value) in fields {
+                if imported {
+                    set.insert(
+                        format!("variants.$[{}].{}", name, field),
+                        value.map_err(encode)?,
+                    );
+                    used = true;
+                }
+            }
+            // Filters the update does not use are refused
+            if used {
+                array_filters.push(doc! { format!("{}.sku", name): &variant.sku });
+            }
+        }
+
+        let mut update = collection.update_one(doc! { "_id": product._id }, doc! { "$set": set });
+        if !array_filters.is_empty() {
+            update = update.array_filters(array_filters);
+        }
+        let result = update
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update product: {}", e)))?;
+
+        if result.matched_count == 0 {
+            return Err(ApiError::not_found("product", "Product not found"));
+        }
+
+        Ok(())
+    }
+
     async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
         let collection = self.get_collection(business_id);
 
@@ -221,6 +498,57 @@ impl ProductRepo for MongoProductRepo {
         Ok(())
     }
 
+    async fn rename_category(&self, business_id: ObjectId, from: &str, to: &str) -> ApiResult<u64> {
+        let collection = self.get_collection(business_id);
+
+        let result = collection
+            .update_many(
+                doc! { "category": from },
+                doc! { "$set": { "category": to, "updated_at": DateTime::now() } },
+            )
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to rename category: {}", e)))?;
+
+        Ok(result.modified_count)
+    }
+
+    async fn find_image_urls(&self, business_id: ObjectId) -> ApiResult<Vec<String>> {
+        let collection = self.get_collection(business_id);
+
+        let mut urls = Vec::new();
+        for field in ["images", "variants.images"] {
+            let values = collection
+                .distinct(field, doc! {})
+                .await
+                .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+            urls.extend(
+                values
+                    .into_iter()
+                    .filter_map(|v| v.as_str().map(str::to_string)),
+            );
+        }
+
+        Ok(urls)
+    }
+
+    async fn references_file(&self, business_id: ObjectId, file_id: ObjectId) -> ApiResult<bool> {
+        let collection = self.get_collection(business_id);
+
+        // Same links as `file_ids_in` recognizes
+        let pattern = format!("/(files|img)/{}", file_id.to_hex());
+        let count = collection
+            .count_documents(doc! { "$or": [
+                { "images": { "$regex": &pattern } },
+                { "variants.images": { "$regex": &pattern } },
+            ] })
+            .limit(1)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        Ok(count > 0)
+    }
+
     async fn list(
         &self,
         business_id: ObjectId,
@@ -261,4 +589,125 @@ impl ProductRepo for MongoProductRepo {
 
         Ok((products, total))
     }
+
+    async fn search(
+        &self,
+        business_id: ObjectId,
+        filter: ProductFilter,
+        sort: ProductSort,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<ProductSearchResult> {
+        let collection = self.get_collection(business_id);
+        let query = self.build_filter_query(&filter);
+
+        let skip = ((page.max(1) - 1) * limit) as i64;
+        let (variants, price) = Self::priced_variants(&filter);
+        let variant_prices = doc! {
+            "$map": {
+                "input": variants,
+                "as": "v",
+                "in": { "$toDecimal": price },
+            }
+        };
+
+        let pipeline = vec![
+            doc! { "$match": query },
+            doc! { "$addFields": {
+                "_min_price": { "$min": variant_prices.clone() },
+                "_max_price": { "$max": variant_prices },
+            }},
+            doc! { "$facet": {
+                "results": [
+                    { "$sort": sort.sort_doc() },
+                    { "$skip": skip },
+                    { "$limit": limit as i64 },
+                    { "$unset": ["_min_price", "_max_price"] },
+                ],
+                "total": [
+                    { "$count": "count" },
+                ],
+                "categories": [
+                    { "$match": { "category": { "$ne": "" } } },
+                    { "$group": { "_id": "$category", "count": { "$sum": 1 } } },
+                    { "$sort": { "count": -1, "_id": 1 } },
+                    { "$project": { "_id": 0, "value": "$_id", "count": 1 } },
+                ],
+                "options": [
+                    { "$unwind": "$variants" },
+                    { "$project": { "option": { "$objectToArray": "$variants.options" } } },
+                    { "$unwind": "$option" },
+                    { "$group": {
+                        "_id": { "name": "$option.k", "value": "$option.v" },
+                        "products": { "$addToSet": "$_id" },
+                    }},
+                    { "$sort": { "_id.name": 1, "_id.value": 1 } },
+                    { "$project": {
+                        "_id": 0,
+                        "name": "$_id.name",
+                        "value": "$_id.value",
+                        "count": { "$size": "$products" },
+                    }},
+                ],
+                "prices": [
+                    { "$group": {
+                        "_id": null,
+                        "min": { "$min": "$_min_price" },
+                        "max": { "$max": "$_max_price" },
+                    }},
+                    { "$project": {
+                        "_id": 0,
+                        "min": { "$toString": "$min" },
+                        "max": { "$toString": "$max" },
+                    }},
+                ],
+            }},
+        ];
+
+        let result = collection
+            .aggregate(pipeline)
+            .await
+            .map_err(|e| ApiError::internal(format!("Aggregation failed: {}", e)))?
+            .next()
+            .await
+            .transpose()
+            .map_err(|e| {
+                ApiError::internal(format!("Failed to retrieve aggregation result: {}", e))
+            })?
+            .ok_or_else(|| ApiError::internal("Aggregation returned no result"))?;
+
+        let facets: SearchFacets = bson::from_document(result)
+            .map_err(|e| ApiError::internal(format!("Failed to decode search: {}", e)))?;
+
+        let prices = facets.prices.into_iter().next().unwrap_or_default();
+
+        Ok(ProductSearchResult {
+            products: facets.results,
+            total: facets.total.into_iter().next().map_or(0, |t| t.count),
+            categories: facets.categories,
+            options: facets.options,
+            price_min: prices.min,
+            price_max: prices.max,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchFacets {
+    results: Vec<ProductRecord>,
+    total: Vec<CountRow>,
+    categories: Vec<FacetCount>,
+    options: Vec<OptionFacetCount>,
+    prices: Vec<PriceRow>,
+}
+
+#[derive(Deserialize)]
+struct CountRow {
+    count: u64,
+}
+
+#[derive(Deserialize, Default)]
+struct PriceRow {
+    min: Option<BigDecimal>,
+    max: Option<BigDecimal>,
 }
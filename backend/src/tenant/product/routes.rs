@@ -1,21 +1,24 @@
-use axum::extract::{Path, Query, State};
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::header;
+use axum::response::IntoResponse;
+use chrono::Utc;
 use macros::routes;
 
-use super::api::*;
 use super::super::store::extractors::Store;
+use super::api::*;
 use crate::extractors::cookies::FromCookies;
 use crate::extractors::json::Json;
 use crate::platform::business::api::BusinessSession;
 use crate::platform::user::api::MessageResponse;
 use crate::types::id::Id;
-use crate::utils::error::ApiResult;
+use crate::utils::error::{ApiError, ApiResult};
 use crate::AppState;
 
 pub struct ProductRoutes;
 
 #[routes(prefix = "/api/v1/products", state = AppState)]
 impl ProductRoutes {
-    #[route(method=post, path="/create", res=ProductDto)]
+    #[route(method=post, path="/create", res=ProductDto, perm="products:write")]
     async fn create_product(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -23,12 +26,17 @@ impl ProductRoutes {
     ) -> ApiResult<Json<ProductDto>> {
         state
             .product_service
-            .create(business, product)
+            .create(
+                &state.category_service,
+                &state.tax_service,
+                business,
+                product,
+            )
             .await
             .map(Json)
     }
 
-    #[route(method=post, path="/{product_id}", res=ProductDto)]
+    #[route(method=post, path="/{product_id}", res=ProductDto, perm="products:read")]
     async fn get_product(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -41,7 +49,7 @@ impl ProductRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/list", res=ProductListResponse)]
+    #[route(method=post, path="/list", res=ProductListResponse, perm="products:read")]
     async fn list_products(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -54,7 +62,67 @@ impl ProductRoutes {
             .map(Json)
     }
 
-    #[route(method=patch, path="/{product_id}", res=ProductDto)]
+    #[route(method=get, path="/export", perm="products:read")]
+    async fn export_products(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: ProductExportQuery,
+    ) -> ApiResult<impl IntoResponse> {
+        let bytes = state
+            .product_service
+            .export_products(business, query)
+            .await?;
+        let file_name = format!("products-{}.csv", Utc::now().format("%Y-%m-%d"));
+
+        Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", file_name),
+                ),
+            ],
+            bytes,
+        ))
+    }
+
+    #[route(method=post, path="/import", res=ProductImportReport, perm="products:write")]
+    async fn import_products(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: ProductImportQuery,
+        mut multipart: Multipart,
+    ) -> ApiResult<Json<ProductImportReport>> {
+        let mut file = None;
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?
+        {
+            if field.name() == Some("file") {
+                let bytes = field
+                    .bytes()
+                    .await
+                    .map_err(|e| ApiError::invalid_body("multipart/form-data", e.to_string()))?;
+                file = Some(bytes.to_vec());
+            }
+        }
+        let bytes = file.ok_or(ApiError::missing_field("file"))?;
+
+        state
+            .product_service
+            .import_products(
+                &state.category_service,
+                &state.tax_service,
+                business,
+                bytes,
+                query,
+            )
+            .await
+            .map(Json)
+    }
+
+    #[route(method=patch, path="/{product_id}", res=ProductDto, perm="products:write")]
     async fn edit_product(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -63,12 +131,18 @@ impl ProductRoutes {
     ) -> ApiResult<Json<ProductDto>> {
         state
             .product_service
-            .update_product(business, product_id, update_req)
+            .update_product(
+                &state.category_service,
+                &state.tax_service,
+                business,
+                product_id,
+                update_req,
+            )
             .await
             .map(Json)
     }
 
-    #[route(method=delete, path="/{product_id}", res=MessageResponse)]
+    #[route(method=delete, path="/{product_id}", res=MessageResponse, perm="products:delete")]
     async fn delete_product(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -85,7 +159,6 @@ impl ProductRoutes {
     }
 }
 
-
 pub struct PubProductRoutes;
 
 #[routes(prefix = "/api/v1/products", state = AppState)]
@@ -99,8 +172,10 @@ impl PubProductRoutes {
         state
             .product_service
             .pub_get_product(store_key.business_id, product_id)
-            .await
+            .await?
+            .localize(&store_key.currency, &store_key.base_currency)
             .map(Json)
+            .ok_or(ApiError::not_found("product", product_id.to_string()))
     }
 
     #[route(method=get, path="/list", res=ProductListResponse)]
@@ -111,8 +186,20 @@ impl PubProductRoutes {
     ) -> ApiResult<Json<ProductListResponse>> {
         state
             .product_service
-            .pub_list_products(store_key.business_id, query)
+            .pub_list_products(
+                store_key.business_id,
+                &store_key.currency,
+                &store_key.base_currency,
+                query,
+            )
             .await
-            .map(Json)
+            .map(|mut res| {
+                res.products = res
+                    .products
+                    .into_iter()
+                    .filter_map(|p| p.localize(&store_key.currency, &store_key.base_currency))
+                    .collect();
+                Json(res)
+            })
     }
 }
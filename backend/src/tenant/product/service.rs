@@ -1,10 +1,31 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use bson::oid::ObjectId;
+
 use super::api::*;
 use super::domain::*;
 use super::repo::ProductRepo;
+use super::table::{self, Layout, ProductColumn};
 use crate::platform::business::api::BusinessSession;
+use crate::tenant::category::repo::CategoryRepo;
+use crate::tenant::category::service::CategoryService;
 use crate::tenant::product::domain::ProductVariant;
+use crate::tenant::tax::repo::TaxRepo;
+use crate::tenant::tax::service::TaxService;
+use crate::types::currency::Currency;
 use crate::types::id::Id;
+use crate::types::slug::Slug;
 use crate::utils::error::{ApiError, ApiResult};
+use crate::utils::serde_helpers::JsonOption;
+use crate::utils::spreadsheet::{self, ImportRowError, Table};
+
+const MAX_IMPORTED_ROWS: usize = 5000;
+
+fn reason(e: ApiError) -> String {
+    let problem = e.to_problem_details();
+    problem.detail.unwrap_or(problem.title)
+}
 
 pub struct ProductService<R: ProductRepo> {
     repo: R,
@@ -15,31 +36,44 @@ impl<R: ProductRepo> ProductService<R> {
         Self { repo }
     }
 
-    pub async fn create(
+    pub async fn create<C: CategoryRepo, T: TaxRepo>(
         &self,
+        category_service: &CategoryService<C>,
+        tax_service: &TaxService<T>,
         business: BusinessSession,
         create_req: ProductCreateDto,
     ) -> ApiResult<ProductDto> {
+        category_service
+            .ensure_exists(business.business_id, &create_req.category)
+            .await?;
+        if let Some(ref tax_class) = create_req.tax_class {
+            tax_service
+                .ensure_exists(business.business_id, tax_class)
+                .await?;
+        }
+
+        let mut record = ProductRecord::new(
+            create_req.title,
+            create_req.description,
+            create_req.status.into(),
+            create_req.featured,
+            create_req.category,
+            create_req.images,
+            create_req.variants,
+            create_req.slug,
+        );
+        record.tax_class = create_req.tax_class;
+
         self.repo
-            .create(
-                business.business_id.into_inner(),
-                ProductRecord::new(
-                    create_req.title,
-                    create_req.description,
-                    create_req.status.into(),
-                    create_req.featured,
-                    create_req.category,
-                    create_req.images,
-                    create_req.variants,
-                    create_req.slug,
-                ),
-            )
+            .create(business.business_id.into_inner(), record)
             .await
             .map(Into::into)
     }
 
-    pub async fn update_product(
+    pub async fn update_product<C: CategoryRepo, T: TaxRepo>(
         &self,
+        category_service: &CategoryService<C>,
+        tax_service: &TaxService<T>,
         business: BusinessSession,
         product_id: Id,
         update_req: ProductUpdate,
@@ -52,6 +86,17 @@ impl<R: ProductRepo> ProductService<R> {
             .await?
             .ok_or(ApiError::not_found("product", id.to_hex()))?;
 
+        if let JsonOption::Value(ref category) = update_req.category {
+            category_service
+                .ensure_exists(business.business_id, category)
+                .await?;
+        }
+        if let JsonOption::Value(ref tax_class) = update_req.tax_class {
+            tax_service
+                .ensure_exists(business.business_id, tax_class)
+                .await?;
+        }
+
         update_req.title.map(|v| record.title = v);
         update_req.description.map(|v| record.description = v);
         update_req.category.map(|v| record.category = v);
@@ -60,6 +105,7 @@ impl<R: ProductRepo> ProductService<R> {
         update_req.images.map(|v| record.images = v);
         update_req.featured.map(|v| record.featured = v);
         update_req.status.map(|v| record.status = v.into());
+        update_req.tax_class.ok_then(|v| record.tax_class = v);
 
         self.repo
             .update(business_id, id, record)
@@ -98,11 +144,7 @@ impl<R: ProductRepo> ProductService<R> {
             .map(Into::into)
     }
 
-    pub async fn pub_get_product(
-        &self,
-        business_id: Id,
-        product_id: Id,
-    ) -> ApiResult<ProductDto> {
+    pub async fn pub_get_product(&self, business_id: Id, product_id: Id) -> ApiResult<ProductDto> {
         let id = product_id.into_inner();
         self.repo
             .find_active_by_id(business_id.into_inner(), id)
@@ -111,6 +153,191 @@ impl<R: ProductRepo> ProductService<R> {
             .map(Into::into)
     }
 
+    pub async fn find_by_skus(
+        &self,
+        business_id: Id,
+        skus: &[String],
+    ) -> ApiResult<Vec<ProductDto>> {
+        self.repo
+            .find_by_skus(business_id.into_inner(), skus)
+            .await
+            .map(|products| products.into_iter().map(Into::into).collect())
+    }
+
+    /// The catalog as CSV, one row per variant.
+    pub async fn export_products(
+        &self,
+        business: BusinessSession,
+        query: ProductExportQuery,
+    ) -> ApiResult<Vec<u8>> {
+        let filter = ProductFilter {
+            status: query.status.map(Into::into),
+            category: query.category,
+            ..Default::default()
+        };
+        let products = self
+            .repo
+            .find_all(business.business_id.into_inner(), filter)
+            .await?;
+
+        let layout = Layout::of(&products);
+        let mut bytes = spreadsheet::CSV_BOM.to_vec();
+        let rows =
+            std::iter::once(layout.header()).chain(products.iter().flat_map(|p| layout.rows(p)));
+        for row in rows {
+            bytes.extend(spreadsheet::csv_row(&row).map_err(ApiError::internal)?);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Creates and updates products from a CSV laid out like an export,
+    /// matching them by slug and their variants by SKU. A dry run only
+    /// reports what is wrong with the file, otherwise it is written once it
+    /// has no errors.
+    pub async fn import_products<C: CategoryRepo, T: TaxRepo>(
+        &self,
+        category_service: &CategoryService<C>,
+        tax_service: &TaxService<T>,
+        business: BusinessSession,
+        bytes: Vec<u8>,
+        query: ProductImportQuery,
+    ) -> ApiResult<ProductImportReport> {
+        let dry_run = query.dry_run.unwrap_or(true);
+        let business_id = business.business_id.into_inner();
+
+        let sheet = tokio::task::spawn_blocking(move || Table::read(&bytes))
+            .await
+            .map_err(|_| ApiError::internal("Import task failed"))?
+            .map_err(|e| ApiError::validation("file", e))?;
+        if sheet.rows.len() > MAX_IMPORTED_ROWS {
+            return Err(ApiError::validation(
+                "file",
+                format!("At most {} rows can be imported at once", MAX_IMPORTED_ROWS),
+            ));
+        }
+
+        let mut errors = Vec::new();
+        let imported = table::parse(&sheet, &mut errors);
+
+        // Each category and tax class is looked up once, keeping the reason
+        // it was refused
+        let mut refused: HashMap<(ProductColumn, String), Option<String>> = HashMap::new();
+        for product in &imported {
+            if let Some(ref category) = product.category {
+                let key = (ProductColumn::Category, category.clone());
+                if let Entry::Vacant(entry) = refused.entry(key) {
+                    let result = category_service
+                        .ensure_exists(business.business_id, category)
+                        .await;
+                    entry.insert(result.err().map(reason));
+                }
+            }
+            if let Some(ref tax_class) = product.tax_class {
+                let key = (ProductColumn::TaxClass, tax_class.to_string());
+                if let Entry::Vacant(entry) = refused.entry(key) {
+                    let result = tax_service
+                        .ensure_exists(business.business_id, tax_class)
+                        .await;
+                    entry.insert(result.err().map(reason));
+                }
+            }
+        }
+
+        let slugs: Vec<String> = imported.iter().map(|p| p.slug.clone()).collect();
+        let skus: Vec<String> = imported
+            .iter()
+            .flat_map(|p| p.variants.iter().map(|v| v.sku.clone()))
+            .collect();
+        let existing = match slugs.is_empty() {
+            true => Vec::new(),
+            false => self.repo.find_by_slugs(business_id, &slugs).await?,
+        };
+        let owners = match skus.is_empty() {
+            true => Vec::new(),
+            false => self.repo.find_by_skus(business_id, &skus).await?,
+        };
+
+        let mut records = Vec::with_capacity(imported.len());
+        for product in imported {
+            let before = errors.len();
+
+            let references = [
+                (ProductColumn::Category, product.category.clone()),
+                (
+                    ProductColumn::TaxClass,
+                    product.tax_class.as_ref().map(Slug::to_string),
+                ),
+            ];
+            for (column, value) in references {
+                let Some(value) = value else { continue };
+                if let Some(Some(message)) = refused.get(&(column, value)) {
+                    errors.push(ImportRowError {
+                        row: product.row,
+                        column: Some(column.as_ref().to_string()),
+                        message: message.clone(),
+                    });
+                }
+            }
+
+            for variant in &product.variants {
+                let owner = owners.iter().find(|p| {
+                    p.slug != product.slug && p.get_variant_by_sku(&variant.sku).is_some()
+                });
+                if let Some(owner) = owner {
+                    errors.push(ImportRowError {
+                        row: variant.row,
+                        column: Some(ProductColumn::Sku.as_ref().to_string()),
+                        message: format!(
+                            "SKU '{}' belongs to product '{}'",
+                            variant.sku, owner.slug
+                        ),
+                    });
+                }
+            }
+
+            if errors.len() > before {
+                continue;
+            }
+
+            // Existing products keep the rows, so only what they set is saved
+            let current = existing.iter().find(|p| p.slug == product.slug).cloned();
+            let rows = current.is_some().then(|| product.clone());
+            match product.merge(current) {
+                Ok(record) => records.push((rows, record)),
+                Err(e) => errors.push(e),
+            }
+        }
+        errors.sort_by_key(|e| e.row);
+
+        let created = records.iter().filter(|(rows, _)| rows.is_none()).count() as u32;
+        let mut report = ProductImportReport {
+            dry_run,
+            rows: sheet.rows.len() as u32,
+            created,
+            updated: records.len() as u32 - created,
+            errors,
+            imported: 0,
+        };
+        if dry_run || !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        for (rows, record) in records {
+            match rows {
+                None => self.repo.create(business_id, record).await.map(|_| ())?,
+                Some(rows) => {
+                    self.repo
+                        .update_imported(business_id, &rows, &record)
+                        .await?
+                }
+            }
+            report.imported += 1;
+        }
+
+        Ok(report)
+    }
+
     pub async fn get_variant_by_sku(
         &self,
         business: BusinessSession,
@@ -136,8 +363,10 @@ impl<R: ProductRepo> ProductService<R> {
         let filter = ProductFilter {
             status: status.map(Into::into),
             category,
+            categories: None,
             featured,
             search,
+            ..Default::default()
         };
         let page = page.unwrap_or(1);
         let limit = limit.unwrap_or(10);
@@ -156,9 +385,12 @@ impl<R: ProductRepo> ProductService<R> {
         })
     }
 
+    /// Active products a store selling in `currency` can sell.
     pub async fn pub_list_products(
         &self,
         business_id: Id,
+        currency: &Currency,
+        base: &Currency,
         query: ProductListQuery,
     ) -> ApiResult<ProductListResponse> {
         let ProductListQuery {
@@ -173,9 +405,12 @@ impl<R: ProductRepo> ProductService<R> {
         let filter = ProductFilter {
             status: ProductStatus::Active.into(),
             category,
+            categories: None,
             featured,
             search,
-        };
+            ..Default::default()
+        }
+        .sold_in(currency, base);
         let page = page.unwrap_or(1);
         let limit = limit.unwrap_or(10);
 
@@ -193,6 +428,156 @@ impl<R: ProductRepo> ProductService<R> {
         })
     }
 
+    /// Searches what a store selling in `currency` can sell, filtering,
+    /// sorting and faceting on its prices there.
+    pub async fn pub_search_products<C: CategoryRepo>(
+        &self,
+        category_service: &CategoryService<C>,
+        business_id: Id,
+        currency: &Currency,
+        base: &Currency,
+        query: ProductSearchQuery,
+    ) -> ApiResult<ProductSearchResponse> {
+        // A known category also matches its subcategories, anything else is
+        // matched literally so free-form product categories keep working.
+        let (category, categories) = match query.category {
+            Some(ref slug) => match category_service.pub_get_collection(business_id, slug).await {
+                Ok(collection) => (None, Some(collection.subtree_slugs)),
+                Err(ApiError::NotFound { .. }) => (Some(slug.clone()), None),
+                Err(e) => return Err(e),
+            },
+            None => (None, None),
+        };
+
+        let filter = ProductFilter {
+            status: ProductStatus::Active.into(),
+            category,
+            categories,
+            featured: query.featured,
+            search: query.search.clone(),
+            min_price: query.min_price.clone(),
+            max_price: query.max_price.clone(),
+            options: query.options.clone(),
+            currency: None,
+        }
+        .sold_in(currency, base);
+
+        let result = self
+            .repo
+            .search(
+                business_id.into_inner(),
+                filter,
+                query.sort,
+                query.page,
+                query.limit,
+            )
+            .await?;
+
+        let names = category_service.pub_category_names(business_id).await?;
+        let categories = result
+            .categories
+            .into_iter()
+            .map(|c| FacetValue {
+                label: names
+                    .get(&c.value)
+                    .cloned()
+                    .unwrap_or_else(|| c.value.clone()),
+                selected: query.category.as_ref() == Some(&c.value),
+                value: c.value,
+                count: c.count,
+            })
+            .collect();
+
+        let mut options: Vec<OptionFacet> = Vec::new();
+        for o in result.options {
+            let value = FacetValue {
+                label: o.value.clone(),
+                selected: query.options.get(&o.name) == Some(&o.value),
+                value: o.value,
+                count: o.count,
+            };
+            match options.iter_mut().find(|f| f.name == o.name) {
+                Some(facet) => facet.values.push(value),
+                None => options.push(OptionFacet {
+                    name: o.name,
+                    values: vec![value],
+                }),
+            }
+        }
+
+        Ok(ProductSearchResponse {
+            products: result.products.into_iter().map(Into::into).collect(),
+            total: result.total,
+            page: query.page,
+            limit: query.limit,
+            facets: ProductFacets {
+                categories,
+                options,
+                price_min: result.price_min,
+                price_max: result.price_max,
+            },
+        })
+    }
+
+    pub async fn pub_list_products_in_categories(
+        &self,
+        business_id: Id,
+        currency: &Currency,
+        base: &Currency,
+        categories: Vec<String>,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<ProductListResponse> {
+        let filter = ProductFilter {
+            status: ProductStatus::Active.into(),
+            categories: Some(categories),
+            ..Default::default()
+        }
+        .sold_in(currency, base);
+
+        let (products, total) = self
+            .repo
+            .list(business_id.into_inner(), filter, page, limit)
+            .await?;
+
+        let views: Vec<_> = products.into_iter().map(Into::into).collect();
+        Ok(ProductListResponse {
+            products: views,
+            total,
+            page,
+            limit,
+        })
+    }
+
+    pub async fn count_in_category(&self, business_id: Id, category: &str) -> ApiResult<u64> {
+        let filter = ProductFilter {
+            category: Some(category.to_string()),
+            ..Default::default()
+        };
+
+        let (_, total) = self
+            .repo
+            .list(business_id.into_inner(), filter, 1, 1)
+            .await?;
+        Ok(total)
+    }
+
+    pub async fn rename_category(&self, business_id: Id, from: &str, to: &str) -> ApiResult<u64> {
+        self.repo
+            .rename_category(business_id.into_inner(), from, to)
+            .await
+    }
+
+    pub async fn image_urls(&self, business_id: Id) -> ApiResult<Vec<String>> {
+        self.repo.find_image_urls(business_id.into_inner()).await
+    }
+
+    pub async fn references_file(&self, business_id: Id, file_id: ObjectId) -> ApiResult<bool> {
+        self.repo
+            .references_file(business_id.into_inner(), file_id)
+            .await
+    }
+
     pub async fn pub_list_related_products(
         &self,
         business_id: Id,
@@ -212,8 +597,10 @@ impl<R: ProductRepo> ProductService<R> {
         let filter = ProductFilter {
             status: ProductStatus::Active.into(),
             category: Some(category),
+            categories: None,
             featured: None,
             search: None,
+            ..Default::default()
         };
 
         let (products, _) = self
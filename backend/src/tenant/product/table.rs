@@ -0,0 +1,566 @@
+//! Products as CSV rows, one per variant, for catalog exports and imports.
+//!
+//! Product columns are repeated on every row of its variants and read from
+//! the first one on import. Options and currency prices get a column each,
+//! named `option:<name>` and `price:<currency>`. The export is laid out the
+//! way imports read it, so a catalog round trips.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use bson::DateTime;
+use indexmap::{IndexMap, IndexSet};
+use strum::{AsRefStr, EnumString};
+
+use super::domain::{ProductRecord, ProductStatus, ProductVariant};
+use crate::types::currency::Currency;
+use crate::types::name::Name;
+use crate::types::slug::Slug;
+use crate::utils::spreadsheet::{Cell, ImportRowError, Row, Table};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ProductColumn {
+    Slug,
+    Title,
+    Description,
+    Status,
+    Featured,
+    Category,
+    TaxClass,
+    Images,
+    Sku,
+    Price,
+    CompareAt,
+    Stocks,
+    Weight,
+    VariantImages,
+}
+
+const COLUMNS: [ProductColumn; 14] = [
+    ProductColumn::Slug,
+    ProductColumn::Title,
+    ProductColumn::Description,
+    ProductColumn::Status,
+    ProductColumn::Featured,
+    ProductColumn::Category,
+    ProductColumn::TaxClass,
+    ProductColumn::Images,
+    ProductColumn::Sku,
+    ProductColumn::Price,
+    ProductColumn::CompareAt,
+    ProductColumn::Stocks,
+    ProductColumn::Weight,
+    ProductColumn::VariantImages,
+];
+
+const OPTION_PREFIX: &str = "option:";
+const PRICE_PREFIX: &str = "price:";
+// Between image URLs sharing a cell
+const IMAGE_SEPARATOR: &str = " | ";
+
+/// The option and currency columns needed by a set of products.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    options: IndexSet<String>,
+    currencies: IndexSet<Currency>,
+}
+
+impl Layout {
+    pub fn of(products: &[ProductRecord]) -> Self {
+        let mut layout = Self::default();
+        for variant in products.iter().flat_map(|p| &p.variants) {
+            layout.options.extend(variant.options.keys().cloned());
+            layout.currencies.extend(variant.prices.keys().cloned());
+        }
+        layout
+    }
+
+    pub fn header(&self) -> Vec<Cell> {
+        COLUMNS
+            .iter()
+            .map(|c| c.as_ref().to_string())
+            .chain(
+                self.options
+                    .iter()
+                    .map(|o| format!("{}{}", OPTION_PREFIX, o)),
+            )
+            .chain(
+                self.currencies
+                    .iter()
+                    .map(|c| format!("{}{}", PRICE_PREFIX, c)),
+            )
+            .map(Cell::Text)
+            .collect()
+    }
+
+    pub fn rows(&self, product: &ProductRecord) -> Vec<Vec<Cell>> {
+        product
+            .variants
+            .iter()
+            .map(|variant| {
+                COLUMNS
+                    .iter()
+                    .map(|c| cell(product, variant, *c))
+                    .chain(
+                        self.options
+                            .iter()
+                            .map(|o| Cell::optional(variant.options.get(o).cloned())),
+                    )
+                    .chain(self.currencies.iter().map(|c| {
+                        variant
+                            .prices
+                            .get(c)
+                            .map_or(Cell::Empty, |p| Cell::Number(p.clone()))
+                    }))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn cell(product: &ProductRecord, variant: &ProductVariant, column: ProductColumn) -> Cell {
+    let number = |n: &Option<BigDecimal>| n.clone().map_or(Cell::Empty, Cell::Number);
+
+    match column {
+        ProductColumn::Slug => Cell::text(&product.slug),
+        ProductColumn::Title => Cell::text(product.title.to_string()),
+        ProductColumn::Description => Cell::text(&product.description),
+        ProductColumn::Status => Cell::text(match product.status {
+            ProductStatus::Active => "active",
+            ProductStatus::Inactive => "inactive",
+            ProductStatus::Archived => "archived",
+        }),
+        ProductColumn::Featured => Cell::text(product.featured.to_string()),
+        ProductColumn::Category => Cell::text(&product.category),
+        ProductColumn::TaxClass => Cell::optional(product.tax_class.as_ref().map(Slug::to_string)),
+        ProductColumn::Images => Cell::text(product.images.join(IMAGE_SEPARATOR)),
+        ProductColumn::Sku => Cell::text(&variant.sku),
+        ProductColumn::Price => Cell::Number(variant.price.clone()),
+        ProductColumn::CompareAt => number(&variant.compare_at),
+        ProductColumn::Stocks => Cell::Number(BigDecimal::from(variant.stocks as u64)),
+        ProductColumn::Weight => number(&variant.weight),
+        ProductColumn::VariantImages => Cell::text(variant.images.join(IMAGE_SEPARATOR)),
+    }
+}
+
+/// A variant row. Empty cells leave the value of an existing variant as is.
+#[derive(Debug, Clone)]
+pub struct ImportedVariant {
+    pub row: u32,
+    pub sku: String,
+    pub price: Option<BigDecimal>,
+    pub compare_at: Option<BigDecimal>,
+    pub stocks: Option<usize>,
+    pub weight: Option<BigDecimal>,
+    pub images: Option<Vec<String>>,
+    pub options: IndexMap<String, String>,
+    pub prices: IndexMap<Currency, BigDecimal>,
+}
+
+/// The rows of a product. Empty cells leave the value of an existing
+/// product as is.
+#[derive(Debug, Clone)]
+pub struct ImportedProduct {
+    pub row: u32,
+    pub slug: String,
+    pub title: Option<Name>,
+    pub description: Option<String>,
+    pub status: Option<ProductStatus>,
+    pub featured: Option<bool>,
+    pub category: Option<String>,
+    pub tax_class: Option<Slug>,
+    pub images: Option<Vec<String>>,
+    pub variants: Vec<ImportedVariant>,
+}
+
+impl ImportedProduct {
+    /// Rows of the product, the variant ones included.
+    pub fn rows(&self) -> impl Iterator<Item = u32> + '_ {
+        std::iter::once(self.row).chain(self.variants.iter().map(|v| v.row))
+    }
+
+    /// Applies the rows onto the product with the same slug, or makes a new
+    /// one. Variants are matched by SKU, those missing from the sheet are
+    /// kept.
+    pub fn merge(self, existing: Option<ProductRecord>) -> Result<ProductRecord, ImportRowError> {
+        let mut product = match existing {
+            Some(product) => product,
+            None => {
+                let title = self.title.clone().ok_or_else(|| ImportRowError {
+                    row: self.row,
+                    column: Some(ProductColumn::Title.as_ref().to_string()),
+                    message: "Required for a new product".to_string(),
+                })?;
+                ProductRecord::new(
+                    title,
+                    String::new(),
+                    ProductStatus::default(),
+                    false,
+                    String::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    self.slug.clone(),
+                )
+            }
+        };
+
+        if let Some(v) = self.title {
+            product.title = v;
+        }
+        if let Some(v) = self.description {
+            product.description = v;
+        }
+        if let Some(v) = self.status {
+            product.status = v;
+        }
+        if let Some(v) = self.featured {
+            product.featured = v;
+        }
+        if let Some(v) = self.category {
+            product.category = v;
+        }
+        if let Some(v) = self.tax_class {
+            product.tax_class = Some(v);
+        }
+        if let Some(v) = self.images {
+            product.images = v;
+        }
+
+        for imported in self.variants {
+            let index = match product.variants.iter().position(|v| v.sku == imported.sku) {
+                Some(index) => index,
+                None => {
+                    let price = imported.price.clone().ok_or_else(|| ImportRowError {
+                        row: imported.row,
+                        column: Some(ProductColumn::Price.as_ref().to_string()),
+                        message: "Required for a new variant".to_string(),
+                    })?;
+                    product.variants.push(ProductVariant {
+                        sku: imported.sku.clone(),
+                        price,
+                        compare_at: None,
+                        stocks: 0,
+                        weight: None,
+                        images: Vec::new(),
+                        options: IndexMap::new(),
+                        prices: IndexMap::new(),
+                    });
+                    product.variants.len() - 1
+                }
+            };
+            let variant = &mut product.variants[index];
+
+            if let Some(v) = imported.price {
+                variant.price = v;
+            }
+            if let Some(v) = imported.compare_at {
+                variant.compare_at = Some(v);
+            }
+            if let Some(v) = imported.stocks {
+                variant.stocks = v;
+            }
+            if let Some(v) = imported.weight {
+                variant.weight = Some(v);
+            }
+            if let Some(v) = imported.images {
+                variant.images = v;
+            }
+            variant.options.extend(imported.options);
+            variant.prices.extend(imported.prices);
+        }
+
+        product.updated_at = DateTime::now();
+        Ok(product)
+    }
+}
+
+/// Reads the products of the sheet, collecting what is wrong with its rows.
+pub fn parse(table: &Table, errors: &mut Vec<ImportRowError>) -> Vec<ImportedProduct> {
+    let required = [ProductColumn::Slug, ProductColumn::Sku];
+    if !table.has_columns(&required, errors) {
+        return Vec::new();
+    }
+
+    let mut products: Vec<ImportedProduct> = Vec::new();
+    let mut slugs: HashMap<String, usize> = HashMap::new();
+    let mut skus: HashMap<String, u32> = HashMap::new();
+
+    for index in 0..table.rows.len() {
+        let mut row = Row::new(table, index, errors);
+        let errors = row.errors();
+
+        let slug = row.parsed(ProductColumn::Slug, |v| Slug::new(v).map(|s| s.to_string()));
+        let slug = slug.or_else(|| {
+            if row.errors() == errors {
+                row.error(ProductColumn::Slug, "Required");
+            }
+            None
+        });
+        let variant = variant(&mut row, table);
+        if let Some(ref variant) = variant {
+            if let Some(first) = skus.insert(variant.sku.clone(), variant.row) {
+                row.error(
+                    ProductColumn::Sku,
+                    format!("SKU '{}' is already used on row {}", variant.sku, first),
+                );
+            }
+        }
+        let (Some(slug), Some(variant)) = (slug, variant) else {
+            continue;
+        };
+
+        match slugs.get(&slug) {
+            Some(&position) => products[position].variants.push(variant),
+            None => {
+                let Some(mut product) = product(&mut row, slug.clone()) else {
+                    continue;
+                };
+                product.variants.push(variant);
+                slugs.insert(slug, products.len());
+                products.push(product);
+            }
+        }
+    }
+
+    products
+}
+
+fn product(row: &mut Row, slug: String) -> Option<ImportedProduct> {
+    let errors = row.errors();
+
+    let title = row.parsed(ProductColumn::Title, Name::new);
+    let status = row.parsed(ProductColumn::Status, |v| match v.to_lowercase().as_str() {
+        "active" => Ok(ProductStatus::Active),
+        "inactive" => Ok(ProductStatus::Inactive),
+        "archived" => Ok(ProductStatus::Archived),
+        _ => Err(format!("Unknown status '{}'", v)),
+    });
+    let featured = row.parsed(ProductColumn::Featured, |v| {
+        match v.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(true),
+            "false" | "no" | "0" => Ok(false),
+            _ => Err(format!("'{}' is not true or false", v)),
+        }
+    });
+    let tax_class = row.parsed(ProductColumn::TaxClass, Slug::new);
+
+    if row.errors() > errors {
+        return None;
+    }
+
+    Some(ImportedProduct {
+        row: row.number,
+        slug,
+        title,
+        description: row.text(ProductColumn::Description),
+        status,
+        featured,
+        category: row.text(ProductColumn::Category),
+        tax_class,
+        images: row.text(ProductColumn::Images).map(|v| images(&v)),
+        variants: Vec::new(),
+    })
+}
+
+fn variant(row: &mut Row, table: &Table) -> Option<ImportedVariant> {
+    let errors = row.errors();
+
+    let sku = row.required(ProductColumn::Sku);
+    let price = row.parsed(ProductColumn::Price, amount);
+    let compare_at = row.parsed(ProductColumn::CompareAt, amount);
+    let weight = row.parsed(ProductColumn::Weight, amount);
+    let stocks = row.parsed(ProductColumn::Stocks, |v| {
+        v.parse::<usize>()
+            .map_err(|_| format!("'{}' is not a whole number", v))
+    });
+
+    let mut options = IndexMap::new();
+    let mut prices = IndexMap::new();
+    for header in &table.headers {
+        if let Some(name) = strip_prefix(header, OPTION_PREFIX) {
+            if let Some(value) = row.text(header) {
+                options.insert(name.to_string(), value);
+            }
+        } else if let Some(code) = strip_prefix(header, PRICE_PREFIX) {
+            if row.text(header).is_none() {
+                continue;
+            }
+            let Ok(currency) = Currency::new(code) else {
+                row.error(header, format!("'{}' is not a currency code", code));
+                continue;
+            };
+            if let Some(price) = row.parsed(header, amount) {
+                prices.insert(currency, price);
+            }
+        }
+    }
+
+    if row.errors() > errors {
+        return None;
+    }
+
+    Some(ImportedVariant {
+        row: row.number,
+        sku: sku?,
+        price,
+        compare_at,
+        stocks,
+        weight,
+        images: row.text(ProductColumn::VariantImages).map(|v| images(&v)),
+        options,
+        prices,
+    })
+}
+
+fn strip_prefix<'a>(header: &'a str, prefix: &str) -> Option<&'a str> {
+    let name = header
+        .get(..prefix.len())
+        .filter(|p| p.eq_ignore_ascii_case(prefix))
+        .map(|_| header[prefix.len()..].trim())?;
+    (!name.is_empty()).then_some(name)
+}
+
+fn images(value: &str) -> Vec<String> {
+    value
+        .split('|')
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn amount(value: &str) -> Result<BigDecimal, String> {
+    match BigDecimal::from_str(value) {
+        Ok(amount) if amount >= BigDecimal::from(0) => Ok(amount),
+        _ => Err(format!("'{}' is not a valid amount", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::spreadsheet::csv_row;
+
+    fn product() -> ProductRecord {
+        let variant = |sku: &str, size: &str| ProductVariant {
+            sku: sku.to_string(),
+            price: BigDecimal::from(1500),
+            compare_at: Some(BigDecimal::from(2000)),
+            stocks: 4,
+            weight: None,
+            images: vec!["https://cdn.test/a.webp".to_string()],
+            options: IndexMap::from([("Size".to_string(), size.to_string())]),
+            prices: IndexMap::from([(Currency::new("EUR").unwrap(), "9.5".parse().unwrap())]),
+        };
+
+        let mut product = ProductRecord::new(
+            Name::new("Basic T-shirt").unwrap(),
+            "Cotton, 180 gsm".to_string(),
+            ProductStatus::Active,
+            true,
+            "shirts".to_string(),
+            vec![
+                "https://cdn.test/1.webp".to_string(),
+                "https://cdn.test/2.webp".to_string(),
+            ],
+            vec![variant("TS-M", "M"), variant("TS-L", "L")],
+            "t-shirt".to_string(),
+        );
+        product.tax_class = Some(Slug::new("standard").unwrap());
+        product
+    }
+
+    fn table(rows: &[&[&str]]) -> Table {
+        Table {
+            headers: rows[0].iter().map(|h| h.to_string()).collect(),
+            rows: rows[1..]
+                .iter()
+                .map(|r| r.iter().map(|v| v.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let original = product();
+        let layout = Layout::of(std::slice::from_ref(&original));
+
+        let mut bytes = csv_row(&layout.header()).unwrap();
+        for row in layout.rows(&original) {
+            bytes.extend(csv_row(&row).unwrap());
+        }
+        let sheet = Table::read(&bytes).unwrap();
+        assert!(sheet.headers.contains(&"option:Size".to_string()));
+
+        let mut errors = Vec::new();
+        let mut products = parse(&sheet, &mut errors);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0].rows().collect::<Vec<_>>(), vec![2, 2, 3]);
+
+        let merged = products.remove(0).merge(None).unwrap();
+        assert_eq!(merged.title, original.title);
+        assert_eq!(merged.description, original.description);
+        assert_eq!(merged.images, original.images);
+        assert_eq!(merged.tax_class, original.tax_class);
+        assert!(merged.featured && merged.is_active());
+        assert_eq!(merged.variants.len(), 2);
+        for (merged, original) in merged.variants.iter().zip(&original.variants) {
+            assert_eq!(merged.sku, original.sku);
+            assert_eq!(merged.price, original.price);
+            assert_eq!(merged.compare_at, original.compare_at);
+            assert_eq!(merged.stocks, original.stocks);
+            assert_eq!(merged.images, original.images);
+            assert_eq!(merged.options, original.options);
+            assert_eq!(merged.prices, original.prices);
+        }
+    }
+
+    #[test]
+    fn test_merge_upserts_variants() {
+        let sheet = table(&[
+            &["slug", "sku", "price", "stocks", "option:Color"],
+            &["t-shirt", "TS-M", "", "9", "Red"],
+            &["t-shirt", "TS-XL", "1700", "", ""],
+        ]);
+        let mut errors = Vec::new();
+        let mut products = parse(&sheet, &mut errors);
+        assert!(errors.is_empty());
+
+        let merged = products.remove(0).merge(Some(product())).unwrap();
+        let skus: Vec<_> = merged.variants.iter().map(|v| v.sku.as_str()).collect();
+        assert_eq!(skus, vec!["TS-M", "TS-L", "TS-XL"]);
+        assert_eq!(merged.variants[0].stocks, 9);
+        assert_eq!(merged.variants[0].price, BigDecimal::from(1500));
+        assert_eq!(merged.variants[0].options.len(), 2);
+        assert_eq!(merged.variants[2].price, BigDecimal::from(1700));
+        assert_eq!(merged.title.to_string(), "Basic T-Shirt");
+    }
+
+    #[test]
+    fn test_row_errors() {
+        let sheet = table(&[
+            &["slug", "title", "sku", "price", "price:EURO"],
+            &["Bad Slug", "Mug", "MUG", "100", ""],
+            &["mug", "Mug", "MUG-1", "-3", ""],
+            &["mug", "", "MUG-2", "100", "4"],
+            &["cup", "Cup", "MUG-3", "", ""],
+            &["cup", "", "MUG", "5", ""],
+        ]);
+        let mut errors = Vec::new();
+        let products = parse(&sheet, &mut errors);
+
+        let flagged: Vec<_> = errors
+            .iter()
+            .map(|e| (e.row, e.column.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            flagged,
+            vec![(2, "slug"), (3, "price"), (4, "price:EURO"), (6, "sku"),]
+        );
+        assert_eq!(products.len(), 1);
+        assert!(products[0].clone().merge(None).is_err());
+    }
+}
@@ -0,0 +1,83 @@
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, Utc};
+use o2o::o2o;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::domain::*;
+use crate::tenant::order::api::OrderItemCreate;
+use crate::types::{id::Id, name::Name};
+use crate::utils::serde_helpers::JsonOption;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct ShippingZoneCreate {
+    pub name: Name,
+    pub active: bool,
+    pub regions: Vec<ZoneRegion>,
+    pub rates: Vec<ShippingRate>,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(ShippingZoneRecord)]
+pub struct ShippingZoneDto {
+    #[from(@._id.into())]
+    pub id: Id,
+    pub name: Name,
+    pub active: bool,
+    pub regions: Vec<ZoneRegion>,
+    pub rates: Vec<ShippingRate>,
+    #[from(~.to_chrono())]
+    pub created_at: DateTime<Utc>,
+    #[from(~.to_chrono())]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct ShippingZoneListQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub active: Option<bool>,
+    pub search: Option<String>,
+}
+
+#[macros::json_option_serde]
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct ShippingZoneUpdate {
+    pub name: JsonOption<Name>,
+    pub active: JsonOption<bool>,
+    pub regions: JsonOption<Vec<ZoneRegion>>,
+    pub rates: JsonOption<Vec<ShippingRate>>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct ShippingZoneListResponse {
+    pub zones: Vec<ShippingZoneDto>,
+    pub total: u64,
+    pub page: u32,
+    pub limit: u32,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct ShippingQuote {
+    pub zone_id: Option<Id>,
+    pub rate_name: Option<String>,
+    pub delivery_method: DeliveryMethod,
+    #[ts(as = "String")]
+    pub cost: BigDecimal,
+}
+
+// ------ Public Api models ------
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct PubShippingQuoteRequest {
+    pub country: String,
+    pub state: String,
+    pub delivery_method: Option<DeliveryMethod>,
+    pub items: Vec<OrderItemCreate>,
+}
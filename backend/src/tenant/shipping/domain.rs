@@ -0,0 +1,273 @@
+use bigdecimal::BigDecimal;
+use bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::types::name::Name;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMethod {
+    #[default]
+    Home,
+    StopDesk,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+pub struct ZoneRegion {
+    pub country: String,
+    // Empty means the whole country
+    pub states: Vec<String>,
+}
+
+impl ZoneRegion {
+    /// Returns how specific the match is: 2 for an explicit state, 1 for a
+    /// country-wide region, `None` when the address is outside the region.
+    pub fn match_rank(&self, country: &str, state: &str) -> Option<u8> {
+        if !self.country.trim().eq_ignore_ascii_case(country.trim()) {
+            return None;
+        }
+
+        if self.states.is_empty() {
+            return Some(1);
+        }
+
+        self.states
+            .iter()
+            .any(|s| s.trim().eq_ignore_ascii_case(state.trim()))
+            .then_some(2)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+pub struct WeightBracket {
+    #[ts(as = "String")]
+    pub max_weight: BigDecimal,
+    #[ts(as = "String")]
+    pub home_price: BigDecimal,
+    #[ts(as = "Option<String>")]
+    pub stop_desk_price: Option<BigDecimal>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RateKind {
+    Flat {
+        #[ts(as = "String")]
+        home_price: BigDecimal,
+        #[ts(as = "Option<String>")]
+        stop_desk_price: Option<BigDecimal>,
+    },
+    WeightBased {
+        brackets: Vec<WeightBracket>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+pub struct ShippingRate {
+    pub name: String,
+    pub kind: RateKind,
+    #[ts(as = "Option<String>")]
+    pub free_shipping_threshold: Option<BigDecimal>,
+}
+
+impl ShippingRate {
+    pub fn price_for(
+        &self,
+        method: DeliveryMethod,
+        subtotal: &BigDecimal,
+        weight: &BigDecimal,
+    ) -> Option<BigDecimal> {
+        let price = match &self.kind {
+            RateKind::Flat {
+                home_price,
+                stop_desk_price,
+            } => match method {
+                DeliveryMethod::Home => Some(home_price.clone()),
+                DeliveryMethod::StopDesk => stop_desk_price.clone(),
+            },
+            RateKind::WeightBased { brackets } => brackets
+                .iter()
+                .filter(|b| weight <= &b.max_weight)
+                .min_by(|a, b| a.max_weight.cmp(&b.max_weight))
+                .and_then(|b| match method {
+                    DeliveryMethod::Home => Some(b.home_price.clone()),
+                    DeliveryMethod::StopDesk => b.stop_desk_price.clone(),
+                }),
+        }?;
+
+        match self.free_shipping_threshold {
+            Some(ref threshold) if subtotal >= threshold => Some(BigDecimal::from(0)),
+            _ => Some(price),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShippingZoneRecord {
+    pub _id: ObjectId,
+    pub name: Name,
+    pub active: bool,
+    pub regions: Vec<ZoneRegion>,
+    pub rates: Vec<ShippingRate>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl ShippingZoneRecord {
+    pub fn new(
+        name: Name,
+        active: bool,
+        regions: Vec<ZoneRegion>,
+        rates: Vec<ShippingRate>,
+    ) -> Self {
+        let now = DateTime::now();
+
+        Self {
+            _id: Default::default(),
+            name,
+            active,
+            regions,
+            rates,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn match_rank(&self, country: &str, state: &str) -> Option<u8> {
+        self.regions
+            .iter()
+            .filter_map(|r| r.match_rank(country, state))
+            .max()
+    }
+
+    pub fn cheapest_rate(
+        &self,
+        method: DeliveryMethod,
+        subtotal: &BigDecimal,
+        weight: &BigDecimal,
+    ) -> Option<(&ShippingRate, BigDecimal)> {
+        self.rates
+            .iter()
+            .filter_map(|r| r.price_for(method, subtotal, weight).map(|p| (r, p)))
+            .min_by(|(_, a), (_, b)| a.cmp(b))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShippingZoneFilter {
+    pub active: Option<bool>,
+    pub search: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn dec(v: &str) -> BigDecimal {
+        BigDecimal::from_str(v).unwrap()
+    }
+
+    fn flat(home: &str, stop_desk: Option<&str>, free_above: Option<&str>) -> ShippingRate {
+        ShippingRate {
+            name: "Standard".into(),
+            kind: RateKind::Flat {
+                home_price: dec(home),
+                stop_desk_price: stop_desk.map(dec),
+            },
+            free_shipping_threshold: free_above.map(dec),
+        }
+    }
+
+    #[test]
+    fn test_region_match_rank() {
+        let region = ZoneRegion {
+            country: "DZ".into(),
+            states: vec!["Alger".into(), "Oran".into()],
+        };
+        assert_eq!(region.match_rank("dz", " alger "), Some(2));
+        assert_eq!(region.match_rank("DZ", "Blida"), None);
+        assert_eq!(region.match_rank("FR", "Alger"), None);
+
+        let whole = ZoneRegion {
+            country: "DZ".into(),
+            states: vec![],
+        };
+        assert_eq!(whole.match_rank("DZ", "Blida"), Some(1));
+    }
+
+    #[test]
+    fn test_flat_rate_methods() {
+        let rate = flat("600", Some("400"), None);
+        let zero = BigDecimal::from(0);
+        assert_eq!(
+            rate.price_for(DeliveryMethod::Home, &zero, &zero),
+            Some(dec("600"))
+        );
+        assert_eq!(
+            rate.price_for(DeliveryMethod::StopDesk, &zero, &zero),
+            Some(dec("400"))
+        );
+
+        let home_only = flat("600", None, None);
+        assert_eq!(
+            home_only.price_for(DeliveryMethod::StopDesk, &zero, &zero),
+            None
+        );
+    }
+
+    #[test]
+    fn test_free_shipping_threshold() {
+        let rate = flat("600", None, Some("5000"));
+        let zero = BigDecimal::from(0);
+        assert_eq!(
+            rate.price_for(DeliveryMethod::Home, &dec("4999.99"), &zero),
+            Some(dec("600"))
+        );
+        assert_eq!(
+            rate.price_for(DeliveryMethod::Home, &dec("5000"), &zero),
+            Some(BigDecimal::from(0))
+        );
+    }
+
+    #[test]
+    fn test_weight_brackets() {
+        let rate = ShippingRate {
+            name: "Heavy".into(),
+            kind: RateKind::WeightBased {
+                brackets: vec![
+                    WeightBracket {
+                        max_weight: dec("5"),
+                        home_price: dec("900"),
+                        stop_desk_price: None,
+                    },
+                    WeightBracket {
+                        max_weight: dec("1"),
+                        home_price: dec("500"),
+                        stop_desk_price: Some(dec("300")),
+                    },
+                ],
+            },
+            free_shipping_threshold: None,
+        };
+        let zero = BigDecimal::from(0);
+        assert_eq!(
+            rate.price_for(DeliveryMethod::Home, &zero, &dec("0.5")),
+            Some(dec("500"))
+        );
+        assert_eq!(
+            rate.price_for(DeliveryMethod::Home, &zero, &dec("3")),
+            Some(dec("900"))
+        );
+        assert_eq!(
+            rate.price_for(DeliveryMethod::StopDesk, &zero, &dec("3")),
+            None
+        );
+        assert_eq!(
+            rate.price_for(DeliveryMethod::Home, &zero, &dec("12")),
+            None
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId, DateTime};
+use futures::stream::TryStreamExt;
+use mongodb::{options::FindOptions, Client, Collection};
+
+use super::domain::*;
+use crate::utils::error::{ApiError, ApiResult};
+
+#[async_trait]
+pub trait ShippingRepo: Send + Sync {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        zone: ShippingZoneRecord,
+    ) -> ApiResult<ShippingZoneRecord>;
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<ShippingZoneRecord>>;
+    async fn find_active(&self, business_id: ObjectId) -> ApiResult<Vec<ShippingZoneRecord>>;
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        zone: ShippingZoneRecord,
+    ) -> ApiResult<ShippingZoneRecord>;
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: ShippingZoneFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<ShippingZoneRecord>, u64)>;
+}
+
+pub struct MongoShippingRepo {
+    client: Client,
+}
+
+impl MongoShippingRepo {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn get_collection(&self, business_id: ObjectId) -> Collection<ShippingZoneRecord> {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+            .collection("shipping_zones")
+    }
+
+    fn build_filter_query(&self, filter: &ShippingZoneFilter) -> bson::Document {
+        let mut query = doc! {};
+
+        if let Some(active) = filter.active {
+            query.insert("active", active);
+        }
+
+        if let Some(ref search) = filter.search {
+            query.insert(
+                "$or",
+                vec![
+                    doc! {
+                        "name": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                    doc! {
+                        "regions.states": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                ],
+            );
+        }
+
+        query
+    }
+}
+
+#[async_trait]
+impl ShippingRepo for MongoShippingRepo {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        zone: ShippingZoneRecord,
+    ) -> ApiResult<ShippingZoneRecord> {
+        let collection = self.get_collection(business_id);
+
+        collection.insert_one(&zone).await.map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                ApiError::conflict("shipping zone", "Shipping zone already exists")
+            } else {
+                ApiError::internal(format!("Failed to create shipping zone: {}", e))
+            }
+        })?;
+
+        Ok(zone)
+    }
+
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<ShippingZoneRecord>> {
+        let collection = self.get_collection(business_id);
+
+        let zone = collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        Ok(zone)
+    }
+
+    async fn find_active(&self, business_id: ObjectId) -> ApiResult<Vec<ShippingZoneRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! { "active": true })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        mut zone: ShippingZoneRecord,
+    ) -> ApiResult<ShippingZoneRecord> {
+        let collection = self.get_collection(business_id);
+
+        zone.updated_at = DateTime::now();
+
+        let result = collection
+            .replace_one(doc! { "_id": id }, &zone)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update shipping zone: {}", e)))?;
+
+        if result.matched_count == 0 {
+            return Err(ApiError::not_found(
+                "shipping zone",
+                "Shipping zone not found",
+            ));
+        }
+
+        Ok(zone)
+    }
+
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+
+        let result = collection
+            .delete_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to delete shipping zone: {}", e)))?;
+
+        if result.deleted_count == 0 {
+            return Err(ApiError::not_found(
+                "shipping zone",
+                "Shipping zone not found",
+            ));
+        }
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: ShippingZoneFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<ShippingZoneRecord>, u64)> {
+        let collection = self.get_collection(business_id);
+        let query = self.build_filter_query(&filter);
+
+        let total = collection
+            .count_documents(query.clone())
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to count shipping zones: {}", e)))?;
+
+        let skip = ((page.max(1) - 1) * limit) as u64;
+
+        let find_options = FindOptions::builder()
+            .skip(skip)
+            .limit(limit as i64)
+            .sort(doc! { "created_at": -1 })
+            .build();
+
+        let mut cursor = collection
+            .find(query)
+            .with_options(find_options)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?;
+
+        let mut zones = Vec::new();
+        while let Some(zone) = cursor
+            .try_next()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))?
+        {
+            zones.push(zone);
+        }
+
+        Ok((zones, total))
+    }
+}
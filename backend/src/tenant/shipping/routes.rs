@@ -0,0 +1,104 @@
+use axum::extract::{Path, Query, State};
+use macros::routes;
+
+use super::super::store::extractors::Store;
+use super::api::*;
+use crate::extractors::cookies::FromCookies;
+use crate::extractors::json::Json;
+use crate::platform::business::api::BusinessSession;
+use crate::platform::user::api::MessageResponse;
+use crate::types::id::Id;
+use crate::utils::error::ApiResult;
+use crate::AppState;
+
+pub struct ShippingRoutes;
+
+#[routes(prefix = "/api/v1/shipping", state = AppState)]
+impl ShippingRoutes {
+    #[route(method=post, path="/zones/create", res=ShippingZoneDto, perm="shipping:write")]
+    async fn create_zone(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[json] create_req: ShippingZoneCreate,
+    ) -> ApiResult<Json<ShippingZoneDto>> {
+        state
+            .shipping_service
+            .create_zone(business, create_req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/zones/{zone_id}", res=ShippingZoneDto, perm="shipping:read")]
+    async fn get_zone(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] zone_id: Id,
+    ) -> ApiResult<Json<ShippingZoneDto>> {
+        state
+            .shipping_service
+            .get_zone(business, zone_id)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/zones/list", res=ShippingZoneListResponse, perm="shipping:read")]
+    async fn list_zones(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: ShippingZoneListQuery,
+    ) -> ApiResult<Json<ShippingZoneListResponse>> {
+        state
+            .shipping_service
+            .list_zones(business, query)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=patch, path="/zones/{zone_id}", res=ShippingZoneDto, perm="shipping:write")]
+    async fn update_zone(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] zone_id: Id,
+        #[json] update_req: ShippingZoneUpdate,
+    ) -> ApiResult<Json<ShippingZoneDto>> {
+        state
+            .shipping_service
+            .update_zone(business, zone_id, update_req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/zones/{zone_id}", res=MessageResponse, perm="shipping:delete")]
+    async fn delete_zone(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] zone_id: Id,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .shipping_service
+            .delete_zone(business, zone_id)
+            .await
+            .map(|_| MessageResponse {
+                message: "Shipping zone deleted successfully".to_string(),
+            })
+            .map(Json)
+    }
+}
+
+pub struct PubShippingRoutes;
+
+#[routes(prefix = "/api/v1/shipping", state = AppState)]
+impl PubShippingRoutes {
+    #[route(method=post, path="/quote", res=ShippingQuote)]
+    async fn quote(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        #[json] quote_req: PubShippingQuoteRequest,
+    ) -> ApiResult<Json<ShippingQuote>> {
+        state
+            .shipping_service
+            .pub_quote(&state.product_service, store_key.business_id, quote_req)
+            .await
+            .map(Json)
+    }
+}
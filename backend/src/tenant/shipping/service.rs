@@ -0,0 +1,204 @@
+use bigdecimal::BigDecimal;
+
+use super::api::*;
+use super::domain::*;
+use super::repo::ShippingRepo;
+use crate::platform::business::api::BusinessSession;
+use crate::tenant::product::repo::ProductRepo;
+use crate::tenant::product::service::ProductService;
+use crate::types::id::Id;
+use crate::utils::error::{ApiError, ApiResult};
+
+pub struct ShippingService<R: ShippingRepo> {
+    repo: R,
+}
+
+impl<R: ShippingRepo> ShippingService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_zone(
+        &self,
+        business: BusinessSession,
+        create_req: ShippingZoneCreate,
+    ) -> ApiResult<ShippingZoneDto> {
+        self.repo
+            .create(
+                business.business_id.into_inner(),
+                ShippingZoneRecord::new(
+                    create_req.name,
+                    create_req.active,
+                    create_req.regions,
+                    create_req.rates,
+                ),
+            )
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn get_zone(
+        &self,
+        business: BusinessSession,
+        zone_id: Id,
+    ) -> ApiResult<ShippingZoneDto> {
+        let id = zone_id.into_inner();
+        self.repo
+            .find_by_id(business.business_id.into_inner(), id)
+            .await?
+            .ok_or(ApiError::not_found("shipping zone", id.to_hex()))
+            .map(Into::into)
+    }
+
+    pub async fn update_zone(
+        &self,
+        business: BusinessSession,
+        zone_id: Id,
+        update_req: ShippingZoneUpdate,
+    ) -> ApiResult<ShippingZoneDto> {
+        let id = zone_id.into_inner();
+        let business_id = business.business_id.into_inner();
+        let mut record = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or(ApiError::not_found("shipping zone", id.to_hex()))?;
+
+        update_req.name.map(|v| record.name = v);
+        update_req.active.map(|v| record.active = v);
+        update_req.regions.map(|v| record.regions = v);
+        update_req.rates.map(|v| record.rates = v);
+
+        self.repo
+            .update(business_id, id, record)
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn delete_zone(&self, business: BusinessSession, zone_id: Id) -> ApiResult<()> {
+        self.repo
+            .delete(business.business_id.into_inner(), zone_id.into_inner())
+            .await
+    }
+
+    pub async fn list_zones(
+        &self,
+        business: BusinessSession,
+        query: ShippingZoneListQuery,
+    ) -> ApiResult<ShippingZoneListResponse> {
+        let ShippingZoneListQuery {
+            page,
+            limit,
+            active,
+            search,
+        } = query;
+
+        let filter = ShippingZoneFilter { active, search };
+        let page = page.unwrap_or(1);
+        let limit = limit.unwrap_or(10);
+
+        let (zones, total) = self
+            .repo
+            .list(business.business_id.into_inner(), filter, page, limit)
+            .await?;
+
+        let views: Vec<_> = zones.into_iter().map(Into::into).collect();
+        Ok(ShippingZoneListResponse {
+            zones: views,
+            total,
+            page,
+            limit,
+        })
+    }
+
+    /// Picks the most specific active zone covering the destination and its
+    /// cheapest applicable rate. Businesses without any active zone ship for free.
+    pub async fn quote(
+        &self,
+        business_id: Id,
+        country: &str,
+        state: &str,
+        delivery_method: DeliveryMethod,
+        subtotal: &BigDecimal,
+        weight: &BigDecimal,
+    ) -> ApiResult<ShippingQuote> {
+        let zones = self.repo.find_active(business_id.into_inner()).await?;
+
+        if zones.is_empty() {
+            return Ok(ShippingQuote {
+                zone_id: None,
+                rate_name: None,
+                delivery_method,
+                cost: BigDecimal::from(0),
+            });
+        }
+
+        let ranked: Vec<_> = zones
+            .iter()
+            .filter_map(|z| z.match_rank(country, state).map(|rank| (rank, z)))
+            .collect();
+
+        let best_rank = ranked.iter().map(|(rank, _)| *rank).max().ok_or_else(|| {
+            ApiError::validation("shipping_address", "We do not ship to this address")
+        })?;
+
+        ranked
+            .into_iter()
+            .filter(|(rank, _)| *rank == best_rank)
+            .filter_map(|(_, zone)| {
+                zone.cheapest_rate(delivery_method, subtotal, weight)
+                    .map(|(rate, cost)| (zone, rate, cost))
+            })
+            .min_by(|(_, _, a), (_, _, b)| a.cmp(b))
+            .map(|(zone, rate, cost)| ShippingQuote {
+                zone_id: Some(zone._id.into()),
+                rate_name: Some(rate.name.clone()),
+                delivery_method,
+                cost,
+            })
+            .ok_or_else(|| {
+                ApiError::validation(
+                    "delivery_method",
+                    "No shipping rate is available for this delivery method",
+                )
+            })
+    }
+
+    pub async fn pub_quote<P: ProductRepo>(
+        &self,
+        product_service: &ProductService<P>,
+        business_id: Id,
+        quote_req: PubShippingQuoteRequest,
+    ) -> ApiResult<ShippingQuote> {
+        let mut subtotal = BigDecimal::from(0);
+        let mut weight = BigDecimal::from(0);
+
+        for item_req in quote_req.items {
+            let product = product_service
+                .pub_get_product(business_id, item_req.product_id)
+                .await?;
+
+            let variant = product
+                .variants
+                .iter()
+                .find(|v| v.sku == item_req.variant_sku)
+                .ok_or_else(|| ApiError::not_found("variant", item_req.variant_sku.clone()))?;
+
+            let quantity = BigDecimal::from(item_req.quantity);
+            subtotal += &variant.price * &quantity;
+            if let Some(ref w) = variant.weight {
+                weight += w * &quantity;
+            }
+        }
+
+        self.quote(
+            business_id,
+            &quote_req.country,
+            &quote_req.state,
+            quote_req.delivery_method.unwrap_or_default(),
+            &subtotal,
+            &weight,
+        )
+        .await
+    }
+}
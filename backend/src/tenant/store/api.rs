@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::str::FromStr;
 
 use bigdecimal::BigDecimal;
 use chrono::{DateTime, Utc};
@@ -8,7 +9,9 @@ use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 use super::domain::*;
-use crate::types::{id::Id, name::Name};
+use crate::tenant::product::api::ProductSearchQuery;
+use crate::tenant::product::domain::ProductSort;
+use crate::types::{currency::Currency, id::Id, name::Name};
 use crate::utils::serde_helpers::JsonOption;
 use crate::utils::types::CowStr;
 
@@ -28,6 +31,8 @@ pub struct StoreCreateDto {
     pub name: Name,
     pub description: String,
     pub status: StoreStatusDto,
+    // The business currency when left out
+    pub currency: Option<Currency>,
 
     pub category: Option<String>,
     pub contact_email: Option<String>,
@@ -67,6 +72,7 @@ pub struct StoreDto {
     pub description: String,
     #[from(~.into())]
     pub status: StoreStatusDto,
+    pub currency: Currency,
 
     pub category: Option<String>,
     pub contact_email: Option<String>,
@@ -88,6 +94,7 @@ pub struct StoreDto {
     pub product_page_template: CowStr,
     pub cart_page_template: CowStr,
     pub shop_page_template: CowStr,
+    pub collection_page_template: CowStr,
     pub not_found_page_template: CowStr,
     pub custom_pages: IndexMap<String, CowStr>,
     pub snippets: IndexMap<String, CowStr>,
@@ -118,6 +125,132 @@ pub struct StoreListQuery {
     pub search: Option<String>,
 }
 
+/// Query string accepted by paginated storefront pages.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PubPageQuery {
+    pub page: Option<u32>,
+}
+
+/// Pagination block exposed to liquid templates.
+#[derive(Debug, Clone, Serialize)]
+pub struct PaginationView {
+    pub page: u32,
+    pub limit: u32,
+    pub total: u64,
+    pub pages: u32,
+    pub prev: Option<u32>,
+    pub next: Option<u32>,
+}
+
+impl PaginationView {
+    pub fn new(page: u32, limit: u32, total: u64) -> Self {
+        let pages = total.div_ceil(limit.max(1) as u64) as u32;
+        Self {
+            page,
+            limit,
+            total,
+            pages,
+            prev: (page > 1).then(|| page - 1),
+            next: (page < pages).then(|| page + 1),
+        }
+    }
+}
+
+pub const SHOP_PAGE_SIZE: u32 = 12;
+const SHOP_MAX_PAGE_SIZE: u32 = 48;
+
+// Marks the keys filtering by variant option, so unrelated parameters such as
+// `utm_source` or `fbclid` are not taken for one
+const SHOP_OPTION_PREFIX: &str = "opt.";
+
+/// Parses the `/shop` query string. `s`, `c`, `f`, `min_price`, `max_price`,
+/// `sort`, `page` and `limit` are reserved, `opt.<name>` keys filter variants
+/// by option value (e.g. `opt.size=M`) and anything else is ignored, as are
+/// malformed values.
+pub fn parse_shop_query(pairs: Vec<(String, String)>) -> ProductSearchQuery {
+    let mut query = ProductSearchQuery {
+        page: 1,
+        limit: SHOP_PAGE_SIZE,
+        ..Default::default()
+    };
+
+    for (key, value) in pairs {
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+
+        match key.as_str() {
+            "s" => query.search = Some(value.to_string()),
+            "c" => query.category = Some(value.to_string()),
+            "f" => query.featured = value.parse().ok(),
+            "min_price" => query.min_price = BigDecimal::from_str(value).ok(),
+            "max_price" => query.max_price = BigDecimal::from_str(value).ok(),
+            "sort" => {
+                query.sort =
+                    serde_json::from_value(serde_json::Value::from(value)).unwrap_or_default()
+            }
+            "page" => query.page = value.parse().unwrap_or(1).max(1),
+            "limit" => {
+                query.limit = value
+                    .parse()
+                    .unwrap_or(SHOP_PAGE_SIZE)
+                    .clamp(1, SHOP_MAX_PAGE_SIZE)
+            }
+            key => {
+                // Option names end up in a mongo field path
+                if let Some(name) = key.strip_prefix(SHOP_OPTION_PREFIX) {
+                    if !name.is_empty() && !name.contains('.') && !name.starts_with('$') {
+                        query.options.insert(name.to_string(), value.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    query
+}
+
+/// Encodes the active filters back into a query string, without the page, so
+/// templates can build pager and sort links.
+pub fn shop_query_params(query: &ProductSearchQuery) -> String {
+    let mut pairs: Vec<(&str, String)> = Vec::new();
+
+    if let Some(ref search) = query.search {
+        pairs.push(("s", search.clone()));
+    }
+    if let Some(ref category) = query.category {
+        pairs.push(("c", category.clone()));
+    }
+    if let Some(featured) = query.featured {
+        pairs.push(("f", featured.to_string()));
+    }
+    if let Some(ref min) = query.min_price {
+        pairs.push(("min_price", min.to_string()));
+    }
+    if let Some(ref max) = query.max_price {
+        pairs.push(("max_price", max.to_string()));
+    }
+    let options: Vec<_> = query
+        .options
+        .iter()
+        .map(|(name, value)| (format!("{}{}", SHOP_OPTION_PREFIX, name), value.clone()))
+        .collect();
+    for (key, value) in &options {
+        pairs.push((key, value.clone()));
+    }
+    if query.sort != ProductSort::default() {
+        if let Ok(serde_json::Value::String(sort)) = serde_json::to_value(query.sort) {
+            pairs.push(("sort", sort));
+        }
+    }
+    if query.limit != SHOP_PAGE_SIZE {
+        pairs.push(("limit", query.limit.to_string()));
+    }
+
+    serde_urlencoded::to_string(pairs).unwrap_or_default()
+}
+
 #[macros::json_option_serde]
 #[derive(Debug, Deserialize, TS)]
 #[ts(export, bound = "")]
@@ -126,6 +259,7 @@ pub struct StoreUpdate {
     pub description: JsonOption<String>,
     pub status: JsonOption<StoreStatusDto>,
     pub slug: JsonOption<String>,
+    pub currency: JsonOption<Currency>,
 
     pub category: JsonOption<String>,
     pub contact_email: JsonOption<String>,
@@ -147,6 +281,7 @@ pub struct StoreUpdate {
     pub product_page_template: JsonOption<CowStr>,
     pub cart_page_template: JsonOption<CowStr>,
     pub shop_page_template: JsonOption<CowStr>,
+    pub collection_page_template: JsonOption<CowStr>,
     pub not_found_page_template: JsonOption<CowStr>,
     pub custom_pages: JsonOption<IndexMap<String, CowStr>>,
     pub snippets: JsonOption<IndexMap<String, CowStr>>,
@@ -172,7 +307,7 @@ pub struct StoreListResponse {
     pub limit: u32,
 }
 
-#[derive(Debug, Serialize, o2o, TS)]
+#[derive(Debug, Clone, Serialize, o2o, TS)]
 #[ts(export, bound = "")]
 #[from_owned(StoreRegRecord)]
 pub struct StoreRegDto {
@@ -182,6 +317,8 @@ pub struct StoreRegDto {
     pub store_id: Id,
     pub slug: String,
     pub domain: Option<String>,
+    pub currency: Currency,
+    pub base_currency: Currency,
     #[from(~.to_chrono())]
     pub created_at: DateTime<Utc>,
     #[from(~.to_chrono())]
@@ -195,3 +332,72 @@ pub struct StoreRegUpdate {
     pub slug: String,
     pub domain: JsonOption<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(items: &[(&str, &str)]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_shop_query() {
+        let query = parse_shop_query(pairs(&[
+            ("s", "shirt"),
+            ("c", "men"),
+            ("min_price", "1000"),
+            ("max_price", "abc"),
+            ("opt.size", "M"),
+            ("opt.variants.sku", "x"),
+            ("opt.color", ""),
+            ("utm_source", "fb"),
+            ("fbclid", "abc"),
+            ("sort", "price-desc"),
+            ("page", "0"),
+            ("limit", "500"),
+        ]));
+
+        assert_eq!(query.search.as_deref(), Some("shirt"));
+        assert_eq!(query.category.as_deref(), Some("men"));
+        assert_eq!(query.min_price, Some(BigDecimal::from(1000)));
+        assert_eq!(query.max_price, None);
+        assert_eq!(query.options.len(), 1);
+        assert_eq!(query.options["size"], "M");
+        assert_eq!(query.sort, ProductSort::PriceDesc);
+        assert_eq!(query.page, 1);
+        assert_eq!(query.limit, SHOP_MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_shop_query_params_round_trip() {
+        let query = parse_shop_query(pairs(&[
+            ("s", "قميص"),
+            ("opt.size", "M"),
+            ("sort", "title-asc"),
+            ("page", "3"),
+        ]));
+        let params = shop_query_params(&query);
+
+        assert!(!params.contains("page="));
+        let again = parse_shop_query(serde_urlencoded::from_str(&params).unwrap());
+        assert_eq!(again.search, query.search);
+        assert_eq!(again.options, query.options);
+        assert_eq!(again.sort, ProductSort::TitleAsc);
+    }
+
+    #[test]
+    fn test_pagination_view() {
+        let view = PaginationView::new(2, 12, 30);
+        assert_eq!(view.pages, 3);
+        assert_eq!(view.prev, Some(1));
+        assert_eq!(view.next, Some(3));
+
+        let empty = PaginationView::new(1, 12, 0);
+        assert_eq!(empty.pages, 0);
+        assert_eq!(empty.next, None);
+    }
+}
@@ -6,6 +6,7 @@ use indexmap::{indexmap, IndexMap, IndexSet};
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
+use crate::types::currency::Currency;
 use crate::types::id::Id;
 use crate::types::name::Name;
 use crate::utils::types::CowStr;
@@ -35,6 +36,9 @@ pub struct MenuItem {
 pub struct FeaturedCollection {
     pub label: String,
     pub img: Option<String>,
+    // Slug of the linked category, rendered at /collections/{slug}
+    #[serde(default)]
+    pub category: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, TS)]
@@ -62,6 +66,9 @@ pub struct StoreRecord {
     pub name: Name,
     pub description: String,
     pub status: StoreStatus,
+    // Stores created before currencies were configurable all sold in DZD
+    #[serde(default)]
+    pub currency: Currency,
 
     pub category: Option<String>,
     pub contact_email: Option<String>,
@@ -84,6 +91,8 @@ pub struct StoreRecord {
     pub product_page_template: CowStr,
     pub cart_page_template: CowStr,
     pub shop_page_template: CowStr,
+    #[serde(default = "default_collection_page_template")]
+    pub collection_page_template: CowStr,
     pub not_found_page_template: CowStr,
     pub custom_pages: IndexMap<String, CowStr>,
     pub snippets: IndexMap<String, CowStr>,
@@ -103,11 +112,20 @@ pub struct StoreRecord {
     pub updated_at: DateTime,
 }
 
+fn default_collection_page_template() -> CowStr {
+    include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../templates/collection.liquid"
+    ))
+    .into()
+}
+
 impl StoreRecord {
     pub fn new(
         name: Name,
         description: String,
         status: StoreStatus,
+        currency: Currency,
         category: Option<String>,
         contact_email: Option<String>,
         contact_phone: Option<String>,
@@ -152,6 +170,7 @@ impl StoreRecord {
             "/../templates/shop.liquid"
         ))
         .into();
+        let collection_page_template = default_collection_page_template();
         let not_found_page_template = include_str!(concat!(
             env!("CARGO_MANIFEST_DIR"),
             "/../templates/404.liquid"
@@ -170,6 +189,7 @@ impl StoreRecord {
             name,
             description,
             status,
+            currency,
             category,
             contact_email,
             contact_phone,
@@ -190,6 +210,7 @@ impl StoreRecord {
             product_page_template,
             cart_page_template,
             shop_page_template,
+            collection_page_template,
             not_found_page_template,
             custom_pages,
             snippets,
@@ -209,6 +230,33 @@ impl StoreRecord {
     pub fn is_active(&self) -> bool {
         matches!(self.status, StoreStatus::Active)
     }
+
+    /// Every field that may point at an uploaded file, templates included.
+    pub fn file_references(&self) -> Vec<&str> {
+        let mut refs: Vec<&str> = [&self.logo, &self.logo_alt, &self.favicon]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect();
+
+        refs.extend(
+            self.featured_collections
+                .iter()
+                .filter_map(|c| c.img.as_deref()),
+        );
+        refs.extend([
+            self.homepage_template.as_ref(),
+            self.product_page_template.as_ref(),
+            self.cart_page_template.as_ref(),
+            self.shop_page_template.as_ref(),
+            self.collection_page_template.as_ref(),
+            self.not_found_page_template.as_ref(),
+        ]);
+        refs.extend(self.custom_pages.values().map(AsRef::as_ref));
+        refs.extend(self.snippets.values().map(AsRef::as_ref));
+
+        refs
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -223,6 +271,12 @@ pub struct StoreRegRecord {
     pub domain: Option<String>,
     pub business_id: ObjectId,
     pub store_id: ObjectId,
+    // Copied from the store so storefront requests can price without loading it
+    #[serde(default)]
+    pub currency: Currency,
+    // The business currency, the only one prices without an override are in
+    #[serde(default)]
+    pub base_currency: Currency,
     pub created_at: DateTime,
     pub updated_at: DateTime,
 }
@@ -233,6 +287,8 @@ impl StoreRegRecord {
         store_id: ObjectId,
         slug: String,
         domain: Option<String>,
+        currency: Currency,
+        base_currency: Currency,
     ) -> Self {
         let now = DateTime::now();
         Self {
@@ -240,6 +296,8 @@ impl StoreRegRecord {
             domain,
             business_id,
             store_id,
+            currency,
+            base_currency,
             created_at: now,
             updated_at: now,
         }
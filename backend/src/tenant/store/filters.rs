@@ -0,0 +1,124 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use liquid_core::model::ScalarCow;
+use liquid_core::{
+    Display_filter, Expression, Filter, FilterParameters, FilterReflection, FromFilterParameters,
+    ParseFilter, Result, Runtime, Value, ValueView,
+};
+
+use crate::tenant::file::processing::rendition_url;
+use crate::types::currency::Currency;
+
+#[derive(Debug, FilterParameters)]
+struct ImageSizeArgs {
+    #[parameter(
+        description = "The rendition to use: thumbnail, card or full.",
+        arg_type = "str"
+    )]
+    size: Expression,
+}
+
+/// `{{ product.images[0] | image_size: "card" }}` points an uploaded image at
+/// one of its WebP renditions. Other URLs are left untouched.
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "image_size",
+    description = "Picks a rendition of an uploaded image.",
+    parameters(ImageSizeArgs),
+    parsed(ImageSizeFilter)
+)]
+pub struct ImageSize;
+
+#[derive(Debug, FromFilterParameters, Display_filter)]
+#[name = "image_size"]
+struct ImageSizeFilter {
+    #[parameters]
+    args: ImageSizeArgs,
+}
+
+impl Filter for ImageSizeFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+        let url = input.to_kstr();
+
+        Ok(Value::scalar(
+            rendition_url(url.as_str(), args.size.as_str()).unwrap_or_else(|| url.into_string()),
+        ))
+    }
+}
+
+#[derive(Debug, FilterParameters)]
+struct MoneyArgs {
+    #[parameter(
+        description = "The currency code, the store currency when left out.",
+        arg_type = "str"
+    )]
+    currency: Option<Expression>,
+}
+
+/// `{{ product.variants[0].price | money }}` writes an amount with the store
+/// currency symbol and decimals, e.g. `1,250.00 د.ج`. Values that are not
+/// numbers are left untouched.
+#[derive(Clone, ParseFilter, FilterReflection)]
+#[filter(
+    name = "money",
+    description = "Formats an amount in a currency.",
+    parameters(MoneyArgs),
+    parsed(MoneyFilter)
+)]
+pub struct Money;
+
+#[derive(Debug, FromFilterParameters, Display_filter)]
+#[name = "money"]
+struct MoneyFilter {
+    #[parameters]
+    args: MoneyArgs,
+}
+
+impl Filter for MoneyFilter {
+    fn evaluate(&self, input: &dyn ValueView, runtime: &dyn Runtime) -> Result<Value> {
+        let args = self.args.evaluate(runtime)?;
+
+        let code = match args.currency {
+            Some(code) => code.into_owned(),
+            None => runtime
+                .try_get(&[ScalarCow::new("currency")])
+                .map(|v| v.to_kstr().into_owned())
+                .unwrap_or_default(),
+        };
+        let currency = Currency::new(code.as_str()).unwrap_or_default();
+
+        let text = input.to_kstr();
+        Ok(Value::scalar(match BigDecimal::from_str(text.trim()) {
+            Ok(amount) => currency.format(&amount),
+            Err(_) => text.into_string(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money() {
+        let parser = liquid::ParserBuilder::with_stdlib()
+            .filter(Money)
+            .build()
+            .unwrap();
+        let template = parser
+            .parse(r#"{{ price | money }}|{{ price | money: "USD" }}|{{ name | money }}"#)
+            .unwrap();
+        let globals = liquid::object!({
+            "currency": "DZD",
+            "price": BigDecimal::from_str("1250.5").unwrap(),
+            "name": "free",
+        });
+
+        assert_eq!(
+            template.render(&globals).unwrap(),
+            "1,250.50 د.ج|$1,250.50|free"
+        );
+    }
+}
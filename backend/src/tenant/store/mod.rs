@@ -1,6 +1,7 @@
 pub mod api;
 pub mod domain;
 pub mod extractors;
+pub mod filters;
 pub mod repo;
 pub mod routes;
 pub mod service;
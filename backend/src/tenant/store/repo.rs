@@ -4,6 +4,7 @@ use futures::stream::TryStreamExt;
 use mongodb::{options::FindOptions, Client, Collection, Database};
 
 use super::domain::*;
+use crate::types::currency::Currency;
 use crate::utils::error::{ApiError, ApiResult};
 
 #[async_trait]
@@ -223,6 +224,7 @@ pub trait StoreRegRepo: Send + Sync {
         store: StoreRegRecord,
     ) -> ApiResult<StoreRegRecord>;
     async fn delete(&self, business_id: ObjectId, store_id: ObjectId) -> ApiResult<()>;
+    async fn set_base_currency(&self, business_id: ObjectId, currency: &Currency) -> ApiResult<()>;
 }
 
 pub struct MongoStoreRegRepo {
@@ -333,4 +335,19 @@ impl StoreRegRepo for MongoStoreRegRepo {
 
         Ok(())
     }
+
+    async fn set_base_currency(&self, business_id: ObjectId, currency: &Currency) -> ApiResult<()> {
+        self.collection
+            .update_many(
+                doc! { "business_id": business_id },
+                doc! { "$set": {
+                    "base_currency": currency.as_str(),
+                    "updated_at": DateTime::now(),
+                } },
+            )
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update store: {}", e)))?;
+
+        Ok(())
+    }
 }
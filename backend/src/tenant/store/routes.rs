@@ -15,11 +15,14 @@ use tracing::{debug, error, trace};
 
 use super::api::*;
 use super::extractors::*;
+use super::filters::{ImageSize, Money};
 use crate::extractors::cookies::FromCookies;
 use crate::extractors::json::Json;
 use crate::platform::business::api::BusinessSession;
 use crate::platform::user::api::MessageResponse;
-use crate::tenant::product::api::ProductListQuery;
+use crate::tenant::cart::api::{CartDto, CartSession, CartToken};
+use crate::tenant::product::api::{ProductDto, ProductListQuery};
+use crate::types::currency::Currency;
 use crate::types::id::Id;
 use crate::utils::error::ApiError;
 use crate::utils::error::ApiResult;
@@ -30,7 +33,7 @@ pub struct StoreRoutes;
 
 #[routes(prefix = "/api/v1/stores", state = AppState)]
 impl StoreRoutes {
-    #[route(method=post, path="/create", res=StoreDto)]
+    #[route(method=post, path="/create", res=StoreDto, perm="stores:write")]
     async fn create_store(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -38,12 +41,12 @@ impl StoreRoutes {
     ) -> ApiResult<Json<StoreDto>> {
         state
             .store_service
-            .create(business, create_req)
+            .create(&state.business_service, business, create_req)
             .await
             .map(Json)
     }
 
-    #[route(method=post, path="/{store_id}", res=StoreDto)]
+    #[route(method=post, path="/{store_id}", res=StoreDto, perm="stores:read")]
     async fn get_store(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -56,7 +59,7 @@ impl StoreRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/list", res=StoreListResponse)]
+    #[route(method=post, path="/list", res=StoreListResponse, perm="stores:read")]
     async fn list_stores(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -69,7 +72,7 @@ impl StoreRoutes {
             .map(Json)
     }
 
-    #[route(method=patch, path="/{store_id}", res=StoreDto)]
+    #[route(method=patch, path="/{store_id}", res=StoreDto, perm="stores:write")]
     async fn update_store(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -83,7 +86,7 @@ impl StoreRoutes {
             .map(Json)
     }
 
-    #[route(method=post, path="/store-reg/{store_id}", res=StoreRegDto)]
+    #[route(method=post, path="/store-reg/{store_id}", res=StoreRegDto, perm="stores:read")]
     async fn get_reg(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -96,7 +99,7 @@ impl StoreRoutes {
             .map(Json)
     }
 
-    #[route(method=patch, path="/store-reg/{store_id}", res=StoreRegDto)]
+    #[route(method=patch, path="/store-reg/{store_id}", res=StoreRegDto, perm="stores:write")]
     async fn set_reg(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -105,12 +108,12 @@ impl StoreRoutes {
     ) -> ApiResult<Json<StoreRegDto>> {
         state
             .store_service
-            .set_reg(business, store_id, update_req)
+            .set_reg(&state.business_service, business, store_id, update_req)
             .await
             .map(Json)
     }
 
-    #[route(method=delete, path="/{store_id}", res=MessageResponse)]
+    #[route(method=delete, path="/{store_id}", res=MessageResponse, perm="stores:delete")]
     async fn delete_store(
         State(state): State<AppState>,
         FromCookies(business): FromCookies<BusinessSession>,
@@ -140,13 +143,17 @@ impl PubStoreRoutes {
         partials
     }
 
-    fn base_store_globals(store: StoreDto) -> liquid::model::Object {
+    fn base_store_globals(store: StoreDto, cart: CartDto) -> liquid::model::Object {
         liquid::object!({
+            "cart": cart,
+            // Read by the `money` filter
+            "currency": store.currency.clone(),
             "store": {
                 "id": store.id,
                 "name": store.name,
                 "description": store.description,
                 "status": store.status,
+                "currency": store.currency,
 
                 // Contact
                 "category": store.category,
@@ -185,6 +192,15 @@ impl PubStoreRoutes {
         })
     }
 
+    /// Products priced for the store, the query already left out those it
+    /// can't sell.
+    fn localize(products: Vec<ProductDto>, store_key: &StoreRegDto) -> Vec<ProductDto> {
+        products
+            .into_iter()
+            .filter_map(|p| p.localize(&store_key.currency, &store_key.base_currency))
+            .collect()
+    }
+
     fn merge_globals(mut base: liquid::Object, extras: liquid::Object) -> liquid::Object {
         for (k, v) in extras {
             base.insert(k, v);
@@ -194,6 +210,8 @@ impl PubStoreRoutes {
 
     fn parser_for(snippets: IndexMap<String, CowStr>) -> ApiResult<Parser> {
         ParserBuilder::with_stdlib()
+            .filter(ImageSize)
+            .filter(Money)
             .partials(Self::build_partials(snippets))
             .build()
             .map_err(|e| ApiError::internal(format!("Failed to build liquid parser: {}", e)))
@@ -215,6 +233,7 @@ impl PubStoreRoutes {
 
     fn store_not_found_page(
         mut store: StoreDto,
+        cart: CartDto,
         slug: Option<String>,
     ) -> (StatusCode, Html<CowStr>) {
         let template = mem::take(&mut store.not_found_page_template);
@@ -226,7 +245,7 @@ impl PubStoreRoutes {
             }
         });
 
-        let globals = Self::merge_globals(Self::base_store_globals(store), extras);
+        let globals = Self::merge_globals(Self::base_store_globals(store, cart), extras);
 
         match Self::render_page(globals, snippets, &template) {
             Ok(out) => (StatusCode::NOT_FOUND, Html(CowStr::from(out))),
@@ -239,20 +258,46 @@ impl PubStoreRoutes {
         }
     }
 
+    /// The visitor's priced cart, an empty one when it cannot be loaded so a
+    /// broken cart never takes the storefront down.
+    async fn load_cart(state: &AppState, store_key: &StoreRegDto, token: &CartToken) -> CartDto {
+        state
+            .cart_service
+            .get_cart(
+                &state.product_service,
+                &CartSession::new(store_key, token.clone()),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                error!("cart load error: {:?}", e);
+                CartDto {
+                    valid: true,
+                    ..Default::default()
+                }
+            })
+    }
+
     // ------ Public routes ------
 
     #[route(method=get, path="/")]
-    pub async fn home(State(state): State<AppState>, Store(store_key): Store) -> impl IntoResponse {
+    pub async fn home(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
+    ) -> impl IntoResponse {
         let mut store = state
             .store_service
             .get_active_store(store_key.business_id, store_key.store_id)
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
 
         let featured = state
             .product_service
             .pub_list_products(
                 store_key.business_id,
+                &store_key.currency,
+                &store_key.base_currency,
                 ProductListQuery {
                     page: None,
                     limit: None,
@@ -265,6 +310,7 @@ impl PubStoreRoutes {
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?
             .products;
+        let featured = Self::localize(featured, &store_key);
 
         let extras = liquid::object!({
             "featured_products": featured,
@@ -273,7 +319,7 @@ impl PubStoreRoutes {
         let template = mem::take(&mut store.homepage_template);
         let snippets = mem::take(&mut store.snippets);
 
-        let globals = Self::merge_globals(Self::base_store_globals(store), extras);
+        let globals = Self::merge_globals(Self::base_store_globals(store, cart), extras);
 
         let out = Self::render_page(globals, snippets, &template)
             .map_err(|e| {
@@ -289,6 +335,7 @@ impl PubStoreRoutes {
     pub async fn product_page(
         State(state): State<AppState>,
         Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
         Path(slug): Path<String>,
     ) -> impl IntoResponse {
         let mut store = state
@@ -296,6 +343,7 @@ impl PubStoreRoutes {
             .get_active_store(store_key.business_id, store_key.store_id)
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
 
         let product = match state
             .product_service
@@ -303,12 +351,15 @@ impl PubStoreRoutes {
             .await
         {
             Err(ApiError::NotFound { .. }) => {
-                return Err(Self::store_not_found_page(store, Some(slug)));
+                return Err(Self::store_not_found_page(store, cart, Some(slug)));
             }
             Err(e) => {
                 return Err(Into::<(StatusCode, Html<CowStr>)>::into(e));
             }
-            Ok(p) => p,
+            Ok(p) => match p.localize(&store_key.currency, &store_key.base_currency) {
+                Some(p) => p,
+                None => return Err(Self::store_not_found_page(store, cart, Some(slug))),
+            },
         };
 
         let related = state
@@ -316,6 +367,7 @@ impl PubStoreRoutes {
             .pub_list_related_products(store_key.business_id, &slug)
             .await
             .unwrap_or_default();
+        let related = Self::localize(related, &store_key);
 
         let extras = liquid::object!({
             "product": product,
@@ -325,7 +377,7 @@ impl PubStoreRoutes {
         let template = mem::take(&mut store.product_page_template);
         let snippets = mem::take(&mut store.snippets);
 
-        let globals = Self::merge_globals(Self::base_store_globals(store), extras);
+        let globals = Self::merge_globals(Self::base_store_globals(store, cart), extras);
         std::fs::write("/tmp/debug.liquid", format!("{:#?}", globals)).ok();
         let out = Self::render_page(globals, snippets, &template).map_err(|e| {
             error!("liquid render error: {:?}", e);
@@ -339,17 +391,19 @@ impl PubStoreRoutes {
     pub async fn cart_page(
         State(state): State<AppState>,
         Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
     ) -> impl IntoResponse {
         let mut store = state
             .store_service
             .get_active_store(store_key.business_id, store_key.store_id)
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
 
         let template = mem::take(&mut store.cart_page_template);
         let snippets = mem::take(&mut store.snippets);
 
-        let globals = Self::base_store_globals(store);
+        let globals = Self::base_store_globals(store, cart);
 
         let out = Self::render_page(globals, snippets, &template).map_err(|e| {
             error!("liquid render error: {:?}", e);
@@ -363,40 +417,124 @@ impl PubStoreRoutes {
     pub async fn shop_page(
         State(state): State<AppState>,
         Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
+        Query(pairs): Query<Vec<(String, String)>>,
     ) -> impl IntoResponse {
         let mut store = state
             .store_service
             .get_active_store(store_key.business_id, store_key.store_id)
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
+
+        let query = parse_shop_query(pairs);
+        let params = shop_query_params(&query);
+        let active = query.search.is_some()
+            || query.category.is_some()
+            || query.featured.is_some()
+            || query.min_price.is_some()
+            || query.max_price.is_some()
+            || !query.options.is_empty();
+
+        let result = state
+            .product_service
+            .pub_search_products(
+                &state.category_service,
+                store_key.business_id,
+                &store_key.currency,
+                &store_key.base_currency,
+                query.clone(),
+            )
+            .await
+            .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+
+        let extras = liquid::object!({
+            "query": {
+                "search": query.search,
+                "category": query.category,
+                "featured": query.featured,
+                "min_price": query.min_price,
+                "max_price": query.max_price,
+                "options": query.options,
+                "sort": query.sort,
+                "page": query.page,
+                "limit": query.limit,
+                "params": params,
+                "active": active,
+            },
+            "products": Self::localize(result.products, &store_key),
+            "pagination": PaginationView::new(result.page, result.limit, result.total),
+            "facets": result.facets,
+        });
+
+        let template = mem::take(&mut store.shop_page_template);
+        let snippets = mem::take(&mut store.snippets);
+
+        let globals = Self::merge_globals(Self::base_store_globals(store, cart), extras);
+
+        let out = Self::render_page(globals, snippets, &template).map_err(|e| {
+            error!("liquid render error: {:?}", e);
+            Into::<(StatusCode, Html<CowStr>)>::into(e)
+        })?;
+
+        Ok::<_, (StatusCode, Html<CowStr>)>(Html(out))
+    }
 
+    #[route(method=get, path="/collections/{slug}")]
+    pub async fn collection_page(
+        State(state): State<AppState>,
+        Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
+        Path(slug): Path<String>,
+        Query(query): Query<PubPageQuery>,
+    ) -> impl IntoResponse {
+        const PAGE_SIZE: u32 = 24;
+
+        let mut store = state
+            .store_service
+            .get_active_store(store_key.business_id, store_key.store_id)
+            .await
+            .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
+
+        let collection = match state
+            .category_service
+            .pub_get_collection(store_key.business_id, &slug)
+            .await
+        {
+            Err(ApiError::NotFound { .. }) => {
+                return Err(Self::store_not_found_page(store, cart, Some(slug)));
+            }
+            Err(e) => {
+                return Err(Into::<(StatusCode, Html<CowStr>)>::into(e));
+            }
+            Ok(c) => c,
+        };
+
+        let page = query.page.unwrap_or(1).max(1);
         let products = state
             .product_service
-            .pub_list_products(
+            .pub_list_products_in_categories(
                 store_key.business_id,
-                // TODO: accept query params
-                ProductListQuery {
-                    page: None,
-                    limit: None,
-                    status: None,
-                    category: None,
-                    featured: None,
-                    search: None,
-                },
+                &store_key.currency,
+                &store_key.base_currency,
+                collection.subtree_slugs.clone(),
+                page,
+                PAGE_SIZE,
             )
             .await
-            .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?
-            .products;
+            .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
 
         let extras = liquid::object!({
-            "query": None::<String>,
-            "products": products
+            "collection": collection,
+            "products": Self::localize(products.products, &store_key),
+            "pagination": PaginationView::new(page, PAGE_SIZE, products.total),
         });
 
-        let template = mem::take(&mut store.shop_page_template);
+        let template = mem::take(&mut store.collection_page_template);
         let snippets = mem::take(&mut store.snippets);
 
-        let globals = Self::merge_globals(Self::base_store_globals(store), extras);
+        let globals = Self::merge_globals(Self::base_store_globals(store, cart), extras);
 
         let out = Self::render_page(globals, snippets, &template).map_err(|e| {
             error!("liquid render error: {:?}", e);
@@ -410,6 +548,7 @@ impl PubStoreRoutes {
     pub async fn custom_page(
         State(state): State<AppState>,
         Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
         Path(slug): Path<String>,
     ) -> impl IntoResponse {
         let mut store = state
@@ -417,6 +556,7 @@ impl PubStoreRoutes {
             .get_active_store(store_key.business_id, store_key.store_id)
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
 
         match mem::take(&mut store.custom_pages).get(&slug) {
             Some(tpl) => {
@@ -427,7 +567,7 @@ impl PubStoreRoutes {
                 });
 
                 let snippets = mem::take(&mut store.snippets);
-                let globals = Self::merge_globals(Self::base_store_globals(store), extras);
+                let globals = Self::merge_globals(Self::base_store_globals(store, cart), extras);
 
                 let out = Self::render_page(globals, snippets, tpl.as_ref()).map_err(|e| {
                     error!("liquid render error: {:?}", e);
@@ -436,7 +576,7 @@ impl PubStoreRoutes {
 
                 Ok::<_, (StatusCode, Html<CowStr>)>(Html(out))
             }
-            None => Err(Self::store_not_found_page(store, Some(slug))),
+            None => Err(Self::store_not_found_page(store, cart, Some(slug))),
         }
     }
 
@@ -444,17 +584,19 @@ impl PubStoreRoutes {
     pub async fn fallback(
         State(state): State<AppState>,
         Store(store_key): Store,
+        FromCookies(cart_token): FromCookies<CartToken>,
     ) -> impl IntoResponse {
         let mut store = state
             .store_service
             .get_active_store(store_key.business_id, store_key.store_id)
             .await
             .map_err(Into::<(StatusCode, Html<CowStr>)>::into)?;
+        let cart = Self::load_cart(&state, &store_key, &cart_token).await;
 
         let template = mem::take(&mut store.not_found_page_template);
         let snippets = mem::take(&mut store.snippets);
 
-        let globals = Self::base_store_globals(store);
+        let globals = Self::base_store_globals(store, cart);
 
         let out = Self::render_page(globals, snippets, &template).map_err(|e| {
             error!("liquid render error: {:?}", e);
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::time::Instant;
 
 use bson::oid::ObjectId;
@@ -9,6 +10,10 @@ use super::api::*;
 use super::domain::*;
 use super::repo::{StoreRegRepo, StoreRepo};
 use crate::platform::business::api::BusinessSession;
+use crate::platform::business::repo::BusinessRepo;
+use crate::platform::business::service::BusinessService;
+use crate::tenant::file::domain::file_ids_in;
+use crate::types::currency::Currency;
 use crate::types::id::Id;
 use crate::utils::error::{ApiError, ApiResult};
 use crate::utils::serde_helpers::JsonOption;
@@ -28,11 +33,22 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
         }
     }
 
-    pub async fn create(
+    pub async fn create<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
         create_req: StoreCreateDto,
     ) -> ApiResult<StoreDto> {
+        let currency = match create_req.currency {
+            Some(currency) => currency,
+            None => {
+                business_service
+                    .get_settings(business.business_id)
+                    .await?
+                    .currency
+            }
+        };
+
         self.repo
             .create(
                 business.business_id.into_inner(),
@@ -40,6 +56,7 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
                     create_req.name,
                     create_req.description,
                     create_req.status.into(),
+                    currency,
                     create_req.category,
                     create_req.contact_email,
                     create_req.contact_phone,
@@ -83,6 +100,7 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
         update_req.name.map(|v| record.name = v);
         update_req.description.map(|v| record.description = v);
         update_req.status.map(|v| record.status = v.into());
+        let currency_changed = update_req.currency.to_option().map(|v| record.currency = v);
         update_req.category.ok_then(|v| record.category = v);
         update_req
             .contact_email
@@ -114,6 +132,9 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
         update_req
             .shop_page_template
             .map(|v| record.shop_page_template = v);
+        update_req
+            .collection_page_template
+            .map(|v| record.collection_page_template = v);
         update_req
             .not_found_page_template
             .map(|v| record.not_found_page_template = v);
@@ -139,10 +160,16 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
             .custom_key_values
             .map(|v| record.custom_key_values = v);
 
-        self.repo
-            .update(business_id, id, record)
-            .await
-            .map(Into::into)
+        let record = self.repo.update(business_id, id, record).await?;
+
+        if currency_changed.is_some() {
+            if let Some(mut store_reg) = self.reg.find_by_store(business_id, id).await? {
+                store_reg.currency = record.currency.clone();
+                self.reg.update(business_id, id, store_reg).await?;
+            }
+        }
+
+        Ok(record.into())
     }
 
     pub async fn delete_store(&self, business: BusinessSession, store_id: Id) -> ApiResult<()> {
@@ -169,6 +196,32 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
             .map(Into::into)
     }
 
+    /// The store whose branding goes on printed documents: the given one, or
+    /// else the latest store of the business, if any.
+    pub async fn get_document_store(
+        &self,
+        business_id: Id,
+        store_id: Option<Id>,
+    ) -> ApiResult<Option<StoreDto>> {
+        let business_id = business_id.into_inner();
+
+        match store_id {
+            Some(store_id) => {
+                let id = store_id.into_inner();
+                self.repo
+                    .find_by_id(business_id, id)
+                    .await?
+                    .ok_or(ApiError::not_found("store", id.to_hex()))
+                    .map(|store| Some(store.into()))
+            }
+            None => self
+                .repo
+                .list(business_id, StoreFilter::default(), 1, 1)
+                .await
+                .map(|(stores, _)| stores.into_iter().next().map(Into::into)),
+        }
+    }
+
     pub async fn get_slug(&self, slug: &str) -> ApiResult<StoreRegDto> {
         self.reg
             .find_by_slug(slug)
@@ -185,14 +238,19 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
             .map(Into::into)
     }
 
-    pub async fn set_reg(
+    pub async fn set_reg<B: BusinessRepo>(
         &self,
+        business_service: &BusinessService<B>,
         business: BusinessSession,
         store_id: Id,
         update_req: StoreRegUpdate,
     ) -> ApiResult<StoreRegDto> {
         let business_id = business.business_id.into_inner();
         let store_id = store_id.into_inner();
+        let base_currency = business_service
+            .get_settings(business.business_id)
+            .await?
+            .currency;
 
         if let Some(this) = self.repo.find_by_id(business_id, store_id).await? {
             if let JsonOption::Value(ref domain) = update_req.domain {
@@ -229,6 +287,8 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
 
             if let Some(mut store_reg) = self.reg.find_by_store(business_id, this._id).await? {
                 store_reg.slug = update_req.slug;
+                store_reg.currency = this.currency;
+                store_reg.base_currency = base_currency;
                 update_req
                     .domain
                     .ok_then(|domain| store_reg.domain = domain);
@@ -243,6 +303,8 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
                         this._id,
                         update_req.slug,
                         update_req.domain.to_option(),
+                        this.currency,
+                        base_currency,
                     ))
                     .await
                     .map(Into::into)
@@ -252,6 +314,14 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
         }
     }
 
+    /// Brings the registry of every store up to date after the business
+    /// currency changed.
+    pub async fn set_base_currency(&self, business_id: Id, currency: &Currency) -> ApiResult<()> {
+        self.reg
+            .set_base_currency(business_id.into_inner(), currency)
+            .await
+    }
+
     pub async fn get_reg(&self, business: BusinessSession, store_id: Id) -> ApiResult<StoreRegDto> {
         let business_id = business.business_id.into_inner();
         let store_id = store_id.into_inner();
@@ -325,4 +395,39 @@ impl<R: StoreRepo, Reg: StoreRegRepo> StoreService<R, Reg> {
 
         Ok(None)
     }
+
+    /// Strings from every store of the business that may point at uploaded
+    /// files.
+    pub async fn references_file(&self, business_id: Id, file_id: ObjectId) -> ApiResult<bool> {
+        let mut ids = HashSet::new();
+        for text in self.file_references(business_id).await? {
+            file_ids_in(&text, &mut ids);
+        }
+
+        Ok(ids.contains(&file_id))
+    }
+
+    pub async fn file_references(&self, business_id: Id) -> ApiResult<Vec<String>> {
+        let mut refs = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let (stores, total) = self
+                .repo
+                .list(business_id.into_inner(), StoreFilter::default(), page, 100)
+                .await?;
+
+            refs.extend(
+                stores
+                    .iter()
+                    .flat_map(|s| s.file_references())
+                    .map(str::to_string),
+            );
+
+            if stores.is_empty() || (page * 100) as u64 >= total {
+                return Ok(refs);
+            }
+            page += 1;
+        }
+    }
 }
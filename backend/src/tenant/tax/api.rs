@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use o2o::o2o;
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use super::domain::*;
+use crate::types::{id::Id, name::Name, slug::Slug};
+use crate::utils::serde_helpers::JsonOption;
+
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct TaxClassCreate {
+    pub key: Slug,
+    pub name: Name,
+    pub rates: Vec<TaxRate>,
+}
+
+#[derive(Debug, Serialize, o2o, TS)]
+#[ts(export, bound = "")]
+#[from_owned(TaxClassRecord)]
+pub struct TaxClassDto {
+    #[from(@._id.into())]
+    pub id: Id,
+    pub key: Slug,
+    pub name: Name,
+    pub rates: Vec<TaxRate>,
+    #[from(~.to_chrono())]
+    pub created_at: DateTime<Utc>,
+    #[from(~.to_chrono())]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, TS)]
+#[ts(export, optional_fields)]
+pub struct TaxClassListQuery {
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+    pub search: Option<String>,
+}
+
+#[macros::json_option_serde]
+#[derive(Debug, Deserialize, TS)]
+#[ts(export, bound = "")]
+pub struct TaxClassUpdate {
+    pub key: JsonOption<Slug>,
+    pub name: JsonOption<Name>,
+    pub rates: JsonOption<Vec<TaxRate>>,
+}
+
+#[derive(Debug, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct TaxClassListResponse {
+    pub classes: Vec<TaxClassDto>,
+    pub total: u64,
+    pub page: u32,
+    pub limit: u32,
+}
@@ -0,0 +1,200 @@
+use bigdecimal::{BigDecimal, RoundingMode};
+use bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+use crate::tenant::shipping::domain::ZoneRegion;
+use crate::types::name::Name;
+use crate::types::slug::Slug;
+
+/// Whether catalog prices already contain the tax or have it added on top.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxMode {
+    #[default]
+    Exclusive,
+    Inclusive,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+pub struct TaxRate {
+    // Shown on invoices, e.g. "TVA"
+    pub name: String,
+    pub region: ZoneRegion,
+    #[ts(as = "String")]
+    pub percent: BigDecimal,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TaxClassRecord {
+    pub _id: ObjectId,
+    // What products reference, e.g. "standard" or "reduced"
+    pub key: Slug,
+    pub name: Name,
+    pub rates: Vec<TaxRate>,
+    pub created_at: DateTime,
+    pub updated_at: DateTime,
+}
+
+impl TaxClassRecord {
+    pub fn new(key: Slug, name: Name, rates: Vec<TaxRate>) -> Self {
+        let now = DateTime::now();
+
+        Self {
+            _id: Default::default(),
+            key,
+            name,
+            rates,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// The rate of the most specific region covering the address.
+    pub fn rate_for(&self, country: &str, state: &str) -> Option<&TaxRate> {
+        self.rates
+            .iter()
+            .filter_map(|r| r.region.match_rank(country, state).map(|rank| (rank, r)))
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, rate)| rate)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, TS)]
+#[ts(export, bound = "")]
+pub struct TaxSettings {
+    pub mode: TaxMode,
+    // Class of the products that do not name one, untaxed when empty
+    pub default_class: Option<Slug>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TaxClassFilter {
+    pub search: Option<String>,
+}
+
+/// The tax rate a line was charged at, kept on the order.
+#[derive(Debug, Clone, Deserialize, Serialize, TS)]
+pub struct AppliedTax {
+    pub class: String,
+    pub name: String,
+    #[ts(as = "String")]
+    pub percent: BigDecimal,
+}
+
+/// Tax of an order for one rate, as printed on invoices.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, TS)]
+pub struct TaxBreakdown {
+    pub name: String,
+    #[ts(as = "String")]
+    pub percent: BigDecimal,
+    #[ts(as = "String")]
+    pub taxable: BigDecimal,
+    #[ts(as = "String")]
+    pub amount: BigDecimal,
+}
+
+/// Every class of a business along with its settings, to price the lines
+/// of an order shipped to a given address.
+#[derive(Debug, Clone, Default)]
+pub struct TaxTable {
+    pub settings: TaxSettings,
+    pub classes: Vec<TaxClassRecord>,
+}
+
+impl TaxTable {
+    pub fn applied_tax(
+        &self,
+        class: Option<&Slug>,
+        country: &str,
+        state: &str,
+    ) -> Option<AppliedTax> {
+        let key = class.or(self.settings.default_class.as_ref())?;
+        let class = self.classes.iter().find(|c| &c.key == key)?;
+
+        class.rate_for(country, state).map(|rate| AppliedTax {
+            class: class.key.to_string(),
+            name: rate.name.clone(),
+            percent: rate.percent.clone(),
+        })
+    }
+}
+
+/// Tax on `amount` at `percent`, rounded to the cent. In inclusive mode the
+/// amount already contains the tax, which is extracted from it.
+pub fn tax_on(amount: &BigDecimal, percent: &BigDecimal, mode: TaxMode) -> BigDecimal {
+    let hundred = BigDecimal::from(100);
+    let tax = match mode {
+        TaxMode::Exclusive => amount * percent / hundred,
+        TaxMode::Inclusive => amount * percent / (hundred + percent),
+    };
+
+    tax.with_scale_round(2, RoundingMode::HalfUp)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn dec(v: &str) -> BigDecimal {
+        BigDecimal::from_str(v).unwrap()
+    }
+
+    fn rate(name: &str, country: &str, states: &[&str], percent: &str) -> TaxRate {
+        TaxRate {
+            name: name.into(),
+            region: ZoneRegion {
+                country: country.into(),
+                states: states.iter().map(|s| s.to_string()).collect(),
+            },
+            percent: dec(percent),
+        }
+    }
+
+    #[test]
+    fn test_tax_on() {
+        assert_eq!(
+            tax_on(&dec("1000"), &dec("19"), TaxMode::Exclusive),
+            dec("190")
+        );
+        assert_eq!(
+            tax_on(&dec("1190"), &dec("19"), TaxMode::Inclusive),
+            dec("190")
+        );
+        assert_eq!(
+            tax_on(&dec("9.99"), &dec("9"), TaxMode::Exclusive),
+            dec("0.90")
+        );
+        assert_eq!(tax_on(&dec("100"), &dec("0"), TaxMode::Inclusive), dec("0"));
+    }
+
+    #[test]
+    fn test_applied_tax() {
+        let standard = TaxClassRecord::new(
+            Slug::new("standard").unwrap(),
+            Name::new("Standard").unwrap(),
+            vec![
+                rate("TVA", "DZ", &[], "19"),
+                rate("TVA Sud", "DZ", &["Tamanrasset"], "9"),
+            ],
+        );
+        let table = TaxTable {
+            settings: TaxSettings {
+                mode: TaxMode::Exclusive,
+                default_class: Some(Slug::new("standard").unwrap()),
+            },
+            classes: vec![standard],
+        };
+
+        let tax = table.applied_tax(None, "DZ", "Alger").unwrap();
+        assert_eq!((tax.name.as_str(), tax.percent), ("TVA", dec("19")));
+        let tax = table.applied_tax(None, "dz", "tamanrasset").unwrap();
+        assert_eq!((tax.name.as_str(), tax.percent), ("TVA Sud", dec("9")));
+
+        assert!(table.applied_tax(None, "FR", "Paris").is_none());
+        let reduced = Slug::new("reduced").unwrap();
+        assert!(table.applied_tax(Some(&reduced), "DZ", "Alger").is_none());
+    }
+}
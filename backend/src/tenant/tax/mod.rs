@@ -0,0 +1,5 @@
+pub mod api;
+pub mod domain;
+pub mod repo;
+pub mod routes;
+pub mod service;
@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use bson::{doc, oid::ObjectId, DateTime};
+use futures::stream::TryStreamExt;
+use mongodb::{options::FindOptions, Client, Collection, Database};
+
+use super::domain::*;
+use crate::utils::error::{ApiError, ApiResult};
+
+#[async_trait]
+pub trait TaxRepo: Send + Sync {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        class: TaxClassRecord,
+    ) -> ApiResult<TaxClassRecord>;
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<TaxClassRecord>>;
+    async fn find_by_key(
+        &self,
+        business_id: ObjectId,
+        key: &str,
+    ) -> ApiResult<Option<TaxClassRecord>>;
+    async fn find_all(&self, business_id: ObjectId) -> ApiResult<Vec<TaxClassRecord>>;
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        class: TaxClassRecord,
+    ) -> ApiResult<TaxClassRecord>;
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()>;
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: TaxClassFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<TaxClassRecord>, u64)>;
+    async fn get_settings(&self, business_id: ObjectId) -> ApiResult<TaxSettings>;
+    async fn set_settings(&self, business_id: ObjectId, settings: &TaxSettings) -> ApiResult<()>;
+}
+
+pub struct MongoTaxRepo {
+    client: Client,
+}
+
+impl MongoTaxRepo {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn get_database(&self, business_id: ObjectId) -> Database {
+        self.client
+            .database(&format!("biz-{}", business_id.to_hex()))
+    }
+
+    fn get_collection(&self, business_id: ObjectId) -> Collection<TaxClassRecord> {
+        self.get_database(business_id).collection("tax_classes")
+    }
+
+    // A single document with `_id: "tax"`
+    fn get_settings_collection(&self, business_id: ObjectId) -> Collection<TaxSettings> {
+        self.get_database(business_id).collection("settings")
+    }
+
+    fn build_filter_query(&self, filter: &TaxClassFilter) -> bson::Document {
+        let mut query = doc! {};
+
+        if let Some(ref search) = filter.search {
+            query.insert(
+                "$or",
+                vec![
+                    doc! {
+                        "key": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                    doc! {
+                        "name": {
+                            "$regex": search,
+                            "$options": "i"
+                        }
+                    },
+                ],
+            );
+        }
+
+        query
+    }
+}
+
+#[async_trait]
+impl TaxRepo for MongoTaxRepo {
+    async fn create(
+        &self,
+        business_id: ObjectId,
+        class: TaxClassRecord,
+    ) -> ApiResult<TaxClassRecord> {
+        let collection = self.get_collection(business_id);
+
+        collection.insert_one(&class).await.map_err(|e| {
+            if e.to_string().contains("duplicate key") {
+                ApiError::conflict("tax class", "Tax class already exists")
+            } else {
+                ApiError::internal(format!("Failed to create tax class: {}", e))
+            }
+        })?;
+
+        Ok(class)
+    }
+
+    async fn find_by_id(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+    ) -> ApiResult<Option<TaxClassRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn find_by_key(
+        &self,
+        business_id: ObjectId,
+        key: &str,
+    ) -> ApiResult<Option<TaxClassRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find_one(doc! { "key": key })
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn find_all(&self, business_id: ObjectId) -> ApiResult<Vec<TaxClassRecord>> {
+        let collection = self.get_collection(business_id);
+
+        collection
+            .find(doc! {})
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn update(
+        &self,
+        business_id: ObjectId,
+        id: ObjectId,
+        mut class: TaxClassRecord,
+    ) -> ApiResult<TaxClassRecord> {
+        let collection = self.get_collection(business_id);
+
+        class.updated_at = DateTime::now();
+
+        let result = collection
+            .replace_one(doc! { "_id": id }, &class)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update tax class: {}", e)))?;
+
+        if result.matched_count == 0 {
+            return Err(ApiError::not_found("tax class", "Tax class not found"));
+        }
+
+        Ok(class)
+    }
+
+    async fn delete(&self, business_id: ObjectId, id: ObjectId) -> ApiResult<()> {
+        let collection = self.get_collection(business_id);
+
+        let result = collection
+            .delete_one(doc! { "_id": id })
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to delete tax class: {}", e)))?;
+
+        if result.deleted_count == 0 {
+            return Err(ApiError::not_found("tax class", "Tax class not found"));
+        }
+
+        Ok(())
+    }
+
+    async fn list(
+        &self,
+        business_id: ObjectId,
+        filter: TaxClassFilter,
+        page: u32,
+        limit: u32,
+    ) -> ApiResult<(Vec<TaxClassRecord>, u64)> {
+        let collection = self.get_collection(business_id);
+        let query = self.build_filter_query(&filter);
+
+        let total = collection
+            .count_documents(query.clone())
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to count tax classes: {}", e)))?;
+
+        let skip = ((page.max(1) - 1) * limit) as u64;
+
+        let find_options = FindOptions::builder()
+            .skip(skip)
+            .limit(limit as i64)
+            .sort(doc! { "created_at": -1 })
+            .build();
+
+        collection
+            .find(query)
+            .with_options(find_options)
+            .await
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))?
+            .try_collect()
+            .await
+            .map(|classes| (classes, total))
+            .map_err(|e| ApiError::internal(format!("Failed to read cursor: {}", e)))
+    }
+
+    async fn get_settings(&self, business_id: ObjectId) -> ApiResult<TaxSettings> {
+        let collection = self.get_settings_collection(business_id);
+
+        collection
+            .find_one(doc! { "_id": "tax" })
+            .await
+            .map(Option::unwrap_or_default)
+            .map_err(|e| ApiError::internal(format!("Database query failed: {}", e)))
+    }
+
+    async fn set_settings(&self, business_id: ObjectId, settings: &TaxSettings) -> ApiResult<()> {
+        let collection = self.get_settings_collection(business_id);
+
+        collection
+            .replace_one(doc! { "_id": "tax" }, settings)
+            .upsert(true)
+            .await
+            .map_err(|e| ApiError::internal(format!("Failed to update tax settings: {}", e)))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use axum::extract::{Path, Query, State};
+use macros::routes;
+
+use super::api::*;
+use super::domain::TaxSettings;
+use crate::extractors::cookies::FromCookies;
+use crate::extractors::json::Json;
+use crate::platform::business::api::BusinessSession;
+use crate::platform::user::api::MessageResponse;
+use crate::types::id::Id;
+use crate::utils::error::ApiResult;
+use crate::AppState;
+
+pub struct TaxRoutes;
+
+#[routes(prefix = "/api/v1/tax", state = AppState)]
+impl TaxRoutes {
+    #[route(method=post, path="/classes/create", res=TaxClassDto, perm="taxes:write")]
+    async fn create_class(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[json] create_req: TaxClassCreate,
+    ) -> ApiResult<Json<TaxClassDto>> {
+        state
+            .tax_service
+            .create_class(business, create_req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/classes/list", res=TaxClassListResponse, perm="taxes:read")]
+    async fn list_classes(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[query] query: TaxClassListQuery,
+    ) -> ApiResult<Json<TaxClassListResponse>> {
+        state
+            .tax_service
+            .list_classes(business, query)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=post, path="/classes/{class_id}", res=TaxClassDto, perm="taxes:read")]
+    async fn get_class(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] class_id: Id,
+    ) -> ApiResult<Json<TaxClassDto>> {
+        state
+            .tax_service
+            .get_class(business, class_id)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=patch, path="/classes/{class_id}", res=TaxClassDto, perm="taxes:write")]
+    async fn update_class(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] class_id: Id,
+        #[json] update_req: TaxClassUpdate,
+    ) -> ApiResult<Json<TaxClassDto>> {
+        state
+            .tax_service
+            .update_class(business, class_id, update_req)
+            .await
+            .map(Json)
+    }
+
+    #[route(method=delete, path="/classes/{class_id}", res=MessageResponse, perm="taxes:delete")]
+    async fn delete_class(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[path] class_id: Id,
+    ) -> ApiResult<Json<MessageResponse>> {
+        state
+            .tax_service
+            .delete_class(business, class_id)
+            .await
+            .map(|_| MessageResponse {
+                message: "Tax class deleted successfully".to_string(),
+            })
+            .map(Json)
+    }
+
+    #[route(method=post, path="/settings", res=TaxSettings, perm="taxes:read")]
+    async fn get_settings(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+    ) -> ApiResult<Json<TaxSettings>> {
+        state.tax_service.get_settings(business).await.map(Json)
+    }
+
+    #[route(method=put, path="/settings", res=TaxSettings, perm="taxes:write")]
+    async fn update_settings(
+        State(state): State<AppState>,
+        FromCookies(business): FromCookies<BusinessSession>,
+        #[json] settings: TaxSettings,
+    ) -> ApiResult<Json<TaxSettings>> {
+        state
+            .tax_service
+            .update_settings(business, settings)
+            .await
+            .map(Json)
+    }
+}
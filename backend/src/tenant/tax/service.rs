@@ -0,0 +1,178 @@
+use bigdecimal::BigDecimal;
+use bson::oid::ObjectId;
+
+use super::api::*;
+use super::domain::*;
+use super::repo::TaxRepo;
+use crate::platform::business::api::BusinessSession;
+use crate::types::id::Id;
+use crate::types::slug::Slug;
+use crate::utils::error::{ApiError, ApiResult};
+
+pub struct TaxService<R: TaxRepo> {
+    repo: R,
+}
+
+impl<R: TaxRepo> TaxService<R> {
+    pub fn new(repo: R) -> Self {
+        Self { repo }
+    }
+
+    pub async fn create_class(
+        &self,
+        business: BusinessSession,
+        create_req: TaxClassCreate,
+    ) -> ApiResult<TaxClassDto> {
+        let business_id = business.business_id.into_inner();
+        let record = TaxClassRecord::new(create_req.key, create_req.name, create_req.rates);
+
+        self.validate(business_id, &record).await?;
+
+        self.repo.create(business_id, record).await.map(Into::into)
+    }
+
+    pub async fn get_class(
+        &self,
+        business: BusinessSession,
+        class_id: Id,
+    ) -> ApiResult<TaxClassDto> {
+        let id = class_id.into_inner();
+        self.repo
+            .find_by_id(business.business_id.into_inner(), id)
+            .await?
+            .ok_or(ApiError::not_found("tax class", id.to_hex()))
+            .map(Into::into)
+    }
+
+    pub async fn update_class(
+        &self,
+        business: BusinessSession,
+        class_id: Id,
+        update_req: TaxClassUpdate,
+    ) -> ApiResult<TaxClassDto> {
+        let id = class_id.into_inner();
+        let business_id = business.business_id.into_inner();
+        let mut record = self
+            .repo
+            .find_by_id(business_id, id)
+            .await?
+            .ok_or(ApiError::not_found("tax class", id.to_hex()))?;
+
+        update_req.key.map(|v| record.key = v);
+        update_req.name.map(|v| record.name = v);
+        update_req.rates.map(|v| record.rates = v);
+
+        self.validate(business_id, &record).await?;
+
+        self.repo
+            .update(business_id, id, record)
+            .await
+            .map(Into::into)
+    }
+
+    pub async fn delete_class(&self, business: BusinessSession, class_id: Id) -> ApiResult<()> {
+        self.repo
+            .delete(business.business_id.into_inner(), class_id.into_inner())
+            .await
+    }
+
+    pub async fn list_classes(
+        &self,
+        business: BusinessSession,
+        query: TaxClassListQuery,
+    ) -> ApiResult<TaxClassListResponse> {
+        let TaxClassListQuery {
+            page,
+            limit,
+            search,
+        } = query;
+
+        let filter = TaxClassFilter { search };
+        let page = page.unwrap_or(1);
+        let limit = limit.unwrap_or(10);
+
+        let (classes, total) = self
+            .repo
+            .list(business.business_id.into_inner(), filter, page, limit)
+            .await?;
+
+        let views: Vec<_> = classes.into_iter().map(Into::into).collect();
+        Ok(TaxClassListResponse {
+            classes: views,
+            total,
+            page,
+            limit,
+        })
+    }
+
+    pub async fn get_settings(&self, business: BusinessSession) -> ApiResult<TaxSettings> {
+        self.repo
+            .get_settings(business.business_id.into_inner())
+            .await
+    }
+
+    pub async fn update_settings(
+        &self,
+        business: BusinessSession,
+        settings: TaxSettings,
+    ) -> ApiResult<TaxSettings> {
+        if let Some(ref key) = settings.default_class {
+            self.ensure_exists(business.business_id, key).await?;
+        }
+
+        self.repo
+            .set_settings(business.business_id.into_inner(), &settings)
+            .await?;
+        Ok(settings)
+    }
+
+    pub async fn ensure_exists(&self, business_id: Id, key: &Slug) -> ApiResult<()> {
+        self.repo
+            .find_by_key(business_id.into_inner(), key.as_str())
+            .await?
+            .map(|_| ())
+            .ok_or(ApiError::validation(
+                "tax_class",
+                format!("Tax class '{}' does not exist", key),
+            ))
+    }
+
+    /// Everything needed to tax the lines of an order.
+    pub async fn table(&self, business_id: Id) -> ApiResult<TaxTable> {
+        let business_id = business_id.into_inner();
+
+        Ok(TaxTable {
+            settings: self.repo.get_settings(business_id).await?,
+            classes: self.repo.find_all(business_id).await?,
+        })
+    }
+
+    async fn validate(&self, business_id: ObjectId, record: &TaxClassRecord) -> ApiResult<()> {
+        let zero = BigDecimal::from(0);
+        let hundred = BigDecimal::from(100);
+        if record
+            .rates
+            .iter()
+            .any(|r| r.percent < zero || r.percent > hundred)
+        {
+            return Err(ApiError::validation(
+                "rates",
+                "Tax rates must be between 0 and 100 percent",
+            ));
+        }
+
+        let taken = self
+            .repo
+            .find_by_key(business_id, record.key.as_str())
+            .await?
+            .is_some_and(|c| c._id != record._id);
+        if taken {
+            return Err(ApiError::conflict(
+                "tax class",
+                "Tax class with this key already exists",
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode};
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use ts_rs::TS;
+
+/// An ISO 4217 currency code, e.g. `DZD`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, TS)]
+pub struct Currency(#[ts(as = "String")] String);
+
+/// How amounts in a currency are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrencyFormat {
+    pub symbol: &'static str,
+    pub decimals: u32,
+    // Latin symbols go before the amount, Arabic ones after it
+    pub symbol_first: bool,
+}
+
+const FORMATS: &[(&str, CurrencyFormat)] = &[
+    ("DZD", CurrencyFormat::after("د.ج", 2)),
+    ("MAD", CurrencyFormat::after("د.م.", 2)),
+    ("TND", CurrencyFormat::after("د.ت", 3)),
+    ("LYD", CurrencyFormat::after("ل.د", 3)),
+    ("EGP", CurrencyFormat::after("ج.م", 2)),
+    ("SAR", CurrencyFormat::after("ر.س", 2)),
+    ("AED", CurrencyFormat::after("د.إ", 2)),
+    ("QAR", CurrencyFormat::after("ر.ق", 2)),
+    ("USD", CurrencyFormat::before("$", 2)),
+    ("EUR", CurrencyFormat::before("€", 2)),
+    ("GBP", CurrencyFormat::before("£", 2)),
+    ("CAD", CurrencyFormat::before("CA$", 2)),
+    ("TRY", CurrencyFormat::before("₺", 2)),
+    ("JPY", CurrencyFormat::before("¥", 0)),
+];
+
+impl CurrencyFormat {
+    const fn before(symbol: &'static str, decimals: u32) -> Self {
+        Self {
+            symbol,
+            decimals,
+            symbol_first: true,
+        }
+    }
+
+    const fn after(symbol: &'static str, decimals: u32) -> Self {
+        Self {
+            symbol,
+            decimals,
+            symbol_first: false,
+        }
+    }
+}
+
+impl Currency {
+    pub fn new(s: &str) -> Result<Self, String> {
+        let code = s.trim().to_ascii_uppercase();
+
+        if code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase()) {
+            return Err("Currency must be a 3 letter ISO 4217 code".to_string());
+        }
+
+        Ok(Currency(code))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Codes missing from the table are written with the code as symbol.
+    pub fn format_spec(&self) -> CurrencyFormat {
+        FORMATS
+            .iter()
+            .find(|(code, _)| *code == self.0)
+            .map(|(_, format)| *format)
+            .unwrap_or(CurrencyFormat {
+                symbol: "",
+                decimals: 2,
+                symbol_first: true,
+            })
+    }
+
+    /// Rounds the amount to the currency's decimals and writes it with
+    /// grouped thousands and the symbol, e.g. `1,250.00 د.ج` or `$1,250.00`.
+    pub fn format(&self, amount: &BigDecimal) -> String {
+        let spec = self.format_spec();
+        let rounded = amount
+            .with_scale_round(spec.decimals as i64, RoundingMode::HalfUp)
+            .to_plain_string();
+
+        let (sign, digits) = match rounded.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", rounded.as_str()),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let mut grouped = String::with_capacity(int.len() + int.len() / 3);
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(c);
+        }
+        if !frac.is_empty() {
+            grouped.push('.');
+            grouped.push_str(frac);
+        }
+
+        match (spec.symbol, spec.symbol_first) {
+            ("", _) => format!("{}{} {}", sign, self.0, grouped),
+            (symbol, true) => format!("{}{}{}", sign, symbol, grouped),
+            (symbol, false) => format!("{}{} {}", sign, grouped, symbol),
+        }
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Currency("DZD".to_string())
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Currency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Currency::new(s)
+    }
+}
+
+impl Serialize for Currency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct CurrencyVisitor;
+
+        impl<'de> Visitor<'de> for CurrencyVisitor {
+            type Value = Currency;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a 3 letter currency code")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Currency, E>
+            where
+                E: de::Error,
+            {
+                Currency::new(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(CurrencyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(s: &str) -> BigDecimal {
+        BigDecimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_currency_codes() {
+        assert_eq!(Currency::new(" dzd ").unwrap().as_str(), "DZD");
+        assert!(Currency::new("").is_err());
+        assert!(Currency::new("DZ").is_err());
+        assert!(Currency::new("D1D").is_err());
+        assert!(Currency::new("دج").is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        let dzd = Currency::default();
+        assert_eq!(dzd.format(&amount("1250")), "1,250.00 د.ج");
+        assert_eq!(dzd.format(&amount("999.995")), "1,000.00 د.ج");
+        assert_eq!(dzd.format(&amount("-12.5")), "-12.50 د.ج");
+
+        let usd = Currency::new("USD").unwrap();
+        assert_eq!(usd.format(&amount("1234567.891")), "$1,234,567.89");
+        assert_eq!(usd.format(&amount("0.5")), "$0.50");
+
+        let jpy = Currency::new("JPY").unwrap();
+        assert_eq!(jpy.format(&amount("1500.5")), "¥1,501");
+
+        let tnd = Currency::new("TND").unwrap();
+        assert_eq!(tnd.format(&amount("12")), "12.000 د.ت");
+
+        let chf = Currency::new("CHF").unwrap();
+        assert_eq!(chf.format(&amount("100")), "CHF 100.00");
+    }
+}
@@ -1,6 +1,8 @@
+pub mod currency;
 pub mod email;
 pub mod id;
 pub mod name;
 pub mod password;
 pub mod phone;
+pub mod slug;
 pub mod username;
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use ts_rs::TS;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, TS)]
+pub struct Slug(#[ts(as = "String")] String);
+
+impl Slug {
+    pub fn new(s: &str) -> Result<Self, String> {
+        let trimmed = s.trim();
+
+        if trimmed.is_empty() {
+            return Err("Slug cannot be empty".to_string());
+        }
+
+        if trimmed.len() > 100 {
+            return Err("Slug cannot exceed 100 characters".to_string());
+        }
+
+        // Only lowercase ascii letters, digits and hyphens so it is url safe as is
+        if !trimmed
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(
+                "Slug can only contain lowercase letters, numbers, and hyphens".to_string(),
+            );
+        }
+
+        if trimmed.starts_with('-') || trimmed.ends_with('-') || trimmed.contains("--") {
+            return Err("Slug cannot start, end or repeat hyphens".to_string());
+        }
+
+        Ok(Slug(trimmed.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Slug {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Slug {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Slug::new(s)
+    }
+}
+
+impl Serialize for Slug {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Slug {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SlugVisitor;
+
+        impl<'de> Visitor<'de> for SlugVisitor {
+            type Value = Slug;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a valid slug")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Slug, E>
+            where
+                E: de::Error,
+            {
+                Slug::new(v).map_err(de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_str(SlugVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_slugs() {
+        assert_eq!(Slug::new(" summer-2025 ").unwrap().as_str(), "summer-2025");
+        assert!(Slug::new("shoes").is_ok());
+    }
+
+    #[test]
+    fn test_invalid_slugs() {
+        assert!(Slug::new("").is_err());
+        assert!(Slug::new("Shoes").is_err());
+        assert!(Slug::new("mens shoes").is_err());
+        assert!(Slug::new("-shoes").is_err());
+        assert!(Slug::new("shoes--sale").is_err());
+        assert!(Slug::new("chaussures-été").is_err());
+    }
+}
@@ -83,6 +83,20 @@ pub enum ApiError {
     #[error("Conflict occurred")]
     Conflict { resource: CowStr, reason: CowStr },
 
+    #[error("Invalid state transition")]
+    InvalidTransition {
+        resource: CowStr,
+        from: CowStr,
+        to: CowStr,
+    },
+
+    #[error("Quota exceeded")]
+    QuotaExceeded {
+        resource: CowStr,
+        used: u64,
+        limit: u64,
+    },
+
     #[error("Rate limit exceeded")]
     RateLimitExceeded { retry_after: u64 },
 
@@ -235,8 +249,8 @@ impl ApiError {
     }
 
     /// Create a permission denied error
-    pub fn permission_denied(action: &'static str) -> Self {
-        Self::forbidden("Resource", action)
+    pub fn permission_denied(resource: &'static str, action: &'static str) -> Self {
+        Self::forbidden(resource, action)
     }
 
     // === Request Format Errors ===
@@ -367,6 +381,28 @@ impl ApiError {
         }
     }
 
+    /// Create an invalid state transition error
+    pub fn invalid_transition(
+        resource: impl Into<CowStr>,
+        from: impl Into<CowStr>,
+        to: impl Into<CowStr>,
+    ) -> Self {
+        Self::InvalidTransition {
+            resource: resource.into(),
+            from: from.into(),
+            to: to.into(),
+        }
+    }
+
+    /// Create a quota exceeded error
+    pub fn quota_exceeded(resource: impl Into<CowStr>, used: u64, limit: u64) -> Self {
+        Self::QuotaExceeded {
+            resource: resource.into(),
+            used,
+            limit,
+        }
+    }
+
     // === Rate Limiting ===
 
     /// Create a rate limit exceeded error
@@ -453,6 +489,32 @@ impl ApiError {
                     .with_extension("resource", serde_json::Value::String(resource.to_string()))
             }
 
+            ApiError::InvalidTransition { resource, from, to } => ProblemDetails::new(
+                "/docs/problems/invalid-transition",
+                "Invalid State Transition",
+                409,
+            )
+            .with_detail(format!(
+                "Cannot move {} from '{}' to '{}'",
+                resource, from, to
+            ))
+            .with_extension("resource", serde_json::Value::String(resource.to_string()))
+            .with_extension("from", serde_json::Value::String(from.to_string()))
+            .with_extension("to", serde_json::Value::String(to.to_string())),
+
+            ApiError::QuotaExceeded {
+                resource,
+                used,
+                limit,
+            } => ProblemDetails::new("/docs/problems/quota-exceeded", "Quota Exceeded", 403)
+                .with_detail(format!(
+                    "The {} quota of {} is used up ({} in use)",
+                    resource, limit, used
+                ))
+                .with_extension("resource", serde_json::Value::String(resource.to_string()))
+                .with_extension("used", serde_json::Value::Number((*used).into()))
+                .with_extension("limit", serde_json::Value::Number((*limit).into())),
+
             ApiError::RateLimitExceeded { retry_after } => ProblemDetails::new(
                 "/docs/problems/rate-limit-exceeded",
                 "Rate Limit Exceeded",
@@ -669,6 +731,8 @@ impl Into<StatusCode> for ApiError {
             ApiError::Unauthorized { .. } => StatusCode::UNAUTHORIZED,
             ApiError::Forbidden { .. } => StatusCode::FORBIDDEN,
             ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::InvalidTransition { .. } => StatusCode::CONFLICT,
+            ApiError::QuotaExceeded { .. } => StatusCode::FORBIDDEN,
             ApiError::RateLimitExceeded { .. } => StatusCode::TOO_MANY_REQUESTS,
             ApiError::InternalError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
@@ -1,13 +1,17 @@
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::sync::OnceLock;
 
 use chrono::{Duration, Utc};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::{
+    decode, decode_header, encode, DecodingKey, EncodingKey, Header, TokenData, Validation,
+};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use tracing::{error, trace};
 
 use crate::utils::error::{ApiError, ApiResult};
 
-const SECRET: &'static str = "secret";
+static KEYS: OnceLock<SigningKeys> = OnceLock::new();
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Claims<T> {
@@ -15,36 +19,138 @@ struct Claims<T> {
     pub data: T,
 }
 
+/// Keys tokens are signed with, by `kid`. The current key signs new tokens and
+/// the others are only accepted, so a key can be rotated out once the tokens
+/// it signed have expired.
+pub struct SigningKeys {
+    current: String,
+    keys: HashMap<String, (EncodingKey, DecodingKey)>,
+}
+
+impl SigningKeys {
+    /// Reads comma separated `kid:secret` pairs, the first key being the current one.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut current = None;
+        let mut keys = HashMap::new();
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (kid, secret) = pair
+                .split_once(':')
+                .filter(|(kid, secret)| !kid.is_empty() && !secret.is_empty())
+                .ok_or_else(|| format!("Expected kid:secret, got '{}'", pair))?;
+
+            let key = (
+                EncodingKey::from_secret(secret.as_bytes()),
+                DecodingKey::from_secret(secret.as_bytes()),
+            );
+            if keys.insert(kid.to_string(), key).is_some() {
+                return Err(format!("Key '{}' is listed twice", kid));
+            }
+            current.get_or_insert_with(|| kid.to_string());
+        }
+
+        Ok(Self {
+            current: current.ok_or("No signing key configured")?,
+            keys,
+        })
+    }
+
+    pub fn current(&self) -> &str {
+        &self.current
+    }
+
+    fn encode<T: Serialize>(&self, claims: &Claims<T>) -> ApiResult<String> {
+        let header = Header {
+            kid: Some(self.current.clone()),
+            ..Default::default()
+        };
+        encode(&header, claims, &self.keys[&self.current].0).map_err(|_| ApiError::InternalError {
+            message: "Can't encode the token".into(),
+        })
+    }
+
+    fn decode<T: DeserializeOwned>(&self, token: &str) -> Result<T, String> {
+        let kid = decode_header(token)
+            .map_err(|e| e.to_string())?
+            .kid
+            .ok_or("The token has no key id")?;
+        let (_, key) = self
+            .keys
+            .get(&kid)
+            .ok_or_else(|| format!("Unknown key id '{}'", kid))?;
+
+        decode::<Claims<T>>(token, key, &Validation::default())
+            .map(|data: TokenData<Claims<T>>| data.claims.data)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Sets the keys once at startup, before any token is handled.
+pub fn configure(keys: SigningKeys) {
+    if KEYS.set(keys).is_err() {
+        panic!("JWT signing keys are already configured");
+    }
+}
+
+fn keys() -> ApiResult<&'static SigningKeys> {
+    KEYS.get()
+        .ok_or_else(|| ApiError::internal("JWT signing keys are not configured"))
+}
+
 pub fn encode_jwt<T: Serialize>(payload: T, ttl: Duration) -> ApiResult<String> {
     let exp = (Utc::now() + ttl).timestamp() as usize;
     let claims = Claims { exp, data: payload };
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(SECRET.as_ref()),
-    )
-    .map_err(|_| ApiError::InternalError {
-        message: "Can't encode the token".into(),
-    })
+    keys()?.encode(&claims)
 }
 
 pub fn decode_jwt<T: DeserializeOwned + Debug>(token: &str) -> ApiResult<T> {
     trace!("decode jwt: {:?}", token);
-    decode::<Claims<T>>(
-        token,
-        &DecodingKey::from_secret(SECRET.as_ref()),
-        &Validation::default(),
-    )
-    .map(|data: TokenData<Claims<T>>| data.claims.data)
-    .map_err(|e| {
-        error!("jwt decode error: {:?}", e);
-        ApiError::InvalidRequestBody {
-            expected: "A valid token".into(),
-            message: "The Token you have Provided is invalid".into(),
+    keys()?
+        .decode(token)
+        .map_err(|e| {
+            error!("jwt decode error: {:?}", e);
+            ApiError::InvalidRequestBody {
+                expected: "A valid token".into(),
+                message: "The Token you have Provided is invalid".into(),
+            }
+        })
+        .map(|v| {
+            trace!("decoded jwt: {:?}", v);
+            v
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims() -> Claims<String> {
+        Claims {
+            exp: (Utc::now() + Duration::minutes(5)).timestamp() as usize,
+            data: "payload".to_string(),
         }
-    })
-    .map(|v| {
-        trace!("decoded jwt: {:?}", v);
-        v
-    })
+    }
+
+    #[test]
+    fn test_rotation() {
+        let old = SigningKeys::parse("2024:first-secret").unwrap();
+        let rotated = SigningKeys::parse("2025:second-secret, 2024:first-secret").unwrap();
+        assert_eq!(rotated.current(), "2025");
+
+        // Tokens signed before the rotation are still accepted
+        let token = old.encode(&claims()).unwrap();
+        assert_eq!(rotated.decode::<String>(&token).unwrap(), "payload");
+
+        // Until the old key is dropped
+        let token = rotated.encode(&claims()).unwrap();
+        assert!(old.decode::<String>(&token).is_err());
+        let dropped = SigningKeys::parse("2025:another-secret").unwrap();
+        assert!(dropped.decode::<String>(&token).is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(SigningKeys::parse("").is_err());
+        assert!(SigningKeys::parse("no-secret").is_err());
+        assert!(SigningKeys::parse("a:x,a:y").is_err());
+    }
 }
@@ -3,8 +3,10 @@ pub mod error;
 pub mod jwt;
 pub mod log;
 pub mod macros;
+pub mod pdf;
 pub mod problem;
 pub mod rand;
 pub mod router;
 pub mod serde_helpers;
+pub mod spreadsheet;
 pub mod types;
@@ -0,0 +1,615 @@
+//! A small PDF writer for the documents the backend prints, such as invoices.
+//!
+//! Text is shaped with rustybuzz and laid out with the Unicode bidi algorithm,
+//! so Arabic is joined and right to left runs are ordered as they should be.
+//! Fonts are bundled and embedded as subsets. Coordinates are in points from
+//! the top left corner of the page, `y` being the baseline of text.
+
+use std::collections::BTreeMap;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::types::{CidFontType, FontFlags, SystemInfo, UnicodeCmap};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use rustybuzz::ttf_parser::GlyphId;
+use rustybuzz::{Direction, Face, UnicodeBuffer};
+use unicode_bidi::ParagraphBidiInfo;
+
+pub const A4: (f32, f32) = (595.0, 842.0);
+
+const REGULAR_FONT: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/fonts/DejaVuSans.ttf"
+));
+const BOLD_FONT: &[u8] = include_bytes!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/assets/fonts/DejaVuSans-Bold.ttf"
+));
+
+const SYSTEM_INFO: SystemInfo = SystemInfo {
+    registry: Str(b"Adobe"),
+    ordering: Str(b"Identity"),
+    supplement: 0,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weight {
+    Regular,
+    Bold,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub f32, pub f32, pub f32);
+
+impl Color {
+    pub const BLACK: Color = Color(0.0, 0.0, 0.0);
+
+    pub const fn gray(level: f32) -> Self {
+        Color(level, level, level)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub size: f32,
+    pub weight: Weight,
+    pub color: Color,
+}
+
+impl TextStyle {
+    pub const fn new(size: f32, weight: Weight) -> Self {
+        Self {
+            size,
+            weight,
+            color: Color::BLACK,
+        }
+    }
+
+    pub const fn color(self, color: Color) -> Self {
+        Self { color, ..self }
+    }
+}
+
+/// A decoded image, kept as 8 bit RGB samples with an optional alpha channel.
+pub struct Image {
+    width: u32,
+    height: u32,
+    rgb: Vec<u8>,
+    alpha: Option<Vec<u8>>,
+}
+
+impl Image {
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let image =
+            image::load_from_memory(bytes).map_err(|e| format!("Failed to decode image: {}", e))?;
+        let alpha = image.color().has_alpha().then(|| {
+            image
+                .to_rgba8()
+                .pixels()
+                .map(|p| p.0[3])
+                .collect::<Vec<_>>()
+        });
+
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            rgb: image.to_rgb8().into_raw(),
+            alpha,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageId(usize);
+
+struct Font {
+    data: &'static [u8],
+    face: Face<'static>,
+    base_name: &'static [u8],
+    // Every glyph drawn, with the text it stands for
+    used: BTreeMap<u16, String>,
+}
+
+impl Font {
+    fn load(data: &'static [u8], base_name: &'static [u8]) -> Self {
+        Self {
+            data,
+            face: Face::from_slice(data, 0).expect("bundled font is valid"),
+            base_name,
+            used: BTreeMap::new(),
+        }
+    }
+
+    fn scale(&self, size: f32) -> f32 {
+        size / self.face.units_per_em() as f32
+    }
+
+    // In thousandths of the font size, as PDF wants them
+    fn to_pdf_units(&self, value: f32) -> f32 {
+        value * 1000.0 / self.face.units_per_em() as f32
+    }
+}
+
+struct ShapedGlyph {
+    id: u16,
+    x: f32,
+    y: f32,
+    text: String,
+}
+
+struct ShapedText {
+    glyphs: Vec<ShapedGlyph>,
+    width: f32,
+}
+
+pub struct PdfDocument {
+    title: String,
+    fonts: [Font; 2],
+    pages: Vec<Content>,
+    current: usize,
+    images: Vec<Image>,
+}
+
+impl PdfDocument {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            fonts: [
+                Font::load(REGULAR_FONT, b"BXAAAA+DejaVuSans"),
+                Font::load(BOLD_FONT, b"BXAAAB+DejaVuSans-Bold"),
+            ],
+            pages: Vec::new(),
+            current: 0,
+            images: Vec::new(),
+        }
+    }
+
+    fn font(&self, weight: Weight) -> &Font {
+        &self.fonts[weight as usize]
+    }
+
+    fn content(&mut self) -> &mut Content {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+        &mut self.pages[self.current]
+    }
+
+    /// Starts a page and makes it the one drawn on.
+    pub fn new_page(&mut self) -> usize {
+        self.pages.push(Content::new());
+        self.current = self.pages.len() - 1;
+        self.current
+    }
+
+    /// Goes back to an earlier page, to add footers once the page count is
+    /// known.
+    pub fn select_page(&mut self, page: usize) {
+        assert!(page < self.pages.len(), "page {} does not exist", page);
+        self.current = page;
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn add_image(&mut self, image: Image) -> ImageId {
+        self.images.push(image);
+        ImageId(self.images.len() - 1)
+    }
+
+    /// Size of the image in pixels.
+    pub fn image_size(&self, image: ImageId) -> (f32, f32) {
+        let image = &self.images[image.0];
+        (image.width as f32, image.height as f32)
+    }
+
+    /// Shapes a single line of text. Runs are laid out in visual order, the
+    /// base direction being taken from the first strong character.
+    fn shape(&self, text: &str, size: f32, weight: Weight) -> ShapedText {
+        let text = text.replace(['\n', '\r', '\t'], " ");
+        let font = self.font(weight);
+        let scale = font.scale(size);
+
+        let mut glyphs = Vec::new();
+        let mut x = 0.0;
+        if text.is_empty() {
+            return ShapedText { glyphs, width: x };
+        }
+
+        let bidi = ParagraphBidiInfo::new(&text, None);
+        let (levels, runs) = bidi.visual_runs(0..text.len());
+        for run in runs {
+            let run_text = &text[run.clone()];
+            let mut buffer = UnicodeBuffer::new();
+            buffer.push_str(run_text);
+            buffer.set_direction(if levels[run.start].is_rtl() {
+                Direction::RightToLeft
+            } else {
+                Direction::LeftToRight
+            });
+            buffer.guess_segment_properties();
+
+            let shaped = rustybuzz::shape(&font.face, &[], buffer);
+            let infos = shaped.glyph_infos();
+            let positions = shaped.glyph_positions();
+
+            let mut clusters: Vec<usize> = infos.iter().map(|i| i.cluster as usize).collect();
+            clusters.sort_unstable();
+            clusters.dedup();
+
+            let mut seen = Vec::new();
+            for (info, pos) in infos.iter().zip(positions) {
+                let cluster = info.cluster as usize;
+                // Only the first glyph of a cluster carries its text
+                let text = if seen.contains(&cluster) {
+                    String::new()
+                } else {
+                    seen.push(cluster);
+                    let end = clusters
+                        .iter()
+                        .find(|&&c| c > cluster)
+                        .copied()
+                        .unwrap_or(run_text.len());
+                    run_text[cluster..end].to_string()
+                };
+
+                glyphs.push(ShapedGlyph {
+                    id: info.glyph_id as u16,
+                    x: x + pos.x_offset as f32 * scale,
+                    y: pos.y_offset as f32 * scale,
+                    text,
+                });
+                x += pos.x_advance as f32 * scale;
+            }
+        }
+
+        ShapedText { glyphs, width: x }
+    }
+
+    pub fn measure(&self, text: &str, style: &TextStyle) -> f32 {
+        self.shape(text, style.size, style.weight).width
+    }
+
+    /// Draws one line of text anchored at `x` as given by `align`, returning
+    /// its width.
+    pub fn text(&mut self, x: f32, y: f32, text: &str, style: &TextStyle, align: Align) -> f32 {
+        let shaped = self.shape(text, style.size, style.weight);
+        let start = match align {
+            Align::Left => x,
+            Align::Center => x - shaped.width / 2.0,
+            Align::Right => x - shaped.width,
+        };
+
+        let font_name = Self::font_name(style.weight);
+        let baseline = A4.1 - y;
+        let font = &mut self.fonts[style.weight as usize];
+        for glyph in &shaped.glyphs {
+            font.used
+                .entry(glyph.id)
+                .or_insert_with(|| glyph.text.clone());
+        }
+
+        let content = self.content();
+        content.begin_text();
+        content.set_font(font_name, style.size);
+        content.set_fill_rgb(style.color.0, style.color.1, style.color.2);
+        for glyph in &shaped.glyphs {
+            content.set_text_matrix([1.0, 0.0, 0.0, 1.0, start + glyph.x, baseline + glyph.y]);
+            content.show(Str(&glyph.id.to_be_bytes()));
+        }
+        content.end_text();
+
+        shaped.width
+    }
+
+    /// Breaks text into lines no wider than `width`. Words longer than a line
+    /// are left whole.
+    pub fn wrap(&self, text: &str, width: f32, style: &TextStyle) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+
+        for word in text.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+
+            if !line.is_empty() && self.measure(&candidate, style) > width {
+                lines.push(std::mem::replace(&mut line, word.to_string()));
+            } else {
+                line = candidate;
+            }
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+
+        lines
+    }
+
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+        let content = self.content();
+        content.save_state();
+        content.set_line_width(width);
+        content.set_stroke_rgb(color.0, color.1, color.2);
+        content.move_to(from.0, A4.1 - from.1);
+        content.line_to(to.0, A4.1 - to.1);
+        content.stroke();
+        content.restore_state();
+    }
+
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let content = self.content();
+        content.save_state();
+        content.set_fill_rgb(color.0, color.1, color.2);
+        content.rect(x, A4.1 - y - height, width, height);
+        content.fill_nonzero();
+        content.restore_state();
+    }
+
+    /// Draws the image with its top left corner at `x`, `y`.
+    pub fn image(&mut self, image: ImageId, x: f32, y: f32, width: f32, height: f32) {
+        let name = Self::image_name(image.0);
+        let content = self.content();
+        content.save_state();
+        content.transform([width, 0.0, 0.0, height, x, A4.1 - y - height]);
+        content.x_object(Name(name.as_bytes()));
+        content.restore_state();
+    }
+
+    fn font_name(weight: Weight) -> Name<'static> {
+        match weight {
+            Weight::Regular => Name(b"F0"),
+            Weight::Bold => Name(b"F1"),
+        }
+    }
+
+    fn image_name(index: usize) -> String {
+        format!("Im{}", index)
+    }
+
+    pub fn finish(mut self) -> Result<Vec<u8>, String> {
+        if self.pages.is_empty() {
+            self.new_page();
+        }
+
+        let mut next = 1;
+        let mut alloc = || {
+            let id = Ref::new(next);
+            next += 1;
+            id
+        };
+
+        let mut pdf = Pdf::new();
+        let catalog_id = alloc();
+        let tree_id = alloc();
+        let info_id = alloc();
+
+        let font_ids: Vec<Ref> = self
+            .fonts
+            .iter()
+            .map(|font| write_font(&mut pdf, font, &mut alloc))
+            .collect::<Result<_, _>>()?;
+        let image_ids: Vec<Ref> = self
+            .images
+            .iter()
+            .map(|image| write_image(&mut pdf, image, &mut alloc))
+            .collect();
+
+        let mut page_ids = Vec::with_capacity(self.pages.len());
+        for content in self.pages {
+            let page_id = alloc();
+            let content_id = alloc();
+            page_ids.push(page_id);
+
+            let data = compress_to_vec_zlib(&content.finish(), 6);
+            pdf.stream(content_id, &data).filter(Filter::FlateDecode);
+
+            let mut page = pdf.page(page_id);
+            page.media_box(Rect::new(0.0, 0.0, A4.0, A4.1))
+                .parent(tree_id)
+                .contents(content_id);
+            let mut resources = page.resources();
+            let mut fonts = resources.fonts();
+            fonts.pair(Self::font_name(Weight::Regular), font_ids[0]);
+            fonts.pair(Self::font_name(Weight::Bold), font_ids[1]);
+            fonts.finish();
+            let mut x_objects = resources.x_objects();
+            for (index, id) in image_ids.iter().enumerate() {
+                x_objects.pair(Name(Self::image_name(index).as_bytes()), *id);
+            }
+        }
+
+        pdf.catalog(catalog_id).pages(tree_id);
+        pdf.pages(tree_id)
+            .count(page_ids.len() as i32)
+            .kids(page_ids);
+        pdf.document_info(info_id).title(TextStr(&self.title));
+
+        Ok(pdf.finish())
+    }
+}
+
+/// Embeds the glyphs a font was used for and returns its Type0 font.
+fn write_font(pdf: &mut Pdf, font: &Font, alloc: &mut impl FnMut() -> Ref) -> Result<Ref, String> {
+    let type0_id = alloc();
+    let cid_id = alloc();
+    let descriptor_id = alloc();
+    let cmap_id = alloc();
+    let file_id = alloc();
+
+    let glyphs: Vec<u16> = std::iter::once(0)
+        .chain(font.used.keys().copied())
+        .collect();
+    let subset = subsetter::subset(font.data, 0, subsetter::Profile::pdf(&glyphs))
+        .map_err(|e| format!("Failed to subset font: {}", e))?;
+    let data = compress_to_vec_zlib(&subset, 6);
+
+    pdf.type0_font(type0_id)
+        .base_font(Name(font.base_name))
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_id)
+        .to_unicode(cmap_id);
+
+    let mut cid = pdf.cid_font(cid_id);
+    cid.subtype(CidFontType::Type2)
+        .base_font(Name(font.base_name))
+        .system_info(SYSTEM_INFO)
+        .font_descriptor(descriptor_id)
+        .cid_to_gid_map_predefined(Name(b"Identity"));
+    let mut widths = cid.widths();
+    for &id in &glyphs {
+        let advance = font.face.glyph_hor_advance(GlyphId(id)).unwrap_or(0);
+        widths.consecutive(id, [font.to_pdf_units(advance as f32)]);
+    }
+    widths.finish();
+    cid.finish();
+
+    let face = &font.face;
+    let bbox = face.global_bounding_box();
+    pdf.font_descriptor(descriptor_id)
+        .name(Name(font.base_name))
+        .flags(FontFlags::NON_SYMBOLIC)
+        .bbox(Rect::new(
+            font.to_pdf_units(bbox.x_min as f32),
+            font.to_pdf_units(bbox.y_min as f32),
+            font.to_pdf_units(bbox.x_max as f32),
+            font.to_pdf_units(bbox.y_max as f32),
+        ))
+        .italic_angle(0.0)
+        .ascent(font.to_pdf_units(face.ascender() as f32))
+        .descent(font.to_pdf_units(face.descender() as f32))
+        .cap_height(font.to_pdf_units(face.capital_height().unwrap_or(face.ascender()) as f32))
+        .stem_v(80.0)
+        .font_file2(file_id);
+
+    let mut cmap = UnicodeCmap::new(Name(b"Custom"), SYSTEM_INFO);
+    for (&id, text) in &font.used {
+        if !text.is_empty() {
+            cmap.pair_with_multiple(id, text.chars());
+        }
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+
+    pdf.stream(file_id, &data)
+        .filter(Filter::FlateDecode)
+        .pair(Name(b"Length1"), subset.len() as i32);
+
+    Ok(type0_id)
+}
+
+fn write_image(pdf: &mut Pdf, image: &Image, alloc: &mut impl FnMut() -> Ref) -> Ref {
+    let id = alloc();
+    let mask_id = image.alpha.as_ref().map(|_| alloc());
+
+    let data = compress_to_vec_zlib(&image.rgb, 6);
+    let mut xobject = pdf.image_xobject(id, &data);
+    xobject.filter(Filter::FlateDecode);
+    xobject
+        .width(image.width as i32)
+        .height(image.height as i32)
+        .color_space_name(Name(b"DeviceRGB"))
+        .bits_per_component(8);
+    if let Some(mask_id) = mask_id {
+        xobject.s_mask(mask_id);
+    }
+    xobject.finish();
+
+    if let (Some(mask_id), Some(alpha)) = (mask_id, &image.alpha) {
+        let data = compress_to_vec_zlib(alpha, 6);
+        let mut mask = pdf.image_xobject(mask_id, &data);
+        mask.filter(Filter::FlateDecode);
+        mask.width(image.width as i32)
+            .height(image.height as i32)
+            .color_space_name(Name(b"DeviceGray"))
+            .bits_per_component(8);
+    }
+
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: TextStyle = TextStyle::new(10.0, Weight::Regular);
+
+    #[test]
+    fn test_arabic_is_joined_and_reordered() {
+        let doc = PdfDocument::new("test");
+
+        // The letters of a word take their joined forms, which differ from
+        // the isolated ones shaped one by one
+        let word = doc.shape("سلام", 10.0, Weight::Regular);
+        let isolated: Vec<u16> = "سلام"
+            .chars()
+            .map(|c| doc.shape(&c.to_string(), 10.0, Weight::Regular).glyphs[0].id)
+            .collect();
+        assert!(word.glyphs.iter().all(|g| g.id != 0));
+        assert_ne!(
+            word.glyphs.iter().map(|g| g.id).collect::<Vec<_>>(),
+            isolated
+        );
+
+        // Right to left: the first letter ends up on the right
+        let first = word
+            .glyphs
+            .iter()
+            .find(|g| g.text.starts_with('س'))
+            .unwrap();
+        assert!(word.glyphs.iter().all(|g| g.x <= first.x));
+
+        // Latin runs inside Arabic keep their own order
+        let mixed = doc.shape("طلب ABC", 10.0, Weight::Bold);
+        let a = mixed.glyphs.iter().position(|g| g.text == "A").unwrap();
+        let c = mixed.glyphs.iter().position(|g| g.text == "C").unwrap();
+        assert!(mixed.glyphs[a].x < mixed.glyphs[c].x);
+        assert!(mixed.glyphs.iter().all(|g| g.id != 0));
+    }
+
+    #[test]
+    fn test_wrap() {
+        let doc = PdfDocument::new("test");
+        let text = "a fairly long product title that does not fit on one line";
+        let width = doc.measure("a fairly long product", &BODY) + 1.0;
+
+        let lines = doc.wrap(text, width, &BODY);
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| doc.measure(l, &BODY) <= width));
+        assert_eq!(lines.join(" "), text);
+
+        assert_eq!(doc.wrap("", width, &BODY), vec![String::new()]);
+        assert_eq!(doc.wrap("Supercalifragilistic", 5.0, &BODY).len(), 1);
+    }
+
+    #[test]
+    fn test_finish() {
+        let mut doc = PdfDocument::new("Invoice");
+        doc.text(40.0, 60.0, "Invoice INV-000001", &BODY, Align::Left);
+        doc.new_page();
+        doc.text(
+            40.0,
+            60.0,
+            "فاتورة",
+            &BODY.color(Color::gray(0.4)),
+            Align::Right,
+        );
+        doc.line((40.0, 70.0), (555.0, 70.0), 0.5, Color::BLACK);
+
+        let bytes = doc.finish().unwrap();
+        assert!(bytes.starts_with(b"%PDF-"));
+        let text = String::from_utf8_lossy(&bytes);
+        let pages = text.matches("/Type /Page").count() - text.matches("/Type /Pages").count();
+        assert_eq!(pages, 2);
+        // Fonts are embedded as subsets, far smaller than the bundled files
+        assert!(bytes.len() < REGULAR_FONT.len() / 4);
+    }
+}
@@ -10,3 +10,31 @@ pub fn generate_otp() -> ApiResult<String> {
     let num = u32::from_be_bytes(bytes) % 1_000_000;
     Ok(format!("{:06}", num))
 }
+
+/// 32 random bytes, hex encoded.
+pub fn generate_token() -> ApiResult<String> {
+    let mut bytes = [0u8; 32];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| ApiError::internal("Can't generate random token"))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Random bytes to seed a TOTP authenticator with.
+pub fn generate_totp_secret() -> ApiResult<Vec<u8>> {
+    let mut bytes = vec![0u8; 20];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| ApiError::internal("Can't generate random secret"))?;
+    Ok(bytes)
+}
+
+/// A single use code in the `xxxxx-xxxxx` form, easy to write down.
+pub fn generate_recovery_code() -> ApiResult<String> {
+    let mut bytes = [0u8; 5];
+    OsRng
+        .try_fill_bytes(&mut bytes)
+        .map_err(|_| ApiError::internal("Can't generate random recovery code"))?;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("{}-{}", &hex[..5], &hex[5..]))
+}
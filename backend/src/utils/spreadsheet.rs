@@ -0,0 +1,353 @@
+//! CSV and XLSX tables for imports and exports.
+//!
+//! CSV is written row by row so large exports can be streamed, XLSX is built
+//! in memory. Both are read back into the same `Table` of strings.
+
+use std::io::Cursor;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+use calamine::{open_workbook_from_rs, Data, Reader, Xlsx};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use ts_rs::TS;
+
+// Lets spreadsheet applications detect UTF-8, Arabic is garbled otherwise
+pub const CSV_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Rows of a worksheet, the header included
+pub const XLSX_MAX_ROWS: usize = 1_048_576;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, TS)]
+#[serde(rename_all = "snake_case")]
+#[ts(export)]
+pub enum SpreadsheetFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+impl SpreadsheetFormat {
+    /// XLSX files are zip archives, anything else is read as CSV.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(b"PK\x03\x04") {
+            SpreadsheetFormat::Xlsx
+        } else {
+            SpreadsheetFormat::Csv
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SpreadsheetFormat::Csv => "text/csv; charset=utf-8",
+            SpreadsheetFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SpreadsheetFormat::Csv => "csv",
+            SpreadsheetFormat::Xlsx => "xlsx",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(BigDecimal),
+}
+
+impl Cell {
+    pub fn text(value: impl Into<String>) -> Self {
+        Cell::Text(value.into())
+    }
+
+    pub fn optional(value: Option<impl Into<String>>) -> Self {
+        value.map_or(Cell::Empty, Cell::text)
+    }
+}
+
+/// One CSV record, line break included.
+pub fn csv_row(cells: &[Cell]) -> Result<Vec<u8>, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(cells.iter().map(|cell| match cell {
+            Cell::Empty => String::new(),
+            Cell::Text(text) => text.clone(),
+            Cell::Number(number) => number.to_string(),
+        }))
+        .map_err(|e| format!("Failed to write CSV: {}", e))?;
+
+    writer
+        .into_inner()
+        .map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+pub fn xlsx(sheet_name: &str, header: &[Cell], rows: &[Vec<Cell>]) -> Result<Vec<u8>, String> {
+    if rows.len() >= XLSX_MAX_ROWS {
+        return Err(format!(
+            "XLSX worksheets hold at most {} rows",
+            XLSX_MAX_ROWS - 1
+        ));
+    }
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet
+        .set_name(sheet_name)
+        .and_then(|w| w.set_freeze_panes(1, 0))
+        .map_err(|e| e.to_string())?;
+
+    let bold = Format::new().set_bold();
+    for (row, cells) in std::iter::once(header)
+        .chain(rows.iter().map(Vec::as_slice))
+        .enumerate()
+    {
+        for (col, cell) in cells.iter().enumerate() {
+            let (row, col) = (row as u32, col as u16);
+            let result = match cell {
+                Cell::Empty => continue,
+                Cell::Text(text) if row == 0 => {
+                    worksheet.write_string_with_format(row, col, text, &bold)
+                }
+                Cell::Text(text) => worksheet.write_string(row, col, text),
+                Cell::Number(number) => {
+                    worksheet.write_number(row, col, number.to_f64().unwrap_or_default())
+                }
+            };
+            result.map_err(|e| e.to_string())?;
+        }
+    }
+
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
+
+/// What is wrong with a row of an imported sheet.
+#[derive(Debug, Clone, PartialEq, Serialize, TS)]
+#[ts(export)]
+pub struct ImportRowError {
+    // As numbered in a spreadsheet application, the header row being 1
+    pub row: u32,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+/// A sheet read back as text, with the header row split off. Columns are
+/// looked up ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn read(bytes: &[u8]) -> Result<Self, String> {
+        let mut records = match SpreadsheetFormat::detect(bytes) {
+            SpreadsheetFormat::Csv => read_csv(bytes)?,
+            SpreadsheetFormat::Xlsx => read_xlsx(bytes)?,
+        };
+
+        // Blank lines are common at the end of exported sheets
+        records.retain(|record| record.iter().any(|value| !value.trim().is_empty()));
+        if records.is_empty() {
+            return Err("The file has no header row".to_string());
+        }
+
+        let headers = records
+            .remove(0)
+            .into_iter()
+            .map(|h| h.trim().to_string())
+            .collect();
+
+        Ok(Self {
+            headers,
+            rows: records,
+        })
+    }
+
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+    }
+
+    /// Reports the missing columns against the header row.
+    pub fn has_columns<C: AsRef<str>>(
+        &self,
+        names: &[C],
+        errors: &mut Vec<ImportRowError>,
+    ) -> bool {
+        let before = errors.len();
+        for name in names.iter().map(AsRef::as_ref) {
+            if self.column(name).is_none() {
+                errors.push(ImportRowError {
+                    row: 1,
+                    column: Some(name.to_string()),
+                    message: "Missing column".to_string(),
+                });
+            }
+        }
+        errors.len() == before
+    }
+}
+
+/// A row of a sheet being imported, collecting what is wrong with its values.
+pub struct Row<'a> {
+    table: &'a Table,
+    values: &'a [String],
+    // As numbered in a spreadsheet application
+    pub number: u32,
+    errors: &'a mut Vec<ImportRowError>,
+}
+
+impl<'a> Row<'a> {
+    pub fn new(table: &'a Table, index: usize, errors: &'a mut Vec<ImportRowError>) -> Self {
+        Self {
+            table,
+            values: &table.rows[index],
+            number: index as u32 + 2,
+            errors,
+        }
+    }
+
+    /// The trimmed value, none when empty or when the column is missing.
+    pub fn text(&self, column: impl AsRef<str>) -> Option<String> {
+        let index = self.table.column(column.as_ref())?;
+        let value = self.values.get(index)?.trim();
+        (!value.is_empty()).then(|| value.to_string())
+    }
+
+    pub fn error(&mut self, column: impl AsRef<str>, message: impl Into<String>) {
+        self.errors.push(ImportRowError {
+            row: self.number,
+            column: Some(column.as_ref().to_string()),
+            message: message.into(),
+        });
+    }
+
+    /// Errors reported so far, for the whole sheet.
+    pub fn errors(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub fn required(&mut self, column: impl AsRef<str>) -> Option<String> {
+        let value = self.text(&column);
+        if value.is_none() {
+            self.error(column, "Required");
+        }
+        value
+    }
+
+    pub fn parsed<T>(
+        &mut self,
+        column: impl AsRef<str>,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Option<T> {
+        let value = self.text(&column)?;
+        parse(&value).map_err(|e| self.error(column, e)).ok()
+    }
+}
+
+fn read_csv(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let bytes = bytes.strip_prefix(CSV_BOM).unwrap_or(bytes);
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes);
+
+    reader
+        .records()
+        .map(|record| {
+            record
+                .map(|r| r.iter().map(str::to_string).collect())
+                .map_err(|e| format!("Invalid CSV: {}", e))
+        })
+        .collect()
+}
+
+fn read_xlsx(bytes: &[u8]) -> Result<Vec<Vec<String>>, String> {
+    let mut workbook: Xlsx<_> =
+        open_workbook_from_rs(Cursor::new(bytes)).map_err(|e| format!("Invalid XLSX: {}", e))?;
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or("The workbook has no worksheet")?
+        .map_err(|e| format!("Invalid XLSX: {}", e))?;
+
+    Ok(range
+        .rows()
+        .map(|row| row.iter().map(cell_text).collect())
+        .collect())
+}
+
+fn cell_text(data: &Data) -> String {
+    match data {
+        Data::Empty | Data::Error(_) => String::new(),
+        Data::String(s) | Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        // Whole numbers are read back without the trailing ".0"
+        Data::Float(f) if f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", *f as i64),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(d) => d
+            .as_datetime()
+            .map(|d| d.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            .unwrap_or_else(|| d.as_f64().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (Vec<Cell>, Vec<Vec<Cell>>) {
+        let header = vec![Cell::text("Name"), Cell::text("Total")];
+        let rows = vec![
+            vec![
+                Cell::text("أحمد, \"Ali\""),
+                Cell::Number("1250.5".parse().unwrap()),
+            ],
+            vec![Cell::Empty, Cell::Number(BigDecimal::from(3))],
+        ];
+        (header, rows)
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let (header, rows) = sample();
+        let mut bytes = CSV_BOM.to_vec();
+        for cells in std::iter::once(&header).chain(&rows) {
+            bytes.extend(csv_row(cells).unwrap());
+        }
+        bytes.extend(b"\n,\n");
+
+        let table = Table::read(&bytes).unwrap();
+        assert_eq!(table.headers, vec!["Name", "Total"]);
+        assert_eq!(table.column("total"), Some(1));
+        assert_eq!(
+            table.rows,
+            vec![vec!["أحمد, \"Ali\"", "1250.5"], vec!["", "3"]]
+        );
+    }
+
+    #[test]
+    fn test_xlsx_round_trip() {
+        let (header, rows) = sample();
+        let bytes = xlsx("Orders", &header, &rows).unwrap();
+        assert_eq!(SpreadsheetFormat::detect(&bytes), SpreadsheetFormat::Xlsx);
+
+        let table = Table::read(&bytes).unwrap();
+        assert_eq!(table.headers, vec!["Name", "Total"]);
+        assert_eq!(
+            table.rows,
+            vec![vec!["أحمد, \"Ali\"", "1250.5"], vec!["", "3"]]
+        );
+    }
+
+    #[test]
+    fn test_empty_file() {
+        assert!(Table::read(b"\n\n").is_err());
+    }
+}
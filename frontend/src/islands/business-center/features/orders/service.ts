@@ -139,11 +139,6 @@ export const OrderCreateSchema = yup.object({
         .required("Shipping cost is required.")
         .matches(/^\d+(\.\d{1,2})?$/, "Shipping cost must be a valid decimal (e.g., 5.99)."),
 
-    tax_amount: yup
-        .string()
-        .required("Tax amount is required.")
-        .matches(/^\d+(\.\d{1,2})?$/, "Tax amount must be a valid decimal (e.g., 1.50)."),
-
     currency: yup
         .string()
         .nullable()
@@ -177,7 +172,7 @@ export const OrderStatusUpdateSchema = yup.object({
         .transform(nullStr)
         .max(500, "Note cannot exceed 500 characters."),
 
-    created_by: yup
+    custom_state: yup
         .string()
         .nullable()
         .defined()
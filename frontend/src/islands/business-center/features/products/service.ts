@@ -197,6 +197,7 @@ export function createDefaultVariant(): any {
         price: '0.00',
         compare_at: null,
         stocks: 0,
+        weight: null,
         images: [],
         options: {}
     };
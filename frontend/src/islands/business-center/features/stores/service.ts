@@ -144,6 +144,16 @@ export const StoreSchema = yup.object({
                     .nullable()
                     .defined()
                     .transform(nullStr),
+
+                category: yup
+                    .string()
+                    .matches(
+                        /^[a-z0-9]+(-[a-z0-9]+)*$/,
+                        "Category must be a valid slug."
+                    )
+                    .nullable()
+                    .defined()
+                    .transform(nullStr),
             })
         )
         .max(20, "You can only feature up to 20 collections.")
@@ -198,6 +208,11 @@ export const StoreSchema = yup.object({
         .default("")
         .transform(defaultStr),
 
+    collection_page_template: yup
+        .string()
+        .default("")
+        .transform(defaultStr),
+
     not_found_page_template: yup
         .string()
         .default("")
@@ -244,6 +244,7 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
     // TODO: check the routes for duplicate routes same method same path!!
 
     let mut route_entries = vec![];
+    let mut route_permissions = vec![];
     let mut ts_routes = vec![];
     let mut import_lines = HashSet::new();
     import_lines.insert("import queryString from '@/../node_modules/query-string';".to_string());
@@ -277,6 +278,7 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
                     let mut path: Option<String> = None;
                     let mut route_type: Option<Ident> = None;
                     let mut res_type: Option<Type> = None;
+                    let mut perm: Option<LitStr> = None;
 
                     let _ = attr.parse_nested_meta(|meta| {
                         if meta.path.is_ident("method") {
@@ -288,8 +290,10 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
                             route_type = Some(meta.value()?.parse()?);
                         } else if meta.path.is_ident("res") {
                             res_type = Some(meta.value()?.parse()?);
+                        } else if meta.path.is_ident("perm") {
+                            perm = Some(meta.value()?.parse()?);
                         } else {
-                            return Err(meta.error("expected #[route(method = ..., path = ..., type = ..., res = ...)]"));
+                            return Err(meta.error("expected #[route(method = ..., path = ..., type = ..., res = ..., perm = ...)]"));
                         }
                         Ok(())
                     });
@@ -307,7 +311,19 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
                         }
                     }
 
-                    route_info = Some((method, path, route_type, res_type));
+                    // Permissions are written as "resource:action"
+                    let perm = perm.map(|p| match p.value().split_once(':') {
+                        Some((resource, action))
+                            if !resource.is_empty()
+                                && !action.is_empty()
+                                && !action.contains(':') =>
+                        {
+                            (resource.to_string(), action.to_string())
+                        }
+                        _ => panic!("invalid perm: {}, expected \"resource:action\"", p.value()),
+                    });
+
+                    route_info = Some((method, path, route_type, res_type, perm));
                     break;
                 } else if attr.path().is_ident("fallback") {
                     if fallback_route.is_some() {
@@ -325,7 +341,7 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
                 }
             }
 
-            if let Some((method, path, _route_type, res_type)) = route_info {
+            if let Some((method, path, _route_type, res_type, perm)) = route_info {
                 // Remove the route attribute
                 if let Some(index) = route_attr_index {
                     attrs.remove(index);
@@ -419,9 +435,29 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
                 //     -> std::result::Result<axum::Json<#return_type>, axum::http::StatusCode>
                 // };
 
-                route_entries.push(quote! {
-                    .route(#path, axum::routing::#method_ident(Self::#fn_name))
-                });
+                if let Some((resource, action)) = perm {
+                    // Checked before any extractor of the handler runs
+                    route_entries.push(quote! {
+                        .route(
+                            #path,
+                            axum::routing::#method_ident(Self::#fn_name).route_layer(
+                                axum::middleware::from_fn(
+                                    |request: axum::extract::Request,
+                                     next: axum::middleware::Next| {
+                                        crate::middlewares::permission::require(
+                                            #resource, #action, request, next,
+                                        )
+                                    },
+                                ),
+                            ),
+                        )
+                    });
+                    route_permissions.push(quote! { (#resource, #action) });
+                } else {
+                    route_entries.push(quote! {
+                        .route(#path, axum::routing::#method_ident(Self::#fn_name))
+                    });
+                }
 
                 let mut full_path = prefix_str.clone() + path.as_str();
 
@@ -544,6 +580,13 @@ pub fn routes(args: TokenStream, input: TokenStream) -> TokenStream {
     };
     input_impl.items.push(router_fn);
 
+    let permissions_const: ImplItem = syn::parse_quote! {
+        /// The `(resource, action)` pairs required by the routes.
+        pub const PERMISSIONS: &'static [(&'static str, &'static str)] =
+            &[#(#route_permissions),*];
+    };
+    input_impl.items.push(permissions_const);
+
     let imports = import_lines.into_iter().collect::<Vec<String>>().join("\n");
     let mut expanded_test = quote! {};
     if ts_routes.len() > 0 {